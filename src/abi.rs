@@ -0,0 +1,178 @@
+use anyhow::{bail, Result};
+
+use crate::config::AbiConfig;
+use crate::cpu::Cpu;
+
+// The most frames which are shown, so that a corrupt or cyclic stack can't go on forever.
+const MAX_FRAMES: usize = 0x40;
+
+// The number of words above the stack pointer which are searched for return addresses, when there
+// is no frame pointer to follow.
+const SCAN_LIMIT: usize = 0x200;
+
+// Where the return address of a frame was found.
+#[derive(Clone, Copy)]
+pub enum Origin {
+    // The frame is the one which is executing, at the program counter.
+    ProgramCounter,
+    // The return address was still in the link register.
+    Link,
+    // The return address was saved on the stack at an address, and found by following the chain
+    // of frame pointers or by searching the stack.
+    Stack(u16),
+}
+
+// A frame of the guest's call stack.
+pub struct Frame {
+    // The address at which execution will resume in the frame; the program counter for the
+    // innermost frame, and a return address for every other frame.
+    pub address: u16,
+    // The entry point of the function to which the frame belongs, if the call into it was a JAL
+    // to a fixed address.
+    pub function: Option<u16>,
+    pub origin: Origin,
+}
+
+impl Frame {
+    pub fn describe(&self, depth: usize) -> String {
+        let function = match self.function {
+            Some(function) => format!(" in {:#06x}", function),
+            None => String::new(),
+        };
+        let origin = match self.origin {
+            Origin::ProgramCounter => String::new(),
+            Origin::Link => " (link)".to_string(),
+            Origin::Stack(address) => format!(" (saved at {:#06x})", address),
+        };
+        format!("#{} {:#06x}{}{}", depth, self.address, function, origin)
+    }
+}
+
+// The calling convention followed by the guest, by which its call stack is unwound and its
+// functions are called from the debugger.
+pub struct Abi {
+    pub link: usize,
+    pub stack: usize,
+    frame: Option<usize>,
+    saved_return: i16,
+    saved_frame: i16,
+    pub results: Vec<usize>,
+}
+
+impl Abi {
+    pub fn new(abi: &AbiConfig) -> Result<Self> {
+        for &register in [abi.link, abi.stack]
+            .iter()
+            .chain(&abi.frame)
+            .chain(&abi.results)
+        {
+            if !(1..0x20).contains(&register) {
+                bail!(
+                    "r{:02} in the ABI is not a register from r01 to r31.",
+                    register
+                );
+            }
+        }
+        Ok(Self {
+            link: abi.link,
+            stack: abi.stack,
+            frame: abi.frame,
+            saved_return: abi.saved_return,
+            saved_frame: abi.saved_frame,
+            results: abi.results.clone(),
+        })
+    }
+
+    // Unwind the call stack, from the innermost frame outwards. Since this only looks at the
+    // registers and the stack, rather than tracking calls and returns as they happen, it isn't
+    // confused by tail calls or by the program counter being moved by hand. It is necessarily a
+    // best guess, though, since a function which hasn't yet saved its return address can't be
+    // told apart from one which already has and has gone on to reuse the link register.
+    pub fn unwind(&self, cpu: &Cpu) -> Vec<Frame> {
+        let mut frames = vec![Frame {
+            address: cpu.program_counter,
+            function: None,
+            origin: Origin::ProgramCounter,
+        }];
+
+        // Return addresses saved on the stack, outermost last.
+        let mut saved = Vec::new();
+        match self.frame {
+            Some(frame) => {
+                // Each frame pointer points to a record holding the return address and the frame
+                // pointer of the caller, which is further up the stack.
+                let mut pointer = cpu.registers[frame];
+                while pointer != 0x0000 && saved.len() < MAX_FRAMES {
+                    let slot = pointer.wrapping_add_signed(self.saved_return);
+                    let return_address = cpu.peek(slot);
+                    if self.call_before(cpu, return_address).is_none() {
+                        break;
+                    }
+                    saved.push((slot, return_address));
+                    let next = cpu.peek(pointer.wrapping_add_signed(self.saved_frame));
+                    if next <= pointer {
+                        break;
+                    }
+                    pointer = next;
+                }
+            }
+            None => {
+                // Without frame pointers, anything on the stack which looks like a return address
+                // is taken to be one. Stale return addresses can only be left beneath the stack
+                // pointer, so the search starts there. The stack grows down, so a stack pointer of
+                // 0x0000 means that nothing has been pushed yet.
+                let pointer = cpu.registers[self.stack];
+                let slots = (pointer..=0xffff).take(if pointer == 0x0000 { 0 } else { SCAN_LIMIT });
+                for slot in slots {
+                    let return_address = cpu.peek(slot);
+                    if self.call_before(cpu, return_address).is_some() {
+                        saved.push((slot, return_address));
+                        if saved.len() == MAX_FRAMES {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        // A return address which is still in the link register belongs to the innermost call,
+        // unless it has already been saved, in which case the stack will turn it up.
+        let link = cpu.registers[self.link];
+        if self.call_before(cpu, link).is_some()
+            && saved.first().map(|&(_, address)| address) != Some(link)
+        {
+            frames.push(Frame {
+                address: link,
+                function: None,
+                origin: Origin::Link,
+            });
+        }
+        frames.extend(saved.into_iter().map(|(slot, address)| Frame {
+            address,
+            function: None,
+            origin: Origin::Stack(slot),
+        }));
+
+        // The call which each return address follows tells us which function the frame within it
+        // belongs to.
+        for depth in 1..frames.len() {
+            frames[depth - 1].function = self.call_before(cpu, frames[depth].address).flatten();
+        }
+        frames.truncate(MAX_FRAMES);
+        frames
+    }
+
+    // Determine whether an address is one which a call returns to, which is the case if it follows
+    // a JAL which links into the link register. If it is, then the target of the call is given as
+    // well, if it's a fixed address.
+    fn call_before(&self, cpu: &Cpu, address: u16) -> Option<Option<u16>> {
+        let call = address.checked_sub(2)?;
+        let instruction = cpu.peek(call);
+        let destination = usize::from((instruction & 0b0000011111000000) >> 6);
+        if instruction & 0b0000000000111111 != 0b101000 || destination != self.link {
+            return None;
+        }
+        let source = instruction & 0b1111100000000000;
+        Some((source == 0).then(|| cpu.peek(call.wrapping_add(1))))
+    }
+}
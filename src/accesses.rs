@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::explain::dataflow;
+
+// The most accesses which are kept, which is as far back as the accesses pane can reach.
+const ACCESSES_LENGTH: usize = 0x1000;
+
+// A load from or a store to a word of RAM made by the program.
+#[derive(Clone, Copy)]
+pub struct Access {
+    // The number of steps which had been taken before the instruction which made it, since the
+    // image was loaded.
+    pub step: u64,
+    pub address: u16,
+    pub written: bool,
+}
+
+// How many of the accesses plotted in a cell of the ribbon were loads and stores.
+#[derive(Clone, Copy, Default)]
+pub struct Cell {
+    pub reads: u32,
+    pub writes: u32,
+}
+
+// The recent accesses laid out as a grid, with a row for each range of addresses, from the lowest
+// to the highest, and a column for each span of steps, from the oldest to the newest.
+pub struct Ribbon {
+    pub start: u16,
+    // The number of words covered by each row, and the number of steps covered by each column.
+    pub words: u32,
+    pub steps: u64,
+    pub cells: Vec<Vec<Cell>>,
+}
+
+// The loads and stores most recently made by the program, in the order in which they were made,
+// for plotting where in memory it has been working over time. Sweeps through a buffer show up as
+// diagonals, and the stack as a band which moves as it grows and shrinks. Only accesses to RAM are
+// recorded, so those to devices, the performance counters, and the interrupt controller are left
+// out.
+pub struct Accesses {
+    recent: VecDeque<Access>,
+}
+
+impl Accesses {
+    pub fn new() -> Self {
+        Self {
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Access> {
+        self.recent.iter()
+    }
+
+    // Forget every access, as when a new image is loaded and the count of steps begins again.
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+
+    // Record the access made by the instruction to which the program counter currently points, if
+    // it makes one, given the number of steps taken so far. This must be done before it is
+    // executed, while the address that it accesses can still be found.
+    pub fn record(&mut self, cpu: &Cpu, step: u64) {
+        let Some((address, written)) = dataflow(cpu).memory else {
+            return;
+        };
+        if cpu.devices.peek(address).is_some()
+            || cpu.counters.contains(address)
+            || cpu.interrupts.contains(address)
+            || cpu.memory.decode(address).is_none()
+        {
+            return;
+        }
+        self.recent.push_back(Access {
+            step,
+            address,
+            written,
+        });
+        if self.recent.len() > ACCESSES_LENGTH {
+            self.recent.pop_front();
+        }
+    }
+
+    // Lay the accesses out over a grid of a given size, given the number of steps taken so far, with
+    // the newest steps in the last column. The addresses covered are either a range given as its
+    // start and its length, or else just those which were accessed. Each row and each column
+    // covers a power of two of words and of steps, so that the grid doesn't shift about with every
+    // step.
+    pub fn ribbon(
+        &self,
+        now: u64,
+        rows: usize,
+        columns: usize,
+        range: Option<(u16, u16)>,
+    ) -> Ribbon {
+        let (low, high) = match range {
+            Some((start, length)) => (
+                u32::from(start),
+                u32::from(start) + u32::from(length.max(1)) - 1,
+            ),
+            None => {
+                let addresses = self.recent.iter().map(|access| u32::from(access.address));
+                (
+                    addresses.clone().min().unwrap_or(0),
+                    addresses.max().unwrap_or(0),
+                )
+            }
+        };
+        let words = (high - low + 1)
+            .div_ceil(rows.max(1) as u32)
+            .next_power_of_two();
+        let start = low - low % words;
+
+        let oldest = self.recent.front().map_or(now, |access| access.step);
+        let steps = (now - oldest)
+            .div_ceil(columns.max(1) as u64)
+            .max(1)
+            .next_power_of_two();
+        // The last column ends with the latest step, and is aligned just like the others are.
+        let end = now.div_ceil(steps) * steps;
+
+        let mut cells = vec![vec![Cell::default(); columns]; rows];
+        for access in &self.recent {
+            let row = (u32::from(access.address).wrapping_sub(start) / words) as usize;
+            let age = ((end - 1 - access.step) / steps) as usize;
+            if row >= rows || age >= columns {
+                continue;
+            }
+            let cell = &mut cells[row][columns - 1 - age];
+            if access.written {
+                cell.writes += 1;
+            } else {
+                cell.reads += 1;
+            }
+        }
+        Ribbon {
+            start: start as u16,
+            words,
+            steps,
+            cells,
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+
+use crate::cpu::Cpu;
+use crate::diagnostic::Diagnostic;
+use crate::disassemble::{
+    disassemble, find_reachable, instruction_length, is_call, is_defined, is_return, static_target,
+    successors,
+};
+
+// The number of instructions shown on either side of the address at fault.
+const CONTEXT: usize = 2;
+
+// Look for common mistakes in the code in a region of memory, as the CPU sees it, following every
+// statically known path of execution from its start, without running it. Each problem is reported at the address of the
+// instruction at fault, and the addresses at which instructions were found to begin are returned
+// along with them. The register which holds return addresses is needed to tell returns apart from
+// other jumps.
+pub fn analyze(cpu: &Cpu, start: u16, end: u16, link: usize) -> (Vec<Diagnostic>, Vec<u16>) {
+    let image = cpu.image();
+    let ram = &image[..];
+    let reachable = find_reachable(ram, start);
+    let word = |address: u16| ram[usize::from(address)];
+    let is_start = |address: u16| reachable[usize::from(address)];
+    let starts = (start..=end).filter(|&a| is_start(a)).collect::<Vec<_>>();
+    // The words which hold the immediate of an instruction which may be executed.
+    let immediates = starts
+        .iter()
+        .filter(|&&address| instruction_length(word(address)) == 2)
+        .map(|&address| address.wrapping_add(1))
+        .collect::<BTreeSet<_>>();
+    let describe = |address: u16| {
+        disassemble(word(address), word(address.wrapping_add(1)))
+            .trim()
+            .to_string()
+    };
+
+    let mut problems = Vec::new();
+    for &address in &starts {
+        let instruction = word(address);
+        let immediate = word(address.wrapping_add(1));
+
+        // Execution which lands on an immediate runs it as an instruction of its own, and goes on
+        // out of step with the code around it.
+        if let Some(target) = static_target(address, instruction, immediate) {
+            if immediates.contains(&target) {
+                problems.push((
+                    address,
+                    format!(
+                        "{} goes to {:#06x}, which is the middle of {} at {:#06x}",
+                        describe(address),
+                        target,
+                        describe(target.wrapping_sub(1)),
+                        target.wrapping_sub(1)
+                    ),
+                ));
+            }
+        }
+
+        // The assembler never writes an operand which an instruction doesn't take, but code built
+        // by hand might, which leaves the operand to be executed as if it were an instruction.
+        let next = address.wrapping_add(1);
+        if instruction_length(instruction) == 1
+            && (start..=end).contains(&next)
+            && !is_defined(word(next))
+        {
+            problems.push((
+                address,
+                format!(
+                    "{} takes no immediate, but is followed by {:#06x}, which isn't an instruction",
+                    describe(address),
+                    word(next)
+                ),
+            ));
+        }
+    }
+
+    // Words which can't be reached are only reported as code where they lie before code which
+    // can, since whatever follows the last of the code is usually data. Words of 0x0000 are
+    // taken to be padding between pieces of code, rather than strings of ADDs.
+    let unreached = (start..=end)
+        .filter(|&address| !is_start(address) && !immediates.contains(&address))
+        .collect::<Vec<_>>();
+    for stretch in unreached.chunk_by(|&a, &b| b == a.wrapping_add(1)) {
+        let (first, last) = (stretch[0], stretch[stretch.len() - 1]);
+        let code = stretch.iter().all(|&a| is_defined(word(a)))
+            && stretch.iter().any(|&a| word(a) != 0x0000);
+        if code && last != end && is_start(last.wrapping_add(1)) {
+            problems.push((
+                first,
+                format!(
+                    "{:#06x} words of code are never reached from {:#06x}",
+                    stretch.len(),
+                    start
+                ),
+            ));
+        }
+    }
+
+    // When the code at the start isn't itself a function which returns, it has no return address
+    // to give, so a function which it jumps into without writing a link returns to wherever the
+    // link register happens to point.
+    let calls = starts
+        .iter()
+        .filter(|&&address| is_call(word(address)))
+        .filter_map(|&address| static_target(address, word(address), word(address.wrapping_add(1))))
+        .collect::<BTreeSet<_>>();
+    let top_level = body(ram, start, &reachable, &calls);
+    if !top_level.iter().any(|&address| returns(ram, address, link)) {
+        for &address in top_level.range(start..=end) {
+            let instruction = word(address);
+            let is_jump = instruction & 0b0000000000111111 == 0b101000
+                && (instruction & 0b0000011111000000) >> 6 == 0;
+            let Some(target) = static_target(address, instruction, word(address.wrapping_add(1)))
+                .filter(|target| is_jump && calls.contains(target))
+            else {
+                continue;
+            };
+            if let Some(&ret) = body(ram, target, &reachable, &calls)
+                .iter()
+                .find(|&&address| returns(ram, address, link))
+            {
+                problems.push((
+                    address,
+                    format!(
+                        "{} jumps into the function at {:#06x} without writing a link, but it returns at {:#06x}",
+                        describe(address),
+                        target,
+                        ret
+                    ),
+                ));
+            }
+        }
+    }
+
+    problems.sort_by_key(|&(address, _)| address);
+    let diagnostics = problems
+        .into_iter()
+        .map(|(address, message)| {
+            let lines = around(&starts, address)
+                .into_iter()
+                .map(|line| (usize::from(line), describe(line)))
+                .collect();
+            Diagnostic::at(address, &message, lines)
+        })
+        .collect();
+    (diagnostics, starts)
+}
+
+// Whether or not the instruction at an address is a return.
+fn returns(ram: &[u16], address: u16, link: usize) -> bool {
+    is_return(
+        ram[usize::from(address)],
+        ram[usize::from(address.wrapping_add(1))],
+        link,
+    )
+}
+
+// The instructions which may be executed from an entry point without entering a function, which
+// is to say the body of the function beginning there, less any function which it calls or jumps
+// into. Functions are known by being called from somewhere.
+fn body(ram: &[u16], entry: u16, reachable: &[bool], functions: &BTreeSet<u16>) -> BTreeSet<u16> {
+    let mut found = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(address) = pending.pop() {
+        if !reachable[usize::from(address)] || !found.insert(address) {
+            continue;
+        }
+        let instruction = ram[usize::from(address)];
+        let next = address.wrapping_add(instruction_length(instruction));
+        if is_call(instruction) {
+            pending.push(next);
+        } else {
+            pending.extend(
+                successors(
+                    address,
+                    instruction,
+                    ram[usize::from(address.wrapping_add(1))],
+                )
+                .into_iter()
+                .filter(|target| !functions.contains(target)),
+            );
+        }
+    }
+    found
+}
+
+// The addresses of the instructions on either side of an address, including the address itself.
+fn around(starts: &[u16], address: u16) -> Vec<u16> {
+    let index = starts.partition_point(|&start| start < address);
+    let mut lines = starts[index.saturating_sub(CONTEXT)..index].to_vec();
+    lines.push(address);
+    lines.extend(
+        starts[index..]
+            .iter()
+            .skip_while(|&&start| start == address)
+            .take(CONTEXT),
+    );
+    lines
+}
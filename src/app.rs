@@ -1,123 +1,4307 @@
 use anyhow::{anyhow, Result};
 
-use regex::RegexBuilder;
+use ratatui::layout::{Position, Rect};
 
-use std::collections::VecDeque;
+use regex::{Regex, RegexBuilder};
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
 use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::abi::Abi;
+use crate::accesses::Accesses;
+use crate::analyze::analyze;
+use crate::assemble::{assemble, assemble_lines, parse_literal, parse_signed_literal, LineError};
+use crate::breakpoint::{AddressMap, Breakpoint};
+use crate::build::PendingBuild;
+use crate::checksum::{crc32, crc32_words};
+use crate::clipboard;
+use crate::command::{self, quote, Arguments};
+use crate::config::Config;
+use crate::config::DeviceConfig;
+use crate::cost::CostModel;
+use crate::counters::COUNTERS_BASE;
 use crate::cpu::Cpu;
+use crate::debug::Output;
+use crate::device::{create, Devices};
+use crate::diagnostic::Diagnostic;
+use crate::disassemble::{disassemble, disassemble_region, instruction_length, list_region};
+use crate::exception::Exception;
+use crate::explain::explain;
+use crate::export::export;
+use crate::expr::{format_values, read_string, Expression, Packing};
+use crate::heap::Heap;
+use crate::history::{History, InstructionHistory};
+use crate::hypercall::Request;
+use crate::interrupt::{Interrupts, Stepping};
+use crate::keymap::Keymap;
+use crate::listing;
+use crate::liveness::{self, Trace};
+use crate::load::{is_hex_image, parse_hex_image, ByteOrder, PendingLoad};
+use crate::measure::Measure;
+use crate::memory::{MemoryMap, Rom, ROM_CONTROL};
+use crate::profile::Profile;
+use crate::project::Project;
+use crate::random::Random;
+use crate::recent::{self, Recent};
+use crate::record::{Field, Record};
+use crate::region;
+use crate::report::report;
+use crate::snapshot;
+use crate::starts::Starts;
+use crate::stats::Stats;
+use crate::taint::Taint;
+use crate::terminal::{Terminal, COLUMNS, ROWS};
+use crate::timeline::Timeline;
+use crate::trace::format_trace;
+use crate::ui::RamRow;
+use crate::until::Until;
+use crate::usage::BLOCK_SIZE;
+use crate::warning::{self, Overflow};
+use crate::wave::Wave;
+use crate::writers::Writers;
 
 pub struct App {
     pub cpu: Cpu,
     pub command_buffer: String,
+    // The position of the cursor within the command buffer, as a byte offset which always falls
+    // on a character boundary.
+    pub command_cursor: usize,
     pub command_result: Result<String>,
-    pub instruction_history: VecDeque<(u16, u16)>,
+    pub instruction_history: InstructionHistory,
     pub running: bool,
+    // The number of instructions executed since the simulation was last set running.
+    pub run_count: u64,
+    // A long STEP which is being spread across frames, as the number of steps taken so far and the
+    // number of steps requested.
+    pub pending_steps: Option<(u16, u16)>,
+    // A STEPWHILE or STEPUNTIL which is being spread across frames.
+    pub pending_condition: Option<PendingCondition>,
+    // A LOAD whose file is still being read on a worker thread.
+    pub pending_load: Option<PendingLoad>,
+    // The build which is running in the background, if any, after which the image is reloaded.
+    pub pending_build: Option<PendingBuild>,
+    // Turbo stepping, while its key is held.
+    pub turbo: Option<Turbo>,
+    pub breakpoints: AddressMap<Breakpoint>,
+    // Tracepoints map addresses to format strings, which are expanded and logged to the console
+    // whenever the address is reached, without halting the simulation.
+    pub tracepoints: AddressMap<String>,
+    // Whether or not suspicious instructions are flagged in the console as they're executed, along
+    // with the addresses which have already been flagged, since a warning inside a loop would
+    // otherwise flood the console.
+    pub warnings: bool,
+    warned: BTreeSet<u16>,
+    // What is done when an ADD, SUB, or ADDI overflows, taking its operands as signed. Overflows
+    // which are only noted are flagged once per address, just as warnings are.
+    pub overflow: Overflow,
+    // What stepping does about interrupts, which is either to stop at the entry of a handler once
+    // one is taken, or to hold them off.
+    pub interrupt_stepping: Stepping,
+    // Which values are derived from the program's input, if taint is being tracked.
+    pub taint: Option<Taint>,
+    // The number of steps which have been taken since the image was loaded. Unlike the performance
+    // counters, this isn't cleared by a reset, so that it orders the whole run.
+    pub steps: u64,
+    // The instruction which last wrote to each register and word of RAM.
+    writers: Writers,
+    // Where instructions are known to begin, as learned from execution and from ANALYZE.
+    pub starts: Starts,
+    // The loads and stores most recently made by the program, the range of addresses which the
+    // accesses pane plots them over, if not just those accessed, and whether or not it's displayed.
+    pub accesses: Accesses,
+    pub accesses_range: Option<(u16, u16)>,
+    pub accesses_pane: bool,
+    // A recording of the run, for travelling back through it with SEEK.
+    timeline: Timeline,
+    pub console: VecDeque<String>,
+    // The commands most recently executed, whether typed, pasted, bound to keys, or run from
+    // sessions and breakpoint actions, so that a crash dump can tell how the machine got here.
+    pub recent_commands: VecDeque<String>,
+    // The VCD file into which the state of the CPU is being recorded, if any.
+    pub wave: Option<Wave>,
+    // The usage statistics of this session, if they're being recorded.
+    pub stats: Option<Stats>,
+    // The profile restricting which commands may be used, if any.
+    pub profile: Option<Profile>,
+    // A second CPU which is stepped in lockstep with the first, so that two versions of a program
+    // can be compared against one another.
+    pub reference: Option<Cpu>,
+    pub cost: CostModel,
+    // The routine being benchmarked by MEASURE, if any.
+    pub measure: Option<Measure>,
+    // The register which is displayed in large digits, if any.
+    pub focus: Option<usize>,
+    // The address at which the RAM pane is pinned, if it is pinned. Otherwise, the RAM pane
+    // follows the program counter.
+    pub ram_pin: Option<u16>,
+    // While the RAM pane is pinned, the contents of RAM as of the last frame, and the time at which
+    // each word last changed, so that words which are changing can be highlighted.
+    ram_shadow: Vec<u16>,
+    pub ram_changed: Vec<Option<Instant>>,
+    // The time at which any word of RAM last changed, so that the UI knows how long it must keep
+    // redrawing for the highlights to fade.
+    pub ram_last_changed: Option<Instant>,
+    // Named addresses, which may be jumped to with GOTO.
+    pub bookmarks: BTreeMap<String, u16>,
+    // The problems most recently reported by ASM or BUILD, along with the one which is selected
+    // and the lines of source around it, and whether or not the diagnostics pane is displayed.
+    pub diagnostics: Vec<Diagnostic>,
+    pub diagnostic: usize,
+    pub excerpt: Vec<(usize, String)>,
+    pub diagnostics_pane: bool,
+    // The state of the machine when it last faulted, which is shown until it is dismissed with Esc
+    // or the machine moves on.
+    pub exception: Option<Exception>,
+    // Whether or not the bookmarks pane is displayed.
+    pub bookmark_pane: bool,
+    // Reconstructs the guest's call stack, for the call stack pane.
+    pub abi: Abi,
+    // Whether or not the call stack pane is displayed.
+    pub stack_pane: bool,
+    // Walks the guest's heap, for the heap pane, if the configuration describes one.
+    pub heap: Option<Heap>,
+    // Whether or not the heap pane is displayed.
+    pub heap_pane: bool,
+    // Expressions which are evaluated afresh every frame, and shown in the watches pane.
+    pub watches: Vec<Expression>,
+    // Expressions which must hold after every step, as set with ASSERT.
+    assertions: Vec<Expression>,
+    // Record types defined with TYPE, by name, for reading memory as structures.
+    pub types: BTreeMap<String, Arc<Record>>,
+    // How the guest holds strings, from the configuration.
+    pub packing: Packing,
+    // The arguments passed to the guest on the command line, which are given to any hypercall port
+    // attached later on as well.
+    arguments: Vec<String>,
+    // The order of the bytes of each word in the files which LOAD reads and DUMP writes, from the
+    // configuration.
+    byte_order: ByteOrder,
+    // The pattern most recently searched for with /, and the address of the current match.
+    pub search: Option<(Regex, u16)>,
+    // Whether or not the minimap of the whole address space is displayed.
+    pub minimap: bool,
+    // A copy of what the panes showed when the view was frozen, if it is frozen. The panes are
+    // drawn from this copy rather than from the live state, so that they hold still while the
+    // simulation runs on.
+    pub frozen: Option<Box<FrozenView>>,
+    // The area in which the cells of the minimap were last drawn, so that clicks can be mapped
+    // back to the blocks of memory which they landed on.
+    pub minimap_area: Cell<Rect>,
+    // The most frames which are drawn each second.
+    pub frame_rate: u32,
+    // The panes which are only redrawn once the simulation halts, by name.
+    pub halted_panes: BTreeSet<&'static str>,
+    // What each of those panes showed when it was last rendered, and where, so that it can be drawn
+    // again in place while the simulation runs.
+    pub pane_cache: RefCell<HashMap<&'static str, (Rect, Vec<ratatui::buffer::Cell>)>>,
+    // The rows of the RAM pane as they were last drawn, so that only those which have changed need
+    // to be formatted again.
+    pub ram_rows: RefCell<Vec<RamRow>>,
+    // The disassembly of each instruction drawn recently, by its instruction word and immediate.
+    pub disassembly_cache: RefCell<HashMap<(u16, u16), String>>,
+    // The keys which are bound to actions rather than typed at the prompt.
+    pub keymap: Keymap,
+    // The register under the cursor, while the Registers pane is selected for editing with Tab.
+    pub register_cursor: Option<usize>,
+    // The value being typed into the register under the cursor, if it is being edited.
+    pub register_edit: Option<String>,
+    // Every traced device access, kept so that the trace can be saved to a file. This is capped
+    // at a much larger size than the console.
+    pub device_trace: VecDeque<String>,
+    // Whether or not the devices pane is displayed.
+    pub device_pane: bool,
+    // The guest's terminal, which draws what the UART transmits. It is fed whether or not the
+    // terminal pane is displayed, so that nothing is missed before it is shown.
+    pub terminal: Terminal,
+    pub terminal_pane: bool,
+    // The number of rows by which the terminal pane is scrolled back from the screen.
+    pub terminal_scroll: usize,
+    // Whether or not the UI is in presentation mode, which explains each instruction as it is
+    // executed.
+    pub presenting: bool,
+    // The file from which the current image was most recently loaded, if any. The session file
+    // associated with this image is kept up to date as breakpoints are added and removed.
+    pub image: Option<String>,
+    // The CRC-32 of the current image at the time that it was loaded.
+    pub image_checksum: Option<u32>,
+    // What was most recently opened in this session, to be opened again by RELOAD.
+    opened: Option<Recent>,
+    // The project which was opened, if its image is still the one loaded. Its session file is used
+    // in place of the one kept beside the image.
+    project: Option<Project>,
+    // Whether or not the actions attached to a breakpoint are currently being executed.
+    executing_actions: bool,
+    // Whether or not a session file is currently being restored, during which the commands that it
+    // holds mustn't save the session over the file which they're being read from.
+    restoring: bool,
+    // Whether or not the command being executed was typed at the prompt. Only these commands are
+    // allowed to defer work to later frames, since commands executed from sessions or breakpoint
+    // actions may be followed by others which depend on their results.
+    interactive: bool,
+}
+
+// Everything which the panes show that changes as the simulation runs.
+pub struct FrozenView {
+    // The step of the recording which the view has travelled back to with SEEK, rather than the
+    // moment at which it was frozen, if it has.
+    pub step: Option<u64>,
+    pub cpu: Cpu,
+    pub reference: Option<Cpu>,
+    pub instruction_history: InstructionHistory,
+    pub console: VecDeque<String>,
+}
+
+// Turbo stepping, which steps continuously for as long as its key is held, and faster the longer
+// that it is held, for going through code too quickly to single step but too slowly to RUN.
+pub struct Turbo {
+    // When the key was pressed, which the rate of stepping is worked out from.
+    pressed: Instant,
+    // When the key is taken to have been let go, unless it repeats before then. Terminals which
+    // don't report releases only give away that a key is still held by repeating it.
+    held_until: Instant,
+    // When steps were last taken, and how many steps have come due since then which haven't been
+    // taken yet, including a fraction of the next.
+    last: Instant,
+    owed: f64,
+    steps: u64,
+}
+
+// A STEPWHILE, STEPUNTIL, or RUNUNTIL, which takes steps until its condition is met, along with
+// the number of steps taken so far.
+pub struct PendingCondition {
+    pub condition: Condition,
+    pub steps: u64,
 }
 
+pub enum Condition {
+    // An expression, which is checked after each step. Stepping stops once it is true, as with
+    // STEPUNTIL, or once it is false, as with STEPWHILE.
+    Expression { expression: Expression, until: bool },
+    // A kind of instruction, which stepping stops in front of, as with RUNUNTIL.
+    Next(Until),
+}
+
+// The largest number of steps which are executed in a single frame.
+const STEP_BATCH: u16 = 0x400;
+
+// Turbo stepping begins at this many steps a second, and doubles in speed each time that its key
+// is held for another interval, until it takes a whole batch of steps each tick.
+const TURBO_RATE: f64 = 8.0;
+const TURBO_DOUBLING: Duration = Duration::from_millis(500);
+
+// How long the turbo step key may go without repeating before it is taken to have been let go. The
+// first repeat only arrives after the keyboard's repeat delay, which is longer than the interval
+// between the repeats that follow it.
+const TURBO_FIRST_REPEAT: Duration = Duration::from_millis(700);
+const TURBO_REPEAT: Duration = Duration::from_millis(200);
+
+// The most steps which a STEPWHILE or STEPUNTIL may take when it can't be spread across frames,
+// before it is abandoned, since the condition might never be met.
+const CONDITION_LIMIT: u64 = 1_000_000;
+
+// The most instructions whose disassembly is kept for drawing them again.
+const DISASSEMBLY_CACHE_LENGTH: usize = 0x1000;
+
+// The minimap shows the address space as a square of blocks, with each row labelled by the address
+// at which it begins.
+pub const MINIMAP_COLUMNS: u16 = 0x10;
+pub const MINIMAP_LABEL_WIDTH: u16 = 5;
+
+// The names by which panes are referred to in the refresh configuration and by REFRESH.
+pub const PANES: [&str; 16] = [
+    "registers",
+    "reference",
+    "ram",
+    "minimap",
+    "history",
+    "console",
+    "instruction",
+    "focus",
+    "devices",
+    "bookmarks",
+    "stack",
+    "heap",
+    "watches",
+    "diagnostics",
+    "accesses",
+    "terminal",
+];
+
 impl App {
-    pub fn new() -> Self {
-        Self {
-            cpu: Cpu::new(),
+    pub fn new(config: Config) -> Result<Self> {
+        let cpu = build_cpu(&config)?;
+
+        Ok(Self {
+            cost: CostModel::new(&config.cost)?,
+            measure: None,
+            cpu,
             command_buffer: String::new(),
+            command_cursor: 0,
             command_result: Ok(String::new()),
-            instruction_history: VecDeque::new(),
+            instruction_history: InstructionHistory::new(),
             running: false,
+            run_count: 0,
+            pending_steps: None,
+            pending_condition: None,
+            turbo: None,
+            pending_load: None,
+            pending_build: None,
+            breakpoints: AddressMap::new(),
+            tracepoints: AddressMap::new(),
+            warnings: config.warnings,
+            warned: BTreeSet::new(),
+            overflow: config.overflow,
+            interrupt_stepping: config.interrupt_stepping,
+            taint: None,
+            steps: 0,
+            writers: Writers::new(),
+            starts: Starts::new(),
+            accesses: Accesses::new(),
+            accesses_range: None,
+            accesses_pane: false,
+            timeline: Timeline::new(),
+            console: VecDeque::new(),
+            recent_commands: VecDeque::new(),
+            profile: config
+                .profile
+                .as_deref()
+                .map(|name| Profile::new(name, &config.profiles))
+                .transpose()?,
+            wave: None,
+            stats: config.stats.then(|| Stats {
+                sessions: 1,
+                ..Stats::default()
+            }),
+            reference: None,
+            focus: None,
+            ram_pin: None,
+            ram_shadow: Vec::new(),
+            ram_changed: Vec::new(),
+            ram_last_changed: None,
+            bookmarks: BTreeMap::new(),
+            diagnostics: Vec::new(),
+            diagnostic: 0,
+            excerpt: Vec::new(),
+            diagnostics_pane: false,
+            exception: None,
+            bookmark_pane: false,
+            abi: Abi::new(&config.abi)?,
+            stack_pane: false,
+            heap: config.heap.as_ref().map(Heap::new).transpose()?,
+            heap_pane: false,
+            watches: Vec::new(),
+            assertions: Vec::new(),
+            types: BTreeMap::new(),
+            packing: config.strings,
+            arguments: config.arguments.clone(),
+            byte_order: config.byte_order,
+            search: None,
+            minimap: false,
+            frozen: None,
+            minimap_area: Cell::new(Rect::default()),
+            frame_rate: frame_rate(config.refresh.rate)?,
+            halted_panes: config
+                .refresh
+                .halt
+                .iter()
+                .map(|name| pane_name(name))
+                .collect::<Result<_>>()?,
+            pane_cache: RefCell::new(HashMap::new()),
+            ram_rows: RefCell::new(Vec::new()),
+            disassembly_cache: RefCell::new(HashMap::new()),
+            keymap: Keymap::new(&config.keys)?,
+            register_cursor: None,
+            register_edit: None,
+            presenting: false,
+            device_pane: false,
+            terminal: Terminal::new(),
+            terminal_pane: false,
+            terminal_scroll: 0,
+            device_trace: VecDeque::new(),
+            image: None,
+            image_checksum: None,
+            opened: None,
+            project: None,
+            executing_actions: false,
+            restoring: false,
+            interactive: false,
+        })
+    }
+
+    // Advance the simulation by a single frame's worth of work. This either makes progress on a
+    // pending STEP, or steps the simulation once if it is running. Any pending LOAD is checked for
+    // completion as well.
+    pub fn tick(&mut self) {
+        if let Some(result) = self.pending_load.as_mut().and_then(|load| load.poll()) {
+            let load = self.pending_load.take().unwrap();
+            self.command_result = result
+                .map_err(|e| anyhow!("Failed to read {}: {}", load.filename, e))
+                .and_then(|bytes| self.finish_load(&load.filename, load.address, &bytes));
+            if self.command_result.is_ok() {
+                self.remember(Recent::Image {
+                    path: load.filename,
+                    load: load.address,
+                });
+            }
+        }
+
+        if let Some(build) = &mut self.pending_build {
+            let mut output = Vec::new();
+            let status = build.poll(&mut output);
+            for line in output {
+                self.log(line);
+            }
+            if let Some(status) = status {
+                let build = self.pending_build.take().unwrap();
+                self.command_result = self.finish_build(build, status);
+            }
+        }
+
+        if let Some((done, total)) = self.pending_steps {
+            let (taken, halted) = self.step_up_to(STEP_BATCH.min(total - done));
+            let done = done + taken;
+            if halted {
+                self.pending_steps = None;
+                self.command_result = self.halted_result(u64::from(done));
+            } else if done == total {
+                self.pending_steps = None;
+                self.command_result = Ok(format!("Stepped simulation {:#06x} times.", total));
+            } else {
+                self.pending_steps = Some((done, total));
+            }
+        } else if self.pending_condition.is_some() {
+            if let Some(result) = self.advance_condition(u64::from(STEP_BATCH)) {
+                self.command_result = result;
+            }
+        } else if self.turbo.is_some() {
+            self.advance_turbo();
+        } else if self.running {
+            self.step();
+            self.run_count += 1;
         }
+
+        self.track_ram_changes();
+    }
+
+    // Whether or not a LOAD, a BUILD, a long STEP, a STEPWHILE or STEPUNTIL, turbo stepping, or a
+    // RUN is in progress, which must be advanced by a tick as often as possible.
+    pub fn busy(&self) -> bool {
+        self.running
+            || self.pending_steps.is_some()
+            || self.pending_condition.is_some()
+            || self.turbo.is_some()
+            || self.pending_load.is_some()
+            || self.pending_build.is_some()
     }
 
-    pub fn step(&mut self) {
-        // Update the instruction history. Make sure that it doesn't grow too large in a rather
-        // lazy way.
-        let instruction = self.cpu.ram[usize::from(self.cpu.program_counter)];
-        let immediate = self.cpu.ram[usize::from(self.cpu.program_counter.wrapping_add(1))];
-        self.instruction_history.push_back((instruction, immediate));
-        if self.instruction_history.len() > 0xff {
-            self.instruction_history.pop_front();
+    // Note the time at which each word of RAM changes, while the RAM pane is pinned. Following the
+    // program counter, the pane jumps around too much for highlighting changes to be any use, so
+    // nothing is tracked in that case.
+    fn track_ram_changes(&mut self) {
+        if self.ram_pin.is_none() {
+            self.ram_shadow = Vec::new();
+            self.ram_changed = Vec::new();
+            return;
+        }
+        if self.ram_shadow.is_empty() {
+            self.ram_shadow = self.cpu.ram.to_vec();
+            self.ram_changed = vec![None; self.ram_shadow.len()];
+            return;
         }
 
-        // Step the CPU.
-        self.cpu.step();
+        let now = Instant::now();
+        for ((shadow, &word), changed) in self
+            .ram_shadow
+            .iter_mut()
+            .zip(self.cpu.ram.iter())
+            .zip(self.ram_changed.iter_mut())
+        {
+            if *shadow != word {
+                *shadow = word;
+                *changed = Some(now);
+                self.ram_last_changed = Some(now);
+            }
+        }
     }
 
-    pub fn execute_command(&mut self) {
-        self.command_result = self.execute_command_with_result();
-        self.command_buffer.clear();
+    // Cancel a pending LOAD or STEP, or halt the simulation if it is running. An exception is
+    // dismissed before anything else is cancelled.
+    pub fn cancel(&mut self) {
+        if self.exception.take().is_some() {
+            return;
+        }
+        if let Some(message) = self.cancel_pending() {
+            self.command_result = Ok(message);
+        }
     }
 
-    pub fn execute_command_with_result(&mut self) -> Result<String> {
-        // Build a whole bunch of regexes which are used to match commands.
-        let run_regex = RegexBuilder::new(r"^\s*run\s*$")
-            .case_insensitive(true)
-            .build()
-            .unwrap();
-        let halt_regex = RegexBuilder::new(r"^\s*halt\s*$")
-            .case_insensitive(true)
-            .build()
-            .unwrap();
-        let step_regex = RegexBuilder::new(r"^\s*step(?:\s+(?:(?<decimal_literal>[0-9]+)|0x(?<hex_literal>[0-9a-f]+)|0b(?<binary_literal>[01]+)))?\s*$").case_insensitive(true).build().unwrap();
-        let load_regex = RegexBuilder::new(r"^\s*load(?:\s+(?:(?<decimal_literal>[0-9]+)|0x(?<hex_literal>[0-9a-f]+)|0b(?<binary_literal>[01]+)))?\s+(?<filename>.+)\s*$")
-            .case_insensitive(true)
-            .build()
-            .unwrap();
-
-        // HACK: There's a tonne of repeated code in here for parsing numeric literals. A lot of
-        // the error messages also offer... questionable levels of clarity.
-        if run_regex.is_match(&self.command_buffer) {
-            self.running = true;
-            Ok("Running simulation.".into())
-        } else if halt_regex.is_match(&self.command_buffer) {
+    // Cancel whatever is currently in progress, returning a description of what was cancelled.
+    // Since steps are never interrupted partway through, and a cancelled LOAD never touches RAM,
+    // the machine is always left in a consistent state.
+    fn cancel_pending(&mut self) -> Option<String> {
+        if let Some(load) = self.pending_load.take() {
+            load.cancel();
+            Some(format!(
+                "Cancelled loading {} after reading {} bytes.",
+                load.filename, load.read
+            ))
+        } else if let Some(build) = self.pending_build.take() {
+            let message = format!("Cancelled building with {}.", build.command);
+            build.cancel();
+            Some(message)
+        } else if let Some((done, total)) = self.pending_steps.take() {
+            Some(format!(
+                "Cancelled after stepping simulation {:#06x} of {:#06x} times, at {:#06x}.",
+                done, total, self.cpu.program_counter
+            ))
+        } else if let Some(pending) = self.pending_condition.take() {
+            Some(format!(
+                "Cancelled after stepping simulation {:#06x} times, at {:#06x}.",
+                pending.steps, self.cpu.program_counter
+            ))
+        } else if let Some(turbo) = self.turbo.take() {
+            Some(format!(
+                "Cancelled after turbo stepping simulation {:#06x} times, at {:#06x}.",
+                turbo.steps, self.cpu.program_counter
+            ))
+        } else if self.running {
             self.running = false;
-            Ok(format!(
-                "Simulation halted at {:#06x}.",
-                self.cpu.program_counter
+            Some(format!(
+                "Simulation halted at {:#06x} after executing {} instructions.",
+                self.cpu.program_counter, self.run_count
             ))
-        } else if let Some(caps) = step_regex.captures(&self.command_buffer) {
-            let step_size = if let Some(decimal_literal) = caps.name("decimal_literal") {
-                u16::from_str_radix(decimal_literal.as_str(), 10)?
-            } else if let Some(hex_literal) = caps.name("hex_literal") {
-                u16::from_str_radix(hex_literal.as_str(), 16)?
-            } else if let Some(binary_literal) = caps.name("binary_literal") {
-                u16::from_str_radix(binary_literal.as_str(), 2)?
-            } else {
-                1
-            };
+        } else {
+            None
+        }
+    }
 
-            for _ in 0..step_size {
-                self.step()
+    // Step the simulation up to a given number of times, stopping early if it is halted. The
+    // number of steps taken is returned, along with whether or not the simulation was halted.
+    fn step_up_to(&mut self, count: u16) -> (u16, bool) {
+        for step in 0..count {
+            if self.step() {
+                return (step + 1, true);
             }
+        }
+        (count, false)
+    }
 
-            Ok(format!("Stepping simulation {:#06x} times.", step_size))
-        } else if let Some(caps) = load_regex.captures(&self.command_buffer) {
-            let address = if let Some(decimal_literal) = caps.name("decimal_literal") {
-                u16::from_str_radix(decimal_literal.as_str(), 10)?
-            } else if let Some(hex_literal) = caps.name("hex_literal") {
-                u16::from_str_radix(hex_literal.as_str(), 16)?
-            } else if let Some(binary_literal) = caps.name("binary_literal") {
-                u16::from_str_radix(binary_literal.as_str(), 2)?
-            } else {
-                0
+    // Produce the result of a STEP which was halted after a given number of steps, incorporating
+    // the reason for the halt which was left in the command result.
+    fn halted_result(&mut self, steps: u64) -> Result<String> {
+        let stepped = format!("Stepped simulation {:#06x} times.", steps);
+        match std::mem::replace(&mut self.command_result, Ok(String::new())) {
+            Ok(message) => Ok(format!("{} {}", stepped, message)),
+            Err(error) => Err(anyhow!("{} {}", stepped, error)),
+        }
+    }
+
+    // Begin turbo stepping when its key is pressed, or keep it going when the key repeats. The
+    // first press takes a step straight away, so that a tap of the key is a single step.
+    pub fn hold_turbo(&mut self) {
+        let now = Instant::now();
+        if let Some(turbo) = &mut self.turbo {
+            turbo.held_until = now + TURBO_REPEAT;
+            return;
+        }
+        // Turbo stepping is just another way of using STEP.
+        if let Some(Err(error)) = self.profile.as_ref().map(|profile| profile.check("STEP")) {
+            self.command_result = Err(error);
+            return;
+        }
+        if self.busy() {
+            self.command_result = Err(anyhow!(
+                "Turbo stepping can't begin until whatever is in progress finishes. Press Esc to cancel it."
+            ));
+            return;
+        }
+        self.turbo = Some(Turbo {
+            pressed: now,
+            held_until: now + TURBO_FIRST_REPEAT,
+            last: now,
+            owed: 1.0,
+            steps: 0,
+        });
+        self.advance_turbo();
+    }
+
+    // Stop turbo stepping when its key is let go, on terminals which report it.
+    pub fn release_turbo(&mut self) {
+        if let Some(turbo) = self.turbo.take() {
+            self.command_result = Ok(self.turbo_result(&turbo));
+        }
+    }
+
+    // Describe turbo stepping once it has stopped.
+    fn turbo_result(&self, turbo: &Turbo) -> String {
+        format!(
+            "Turbo stepped simulation {:#06x} times, to {:#06x}.",
+            turbo.steps, self.cpu.program_counter
+        )
+    }
+
+    // Take whichever turbo steps have come due since the last were taken.
+    fn advance_turbo(&mut self) {
+        let Some(mut turbo) = self.turbo.take() else {
+            return;
+        };
+        let now = Instant::now();
+        if now >= turbo.held_until {
+            self.command_result = Ok(self.turbo_result(&turbo));
+            return;
+        }
+
+        let doublings =
+            now.duration_since(turbo.pressed).as_secs_f64() / TURBO_DOUBLING.as_secs_f64();
+        let rate = TURBO_RATE * doublings.exp2();
+        // Steps which couldn't be taken in time aren't made up for later, since that would only
+        // make the rate overshoot once it is no longer capped by the batch.
+        turbo.owed = (turbo.owed + rate * now.duration_since(turbo.last).as_secs_f64())
+            .min(f64::from(STEP_BATCH));
+        turbo.last = now;
+        let count = turbo.owed as u16;
+        turbo.owed -= f64::from(count);
+
+        let (taken, halted) = self.step_up_to(count);
+        turbo.steps += u64::from(taken);
+        if halted {
+            self.command_result = self.halted_result(turbo.steps);
+            return;
+        }
+        self.command_result = Ok(format!(
+            "Turbo stepping at {:.0} steps a second. Let go to stop.",
+            rate
+        ));
+        self.turbo = Some(turbo);
+    }
+
+    // Advance a STEPWHILE or STEPUNTIL by up to a number of steps, returning its result if it
+    // finished, in which case it is no longer pending.
+    fn advance_condition(&mut self, count: u64) -> Option<Result<String>> {
+        let mut pending = self.pending_condition.take()?;
+        for _ in 0..count {
+            // NOTE: The instruction at which stepping began is never stopped in front of, so that
+            // RUNUNTIL from one call finds the next.
+            if let Condition::Next(until) = &pending.condition {
+                if pending.steps > 0 && until.matches(&self.cpu, self.abi.link) {
+                    return Some(Ok(format!(
+                        "Stepped simulation {:#06x} times, to {} at {:#06x}.",
+                        pending.steps,
+                        until.describe(),
+                        self.cpu.program_counter
+                    )));
+                }
+            }
+            let halted = self.step();
+            pending.steps += 1;
+            if halted {
+                return Some(self.halted_result(pending.steps));
+            }
+            if let Condition::Expression { expression, until } = &pending.condition {
+                if expression.holds(&self.cpu) == *until {
+                    return Some(Ok(format!(
+                        "Stepped simulation {:#06x} times, until {} was {}.",
+                        pending.steps, expression.text, until
+                    )));
+                }
+            }
+        }
+        self.pending_condition = Some(pending);
+        None
+    }
+
+    // Begin stepping until a condition is met. Interactively, this is spread across frames, but
+    // otherwise it has to finish before the next command, so it is abandoned if it goes on too
+    // long.
+    fn step_until(&mut self, condition: Condition) -> Result<String> {
+        self.pending_condition = Some(PendingCondition {
+            condition,
+            steps: 0,
+        });
+        if self.interactive && !self.executing_actions {
+            return match self.advance_condition(u64::from(STEP_BATCH)) {
+                Some(result) => result,
+                None => Ok("Stepping simulation until the condition is met.".into()),
             };
+        }
+        match self.advance_condition(CONDITION_LIMIT) {
+            Some(result) => result,
+            None => {
+                let pending = self.pending_condition.take().unwrap();
+                Err(match pending.condition {
+                    Condition::Expression { expression, until } => anyhow!(
+                        "Stepped simulation {:#06x} times, but {} was never {}.",
+                        pending.steps,
+                        expression.text,
+                        until
+                    ),
+                    Condition::Next(until) => anyhow!(
+                        "Stepped simulation {:#06x} times, but never came to {}.",
+                        pending.steps,
+                        until.describe()
+                    ),
+                })
+            }
+        }
+    }
 
-            let bytes = fs::read(&caps["filename"])?;
-            let words = bytes
-                .chunks_exact(2)
-                .map(|c| u16::from_ne_bytes([c[1], c[0]]))
-                .collect::<Vec<_>>();
-            self.cpu.ram[usize::from(address)..(usize::from(address) + bytes.len() / 2)]
-                .copy_from_slice(&words);
+    // Move the program counter on behalf of a command, noting the move in the instruction history
+    // so that it's clear the program didn't get there by itself.
+    fn move_program_counter(&mut self, command: &str, address: u16) {
+        let description = format!("{} -> {:#06x}", command, address);
+        self.instruction_history
+            .push(History::Intervention(description));
+        self.cpu.program_counter = address;
+    }
 
-            Ok(format!(
-                "Loaded {:#06x} words from {} into RAM at address {:#06x}.",
-                words.len(),
-                &caps["filename"],
-                address
-            ))
+    // Step the simulation, returning whether or not it was halted, either by hitting a breakpoint or
+    // by diverging from the reference CPU. In either case, the reason is left in the command
+    // result.
+    pub fn step(&mut self) -> bool {
+        self.exception = None;
+
+        // While stepping, interrupts are held off if that's what was asked for. Whether they're
+        // held off changes what stepping forward from a checkpoint would do, so a change begins a
+        // new checkpoint.
+        let stepping = !self.running;
+        let held = stepping && self.interrupt_stepping == Stepping::Mask;
+        if self.cpu.interrupts.held != held {
+            self.cpu.interrupts.held = held;
+            self.timeline.invalidate();
+        }
+        if let Some(reference) = &mut self.reference {
+            reference.interrupts.held = held;
+        }
+
+        // Update the instruction history.
+        let instruction = self.cpu.peek(self.cpu.program_counter);
+        let immediate = self.cpu.peek(self.cpu.program_counter.wrapping_add(1));
+        self.instruction_history
+            .push(History::Executed(instruction, immediate));
+
+        // Any warning has to be worked out before the instruction changes the state it depends on.
+        // Executing an immediate as an instruction is never what was meant, so it's noted even when
+        // warnings are off.
+        let address = self.cpu.program_counter;
+        if let Some(owner) = self.starts.immediate_of(&self.cpu, address) {
+            if self.warned.insert(address) {
+                let warning = format!(
+                    "Warning at {:#06x}: executing the immediate of {} at {:#06x} as an instruction, so execution is misaligned.",
+                    address,
+                    self.disassembly(self.cpu.peek(owner), instruction).trim(),
+                    owner
+                );
+                self.log(warning);
+            }
+        }
+        self.starts.mark(&self.cpu, address);
+        if self.warnings && !self.warned.contains(&address) {
+            if let Some(warning) = warning::check(&self.cpu) {
+                self.warned.insert(address);
+                self.log(warning);
+            }
+        }
+        let overflow = match self.overflow {
+            Overflow::Off => None,
+            _ => warning::overflow(&self.cpu),
+        };
+        if let Some(overflow) = overflow
+            .as_ref()
+            .filter(|_| self.overflow == Overflow::Warn)
+        {
+            if self.warned.insert(address) {
+                self.log(overflow.clone());
+            }
+        }
+
+        if let Some(taint) = &mut self.taint {
+            taint.propagate(&self.cpu);
+        }
+        self.writers.record(&self.cpu, self.steps);
+        self.accesses.record(&self.cpu, self.steps);
+        self.timeline.record(self.steps, &self.cpu);
+
+        // Step the CPU, along with the reference CPU if we're comparing against one.
+        self.cpu.step();
+        self.steps += 1;
+        if let Some(line) = self.cpu.interrupts.taken {
+            self.instruction_history.push(History::Interrupt(line));
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.instructions += 1;
+        }
+        if let Some(Err(error)) = self.wave.as_mut().map(|wave| wave.sample(&self.cpu)) {
+            self.wave = None;
+            self.log(format!("Stopped recording the waveform: {}", error));
+        }
+        self.cost
+            .record(address, instruction, self.cpu.program_counter);
+        if let Some(line) = self
+            .measure
+            .as_mut()
+            .and_then(|measure| measure.arrive(&self.cpu))
+        {
+            self.log(line);
+        }
+        let divergence = self.reference.as_mut().and_then(|reference| {
+            reference.step();
+            // Only the output of the primary CPU's devices is of interest.
+            reference.devices.take_output();
+            reference.devices.take_trace();
+            reference.devices.take_debug();
+            reference.devices.take_requests();
+            reference.devices.take_transmitted();
+            find_divergence(&self.cpu, reference)
+        });
+
+        // Anything which the devices have produced is logged to the console as well, along with
+        // any traced accesses to them.
+        for line in self.cpu.devices.take_trace() {
+            self.device_trace.push_back(line.clone());
+            if self.device_trace.len() > 0x10000 {
+                self.device_trace.pop_front();
+            }
+            self.log(line);
+        }
+        for line in self.cpu.devices.take_output() {
+            self.log(line);
+        }
+        for line in debug_output(&mut self.cpu, self.packing) {
+            self.log(line);
+        }
+        self.terminal.feed(&self.cpu.devices.take_transmitted());
+        let exit = self.serve_requests();
+
+        // Log the output of any tracepoint that we've arrived at. The format string was validated
+        // when the tracepoint was set, so expanding it can't fail.
+        if let Some(format) = self.tracepoints.get(&self.cpu.program_counter) {
+            let line = format_trace(format, &self.cpu).unwrap();
+            self.log(line);
+        }
+
+        // Halt the simulation if we've arrived at a breakpoint, unless it is disabled or being
+        // ignored. Temporary breakpoints are only good for a single halt.
+        let program_counter = self.cpu.program_counter;
+        let halt = match self.breakpoints.get_mut(&program_counter) {
+            Some(breakpoint) => breakpoint.hit(),
+            None => false,
+        };
+        if halt {
+            let actions = self.breakpoints[&program_counter].actions.clone();
+            if self.breakpoints[&program_counter].temporary {
+                self.breakpoints.remove(&program_counter);
+                // NOTE: Failing to update the session file isn't worth interrupting the user
+                // over, particularly in the middle of a run.
+                let _ = self.save_session();
+            }
+            self.running = false;
+            self.command_result = Ok(format!(
+                "Breakpoint hit at {:#06x}.",
+                self.cpu.program_counter
+            ));
+            if let Some(actions) = actions {
+                self.run_actions(program_counter, &actions);
+            }
+        }
+
+        // A fault means that the program has done something that the machine doesn't allow, which
+        // is more important still than either a breakpoint or a divergence.
+        if let Some(fault) = self.cpu.fault.take() {
+            let message = format!("Faulted at {:#06x}: {}.", address, fault);
+            self.raise(address, instruction, immediate, message);
+            return true;
+        }
+
+        // An assertion which no longer holds is the first violation of something which the user
+        // has said must always be true, so it's reported just like a fault.
+        if let Some(index) = self
+            .assertions
+            .iter()
+            .position(|assertion| !assertion.holds(&self.cpu))
+        {
+            let message = format!(
+                "Assertion {} failed after {:#06x}: {}.",
+                index + 1,
+                address,
+                self.assertions[index].text
+            );
+            self.raise(address, instruction, immediate, message);
+            return true;
+        }
+
+        // A guest which has asked to exit has finished, so there's nothing more to stop for.
+        if let Some(exit) = exit {
+            self.running = false;
+            self.command_result = exit;
+            return true;
+        }
+
+        // An overflow only halts the simulation once the instruction has completed, so that its
+        // result can be inspected.
+        if let Some(overflow) = overflow.filter(|_| self.overflow == Overflow::Halt) {
+            self.running = false;
+            self.command_result = Err(anyhow!(overflow));
+            return true;
+        }
+
+        // A divergence from the reference CPU takes precedence over any breakpoint, since it's
+        // almost certainly what the user is looking for.
+        if let Some(divergence) = divergence {
+            self.running = false;
+            self.command_result = Err(anyhow!(
+                "Diverged from the reference at {:#06x}: {}.",
+                program_counter,
+                divergence
+            ));
+            return true;
+        }
+
+        // Stepping into a handler stops at its entry, as though there were a breakpoint there,
+        // unless interrupts are being held off instead.
+        if let Some(line) = self.cpu.interrupts.taken.filter(|_| stepping) {
+            if !halt {
+                self.command_result = Ok(format!(
+                    "Stopped at the entry of the handler for irq {} at {:#06x}.",
+                    line, self.cpu.program_counter
+                ));
+            }
+            return true;
+        }
+
+        halt
+    }
+
+    // Append a line to the console. Make sure that it doesn't grow too large in the same lazy
+    // way as the instruction history.
+    pub fn log(&mut self, line: String) {
+        self.console.push_back(line);
+        if self.console.len() > 0xff {
+            self.console.pop_front();
+        }
+    }
+
+    // Execute the command list attached to a breakpoint. Actions are not executed if a breakpoint
+    // is hit while another breakpoint's actions are already being executed, as otherwise a pair of
+    // breakpoints which step into each other would recurse forever.
+    fn run_actions(&mut self, address: u16, actions: &str) {
+        if self.executing_actions {
+            return;
+        }
+        self.executing_actions = true;
+
+        let mut count = 0;
+        for action in actions
+            .split(';')
+            .filter(|action| !action.trim().is_empty())
+        {
+            if let Err(error) = self.execute_command_with_result(action) {
+                self.command_result = Err(anyhow!(
+                    "Breakpoint action \"{}\" at {:#06x} failed: {}",
+                    action.trim(),
+                    address,
+                    error
+                ));
+                self.executing_actions = false;
+                return;
+            }
+            count += 1;
+        }
+
+        self.command_result = Ok(format!(
+            "Breakpoint hit at {:#06x}. Executed {} action{}.",
+            address,
+            count,
+            if count == 1 { "" } else { "s" }
+        ));
+        self.executing_actions = false;
+    }
+
+    // Carry out whatever the guest has asked of the emulator through its hypercall ports, returning
+    // the result with which the simulation halts if it asked to exit. A snapshot which can't be
+    // saved, or which would overwrite an existing file, is only noted in the console, since the
+    // guest has no way to hear about it.
+    fn serve_requests(&mut self) -> Option<Result<String>> {
+        let mut exit = None;
+        for (name, request) in self.cpu.devices.take_requests() {
+            match request {
+                Request::Snapshot(filename) => {
+                    let plain = !filename.is_empty()
+                        && Path::new(&filename).file_name() == Some(filename.as_ref());
+                    // NOTE: Nothing which already exists is ever overwritten, since the working
+                    // directory holds the image and its session, which the guest has no business
+                    // replacing.
+                    let line = if !plain {
+                        format!(
+                            "[{}] Refused to save a snapshot to \"{}\", which isn't a plain file name.",
+                            name, filename
+                        )
+                    } else if Path::new(&filename).exists() {
+                        format!(
+                            "[{}] Refused to save a snapshot to {}, which already exists.",
+                            name, filename
+                        )
+                    } else {
+                        match snapshot::save(&self.cpu, &filename, None) {
+                            Ok(size) => format!(
+                                "[{}] Saved a snapshot to {} ({} bytes).",
+                                name, filename, size
+                            ),
+                            Err(error) => format!(
+                                "[{}] Failed to save a snapshot to {}: {}",
+                                name, filename, error
+                            ),
+                        }
+                    };
+                    self.log(line);
+                }
+                Request::Exit { status, message } => {
+                    let mut result = format!(
+                        "The guest exited with status {:#06x} at {:#06x}",
+                        status, self.cpu.program_counter
+                    );
+                    if !message.is_empty() {
+                        result.push_str(&format!(": {}", message));
+                    }
+                    result.push('.');
+                    exit = Some(match status {
+                        0x0000 => Ok(result),
+                        _ => Err(anyhow!(result)),
+                    });
+                }
+            }
+        }
+        exit
+    }
+
+    // A note to add to a message about an address, if it's known to hold the immediate of an
+    // instruction rather than to begin one of its own, or nothing otherwise.
+    fn misalignment(&self, address: u16) -> String {
+        match self.starts.immediate_of(&self.cpu, address) {
+            Some(owner) => format!(
+                " Note that {:#06x} holds the immediate of {} at {:#06x}, so it's only executed if execution is misaligned.",
+                address,
+                self.disassembly(self.cpu.peek(owner), self.cpu.peek(address)).trim(),
+                owner
+            ),
+            None => String::new(),
+        }
+    }
+
+    // Halt the simulation, showing the state of the machine as the instruction at an address left
+    // it.
+    fn raise(&mut self, address: u16, instruction: u16, immediate: u16, message: String) {
+        self.running = false;
+        let length = usize::from(instruction_length(instruction));
+        self.exception = Some(Exception::new(
+            &self.cpu,
+            &self.abi,
+            address,
+            [instruction, immediate][..length].to_vec(),
+            self.disassembly(instruction, immediate),
+            message.clone(),
+        ));
+        self.command_result = Err(anyhow!(message));
+    }
+
+    // Replace the problems shown in the diagnostics pane, selecting the first of them. The pane is
+    // shown if there are any, since they're presumably what the user wants to look at next.
+    fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+        self.select_diagnostic(0);
+        if !self.diagnostics.is_empty() {
+            self.diagnostics_pane = true;
+        }
+    }
+
+    // Select a problem in the diagnostics pane, showing the source around the line at fault.
+    fn select_diagnostic(&mut self, index: usize) {
+        self.diagnostic = index;
+        self.excerpt = self
+            .diagnostics
+            .get(index)
+            .map(Diagnostic::excerpt)
+            .unwrap_or_default();
+    }
+
+    // Reset the machine, as with RESET.
+    fn reset(&mut self) {
+        self.exception = None;
+        // Anything in progress belongs to the old run, and the history describes how the old state
+        // was reached, so neither makes sense after a reset.
+        self.running = false;
+        self.pending_steps = None;
+        self.pending_condition = None;
+        self.instruction_history.clear();
+        self.warned.clear();
+        self.writers.reset_registers();
+        // The counters are zeroed by a reset, so a traversal in progress can't be measured.
+        if let Some(measure) = &mut self.measure {
+            measure.disarm();
+        }
+        self.cpu.reset();
+        if let Some(reference) = &mut self.reference {
+            reference.reset();
+        }
+    }
+
+    // Complete a LOAD once the contents of the file are in hand, by writing them into RAM and
+    // restoring any session associated with the image.
+    fn finish_load(&mut self, filename: &str, address: u16, bytes: &[u8]) -> Result<String> {
+        // Loading some other image leaves the open project behind, along with its session file.
+        if self
+            .project
+            .as_ref()
+            .is_some_and(|project| project.image != filename)
+        {
+            self.project = None;
+        }
+        let (words, checksum) =
+            write_image(&mut self.cpu, address, filename, bytes, self.byte_order)?;
+        self.steps = 0;
+        self.exception = None;
+        self.writers.clear();
+        self.starts.clear();
+        self.accesses.clear();
+        self.timeline.clear();
+        if let Some(measure) = &mut self.measure {
+            measure.disarm();
+        }
+
+        self.image = Some(filename.to_string());
+        self.image_checksum = Some(checksum);
+        let restored = match self.restore_session()? {
+            Some(message) => format!(" {}", message),
+            None => String::new(),
+        };
+
+        Ok(format!(
+            "Loaded {:#06x} words from {} (CRC-32 {:#010x}) into RAM at address {:#06x}.{}",
+            words, filename, checksum, address, restored
+        ))
+    }
+
+    // Write the session file associated with the current image, so that the debugging setup can
+    // be restored the next time that the image is loaded. The session file simply consists of
+    // commands, which are executed in order when it is restored.
+    fn save_session(&self) -> Result<()> {
+        if self.restoring {
+            return Ok(());
+        }
+        if let Some(image) = &self.image {
+            let mut session = self
+                .breakpoints
+                .iter()
+                .map(|(address, breakpoint)| format!("{}\n", breakpoint.command(*address)))
+                .collect::<String>();
+            for (address, format) in &self.tracepoints {
+                session.push_str(&format!("TRACEPOINT {:#06x} {}\n", address, quote(format)));
+            }
+            for (name, address) in &self.bookmarks {
+                session.push_str(&format!("MARK {} {:#06x}\n", name, address));
+            }
+            if let Some(address) = self.ram_pin {
+                session.push_str(&format!("RAM PIN {:#06x}\n", address));
+            }
+            // Types are defined before the types and watches which use them.
+            let mut defined = Vec::new();
+            for record in self.types.values() {
+                define_type(&mut session, &mut defined, record);
+            }
+            for watch in &self.watches {
+                session.push_str(&format!("WATCHEXPR {}\n", watch.text));
+            }
+            for assertion in &self.assertions {
+                session.push_str(&format!("ASSERT {}\n", assertion.text));
+            }
+            // The checksum of the image is recorded in a comment, so that we can tell if the image
+            // has changed since the session was saved.
+            if let Some(checksum) = self.image_checksum {
+                session.insert_str(0, &format!("; crc32 {:#010x}\n", checksum));
+            }
+            fs::write(self.session_path(image), session)?;
+        }
+        Ok(())
+    }
+
+    // Restore the session file associated with the current image, if there is one, returning a
+    // message describing the restored session. A line which can no longer be executed, perhaps
+    // because the image has changed, is skipped and noted in the message, so that the rest of the
+    // session is still restored, and it's dropped from the session file when it's saved again.
+    fn restore_session(&mut self) -> Result<Option<String>> {
+        let Some(image) = self.image.clone() else {
+            return Ok(None);
+        };
+        let path = self.session_path(&image);
+        let Ok(session) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+
+        let mut message = format!("Restored session from {}.", path);
+        let mut skipped = Vec::new();
+        self.breakpoints.clear();
+        self.tracepoints.clear();
+        self.ram_pin = None;
+        self.bookmarks.clear();
+        self.types.clear();
+        self.watches.clear();
+        self.assertions.clear();
+        for line in session
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            // Lines beginning with a semicolon are comments, one of which may record the checksum
+            // of the image at the time that the session was saved.
+            if let Some(comment) = line.strip_prefix(';') {
+                let checksum = comment
+                    .trim()
+                    .strip_prefix("crc32 0x")
+                    .and_then(|c| u32::from_str_radix(c, 16).ok());
+                if checksum.is_some() && checksum != self.image_checksum {
+                    message.push_str(&format!(
+                        " Warning: {} has changed since the session was saved.",
+                        image
+                    ));
+                }
+                continue;
+            }
+            self.restoring = true;
+            let result = self.execute_command_with_result(line);
+            self.restoring = false;
+            if let Err(error) = result {
+                skipped.push(format!("\"{}\" ({})", line, error));
+            }
+        }
+        if !skipped.is_empty() {
+            message.push_str(&format!(
+                " Skipped {} line(s) which failed: {}.",
+                skipped.len(),
+                skipped.join(", ")
+            ));
+        }
+        self.save_session()?;
+        Ok(Some(message))
+    }
+
+    // The path of the session file associated with an image.
+    fn session_path(&self, image: &str) -> String {
+        match self
+            .project
+            .as_ref()
+            .and_then(|project| project.session.as_ref())
+        {
+            Some(session) => session.clone(),
+            None => format!("{}.ilo-session", image),
+        }
+    }
+
+    // Open a project, by loading its image and then bookmarking its symbols, returning a message
+    // describing what was opened. The parts of the project which describe the machine have already
+    // been applied to the configuration.
+    pub fn open(&mut self, project: Project) -> Result<String> {
+        self.project = Some(project);
+        self.load_project()
+    }
+
+    // Load the image of the open project, and bookmark its symbols.
+    fn load_project(&mut self) -> Result<String> {
+        let Some(project) = &self.project else {
+            return Err(anyhow!("No project is open."));
+        };
+        let (image, load, symbols, path) = (
+            project.image.clone(),
+            project.load,
+            project.symbols.clone(),
+            project.path.clone(),
+        );
+        let mut message = self.finish_load(&image, load, &fs::read(&image)?)?;
+
+        if let Some(symbols) = &symbols {
+            let contents = fs::read_to_string(symbols)
+                .map_err(|e| anyhow!("Failed to read {}: {}", symbols, e))?;
+            let mut count = 0;
+            for (number, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with(';') {
+                    continue;
+                }
+                self.execute_command_with_result(&format!("MARK {}", line))
+                    .map_err(|e| anyhow!("{}:{}: {}", symbols, number + 1, e))?;
+                count += 1;
+            }
+            message.push_str(&format!(
+                " Bookmarked {} symbol(s) from {}.",
+                count, symbols
+            ));
+        }
+        self.remember(Recent::Project { path });
+        Ok(message)
+    }
+
+    // Complete a BUILD once the build has exited, by showing whatever problems it reported, and
+    // then reloading the open project and resetting the machine if it succeeded.
+    fn finish_build(
+        &mut self,
+        build: PendingBuild,
+        status: io::Result<ExitStatus>,
+    ) -> Result<String> {
+        let command = build.command;
+        let status = status.map_err(|e| anyhow!("Failed to run {}: {}", command, e))?;
+        let problems = build.diagnostics.len();
+        self.set_diagnostics(build.diagnostics);
+        if !status.success() {
+            return Err(anyhow!(
+                "The build failed ({}), reporting {} problem(s). Its output is in the console.",
+                status,
+                problems
+            ));
+        }
+        // NOTE: Some other image may have been loaded while the build was running, in which case
+        // the project has been left behind, and there's nothing to reload.
+        if self.project.is_none() {
+            return Err(anyhow!(
+                "The build succeeded, but the project is no longer open, so nothing was reloaded."
+            ));
+        }
+        let message = self.load_project()?;
+        self.reset();
+        Ok(format!(
+            "Built with {}. {} Reset the machine. Execution begins at {:#06x}.",
+            command, message, self.cpu.program_counter
+        ))
+    }
+
+    // Open something which was recently opened again, in just the same way, returning a message
+    // describing what was opened. The file is read straight away, rather than on a worker thread,
+    // since it has presumably been opened before without trouble.
+    fn reopen(&mut self, recent: Recent) -> Result<String> {
+        // NOTE: This is a load just like LOAD, so a profile which denies LOAD denies it as well.
+        if let Some(profile) = &self.profile {
+            profile.check("LOAD")?;
+        }
+        if self.pending_load.is_some() {
+            return Err(anyhow!(
+                "Another image is still being loaded. Use CANCEL to abandon it."
+            ));
+        }
+        match recent {
+            Recent::Image { path, load } => {
+                let bytes = fs::read(&path)?;
+                let message = self.finish_load(&path, load, &bytes)?;
+                self.remember(Recent::Image { path, load });
+                Ok(message)
+            }
+            Recent::Project { path } => {
+                let project = Project::read(&path)?;
+                // NOTE: The machine can't be rebuilt out from under a running session, so a
+                // project which describes its own machine has to be opened afresh.
+                if project.machine.is_some() {
+                    return Err(anyhow!(
+                        "{} describes its own machine, so it can only be opened with ilo open.",
+                        path
+                    ));
+                }
+                if let Some(abi) = &project.abi {
+                    self.abi = Abi::new(abi)?;
+                }
+                if let Some(order) = project.byte_order {
+                    self.byte_order = order;
+                }
+                self.open(project)
+            }
+        }
+    }
+
+    // Move something which was just opened to the front of the recent files.
+    fn remember(&mut self, recent: Recent) {
+        self.opened = Some(recent.clone());
+        // NOTE: Failing to update the recent files isn't worth interrupting the user over.
+        let _ = recent::remember(recent);
+    }
+
+    pub fn execute_command(&mut self) {
+        let command = std::mem::take(&mut self.command_buffer);
+        self.command_cursor = 0;
+        self.interactive = true;
+        self.command_result = self.execute_command_with_result(&command);
+        self.interactive = false;
+    }
+
+    // Execute a command bound to a key. This is interactive, just like a command typed at the
+    // prompt, but leaves whatever is being typed at the prompt alone.
+    pub fn execute_hotkey(&mut self, command: &str) {
+        self.interactive = true;
+        self.command_result = self.execute_command_with_result(command);
+        self.interactive = false;
+    }
+
+    // Insert a character into the command buffer at the cursor, leaving the cursor after it.
+    pub fn insert_char(&mut self, char: char) {
+        self.command_buffer.insert(self.command_cursor, char);
+        self.command_cursor += char.len_utf8();
+    }
+
+    // Insert text pasted into the terminal at the cursor. Every complete line of a multi-line paste
+    // is executed in turn, as though Enter had been pressed after it, and whatever follows the last
+    // newline is left at the prompt. Only the last line is executed interactively, since the lines
+    // before it must finish before the ones that follow them can be executed.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let mut lines = text.split('\n').collect::<Vec<_>>();
+        let rest = lines.pop().unwrap_or_default();
+
+        let count = lines.len();
+        for (i, line) in lines.into_iter().enumerate() {
+            self.insert_str(line);
+            if i + 1 == count {
+                self.execute_command();
+                break;
+            }
+            let command = std::mem::take(&mut self.command_buffer);
+            self.command_cursor = 0;
+            if let Err(error) = self.execute_command_with_result(&command) {
+                self.command_result = Err(anyhow!(
+                    "Line {} of the paste failed, so the rest was not executed: {}",
+                    i + 1,
+                    error
+                ));
+                return;
+            }
+        }
+        self.insert_str(rest);
+    }
+
+    // Insert text at the cursor. Tabs become spaces, and other control characters are dropped,
+    // since the prompt has no way of displaying them.
+    fn insert_str(&mut self, text: &str) {
+        for char in text.chars() {
+            match char {
+                '\t' => self.insert_char(' '),
+                _ if char.is_control() => {}
+                _ => self.insert_char(char),
+            }
+        }
+    }
+
+    // Delete the character before the cursor, as Backspace does.
+    pub fn delete_before_cursor(&mut self) {
+        if let Some(char) = self.command_buffer[..self.command_cursor]
+            .chars()
+            .next_back()
+        {
+            self.command_cursor -= char.len_utf8();
+            self.command_buffer.remove(self.command_cursor);
+        }
+    }
+
+    // Delete the character under the cursor, as Delete does.
+    pub fn delete_at_cursor(&mut self) {
+        if self.command_cursor < self.command_buffer.len() {
+            self.command_buffer.remove(self.command_cursor);
+        }
+    }
+
+    // Move the cursor one character to the left or right, stopping at either end of the buffer.
+    pub fn move_command_cursor(&mut self, forward: bool) {
+        let char = if forward {
+            self.command_buffer[self.command_cursor..].chars().next()
         } else {
-            Err(anyhow!(
-                "\"{}\" is not a valid command. Supported commands are RUN, HALT, STEP, SET, and LOAD.",
-                self.command_buffer.trim()
-            ))
+            self.command_buffer[..self.command_cursor]
+                .chars()
+                .next_back()
+        };
+        match char {
+            Some(char) if forward => self.command_cursor += char.len_utf8(),
+            Some(char) => self.command_cursor -= char.len_utf8(),
+            None => {}
+        }
+    }
+
+    // Handle a click at a position on the screen. A click on the minimap pins the RAM pane at the
+    // start of the block of memory which was clicked.
+    pub fn click(&mut self, column: u16, row: u16) {
+        let area = self.minimap_area.get();
+        if !self.minimap || !area.contains(Position { x: column, y: row }) {
+            return;
+        }
+        let Some(x) = (column - area.x).checked_sub(MINIMAP_LABEL_WIDTH) else {
+            return;
+        };
+        let y = row - area.y;
+        if x >= MINIMAP_COLUMNS || y >= MINIMAP_COLUMNS {
+            return;
+        }
+
+        let address = (y * MINIMAP_COLUMNS + x) * BLOCK_SIZE as u16;
+        self.ram_pin = Some(address);
+        // NOTE: Failing to update the session file isn't worth interrupting the user over.
+        let _ = self.save_session();
+        self.command_result = Ok(format!("The RAM pane is pinned at {:#06x}.", address));
+    }
+
+    // Move to the next or previous match for the current search, wrapping around the address
+    // space, and pin the RAM pane there.
+    // NOTE: Matches aren't remembered, but are found afresh each time, since the contents of memory
+    // may well have changed since the search was begun.
+    pub fn search_step(&mut self, forward: bool) -> Result<String> {
+        let Some((pattern, current)) = &self.search else {
+            return Err(anyhow!("Nothing has been searched for yet."));
+        };
+        let found = (1..=0x10000u32)
+            .map(|offset| {
+                if forward {
+                    current.wrapping_add(offset as u16)
+                } else {
+                    current.wrapping_sub(offset as u16)
+                }
+            })
+            .filter(|&address| self.cpu.memory.decode(address).is_some())
+            .find(|&address| pattern.is_match(&search_text(&self.cpu, &self.starts, address)));
+        let Some(address) = found else {
+            return Err(anyhow!("Nothing in memory matches /{}.", pattern));
+        };
+
+        let pattern = pattern.clone();
+        let count = (0..=0xffff)
+            .filter(|&a| self.cpu.memory.decode(a).is_some())
+            .filter(|&a| pattern.is_match(&search_text(&self.cpu, &self.starts, a)))
+            .count();
+        self.search = Some((pattern, address));
+        self.ram_pin = Some(address);
+
+        Ok(format!(
+            "Match at {:#06x}: {}. {} {} found. Press n or N for the next or previous.",
+            address,
+            search_text(&self.cpu, &self.starts, address),
+            count,
+            if count == 1 {
+                "match was"
+            } else {
+                "matches were"
+            }
+        ))
+    }
+
+    // Select or deselect the Registers pane. While it is selected, keys move the cursor between
+    // registers and edit them, rather than being typed at the command prompt.
+    pub fn toggle_register_pane(&mut self) {
+        if self.frozen.is_some() {
+            self.command_result = Err(anyhow!(
+                "Registers can't be edited while the view is frozen. Use THAW or press Ctrl-F first."
+            ));
+            return;
+        }
+        self.register_edit = None;
+        self.register_cursor = match self.register_cursor {
+            Some(_) => None,
+            None => Some(1),
+        };
+    }
+
+    // Move the register cursor up or down, wrapping around at either end.
+    pub fn move_register_cursor(&mut self, delta: isize) {
+        if let Some(cursor) = &mut self.register_cursor {
+            *cursor = cursor.wrapping_add_signed(delta + 0x20) % 0x20;
+        }
+    }
+
+    // Begin editing the register under the cursor.
+    pub fn begin_register_edit(&mut self) {
+        // Editing a register is just another way of using SET.
+        if let Some(Err(error)) = self.profile.as_ref().map(|profile| profile.check("SET")) {
+            self.command_result = Err(error);
+            return;
+        }
+        match self.register_cursor {
+            Some(0) => self.command_result = Err(anyhow!("r00 is always 0x0000.")),
+            Some(_) => self.register_edit = Some(String::new()),
+            None => {}
+        }
+    }
+
+    // Write the value being typed into the register under the cursor. If the value isn't valid,
+    // then editing continues, so that it may be corrected.
+    pub fn commit_register_edit(&mut self) {
+        let (Some(register), Some(edit)) = (self.register_cursor, &self.register_edit) else {
+            return;
+        };
+        match parse_signed_literal(edit.trim()) {
+            Ok(value) => {
+                self.cpu.set_register(register, value);
+                self.timeline.invalidate();
+                self.command_result = Ok(format!(
+                    "Set r{:02} to {:#06x} ({}).",
+                    register, value, value as i16
+                ));
+                self.register_edit = None;
+            }
+            Err(_) => {
+                self.command_result = Err(anyhow!(
+                "\"{}\" is not a valid value. Use decimal, which may be negative, or hexadecimal or binary with 0x or 0b.",
+                edit
+            ))
+            }
+        }
+    }
+
+    // Disassemble an instruction for display, reusing the text from the last time that it was
+    // disassembled, since the same few instructions are drawn over and over again every frame.
+    // NOTE: The disassembly depends on nothing but the words themselves, so nothing written to
+    // memory can make a cached line stale, and there's nothing to invalidate.
+    pub fn disassembly(&self, instruction: u16, immediate: u16) -> String {
+        // The immediate of an instruction which doesn't take one doesn't affect its disassembly.
+        let immediate = match instruction_length(instruction) {
+            1 => 0x0000,
+            _ => immediate,
+        };
+        let mut cache = self.disassembly_cache.borrow_mut();
+        // The cache is bounded by starting over once it fills, which is rare, since a program
+        // only contains so many distinct instructions.
+        if cache.len() >= DISASSEMBLY_CACHE_LENGTH {
+            cache.clear();
+        }
+        cache
+            .entry((instruction, immediate))
+            .or_insert_with(|| disassemble(instruction, immediate))
+            .clone()
+    }
+
+    // The CPU as the panes should show it, which is the live CPU unless the view is frozen.
+    pub fn shown_cpu(&self) -> &Cpu {
+        self.frozen.as_ref().map_or(&self.cpu, |frozen| &frozen.cpu)
+    }
+
+    pub fn shown_reference(&self) -> Option<&Cpu> {
+        match &self.frozen {
+            Some(frozen) => frozen.reference.as_ref(),
+            None => self.reference.as_ref(),
+        }
+    }
+
+    pub fn shown_history(&self) -> &InstructionHistory {
+        self.frozen
+            .as_ref()
+            .map_or(&self.instruction_history, |frozen| {
+                &frozen.instruction_history
+            })
+    }
+
+    pub fn shown_console(&self) -> &VecDeque<String> {
+        self.frozen
+            .as_ref()
+            .map_or(&self.console, |frozen| &frozen.console)
+    }
+
+    // Freeze the view if it is live, or make it live again if it is frozen.
+    pub fn toggle_frozen(&mut self) {
+        let command = if self.frozen.is_some() {
+            "THAW"
+        } else {
+            "FREEZE"
+        };
+        self.command_result = self.execute_command_with_result(command);
+    }
+
+    // Describe the registers as plain text, one per line, for copying elsewhere.
+    pub fn registers_text(&self) -> String {
+        let mut text = self
+            .cpu
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("r{:02}: {:#06x}\n", i, value))
+            .collect::<String>();
+        text.push_str(&format!("pc:  {:#06x}\n", self.cpu.program_counter));
+        text
+    }
+
+    // Describe the instruction history as plain text, with the next instruction first and the
+    // rest most recent first, just as the Instruction History pane shows it.
+    pub fn history_text(&self) -> String {
+        let next = (
+            self.cpu.peek(self.cpu.program_counter),
+            self.cpu.peek(self.cpu.program_counter.wrapping_add(1)),
+        );
+        let mut text = format!("{}\n", self.disassembly(next.0, next.1).trim_end());
+        for entry in self.instruction_history.iter().rev() {
+            match entry {
+                History::Executed(instruction, immediate) => {
+                    text.push_str(self.disassembly(*instruction, *immediate).trim_end())
+                }
+                History::Intervention(description) => text.push_str(&format!("; {}", description)),
+                History::Interrupt(line) => text.push_str(&format!("; irq {} taken", line)),
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // Take the region of RAM for HASH or VERIFY RAM, which is all of the RAM fitted unless an
+    // address and a length are given, along with a description of it.
+    fn hashed_region(&self, arguments: &mut Arguments) -> Result<(Range<usize>, String)> {
+        let region = match arguments.optional_literal("an address")? {
+            Some(address) => region::region(&self.cpu, address, arguments.literal("a length")?)?,
+            None => 0..self.cpu.memory.size,
+        };
+        arguments.finish()?;
+
+        let description = if region.len() == self.cpu.memory.size {
+            "RAM".to_string()
+        } else {
+            format!(
+                "{:#06x} words of RAM at {:#06x}",
+                region.len(),
+                region.start
+            )
+        };
+        Ok((region, description))
+    }
+
+    // Describe a range of RAM as plain text, eight words to a line, as the CPU would see it.
+    fn ram_text(&self, address: u16, length: u16) -> String {
+        (0..length)
+            .step_by(8)
+            .map(|offset| {
+                let start = address.wrapping_add(offset);
+                let words = (offset..length.min(offset.saturating_add(8)))
+                    .map(|i| format!("{:#06x}", self.cpu.peek(address.wrapping_add(i))))
+                    .collect::<Vec<_>>();
+                format!("{:#06x}: {}\n", start, words.join(" "))
+            })
+            .collect()
+    }
+
+    pub fn execute_command_with_result(&mut self, command: &str) -> Result<String> {
+        self.recent_commands.push_back(command.to_string());
+        if self.recent_commands.len() > 0x40 {
+            self.recent_commands.pop_front();
+        }
+
+        // Only commands which were asked for count towards the statistics, rather than those
+        // replayed from sessions or attached to breakpoints.
+        let stats = self.stats.as_mut().filter(|_| self.interactive);
+
+        // Searches take a regex rather than arguments, so they aren't tokenized at all.
+        if let Some(pattern) = command.trim_start().strip_prefix('/') {
+            if let Some(stats) = stats {
+                stats.record("/");
+            }
+            return self.search_command(pattern);
+        }
+
+        let mut arguments = Arguments::parse(command)?;
+        if let Some(stats) = stats {
+            stats.record(arguments.name());
+        }
+        if let Some(profile) = &self.profile {
+            profile.check(arguments.name())?;
+        }
+        // NOTE: It's simpler to assume that any command might change the machine than to keep
+        // track of which ones really do. The cost is only a checkpoint more than is needed.
+        self.timeline.invalidate();
+        match arguments.name() {
+            "RUN" => {
+                arguments.finish()?;
+                self.running = true;
+                self.run_count = 0;
+                Ok("Running simulation.".into())
+            }
+            "RESET" => {
+                arguments.finish()?;
+                self.reset();
+                Ok(format!(
+                    "Reset the machine. Execution begins at {:#06x}.",
+                    self.cpu.program_counter
+                ))
+            }
+            "HALT" => {
+                arguments.finish()?;
+                self.running = false;
+                Ok(format!(
+                    "Simulation halted at {:#06x}.",
+                    self.cpu.program_counter
+                ))
+            }
+            "CANCEL" => {
+                arguments.finish()?;
+                self.cancel_pending()
+                    .ok_or_else(|| anyhow!("There is nothing in progress to cancel."))
+            }
+            "STEP" => {
+                let step_size = arguments.optional_literal("a count")?.unwrap_or(1);
+                arguments.finish()?;
+
+                // Short runs of steps are executed immediately, but long ones are spread across
+                // frames so that the UI remains responsive and the user can cancel them.
+                // Breakpoint actions always step immediately, since later actions may depend on
+                // the result.
+                if step_size > STEP_BATCH && self.interactive && !self.executing_actions {
+                    self.pending_steps = Some((0, step_size));
+                    return Ok(format!("Stepping simulation {:#06x} times.", step_size));
+                }
+
+                let (taken, halted) = self.step_up_to(step_size);
+                if halted {
+                    return self.halted_result(u64::from(taken));
+                }
+
+                Ok(format!("Stepping simulation {:#06x} times.", step_size))
+            }
+            "STEPWHILE" | "STEPUNTIL" => {
+                let expression = Expression::parse(
+                    &rest(&mut arguments, "a condition")?,
+                    &self.types,
+                    self.packing,
+                )?;
+                let until = arguments.name() == "STEPUNTIL";
+
+                // NOTE: The condition is only checked after each step, so at least one step is
+                // always taken, and STEPUNTIL pc == 0x0040 from within a loop finds the next time
+                // around it.
+                self.step_until(Condition::Expression { expression, until })
+            }
+            "RUNUNTIL" => {
+                let kind = arguments.choice(&["BRANCH", "CALL", "RET", "LOAD", "STORE"])?;
+                let range = match arguments.optional_literal("an address")? {
+                    Some(address) => Some((
+                        address,
+                        arguments.optional_literal("a length")?.unwrap_or(1),
+                    )),
+                    None => None,
+                };
+                arguments.finish()?;
+
+                let until = match (kind, range) {
+                    ("LOAD", range) => Until::Load(range),
+                    ("STORE", range) => Until::Store(range),
+                    (_, Some(_)) => {
+                        return Err(arguments.error("only LOAD and STORE take a range of addresses"))
+                    }
+                    ("BRANCH", None) => Until::Branch,
+                    ("CALL", None) => Until::Call,
+                    _ => Until::Return,
+                };
+                self.step_until(Condition::Next(until))
+            }
+            "JUMP" => {
+                let address = arguments.literal("an address")?;
+                arguments.finish()?;
+
+                let from = self.cpu.program_counter;
+                self.move_program_counter("JUMP", address);
+                Ok(format!(
+                    "Jumped from {:#06x} to {:#06x} without executing anything.{}",
+                    from,
+                    address,
+                    self.misalignment(address)
+                ))
+            }
+            "SKIP" => {
+                let count = arguments.optional_literal("a count")?.unwrap_or(1);
+                arguments.finish()?;
+
+                // Instructions are skipped by their length, just as if they had been executed
+                // without doing anything.
+                let from = self.cpu.program_counter;
+                let mut address = from;
+                for _ in 0..count {
+                    address = address.wrapping_add(instruction_length(self.cpu.peek(address)));
+                }
+                self.move_program_counter(&format!("SKIP {}", count), address);
+                Ok(format!(
+                    "Skipped {} instructions from {:#06x} to {:#06x} without executing them.",
+                    count, from, address
+                ))
+            }
+            "CALL" => {
+                let address = arguments.literal("an address")?;
+
+                // The routine is called on a copy of the machine, so that the machine is left just
+                // as it was however the routine behaves.
+                let mut cpu = self.cpu.clone();
+                while let Some(argument) = arguments.optional_word() {
+                    let argument = argument.trim_end_matches(',');
+                    let parsed = argument.split_once('=').and_then(|(register, value)| {
+                        let register = register
+                            .trim()
+                            .strip_prefix(['r', 'R'])?
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|register| (1..0x20).contains(register))?;
+                        let value = parse_signed_literal(&value.trim().to_lowercase()).ok()?;
+                        Some((register, value))
+                    });
+                    let Some((register, value)) = parsed else {
+                        return Err(arguments.error(&format!(
+                            "\"{}\" is not an argument such as r04=5",
+                            argument
+                        )));
+                    };
+                    cpu.set_register(register, value);
+                }
+
+                // NOTE: The routine returns to wherever the machine was stopped. Since the routine
+                // might pass through that address itself, it has only returned once the stack
+                // pointer is back where it started as well.
+                let return_address = cpu.program_counter;
+                let stack_pointer = cpu.registers[self.abi.stack];
+                cpu.set_register(self.abi.link, return_address);
+                cpu.program_counter = address;
+                let mut steps = 0;
+                let returned = loop {
+                    if steps == CONDITION_LIMIT {
+                        break false;
+                    }
+                    let at = cpu.program_counter;
+                    cpu.step();
+                    steps += 1;
+                    for line in cpu.devices.take_output() {
+                        self.log(line);
+                    }
+                    for line in debug_output(&mut cpu, self.packing) {
+                        self.log(line);
+                    }
+                    self.terminal.feed(&cpu.devices.take_transmitted());
+                    // NOTE: The routine is run on a copy of the machine, so a snapshot of it would
+                    // be misleading, and it can only finish by returning.
+                    cpu.devices.take_requests();
+                    if let Some(fault) = cpu.fault.take() {
+                        return Err(anyhow!(
+                            "The routine at {:#06x} faulted at {:#06x} after {} steps: {}. The machine was left as it was.",
+                            address,
+                            at,
+                            steps,
+                            fault
+                        ));
+                    }
+                    if cpu.program_counter == return_address
+                        && cpu.registers[self.abi.stack] == stack_pointer
+                    {
+                        break true;
+                    }
+                };
+                if !returned {
+                    return Err(anyhow!(
+                        "The routine at {:#06x} did not return within {} steps. The machine was left as it was.",
+                        address,
+                        steps
+                    ));
+                }
+
+                let results = self
+                    .abi
+                    .results
+                    .iter()
+                    .map(|&register| {
+                        let value = cpu.registers[register];
+                        format!("r{:02} = {:#06x} ({})", register, value, value as i16)
+                    })
+                    .collect::<Vec<_>>();
+                let results = if results.is_empty() {
+                    String::new()
+                } else {
+                    format!(", with {}", results.join(", "))
+                };
+                Ok(format!(
+                    "The routine at {:#06x} returned after {} steps{}. The machine was left as it was.",
+                    address, steps, results
+                ))
+            }
+            "SET" => {
+                let register = if arguments.keyword("PC") {
+                    None
+                } else {
+                    Some(arguments.register()?)
+                };
+                let value = arguments.value()?;
+                arguments.finish()?;
+
+                match register {
+                    Some(0) => Err(anyhow!("r00 is always 0x0000.")),
+                    Some(register) => {
+                        self.cpu.set_register(register, value);
+                        Ok(format!(
+                            "Set r{:02} to {:#06x} ({}).",
+                            register, value, value as i16
+                        ))
+                    }
+                    None => {
+                        self.move_program_counter("SET PC", value);
+                        Ok(format!("Set the program counter to {:#06x}.", value))
+                    }
+                }
+            }
+            "LOAD" => {
+                // The address is optional, but comes before the filename, so it can only be told
+                // apart from a filename by how many arguments there are.
+                let address = if arguments.remaining() > 1 {
+                    arguments.literal("an address")?
+                } else {
+                    0
+                };
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                if self.pending_load.is_some() {
+                    return Err(anyhow!(
+                        "Another image is still being loaded. Use CANCEL to abandon it."
+                    ));
+                }
+
+                // Files loaded from the prompt are read on a worker thread, so that large images
+                // don't freeze the UI, and the load can be cancelled if it is taking too long.
+                if self.interactive && !self.executing_actions {
+                    self.pending_load = Some(PendingLoad::spawn(&filename, address));
+                    return Ok(format!("Loading {}.", &filename));
+                }
+
+                let bytes = fs::read(&filename)?;
+                let message = self.finish_load(&filename, address, &bytes)?;
+                self.remember(Recent::Image {
+                    path: filename,
+                    load: address,
+                });
+                Ok(message)
+            }
+            "BUILD" => {
+                arguments.finish()?;
+                let Some(project) = &self.project else {
+                    return Err(anyhow!(
+                        "No project is open, so there is nothing to build. Open one with ilo open."
+                    ));
+                };
+                let Some(build) = &project.build else {
+                    return Err(anyhow!(
+                        "{} has no build command. Give it one with build.cmd.",
+                        project.path
+                    ));
+                };
+                // NOTE: A successful build is followed by a load just like LOAD, so a profile
+                // which denies LOAD denies it as well.
+                if let Some(profile) = &self.profile {
+                    profile.check("LOAD")?;
+                }
+                if self.pending_load.is_some() || self.pending_build.is_some() {
+                    return Err(anyhow!(
+                        "Another image is still being loaded or built. Use CANCEL to abandon it."
+                    ));
+                }
+
+                let command = build.cmd.clone();
+                let mut build = PendingBuild::spawn(&command, project.directory())
+                    .map_err(|e| anyhow!("Failed to run {}: {}", command, e))?;
+                self.log(format!("$ {}", command));
+
+                // Builds started from the prompt run in the background, just like loads, so that
+                // the UI stays responsive and the build can be cancelled.
+                if self.interactive && !self.executing_actions {
+                    self.pending_build = Some(build);
+                    return Ok(format!("Building with {}.", command));
+                }
+                let mut output = Vec::new();
+                let status = build.wait(&mut output);
+                for line in output {
+                    self.log(line);
+                }
+                self.finish_build(build, status)
+            }
+            "RELOAD" => {
+                arguments.finish()?;
+                if let Some(profile) = &self.profile {
+                    profile.check("RESET")?;
+                }
+                // What was opened in this session is preferred to what was opened most recently in
+                // any session, since another may be running side by side.
+                let recent = match self.opened.clone() {
+                    Some(recent) => recent,
+                    None => recent::load()?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| anyhow!("Nothing has been loaded yet."))?,
+                };
+                let message = self.reopen(recent)?;
+                self.reset();
+                Ok(format!(
+                    "{} Reset the machine. Execution begins at {:#06x}.",
+                    message, self.cpu.program_counter
+                ))
+            }
+            "RECENT" => {
+                let entry = arguments.optional_literal("an entry")?;
+                arguments.finish()?;
+
+                let mut entries = recent::load()?;
+                let Some(entry) = entry else {
+                    if entries.is_empty() {
+                        return Ok("Nothing has been loaded yet.".into());
+                    }
+                    let entries = entries
+                        .iter()
+                        .enumerate()
+                        .map(|(i, recent)| format!("{} {}", i + 1, recent))
+                        .collect::<Vec<_>>();
+                    return Ok(format!(
+                        "Recently loaded: {}. Use RECENT followed by an entry to load it again.",
+                        entries.join(", ")
+                    ));
+                };
+                let index = usize::from(entry).wrapping_sub(1);
+                if index >= entries.len() {
+                    return Err(anyhow!(
+                        "There is no entry {} among the {} recently loaded.",
+                        entry,
+                        entries.len()
+                    ));
+                }
+                self.reopen(entries.swap_remove(index))
+            }
+            "DISASM" => {
+                // NOTE: A file may follow the entry point, but so may LISTING, so the entry point
+                // is told apart by whether or not it's a number.
+                let entry = match arguments.peek() {
+                    Some(entry) if arguments.remaining() > 1 && parse_literal(entry).is_ok() => {
+                        arguments.literal("an entry point")?
+                    }
+                    _ => 0,
+                };
+                let filename = arguments.word("a file")?;
+                let width = if arguments.keyword("LISTING") {
+                    Some(listing_width(&mut arguments)?)
+                } else {
+                    None
+                };
+                arguments.finish()?;
+
+                // Memory is disassembled as the CPU sees it, so that a boot ROM which shadows RAM is
+                // what's listed, rather than the RAM beneath it. Only the part of the decoded
+                // address space which actually contains something is bothered with, taking care to
+                // include the entry point even if it happens to hold 0x0000. Beyond the fitted RAM,
+                // there's only the open bus or mirrors of what's already listed.
+                let image = self.cpu.image();
+                let decoded = &image[..self.cpu.memory.size];
+                let first = decoded.iter().position(|&w| w != 0x0000).unwrap_or(0);
+                let last = decoded.iter().rposition(|&w| w != 0x0000).unwrap_or(0);
+                let start = u16::try_from(first).unwrap().min(entry);
+                let end = u16::try_from(last).unwrap().max(entry);
+
+                let listing = match width {
+                    Some(width) => list_region(&image, entry, start, end, width),
+                    None => disassemble_region(&image, entry, start, end),
+                };
+                fs::write(&filename, listing)?;
+
+                Ok(format!(
+                    "Disassembled {:#06x}..={:#06x} from entry point {:#06x} into {}.",
+                    start, end, entry, &filename
+                ))
+            }
+            "COMPARE" => {
+                if arguments.remaining() == 1 && arguments.keyword("OFF") {
+                    return match self.reference.take() {
+                        Some(_) => Ok("Stopped comparing against the reference.".into()),
+                        None => Err(anyhow!("No reference is being compared against.")),
+                    };
+                }
+                let address = if arguments.remaining() > 1 {
+                    arguments.literal("an address")?
+                } else {
+                    0
+                };
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                // The reference begins as an exact copy of the current CPU, so that the only
+                // difference between the two is the image loaded into the reference.
+                let mut reference = self.cpu.clone();
+                let (words, _) = load_image(&mut reference, address, &filename, self.byte_order)?;
+                self.reference = Some(reference);
+
+                Ok(format!(
+                    "Loaded {:#06x} words from {} into the reference at address {:#06x}.",
+                    words, &filename, address
+                ))
+            }
+            "COUNTERS" => {
+                let action = arguments.optional_choice(&["ON", "OFF", "RESET"])?;
+                arguments.finish()?;
+
+                let counters = &mut self.cpu.counters;
+                match action {
+                    Some("ON") => {
+                        counters.mapped = true;
+                        Ok(format!(
+                            "Mapped performance counters into memory at {:#06x}.",
+                            COUNTERS_BASE
+                        ))
+                    }
+                    Some("OFF") => {
+                        counters.mapped = false;
+                        Ok("Unmapped performance counters from memory.".into())
+                    }
+                    Some(_) => {
+                        counters.cycles = 0;
+                        counters.instructions = 0;
+                        counters.branches = 0;
+                        if let Some(measure) = &mut self.measure {
+                            measure.disarm();
+                        }
+                        Ok("Reset performance counters.".into())
+                    }
+                    None => Ok(format!(
+                        "Cycles: {}. Instructions retired: {}. Taken branches: {}.",
+                        counters.cycles, counters.instructions, counters.branches
+                    )),
+                }
+            }
+            "STRICT" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.cpu.strict = state;
+                    if let Some(reference) = &mut self.reference {
+                        reference.strict = state;
+                    }
+                }
+                Ok(if self.cpu.strict {
+                    "Strict mode is on, so instructions which write to r00 fault.".into()
+                } else {
+                    "Strict mode is off, so writes to r00 are silently discarded.".into()
+                })
+            }
+            "WARNINGS" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.warnings = state;
+                    self.warned.clear();
+                }
+                Ok(if self.warnings {
+                    "Warnings are on, so suspicious instructions are noted in the console the first time that each is executed.".into()
+                } else {
+                    "Warnings are off.".into()
+                })
+            }
+            "OVERFLOW" => {
+                let mode = arguments.optional_choice(&["OFF", "WARN", "HALT"])?;
+                arguments.finish()?;
+
+                if let Some(mode) = mode {
+                    self.overflow = match mode {
+                        "OFF" => Overflow::Off,
+                        "WARN" => Overflow::Warn,
+                        _ => Overflow::Halt,
+                    };
+                    self.warned.clear();
+                }
+                Ok(match self.overflow {
+                    Overflow::Off => "Signed overflow is ignored, as it is by the machine.".into(),
+                    Overflow::Warn => "Signed overflow by ADD, SUB, and ADDI is noted in the console the first time that each overflows.".into(),
+                    Overflow::Halt => "Signed overflow by ADD, SUB, and ADDI halts the simulation.".into(),
+                })
+            }
+            "STEPIRQ" => {
+                let mode = arguments.optional_choice(&["STOP", "MASK"])?;
+                arguments.finish()?;
+
+                if let Some(mode) = mode {
+                    self.interrupt_stepping = match mode {
+                        "STOP" => Stepping::Stop,
+                        _ => Stepping::Mask,
+                    };
+                }
+                Ok(match self.interrupt_stepping {
+                    Stepping::Stop => {
+                        "Stepping stops at the entry of a handler once an interrupt is taken."
+                            .into()
+                    }
+                    Stepping::Mask => {
+                        "Interrupts are held off while stepping, and only taken while running."
+                            .into()
+                    }
+                })
+            }
+            "TAINT" => self.taint_command(arguments),
+            "TAINT?" => {
+                let taint = self.taint.as_ref().ok_or_else(|| {
+                    anyhow!("Taint isn't being tracked. Use TAINT ON to track it.")
+                })?;
+                let location = location(&mut arguments)?;
+                arguments.finish()?;
+
+                let tainted = match location {
+                    Location::Register(register) => taint.register(register),
+                    Location::Memory(address) => taint.memory(&self.cpu, address),
+                };
+                let name = location.to_string();
+                Ok(if tainted {
+                    format!("{} is tainted, since it was derived from input.", name)
+                } else {
+                    format!("{} is not tainted.", name)
+                })
+            }
+            "WHOWROTE" => {
+                let location = location(&mut arguments)?;
+                arguments.finish()?;
+
+                let writer = match location {
+                    Location::Register(register) => self.writers.register(register),
+                    Location::Memory(address) => self.writers.memory(&self.cpu, address),
+                };
+                let Some(writer) = writer else {
+                    return Ok(format!(
+                        "{} hasn't been written by the program since it was loaded or reset.",
+                        location
+                    ));
+                };
+                let instruction = self.cpu.peek(writer.address);
+                let immediate = self.cpu.peek(writer.address.wrapping_add(1));
+                let ago = self.steps - writer.step;
+                Ok(format!(
+                    "{} was last written by {} at {:#06x}, at step {} ({} step{} ago).",
+                    location,
+                    disassemble(instruction, immediate).trim(),
+                    writer.address,
+                    writer.step,
+                    ago,
+                    if ago == 1 { "" } else { "s" }
+                ))
+            }
+            "DEVICE" => self.device_command(arguments),
+            "INJECT" => self.inject_command(arguments),
+            "MARK" => {
+                let name = arguments.word("a name")?;
+                let address = arguments
+                    .optional_literal("an address")?
+                    .unwrap_or(self.cpu.program_counter);
+                arguments.finish()?;
+
+                // Bookmark names mustn't look like literals, since GOTO accepts either.
+                let mut chars = name.chars();
+                let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                    && chars.all(|c| c.is_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(arguments.error(&format!(
+                        "expected a name made of letters, digits, and underscores, beginning with a letter or an underscore, got '{}'",
+                        name
+                    )));
+                }
+                // Bookmark names are case-insensitive, like everything else typed at the prompt.
+                let name = name.to_lowercase();
+                self.bookmarks.insert(name.clone(), address);
+                self.save_session()?;
+
+                Ok(format!("Bookmarked {:#06x} as {}.", address, name))
+            }
+            "UNMARK" => {
+                let name = arguments.word("a name")?.to_lowercase();
+                arguments.finish()?;
+
+                let address = self
+                    .bookmarks
+                    .remove(&name)
+                    .ok_or_else(|| anyhow!("There is no bookmark named {}.", name))?;
+                self.save_session()?;
+
+                Ok(format!(
+                    "Removed the bookmark {} at {:#06x}.",
+                    name, address
+                ))
+            }
+            "GOTO" => {
+                let target = arguments.word("a bookmark or an address")?;
+                arguments.finish()?;
+
+                let address = match parse_literal(&target) {
+                    Ok(address) => address,
+                    Err(_) => {
+                        let name = target.to_lowercase();
+                        *self
+                            .bookmarks
+                            .get(&name)
+                            .ok_or_else(|| anyhow!("There is no bookmark named {}.", name))?
+                    }
+                };
+                self.ram_pin = Some(address);
+
+                Ok(format!(
+                    "The RAM pane is pinned at {:#06x}. Use RAM FOLLOW to follow the program counter again.",
+                    address
+                ))
+            }
+            "DIAGNOSTICS" => {
+                let state = if arguments.keyword("ON") {
+                    Some(true)
+                } else if arguments.keyword("OFF") {
+                    Some(false)
+                } else {
+                    None
+                };
+                let entry = match state {
+                    Some(_) => None,
+                    None => arguments.optional_number::<usize>("ON, OFF, or an entry")?,
+                };
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.diagnostics_pane = state;
+                    return Ok(if self.diagnostics_pane {
+                        "Showing the diagnostics pane.".into()
+                    } else {
+                        "Hiding the diagnostics pane.".into()
+                    });
+                }
+                if self.diagnostics.is_empty() {
+                    return Ok("No problems were reported.".into());
+                }
+                let Some(entry) = entry else {
+                    return Ok(format!(
+                        "{} problem(s) were reported. Use DIAGNOSTICS followed by an entry to show the source around it.",
+                        self.diagnostics.len()
+                    ));
+                };
+                let index = entry.wrapping_sub(1);
+                if index >= self.diagnostics.len() {
+                    return Err(anyhow!(
+                        "There is no entry {} among the {} problem(s) reported.",
+                        entry,
+                        self.diagnostics.len()
+                    ));
+                }
+                self.select_diagnostic(index);
+                self.diagnostics_pane = true;
+                Ok(format!("{}", self.diagnostics[index]))
+            }
+            "MARKS" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.bookmark_pane = state;
+                    return Ok(if self.bookmark_pane {
+                        "Showing the bookmarks pane.".into()
+                    } else {
+                        "Hiding the bookmarks pane.".into()
+                    });
+                }
+                if self.bookmarks.is_empty() {
+                    return Ok("No bookmarks are set.".into());
+                }
+                let bookmarks = self
+                    .bookmarks
+                    .iter()
+                    .map(|(name, address)| format!("{} at {:#06x}", name, address))
+                    .collect::<Vec<_>>();
+
+                Ok(format!("Bookmarks: {}.", bookmarks.join(", ")))
+            }
+            "ACCESSES" if arguments.keyword("SAVE") => {
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                // The accesses are written as CSV, for plotting with other tools.
+                let mut csv = String::from("step,address,access\n");
+                for access in self.accesses.iter() {
+                    csv.push_str(&format!(
+                        "{},{:#06x},{}\n",
+                        access.step,
+                        access.address,
+                        if access.written { "store" } else { "load" }
+                    ));
+                }
+                fs::write(&filename, csv)?;
+
+                Ok(format!(
+                    "Saved {} recent accesses to {}.",
+                    self.accesses.len(),
+                    &filename
+                ))
+            }
+            "ACCESSES" => {
+                let state = arguments.optional_switch()?;
+                let range = match state {
+                    Some(true) => match arguments.optional_literal("an address")? {
+                        Some(address) => Some((address, arguments.literal("a length")?)),
+                        None => None,
+                    },
+                    _ => None,
+                };
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.accesses_pane = state;
+                    if !state {
+                        return Ok("Hiding the accesses pane.".into());
+                    }
+                    self.accesses_range = range;
+                    return Ok(match range {
+                        Some((address, length)) => format!(
+                            "Showing the accesses pane, over {:#06x} words at {:#06x}.",
+                            length, address
+                        ),
+                        None => "Showing the accesses pane.".into(),
+                    });
+                }
+                let stores = self.accesses.iter().filter(|access| access.written).count();
+                let Some(oldest) = self.accesses.iter().next() else {
+                    return Ok(
+                        "No accesses to RAM have been made since the image was loaded.".into(),
+                    );
+                };
+                Ok(format!(
+                    "Recorded {} loads and {} stores over the last {} steps.",
+                    self.accesses.len() - stores,
+                    stores,
+                    self.steps - oldest.step
+                ))
+            }
+            "ANALYZE" => {
+                let start = arguments.literal("a start address")?;
+                let end = arguments.literal("an end address")?;
+                arguments.finish()?;
+                if end < start {
+                    return Err(anyhow!(
+                        "The end address {:#06x} comes before the start address {:#06x}.",
+                        end,
+                        start
+                    ));
+                }
+
+                let (diagnostics, starts) = analyze(&self.cpu, start, end, self.abi.link);
+                for address in starts {
+                    self.starts.mark(&self.cpu, address);
+                }
+                let count = diagnostics.len();
+                self.set_diagnostics(diagnostics);
+                Ok(match count {
+                    0 => format!(
+                        "Found no problems in the code from {:#06x} to {:#06x}.",
+                        start, end
+                    ),
+                    _ => format!(
+                        "Found {} problem(s) in the code from {:#06x} to {:#06x}, which are listed in the diagnostics pane.",
+                        count, start, end
+                    ),
+                })
+            }
+            "LIVENESS" => {
+                let region = match arguments.optional_literal("an address")? {
+                    Some(address) => Some((address, arguments.literal("a length")?)),
+                    None => None,
+                };
+                arguments.finish()?;
+
+                let (usage, over) = match region {
+                    Some((address, length)) => (
+                        liveness::region(&self.cpu, address, length),
+                        format!("the {:#06x} words at {:#06x}", length, address),
+                    ),
+                    None => {
+                        let mut trace = Trace::new();
+                        let earliest = self.timeline.replay(self.steps, |cpu| trace.record(cpu))?;
+                        if trace.len() == 0 {
+                            return Err(anyhow!("Nothing has been executed yet."));
+                        }
+                        (
+                            trace.usage(),
+                            format!("steps {} through {} of the run", earliest, self.steps),
+                        )
+                    }
+                };
+                let unused = (1..0x20)
+                    .filter(|&register| !usage[register].used())
+                    .map(|register| format!("r{:02}", register))
+                    .collect::<Vec<_>>();
+                self.log(format!("Register usage over {}:", over));
+                for (register, usage) in usage.iter().enumerate() {
+                    if usage.used() {
+                        self.log(usage.describe(&self.cpu, register));
+                    }
+                }
+                if !unused.is_empty() {
+                    self.log(format!("Unused: {}", unused.join(", ")));
+                }
+                Ok(format!(
+                    "Reported the usage of {} registers over {} in the console.",
+                    0x1f - unused.len(),
+                    over
+                ))
+            }
+            "STACK" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                if let Some(state) = state {
+                    self.stack_pane = state;
+                    return Ok(if self.stack_pane {
+                        "Showing the call stack pane.".into()
+                    } else {
+                        "Hiding the call stack pane.".into()
+                    });
+                }
+                let frames = self
+                    .abi
+                    .unwind(&self.cpu)
+                    .iter()
+                    .enumerate()
+                    .map(|(depth, frame)| frame.describe(depth))
+                    .collect::<Vec<_>>();
+                let count = frames.len();
+                for frame in frames {
+                    self.log(frame);
+                }
+
+                Ok(format!(
+                    "Unwound {} frames of the call stack into the console.",
+                    count
+                ))
+            }
+            "REFRESH" => {
+                if arguments.keyword("RATE") {
+                    let rate = frame_rate(arguments.number("a frame rate")?)?;
+                    arguments.finish()?;
+
+                    self.frame_rate = rate;
+                    return Ok(format!("Drawing at most {} frames per second.", rate));
+                }
+                let Some(name) = arguments.optional_word() else {
+                    let halted = match self.halted_panes.is_empty() {
+                        true => "none".to_string(),
+                        false => self
+                            .halted_panes
+                            .iter()
+                            .copied()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    };
+                    return Ok(format!(
+                        "Drawing at most {} frames per second. Panes redrawn only on halt: {}.",
+                        self.frame_rate, halted
+                    ));
+                };
+                let pane = pane_name(&name)?;
+                let halt = arguments.choice(&["FRAME", "HALT"])? == "HALT";
+                arguments.finish()?;
+
+                if halt {
+                    self.halted_panes.insert(pane);
+                    Ok(format!(
+                        "The {} pane will only be redrawn when the simulation halts.",
+                        pane
+                    ))
+                } else {
+                    self.halted_panes.remove(pane);
+                    self.pane_cache.borrow_mut().remove(pane);
+                    Ok(format!("The {} pane will be redrawn every frame.", pane))
+                }
+            }
+            "TERMINAL" => {
+                // NOTE: Only the text is saved, not the escape sequences that drew it, so the file
+                // reads as the terminal looked rather than replaying how it got that way.
+                if arguments.keyword("SAVE") {
+                    let filename = arguments.word("a file")?;
+                    arguments.finish()?;
+
+                    let text = self.terminal.text();
+                    fs::write(&filename, &text)?;
+                    return Ok(format!(
+                        "Wrote {} lines of the terminal to {}.",
+                        text.lines().count(),
+                        &filename
+                    ));
+                }
+                if arguments.keyword("SCROLL") {
+                    let rows = arguments.number::<usize>("a number of rows")?;
+                    arguments.finish()?;
+
+                    self.terminal_scroll = rows.min(self.terminal.scrollback());
+                    return Ok(match self.terminal_scroll {
+                        0 => "Scrolled the terminal pane to the screen.".into(),
+                        rows => format!(
+                            "Scrolled the terminal pane back {} of {} rows of scrollback.",
+                            rows,
+                            self.terminal.scrollback()
+                        ),
+                    });
+                }
+                let action = arguments.optional_choice(&["ON", "OFF", "CLEAR"])?;
+                arguments.finish()?;
+
+                match action {
+                    Some("ON") => {
+                        self.terminal_pane = true;
+                        Ok("Showing the terminal pane.".into())
+                    }
+                    Some("OFF") => {
+                        self.terminal_pane = false;
+                        Ok("Hiding the terminal pane.".into())
+                    }
+                    Some(_) => {
+                        self.terminal.clear();
+                        self.terminal_scroll = 0;
+                        Ok("Cleared the terminal and its scrollback.".into())
+                    }
+                    None => Ok(format!(
+                        "The terminal is {}x{}, with the cursor at row {}, column {}, and {} rows of scrollback.",
+                        COLUMNS,
+                        ROWS,
+                        self.terminal.cursor.0 + 1,
+                        self.terminal.cursor.1.min(COLUMNS - 1) + 1,
+                        self.terminal.scrollback()
+                    )),
+                }
+            }
+            "HEAP" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                let Some(heap) = &self.heap else {
+                    return Err(anyhow!(
+                        "No heap is described. Add a [heap] table to ilo.toml describing the layout of the allocator's blocks."
+                    ));
+                };
+                if let Some(state) = state {
+                    self.heap_pane = state;
+                    return Ok(if self.heap_pane {
+                        "Showing the heap pane.".into()
+                    } else {
+                        "Hiding the heap pane.".into()
+                    });
+                }
+                let walk = heap.walk(&self.cpu);
+                let (free, used) = walk.totals();
+                let count = walk.blocks.len();
+                let corrupt = walk.problems.len()
+                    + walk
+                        .blocks
+                        .iter()
+                        .filter(|block| !block.problems.is_empty())
+                        .count();
+                let lines = walk
+                    .problems
+                    .into_iter()
+                    .chain(walk.blocks.iter().map(|block| block.describe()))
+                    .collect::<Vec<_>>();
+                for line in lines {
+                    self.log(line);
+                }
+
+                Ok(format!(
+                    "Walked {} blocks of the heap into the console, with {:#06x} words free and {:#06x} used.{}",
+                    count,
+                    free,
+                    used,
+                    match corrupt {
+                        0 => String::new(),
+                        corrupt => format!(" {} problem(s) were found.", corrupt),
+                    }
+                ))
+            }
+            "PRINT" => {
+                // A quoted first argument is a format, which is followed by the values for its
+                // placeholders, separated by commas.
+                let line = if arguments.next_is_quoted() {
+                    let format = arguments.word("a format")?;
+                    let values = if arguments.remaining() == 0 {
+                        Vec::new()
+                    } else {
+                        rest(&mut arguments, "a value")?
+                            .split(',')
+                            .map(|value| {
+                                Expression::parse(value.trim(), &self.types, self.packing)
+                                    .map(|value| value.evaluate(&self.cpu))
+                            })
+                            .collect::<Result<Vec<_>>>()?
+                    };
+                    format_values(&format, &values, &self.cpu, self.packing)?
+                } else {
+                    let expression = Expression::parse(
+                        &rest(&mut arguments, "an expression")?,
+                        &self.types,
+                        self.packing,
+                    )?;
+                    // An array is laid out over several rows, which are only shown in the console.
+                    let mut rows = expression.rows(&self.cpu);
+                    if rows.len() > 1 {
+                        let count = rows.len() - 1;
+                        for row in rows {
+                            self.log(row);
+                        }
+                        return Ok(format!(
+                            "Printed {} in {} row{} into the console.",
+                            expression.text,
+                            count,
+                            if count == 1 { "" } else { "s" }
+                        ));
+                    }
+                    rows.remove(0)
+                };
+                self.log(line.clone());
+                Ok(line)
+            }
+            "WATCHEXPR" => {
+                if arguments.remaining() <= 2 && arguments.keyword("DELETE") {
+                    let index = arguments.number::<usize>("the number of a watch")?;
+                    arguments.finish()?;
+                    if !(1..=self.watches.len()).contains(&index) {
+                        return Err(anyhow!("There is no watch numbered {}.", index));
+                    }
+                    let watch = self.watches.remove(index - 1);
+                    self.save_session()?;
+                    return Ok(format!("Stopped watching {}.", watch.text));
+                }
+                if arguments.remaining() == 1 && arguments.keyword("CLEAR") {
+                    self.watches.clear();
+                    self.save_session()?;
+                    return Ok("Stopped watching every expression.".into());
+                }
+
+                let expression = Expression::parse(
+                    &rest(&mut arguments, "an expression")?,
+                    &self.types,
+                    self.packing,
+                )?;
+                let message = format!(
+                    "Watching {} as watch {}.",
+                    expression.text,
+                    self.watches.len() + 1
+                );
+                self.watches.push(expression);
+                self.save_session()?;
+                Ok(message)
+            }
+            "ASSERT" => {
+                if arguments.remaining() == 0 {
+                    if self.assertions.is_empty() {
+                        return Ok("No assertions are set.".into());
+                    }
+                    let assertions = self
+                        .assertions
+                        .iter()
+                        .enumerate()
+                        .map(|(index, assertion)| format!("{}: {}", index + 1, assertion.text))
+                        .collect::<Vec<_>>();
+                    return Ok(format!("Assertions: {}.", assertions.join(", ")));
+                }
+                if arguments.remaining() <= 2 && arguments.keyword("DELETE") {
+                    let index = arguments.number::<usize>("the number of an assertion")?;
+                    arguments.finish()?;
+                    if !(1..=self.assertions.len()).contains(&index) {
+                        return Err(anyhow!("There is no assertion numbered {}.", index));
+                    }
+                    let assertion = self.assertions.remove(index - 1);
+                    self.save_session()?;
+                    return Ok(format!("Stopped asserting {}.", assertion.text));
+                }
+                if arguments.remaining() == 1 && arguments.keyword("CLEAR") {
+                    self.assertions.clear();
+                    self.save_session()?;
+                    return Ok("Stopped asserting every expression.".into());
+                }
+
+                let expression = Expression::parse(
+                    &rest(&mut arguments, "an expression")?,
+                    &self.types,
+                    self.packing,
+                )?;
+                // NOTE: An assertion which doesn't hold already is still set, so that a session
+                // can be restored whatever state the machine is in, but the user is warned.
+                let warning = if expression.holds(&self.cpu) {
+                    ""
+                } else {
+                    " It doesn't hold now, so the next step will halt."
+                };
+                let message = format!(
+                    "Asserting {} after every step, as assertion {}.{}",
+                    expression.text,
+                    self.assertions.len() + 1,
+                    warning
+                );
+                self.assertions.push(expression);
+                self.save_session()?;
+                Ok(message)
+            }
+            "TYPE" => {
+                if arguments.remaining() == 0 {
+                    if self.types.is_empty() {
+                        return Ok("No types have been defined.".into());
+                    }
+                    let lines = self
+                        .types
+                        .values()
+                        .map(|record| format!("{} ({} words)", record.definition(), record.size()))
+                        .collect::<Vec<_>>();
+                    for line in lines {
+                        self.log(line);
+                    }
+                    return Ok(format!("Listed {} types in the console.", self.types.len()));
+                }
+
+                // NOTE: Types and watches which use a type keep the definition which it had when
+                // they were defined, until the session is next loaded.
+                let record = Record::parse(
+                    &rest(&mut arguments, "a definition")?,
+                    &self.types,
+                    self.packing,
+                )?;
+                let message = format!(
+                    "Defined {}, which occupies {} words.",
+                    record.name,
+                    record.size()
+                );
+                self.types.insert(record.name.clone(), Arc::new(record));
+                self.save_session()?;
+                Ok(message)
+            }
+            "COPY" => {
+                let (text, description) =
+                    match arguments.choice(&["HISTORY", "REGISTERS", "TERMINAL", "RAM"])? {
+                        "HISTORY" => (self.history_text(), "the instruction history".to_string()),
+                        "REGISTERS" => (self.registers_text(), "the registers".to_string()),
+                        "TERMINAL" => (self.terminal.text(), "the terminal".to_string()),
+                        _ => {
+                            // By default, roughly what the RAM pane shows is copied.
+                            let address = arguments.optional_literal("an address")?.unwrap_or(
+                                self.ram_pin.unwrap_or(self.cpu.program_counter & 0xffc0),
+                            );
+                            let length = arguments.optional_literal("a length")?.unwrap_or(0x40);
+                            // With a destination, the words are copied within RAM rather than
+                            // to the clipboard.
+                            if arguments.keyword("TO") {
+                                let destination = arguments.literal("a destination")?;
+                                arguments.finish()?;
+                                // NOTE: This patches RAM just as FILL does, so a profile which
+                                // denies FILL denies it as well.
+                                if let Some(profile) = &self.profile {
+                                    profile.check("FILL")?;
+                                }
+                                region::copy(&mut self.cpu, address, destination, length)?;
+                                return Ok(format!(
+                                    "Copied {:#06x} words of RAM from {:#06x} to {:#06x}.",
+                                    length, address, destination
+                                ));
+                            }
+                            (
+                                self.ram_text(address, length),
+                                format!("{:#06x} words of RAM at {:#06x}", length, address),
+                            )
+                        }
+                    };
+                arguments.finish()?;
+                clipboard::copy(&text)?;
+
+                Ok(format!("Copied {} to the clipboard.", description))
+            }
+            "REPORT" => {
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                fs::write(&filename, report(self))?;
+
+                Ok(format!("Wrote a report of this session to {}.", &filename))
+            }
+            "MINIMAP" => {
+                self.minimap = arguments.switch()?;
+                arguments.finish()?;
+
+                Ok(if self.minimap {
+                    "Showing the minimap. Click a block to pin the RAM pane there.".into()
+                } else {
+                    "Hiding the minimap.".into()
+                })
+            }
+            "FREEZE" => {
+                arguments.finish()?;
+                if self.frozen.is_some() {
+                    return Ok("The view is already frozen. Use THAW or press Ctrl-F to make it live again.".into());
+                }
+
+                // NOTE: Registers can't be edited while frozen, since the pane wouldn't show the
+                // edit.
+                self.register_cursor = None;
+                self.register_edit = None;
+                self.frozen = Some(Box::new(FrozenView {
+                    step: None,
+                    cpu: self.cpu.clone(),
+                    reference: self.reference.clone(),
+                    instruction_history: self.instruction_history.clone(),
+                    console: self.console.clone(),
+                }));
+                Ok("Froze the view, though the simulation carries on. Use THAW or press Ctrl-F to make it live again.".into())
+            }
+            "SEEK" => {
+                let Some(step) = arguments.optional_number::<u64>("a step")? else {
+                    return Ok(match self.timeline.earliest() {
+                        Some(earliest) => format!(
+                            "Steps {} through {} of the run can be travelled to.",
+                            earliest, self.steps
+                        ),
+                        None => "Nothing has been recorded yet.".into(),
+                    });
+                };
+                arguments.finish()?;
+
+                if step > self.steps {
+                    return Err(anyhow!(
+                        "Step {} hasn't been reached yet. The run is at step {}.",
+                        step,
+                        self.steps
+                    ));
+                }
+                if step == self.steps {
+                    self.frozen = None;
+                    return Ok(format!(
+                        "Step {} is where the run is now, so the view is live again.",
+                        step
+                    ));
+                }
+                let (cpu, instruction_history) = self.timeline.seek(step)?;
+                self.register_cursor = None;
+                self.register_edit = None;
+                self.frozen = Some(Box::new(FrozenView {
+                    step: Some(step),
+                    cpu: *cpu,
+                    reference: None,
+                    instruction_history,
+                    console: self.console.clone(),
+                }));
+                Ok(format!(
+                    "Travelled back to step {} of {}. Use SEEK again to travel elsewhere, or THAW to return to the present.",
+                    step, self.steps
+                ))
+            }
+            "THAW" => {
+                arguments.finish()?;
+                Ok(match self.frozen.take() {
+                    Some(_) => "The view is live again.".into(),
+                    None => "The view is already live.".into(),
+                })
+            }
+            "RAM" => {
+                let pin = match arguments.optional_choice(&["PIN", "FOLLOW"])? {
+                    Some("PIN") => Some(Some(arguments.literal("an address")?)),
+                    Some(_) => Some(None),
+                    None => None,
+                };
+                arguments.finish()?;
+
+                if let Some(pin) = pin {
+                    self.ram_pin = pin;
+                    self.save_session()?;
+                }
+                Ok(match self.ram_pin {
+                    Some(address) => format!("The RAM pane is pinned at {:#06x}.", address),
+                    None => "The RAM pane follows the program counter.".into(),
+                })
+            }
+            "ROM" => {
+                let state = arguments.optional_switch()?;
+                arguments.finish()?;
+
+                let Some(rom) = &mut self.cpu.memory.rom else {
+                    return Err(anyhow!("This machine has no boot ROM."));
+                };
+                match state {
+                    Some(true) => {
+                        rom.mapped = true;
+                        Ok(format!(
+                            "Mapped the boot ROM over the lowest {:#06x} addresses.",
+                            rom.words.len()
+                        ))
+                    }
+                    Some(false) => {
+                        rom.mapped = false;
+                        Ok("Unmapped the boot ROM.".into())
+                    }
+                    None => Ok(format!(
+                        "The boot ROM occupies {:#06x} words, and is {}. Write to {:#06x} to unmap it from guest code.",
+                        rom.words.len(),
+                        if rom.mapped { "mapped" } else { "unmapped" },
+                        ROM_CONTROL
+                    )),
+                }
+            }
+            "MEASURE" => {
+                if arguments.keyword("OFF") {
+                    arguments.finish()?;
+                    return Ok(match self.measure.take() {
+                        Some(measure) => format!(
+                            "Stopped measuring {:#06x} to {:#06x}. {}",
+                            measure.start,
+                            measure.end,
+                            measure.summary()
+                        ),
+                        None => "Nothing was being measured.".into(),
+                    });
+                }
+                let Some(start) = arguments.optional_literal("a start address")? else {
+                    return Ok(match &self.measure {
+                        Some(measure) => format!(
+                            "Measuring {:#06x} to {:#06x}{}. {}",
+                            measure.start,
+                            measure.end,
+                            if measure.armed() {
+                                ", with a traversal in progress"
+                            } else {
+                                ""
+                            },
+                            measure.summary()
+                        ),
+                        None => "Nothing is being measured.".into(),
+                    });
+                };
+                let end = arguments.literal("an end address")?;
+                arguments.finish()?;
+
+                let measure = Measure::new(start, end, &self.cpu);
+                let armed = measure.armed();
+                self.measure = Some(measure);
+                Ok(format!(
+                    "Measuring {:#06x} to {:#06x}. Each traversal is reported in the console{}.",
+                    start,
+                    end,
+                    if armed {
+                        ", beginning with this one"
+                    } else {
+                        ""
+                    }
+                ))
+            }
+            "COST" => {
+                let reset = arguments.keyword("RESET");
+                arguments.finish()?;
+
+                if reset {
+                    self.cost.reset();
+                    return Ok("Reset accumulated cost.".into());
+                }
+
+                // Report the most expensive functions first, and only as many as will reasonably
+                // fit on a single line.
+                let mut functions = self.cost.functions.iter().collect::<Vec<_>>();
+                functions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+                let functions = functions
+                    .iter()
+                    .take(8)
+                    .map(|(entry, cost)| match entry {
+                        Some(entry) => format!("{:#06x}: {}", entry, cost),
+                        None => format!("top level: {}", cost),
+                    })
+                    .collect::<Vec<_>>();
+
+                if functions.is_empty() {
+                    Ok(format!("Total cost: {}.", self.cost.total))
+                } else {
+                    Ok(format!(
+                        "Total cost: {}. By function: {}.",
+                        self.cost.total,
+                        functions.join(", ")
+                    ))
+                }
+            }
+            "VERIFY" => {
+                // A region of RAM can be checked against a checksum given by HASH, which lets a
+                // script or a tracepoint catch the moment that the region is disturbed.
+                if arguments.keyword("RAM") {
+                    let word = arguments.word("a checksum")?;
+                    let Some(checksum) = word
+                        .strip_prefix("0x")
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    else {
+                        return Err(arguments.error(&format!(
+                            "expected a checksum such as 0x1234abcd, got '{}'",
+                            word
+                        )));
+                    };
+                    let (region, description) = self.hashed_region(&mut arguments)?;
+
+                    let current = crc32_words(&self.cpu.ram[region]);
+                    return if current == checksum {
+                        Ok(format!(
+                            "The CRC-32 of {} still matches ({:#010x}).",
+                            description, checksum
+                        ))
+                    } else {
+                        Err(anyhow!(
+                            "The CRC-32 of {} no longer matches: it was {:#010x}, but is now {:#010x}.",
+                            description,
+                            checksum,
+                            current
+                        ))
+                    };
+                }
+                arguments.finish()?;
+
+                let (Some(image), Some(checksum)) = (&self.image, self.image_checksum) else {
+                    return Err(anyhow!("No image has been loaded."));
+                };
+
+                let current = crc32(&fs::read(image)?);
+                if current == checksum {
+                    Ok(format!(
+                        "{} is unchanged since it was loaded (CRC-32 {:#010x}).",
+                        image, checksum
+                    ))
+                } else {
+                    Err(anyhow!(
+                        "{} has changed since it was loaded (CRC-32 {:#010x}, now {:#010x}).",
+                        image,
+                        checksum,
+                        current
+                    ))
+                }
+            }
+            "FOCUS" => {
+                if arguments.keyword("OFF") {
+                    arguments.finish()?;
+                    self.focus = None;
+                    return Ok("Stopped focusing register.".into());
+                }
+                let register = arguments.register()?;
+                arguments.finish()?;
+
+                self.focus = Some(register);
+
+                Ok(format!("Focusing r{:02}.", register))
+            }
+            "PRESENT" => {
+                self.presenting = arguments.switch()?;
+                arguments.finish()?;
+
+                if self.presenting {
+                    Ok("Entered presentation mode.".into())
+                } else {
+                    Ok("Left presentation mode.".into())
+                }
+            }
+            "EXPLAIN" => {
+                let address = arguments
+                    .optional_literal("an address")?
+                    .unwrap_or(self.cpu.program_counter);
+                arguments.finish()?;
+
+                // The breakdown is far too long for the command prompt, so it goes to the console.
+                for line in explain(&self.cpu, address) {
+                    self.log(line);
+                }
+
+                Ok(format!(
+                    "Explained the instruction at {:#06x} in the console.{}",
+                    address,
+                    self.misalignment(address)
+                ))
+            }
+            "ASM" => {
+                let filename = arguments.word("a file")?;
+                let listing = if arguments.keyword("LISTING") {
+                    let file = arguments.word("a file for the listing")?;
+                    Some((file, listing_width(&mut arguments)?))
+                } else {
+                    None
+                };
+                arguments.finish()?;
+
+                let source = fs::read_to_string(&filename)?;
+                let words = match assemble(&source) {
+                    Ok(words) => words,
+                    Err(error) => {
+                        let diagnostics = error
+                            .downcast_ref::<LineError>()
+                            .map(|error| Diagnostic::new(&filename, error.line, &error.message));
+                        self.set_diagnostics(diagnostics.into_iter().collect());
+                        return Err(error);
+                    }
+                };
+                self.set_diagnostics(Vec::new());
+                let mut indices = Vec::new();
+                for &(address, word) in &words {
+                    match self.cpu.memory.decode(address) {
+                        Some(index) => indices.push((index, word)),
+                        None => {
+                            return Err(anyhow!(
+                                "{} places code at {:#06x}, where no RAM is fitted.",
+                                &filename,
+                                address
+                            ))
+                        }
+                    }
+                }
+                // NOTE: Code assembled beneath the boot ROM lands in RAM, just as LOAD does.
+                for (index, word) in indices {
+                    self.cpu.ram[index] = word;
+                    self.starts.forget(index);
+                }
+
+                if let Some((file, width)) = listing {
+                    let title = format!("Assembled by ilo from {}.", &filename);
+                    let lines = listing::assembled(&source, &assemble_lines(&source)?);
+                    fs::write(&file, listing::format(&title, &lines, width))?;
+                    return Ok(format!(
+                        "Assembled {:#06x} words from {} into RAM, and listed them in {}.",
+                        words.len(),
+                        &filename,
+                        &file
+                    ));
+                }
+
+                Ok(format!(
+                    "Assembled {:#06x} words from {} into RAM.",
+                    words.len(),
+                    &filename
+                ))
+            }
+            "EXPORT" => {
+                let format = arguments.choice(&["MEM", "HEX", "COE", "MIF"])?;
+                let filename = arguments.word("a file")?;
+                let range = match arguments.optional_literal("an address")? {
+                    Some(address) => Some((address, usize::from(arguments.literal("a length")?))),
+                    None => None,
+                };
+                arguments.finish()?;
+
+                // Without a range, everything from the bottom of RAM up to the last word which
+                // holds anything is exported, since that's what a block RAM would be initialized
+                // with.
+                let (address, length) = range.unwrap_or_else(|| {
+                    let last = self.cpu.ram[..self.cpu.memory.size]
+                        .iter()
+                        .rposition(|&word| word != 0x0000)
+                        .unwrap_or(0);
+                    (0x0000, last + 1)
+                });
+                if length == 0 || usize::from(address) + length > self.cpu.memory.size {
+                    return Err(anyhow!(
+                        "Cannot export {:#06x} words starting at {:#06x}, as this must be at least one word, and must not extend past the end of RAM.",
+                        length,
+                        address
+                    ));
+                }
+
+                let words = (0..length)
+                    .map(|offset| self.cpu.peek(address.wrapping_add(offset as u16)))
+                    .collect::<Vec<_>>();
+                fs::write(&filename, export(format, &words))?;
+
+                Ok(format!(
+                    "Exported {:#06x} words from RAM at address {:#06x} into {} as {}.",
+                    length, address, &filename, format
+                ))
+            }
+            "WAVE" => {
+                if arguments.remaining() == 1 && arguments.keyword("OFF") {
+                    return match self.wave.take() {
+                        Some(wave) => {
+                            let filename = wave.filename.clone();
+                            wave.finish()?;
+                            Ok(format!("Stopped recording the waveform into {}.", filename))
+                        }
+                        None => Err(anyhow!("No waveform is being recorded.")),
+                    };
+                }
+                let filename = arguments.word("a file")?;
+                let mut registers = Vec::new();
+                while arguments.remaining() > 0 {
+                    registers.push(arguments.register()?);
+                }
+                if registers.is_empty() {
+                    registers = (1..0x20).collect();
+                }
+
+                if let Some(wave) = self.wave.take() {
+                    wave.finish()?;
+                }
+                self.wave = Some(Wave::create(&filename, registers, &self.cpu)?);
+                Ok(format!(
+                    "Recording a waveform into {} as the simulation runs. Use WAVE OFF to stop.",
+                    filename
+                ))
+            }
+            "FILL" => {
+                let address = arguments.literal("an address")?;
+                let length = arguments.literal("a length")?;
+                let value = arguments.value()?;
+                arguments.finish()?;
+
+                region::fill(&mut self.cpu, address, length, value)?;
+
+                Ok(format!(
+                    "Filled {:#06x} words of RAM at {:#06x} with {:#06x}.",
+                    length, address, value
+                ))
+            }
+            "DIFF" => {
+                let first = arguments.literal("an address")?;
+                let second = arguments.literal("another address")?;
+                let length = arguments.literal("a length")?;
+                arguments.finish()?;
+
+                let offsets = region::differences(&self.cpu, first, second, length)?;
+                let Some(&offset) = offsets.first() else {
+                    return Ok(format!(
+                        "The {:#06x} words of RAM at {:#06x} and at {:#06x} are the same.",
+                        length, first, second
+                    ));
+                };
+                // Every difference goes to the console, since there may be far too many to show at
+                // the command prompt.
+                for &offset in &offsets {
+                    let (a, b) = (first + offset as u16, second + offset as u16);
+                    let line = format!(
+                        "{:#06x}: {:#06x}  {:#06x}: {:#06x}",
+                        a,
+                        self.cpu.ram[usize::from(a)],
+                        b,
+                        self.cpu.ram[usize::from(b)]
+                    );
+                    self.log(line);
+                }
+
+                Ok(format!(
+                    "{:#06x} of the {:#06x} words of RAM at {:#06x} and at {:#06x} differ, the first at offset {:#06x}. Every difference is listed in the console.",
+                    offsets.len(),
+                    length,
+                    first,
+                    second,
+                    offset
+                ))
+            }
+            "HASH" => {
+                let (region, description) = self.hashed_region(&mut arguments)?;
+                let checksum = crc32_words(&self.cpu.ram[region.clone()]);
+
+                // The whole of the command which would check the checksum is given, ready to be
+                // pasted into a script.
+                let mut verify = format!("VERIFY RAM {:#010x}", checksum);
+                if region.len() < self.cpu.memory.size {
+                    verify.push_str(&format!(" {:#06x} {:#06x}", region.start, region.len()));
+                }
+
+                Ok(format!(
+                    "The CRC-32 of {} is {:#010x}. Use {} to check it later.",
+                    description, checksum, verify
+                ))
+            }
+            "DUMP" => {
+                let filename = arguments.word("a file")?;
+                let address = arguments.literal("an address")?;
+                let length = arguments.literal("a length")?;
+                let order = if arguments.keyword("BIG") {
+                    ByteOrder::Big
+                } else if arguments.keyword("LITTLE") {
+                    ByteOrder::Little
+                } else {
+                    self.byte_order
+                };
+                arguments.finish()?;
+
+                if usize::from(address) + usize::from(length) > self.cpu.ram.len() {
+                    return Err(anyhow!(
+                        "Cannot dump {:#06x} words starting at {:#06x}, as this extends past the end of RAM.",
+                        length,
+                        address
+                    ));
+                }
+
+                // Words are written in the same byte order as LOAD expects to read them, unless
+                // another is given for the sake of some other tool. The memory map is respected,
+                // so that the dump contains exactly what the CPU would see.
+                let bytes = (0..length)
+                    .flat_map(|offset| order.bytes(self.cpu.peek(address + offset)))
+                    .collect::<Vec<_>>();
+                fs::write(&filename, bytes)?;
+
+                Ok(format!(
+                    "Dumped {:#06x} words from RAM at address {:#06x} into {}.",
+                    length, address, &filename
+                ))
+            }
+            "SNAPSHOT" => {
+                let filename = arguments.word("a file")?;
+                let baseline = if arguments.keyword("AGAINST") {
+                    Some(arguments.word("a baseline snapshot")?)
+                } else {
+                    None
+                };
+                arguments.finish()?;
+
+                let baseline = baseline.as_deref();
+                let size = snapshot::save(&self.cpu, &filename, baseline)?;
+
+                Ok(match baseline {
+                    Some(baseline) => format!(
+                        "Saved a snapshot to {} ({} bytes, as a delta against {}).",
+                        &filename, size, baseline
+                    ),
+                    None => format!("Saved a snapshot to {} ({} bytes).", &filename, size),
+                })
+            }
+            "RESTORE" => {
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                snapshot::load(&filename)?.apply(&mut self.cpu);
+                self.cpu.usage.clear();
+                // The history describes how the old state was reached, so it is meaningless now.
+                self.instruction_history.clear();
+
+                Ok(format!(
+                    "Restored a snapshot from {}, with the program counter at {:#06x}.",
+                    &filename, self.cpu.program_counter
+                ))
+            }
+            "BREAK" | "TBREAK" => {
+                let temporary = arguments.name() == "TBREAK";
+                let address = arguments.literal("an address")?;
+                let ignore = if arguments.keyword("IGNORE") {
+                    arguments.number::<u32>("a count")?
+                } else {
+                    0
+                };
+                let actions = if arguments.keyword("DO") {
+                    Some(arguments.word("a quoted list of commands")?)
+                } else {
+                    None
+                };
+                arguments.finish()?;
+
+                self.breakpoints
+                    .insert(address, Breakpoint::new(temporary, ignore, actions));
+                self.save_session()?;
+
+                Ok(format!(
+                    "Set {}breakpoint at {:#06x}.{}",
+                    if temporary { "temporary " } else { "" },
+                    address,
+                    self.misalignment(address)
+                ))
+            }
+            "ENABLE" | "DISABLE" => {
+                let enabled = arguments.name() == "ENABLE";
+                let address = arguments.literal("an address")?;
+                arguments.finish()?;
+
+                let Some(breakpoint) = self.breakpoints.get_mut(&address) else {
+                    return Err(anyhow!("There is no breakpoint at {:#06x}.", address));
+                };
+                breakpoint.enabled = enabled;
+                self.save_session()?;
+
+                Ok(format!(
+                    "{} breakpoint at {:#06x}.",
+                    if enabled { "Enabled" } else { "Disabled" },
+                    address
+                ))
+            }
+            "DELETE" => {
+                let address = arguments.literal("an address")?;
+                arguments.finish()?;
+
+                let breakpoint = self.breakpoints.remove(&address).is_some();
+                let tracepoint = self.tracepoints.remove(&address).is_some();
+                self.save_session()?;
+
+                match (breakpoint, tracepoint) {
+                    (true, true) => Ok(format!(
+                        "Deleted breakpoint and tracepoint at {:#06x}.",
+                        address
+                    )),
+                    (true, false) => Ok(format!("Deleted breakpoint at {:#06x}.", address)),
+                    (false, true) => Ok(format!("Deleted tracepoint at {:#06x}.", address)),
+                    (false, false) => Err(anyhow!(
+                        "There is no breakpoint or tracepoint at {:#06x}.",
+                        address
+                    )),
+                }
+            }
+            "TRACEPOINT" => {
+                let address = arguments.literal("an address")?;
+                let format = arguments.word("a quoted format string")?;
+                arguments.finish()?;
+
+                // Expand the format string once up front, so that a malformed format string is
+                // reported now rather than in the middle of a run.
+                format_trace(&format, &self.cpu)?;
+                self.tracepoints.insert(address, format);
+                self.save_session()?;
+
+                Ok(format!(
+                    "Set tracepoint at {:#06x}.{}",
+                    address,
+                    self.misalignment(address)
+                ))
+            }
+            "BREAKS" => {
+                arguments.finish()?;
+
+                if self.breakpoints.is_empty() && self.tracepoints.is_empty() {
+                    return Ok("No breakpoints or tracepoints are set.".into());
+                }
+
+                let mut lists = Vec::new();
+                if !self.breakpoints.is_empty() {
+                    let breakpoints = self
+                        .breakpoints
+                        .iter()
+                        .map(|(address, breakpoint)| format!("{:#06x} {}", address, breakpoint))
+                        .collect::<Vec<_>>();
+                    lists.push(format!("Breakpoints: {}.", breakpoints.join(", ")));
+                }
+                if !self.tracepoints.is_empty() {
+                    let tracepoints = self
+                        .tracepoints
+                        .iter()
+                        .map(|(address, format)| format!("{:#06x} \"{}\"", address, format))
+                        .collect::<Vec<_>>();
+                    lists.push(format!("Tracepoints: {}.", tracepoints.join(", ")));
+                }
+                Ok(lists.join(" "))
+            }
+            "HELP" => {
+                let name = arguments.optional_word();
+                arguments.finish()?;
+
+                match name {
+                    Some(name) => {
+                        let spec = command::find(&name).ok_or_else(|| {
+                            anyhow!(
+                                "\"{}\" is not a valid command. {}",
+                                name,
+                                command::supported()
+                            )
+                        })?;
+                        Ok(format!("Usage: {}", spec.usage))
+                    }
+                    None => Ok(format!(
+                        "{} Use HELP followed by a command to see its usage.",
+                        command::supported()
+                    )),
+                }
+            }
+            // Every command in the table is handled above, so this can only be reached if one was
+            // added to the table and forgotten here.
+            name => Err(anyhow!("{} is not implemented.", name)),
+        }
+    }
+
+    // Handle the DEVICE command, whose first argument chooses what to do with the devices.
+    fn device_command(&mut self, mut arguments: Arguments) -> Result<String> {
+        match arguments.choice(&["ADD", "REMOVE", "INFO", "PANE", "TRACE", "LIST"])? {
+            "ADD" => {
+                let kind = arguments.word("a kind of device")?;
+                let base = arguments.literal("a base address")?;
+                let mut config = DeviceConfig {
+                    name: self.cpu.devices.unused_name(&kind),
+                    kind,
+                    base,
+                    irq: None,
+                    file: None,
+                };
+                // The options may be given in any order.
+                while let Some(option) = arguments.optional_choice(&["AS", "IRQ", "FILE"])? {
+                    match option {
+                        "AS" => config.name = arguments.word("a name")?,
+                        "IRQ" => config.irq = Some(arguments.number::<u8>("an interrupt line")?),
+                        _ => config.file = Some(arguments.word("a file")?),
+                    }
+                }
+                arguments.finish()?;
+
+                let attached = create(&config, &self.arguments)?;
+                self.cpu.devices.attach(attached.clone())?;
+                // The reference gets an identical device, so that the two CPUs remain comparable.
+                if let Some(reference) = &mut self.reference {
+                    reference.devices.attach(attached)?;
+                }
+
+                Ok(format!(
+                    "Attached {}, a {}, at {:#06x}.",
+                    config.name,
+                    config.kind.to_lowercase(),
+                    config.base
+                ))
+            }
+            "REMOVE" => {
+                let name = arguments.word("the name of a device")?;
+                arguments.finish()?;
+
+                let attached = self
+                    .cpu
+                    .devices
+                    .detach(&name)
+                    .ok_or_else(|| anyhow!("There is no device named {}.", &name))?;
+                if let Some(reference) = &mut self.reference {
+                    reference.devices.detach(&name);
+                }
+
+                Ok(format!(
+                    "Detached {} from {:#06x}.",
+                    attached.name, attached.base
+                ))
+            }
+            "INFO" => {
+                let name = arguments.word("the name of a device")?;
+                arguments.finish()?;
+
+                let lines = self
+                    .cpu
+                    .devices
+                    .find(&name)
+                    .ok_or_else(|| anyhow!("There is no device named {}.", &name))?
+                    .describe();
+                for line in lines {
+                    self.log(line);
+                }
+
+                Ok(format!("Described {} in the console.", &name))
+            }
+            "PANE" => {
+                self.device_pane = arguments.switch()?;
+                arguments.finish()?;
+
+                Ok(if self.device_pane {
+                    "Showing the devices pane.".into()
+                } else {
+                    "Hiding the devices pane.".into()
+                })
+            }
+            "TRACE" if arguments.keyword("SAVE") => {
+                let filename = arguments.word("a file")?;
+                arguments.finish()?;
+
+                let trace = self
+                    .device_trace
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect::<String>();
+                fs::write(&filename, trace)?;
+
+                Ok(format!(
+                    "Saved {} traced device accesses to {}.",
+                    self.device_trace.len(),
+                    &filename
+                ))
+            }
+            "TRACE" => {
+                let name = arguments.word("the name of a device")?;
+                let traced = arguments.switch()?;
+                arguments.finish()?;
+
+                let attached = self
+                    .cpu
+                    .devices
+                    .find_mut(&name)
+                    .ok_or_else(|| anyhow!("There is no device named {}.", &name))?;
+                attached.traced = traced;
+                Ok(if attached.traced {
+                    format!(
+                        "Tracing accesses to {}. Use DEVICE TRACE SAVE to save the trace to a file.",
+                        attached.name
+                    )
+                } else {
+                    format!("Stopped tracing accesses to {}.", attached.name)
+                })
+            }
+            _ => {
+                arguments.finish()?;
+
+                if self.cpu.devices.attached.is_empty() {
+                    return Ok("No devices are attached.".into());
+                }
+                let devices = self
+                    .cpu
+                    .devices
+                    .attached
+                    .iter()
+                    .map(|attached| {
+                        let mut description = format!(
+                            "{} ({} at {:#06x}..{:#06x}",
+                            attached.name,
+                            attached.device.kind(),
+                            attached.base,
+                            u32::from(attached.base) + u32::from(attached.device.length())
+                        );
+                        if let Some(irq) = attached.irq {
+                            description.push_str(&format!(", irq {}", irq));
+                        }
+                        description.push(')');
+                        description
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(format!("Devices: {}.", devices.join(", ")))
+            }
+        }
+    }
+
+    // Handle the INJECT command, whose first argument chooses the fault to inject.
+    fn inject_command(&mut self, mut arguments: Arguments) -> Result<String> {
+        match arguments.choice(&["BITFLIP", "UART-OVERRUN", "IRQ-STORM"])? {
+            "BITFLIP" => {
+                let address = arguments.literal("an address")?;
+                let bit = arguments.optional_number::<u32>("a bit")?;
+                arguments.finish()?;
+
+                let Some(index) = self.cpu.memory.decode(address) else {
+                    return Err(anyhow!("There is no RAM at {:#06x}.", address));
+                };
+                // If no bit is given, then one is chosen at random, as a stray particle would.
+                let bit = bit.unwrap_or_else(|| Random::from_time().below(16) as u32);
+                if bit >= 16 {
+                    return Err(anyhow!("Words only have bits 0 through 15."));
+                }
+                let before = self.cpu.ram[index];
+                self.cpu.ram[index] ^= 1 << bit;
+
+                Ok(format!(
+                    "Flipped bit {} at {:#06x}, from {:#06x} to {:#06x}.",
+                    bit, address, before, self.cpu.ram[index]
+                ))
+            }
+            "UART-OVERRUN" => {
+                let name = arguments.optional_word();
+                arguments.finish()?;
+
+                let attached = self.cpu.devices.target(name.as_deref(), Some("uart"))?;
+                let Some(description) = attached.device.inject("overrun") else {
+                    return Err(anyhow!("{} is not a UART.", attached.name));
+                };
+
+                Ok(format!("Overran {}, and {}.", attached.name, description))
+            }
+            _ => {
+                let name = arguments.word("the name of a device")?;
+                let count = arguments.optional_number("a count")?.unwrap_or(0x100);
+                arguments.finish()?;
+
+                let attached = self.cpu.devices.target(Some(&name), None)?;
+                attached.storm = count;
+
+                Ok(format!(
+                    "Holding the interrupt of {} asserted for {} instructions.",
+                    attached.name, attached.storm
+                ))
+            }
+        }
+    }
+
+    fn taint_command(&mut self, mut arguments: Arguments) -> Result<String> {
+        if arguments
+            .peek()
+            .is_some_and(|word| word.eq_ignore_ascii_case("ON") || word.eq_ignore_ascii_case("OFF"))
+        {
+            let state = arguments.switch()?;
+            arguments.finish()?;
+            self.taint = state.then(Taint::new);
+            return Ok(if state {
+                "Tracking taint from device input. Use TAINT address [length] or TAINT FILE to mark other input.".into()
+            } else {
+                "Stopped tracking taint.".into()
+            });
+        }
+
+        // Marking input starts tracking taint, if it wasn't being tracked already.
+        let (address, length, loaded) = if arguments.keyword("FILE") {
+            let address = arguments.literal("an address")?;
+            let filename = arguments.word("a file")?;
+            arguments.finish()?;
+
+            // NOTE: Unlike LOAD, this doesn't replace the image, since the file is only data
+            // which the program works on. It's still a load, though, so a profile which denies
+            // LOAD denies it as well, and since it writes wherever it's told to, it patches RAM
+            // just as FILL does, so a profile which denies FILL denies it too.
+            if let Some(profile) = &self.profile {
+                profile.check("LOAD")?;
+                profile.check("FILL")?;
+            }
+            let words = read_words(&filename, self.byte_order)?;
+            if usize::from(address) + words.len() > self.cpu.memory.size {
+                return Err(anyhow!(
+                    "{} contains {:#06x} words, which do not fit in RAM at address {:#06x}.",
+                    filename,
+                    words.len(),
+                    address
+                ));
+            }
+            let start = usize::from(address);
+            self.cpu.ram[start..start + words.len()].copy_from_slice(&words);
+            (address, words.len() as u16, Some(filename))
+        } else if arguments.remaining() > 0 {
+            let address = arguments.literal("an address")?;
+            let length = match arguments.remaining() {
+                0 => 1,
+                _ => arguments.literal("a length")?,
+            };
+            arguments.finish()?;
+            (address, length, None)
+        } else {
+            let Some(taint) = &self.taint else {
+                return Ok("Taint isn't being tracked.".into());
+            };
+            let (registers, words) = taint.count();
+            return Ok(format!(
+                "Tracking taint. {} registers and {} words of RAM are tainted.",
+                registers, words
+            ));
+        };
+        let count = self
+            .taint
+            .get_or_insert_with(Taint::new)
+            .mark(&self.cpu, address, length);
+        Ok(match loaded {
+            Some(filename) => format!(
+                "Loaded {:#06x} words from {} into RAM at address {:#06x}, and marked them as tainted.",
+                count, filename, address
+            ),
+            None => format!(
+                "Marked {:#06x} words at {:#06x} as tainted.",
+                count, address
+            ),
+        })
+    }
+
+    // Handle a search for a pattern typed after a slash. An empty pattern clears the search.
+    fn search_command(&mut self, pattern: &str) -> Result<String> {
+        if pattern.trim().is_empty() {
+            self.search = None;
+            return Ok("Cleared the search.".into());
+        }
+        let pattern = RegexBuilder::new(pattern.trim())
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| anyhow!("Invalid search pattern: {}", e))?;
+        // The search begins from whatever the RAM pane is showing, so the first match may be the
+        // address at which it is pinned.
+        let start = self.ram_pin.unwrap_or(self.cpu.program_counter);
+        self.search = Some((pattern, start.wrapping_sub(1)));
+        self.search_step(true)
+    }
+}
+
+// The text against which a search pattern is matched for an address, which consists of the word
+// there in hexadecimal and its disassembly. Runs of whitespace in the disassembly are collapsed,
+// so that patterns like "ld r07" match regardless of how the operands are aligned. A word which is
+// known to be the immediate of an instruction isn't disassembled, since it's never executed as
+// one.
+pub fn search_text(cpu: &Cpu, starts: &Starts, address: u16) -> String {
+    let word = cpu.peek(address);
+    if starts.immediate_of(cpu, address).is_some() {
+        return format!("{:#06x}", word);
+    }
+    let disassembly = disassemble(word, cpu.peek(address.wrapping_add(1)));
+    format!(
+        "{:#06x} {}",
+        word,
+        disassembly.split_whitespace().collect::<Vec<_>>().join(" ")
+    )
+}
+
+// Read a file as a sequence of words, in a given byte order.
+fn read_words(filename: &str, order: ByteOrder) -> Result<Vec<u16>> {
+    let bytes = fs::read(filename).map_err(|e| anyhow!("Failed to read {}: {}", filename, e))?;
+    if bytes.len() > 0x20000 {
+        return Err(anyhow!(
+            "{} is too large to fit in the address space.",
+            filename
+        ));
+    }
+    Ok(order.words(&bytes))
+}
+
+// Load an image from a file into the RAM of a CPU at a given address, returning the number of
+// words loaded along with the CRC-32 of the file.
+fn load_image(
+    cpu: &mut Cpu,
+    address: u16,
+    filename: &str,
+    order: ByteOrder,
+) -> Result<(usize, u32)> {
+    write_image(cpu, address, filename, &fs::read(filename)?, order)
+}
+
+// Construct a CPU for the machine described by the configuration, freshly reset.
+pub fn build_cpu(config: &Config) -> Result<Cpu> {
+    let mut cpu = Cpu::new();
+    let machine = &config.machine;
+    cpu.memory = MemoryMap::new(machine.memory.size, machine.memory.decoding)?;
+    cpu.devices = Devices::new(&machine.devices, &config.arguments)?;
+    if let Some(rom) = &machine.rom {
+        cpu.memory.rom = Some(Rom {
+            words: read_words(&rom.image, config.byte_order)?,
+            mapped: true,
+            writes: rom.writes,
+        });
+    }
+    cpu.interrupts = Interrupts::new(machine.interrupts.as_ref());
+    cpu.reset_vector = machine.reset_vector;
+    cpu.strict = config.strict;
+    cpu.reset();
+    Ok(cpu)
+}
+
+// Write the contents of an image which has already been read from a file into the RAM of a CPU.
+pub fn write_image(
+    cpu: &mut Cpu,
+    address: u16,
+    filename: &str,
+    bytes: &[u8],
+    order: ByteOrder,
+) -> Result<(usize, u32)> {
+    let count = if is_hex_image(filename) {
+        // A hex image may leave gaps, which keep whatever RAM held before.
+        let text = std::str::from_utf8(bytes).map_err(|_| {
+            anyhow!(
+                "{} is not a text file, so it can't be a hex image.",
+                filename
+            )
+        })?;
+        let words = parse_hex_image(text).map_err(|e| anyhow!("{}: {}.", filename, e))?;
+        // Nothing is written unless all of it fits, so that a bad image leaves RAM untouched.
+        if let Some(&(offset, _)) = words.iter().find(|(offset, _)| {
+            usize::from(address)
+                .checked_add(*offset)
+                .is_none_or(|index| index >= cpu.memory.size)
+        }) {
+            return Err(anyhow!(
+                "{} places a word at offset {:#06x}, which does not fit in RAM at address {:#06x}.",
+                filename,
+                offset,
+                address
+            ));
+        }
+        for &(offset, word) in &words {
+            cpu.ram[usize::from(address) + offset] = word;
+        }
+        words.len()
+    } else {
+        let words = order.words(bytes);
+        if usize::from(address) + words.len() > cpu.memory.size {
+            return Err(anyhow!(
+                "{} contains {:#06x} words, which do not fit in RAM at address {:#06x}.",
+                filename,
+                words.len(),
+                address
+            ));
+        }
+        cpu.ram[usize::from(address)..(usize::from(address) + words.len())].copy_from_slice(&words);
+        words.len()
+    };
+    // Whatever was known about how the old image used RAM doesn't apply to the new one.
+    cpu.usage.clear();
+
+    Ok((count, crc32(bytes)))
+}
+
+// A register or a word of memory, as named by an argument.
+enum Location {
+    Register(usize),
+    Memory(u16),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Register(register) => write!(f, "r{:02}", register),
+            Location::Memory(address) => write!(f, "[{:#06x}]", address),
+        }
+    }
+}
+
+// Take the next argument as a register or an address, which may be written in brackets as it
+// would be in an expression.
+fn location(arguments: &mut Arguments) -> Result<Location> {
+    if arguments
+        .peek()
+        .is_some_and(|word| word.starts_with(['r', 'R']))
+    {
+        return Ok(Location::Register(arguments.register()?));
+    }
+    let word = arguments.word("a register or an address")?;
+    let address = word.trim_start_matches('[').trim_end_matches(']');
+    parse_literal(address)
+        .map(Location::Memory)
+        .map_err(|_| anyhow!("\"{}\" is not a register or an address.", word))
+}
+
+// Take every remaining argument as a single expression, so that expressions needn't be quoted.
+fn rest(arguments: &mut Arguments, what: &str) -> Result<String> {
+    let mut words = vec![arguments.word(what)?];
+    while let Some(word) = arguments.optional_word() {
+        words.push(word);
+    }
+    Ok(words.join(" "))
+}
+
+// Write the TYPE command which defines a record type into a session, after those of any types
+// which its fields use.
+fn define_type(session: &mut String, defined: &mut Vec<String>, record: &Record) {
+    if defined.contains(&record.name) {
+        return;
+    }
+    for (_, field) in &record.fields {
+        if let Field::Record(field) = field {
+            define_type(session, defined, field);
+        }
+    }
+    defined.push(record.name.clone());
+    session.push_str(&format!("TYPE {}\n", record.definition()));
+}
+
+// Parse the width of the source column of a listing, which may follow LISTING.
+fn listing_width(arguments: &mut Arguments) -> Result<usize> {
+    if arguments.keyword("WIDTH") {
+        arguments.number("a width")
+    } else {
+        Ok(listing::DEFAULT_WIDTH)
+    }
+}
+
+// Look up a pane by the name which the refresh configuration and REFRESH know it by.
+fn pane_name(name: &str) -> Result<&'static str> {
+    PANES
+        .iter()
+        .find(|pane| pane.eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "\"{}\" is not a pane. The panes are {}.",
+                name,
+                PANES.join(", ")
+            )
+        })
+}
+
+fn frame_rate(rate: u32) -> Result<u32> {
+    match rate {
+        1..=1000 => Ok(rate),
+        _ => Err(anyhow!(
+            "{} frames per second is not a frame rate from 1 to 1000.",
+            rate
+        )),
+    }
+}
+
+// Collect the debug output printed by the devices, reading any strings which they were asked to
+// print from RAM. Debug output is tagged with the name of the port in brackets, so that it stands
+// apart from what the UART transmits.
+fn debug_output(cpu: &mut Cpu, packing: Packing) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, output) in cpu.devices.take_debug() {
+        let text = match output {
+            Output::Line(line) => line,
+            Output::String(address) => read_string(cpu, address, packing),
+        };
+        // A string may hold several lines, but an empty line is still printed.
+        match text.is_empty() {
+            true => lines.push(format!("[{}]", name)),
+            false => lines.extend(text.lines().map(|line| format!("[{}] {}", name, line))),
+        }
+    }
+    lines
+}
+
+// Determine whether or not the architectural state of two CPUs differs, describing the first
+// difference found if so. Only the registers and program counter are compared, as comparing all of
+// RAM on every step would be prohibitively slow.
+fn find_divergence(cpu: &Cpu, reference: &Cpu) -> Option<String> {
+    if cpu.program_counter != reference.program_counter {
+        return Some(format!(
+            "pc is {:#06x}, but the reference has {:#06x}",
+            cpu.program_counter, reference.program_counter
+        ));
+    }
+    (1..0x20)
+        .find(|&i| cpu.registers[i] != reference.registers[i])
+        .map(|i| {
+            format!(
+                "r{:02} is {:#06x}, but the reference has {:#06x}",
+                i, cpu.registers[i], reference.registers[i]
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InterruptConfig;
+    use crate::disassemble::tests::ram_of;
+
+    use std::thread;
+
+    // Each machine is self-contained, so several can be run at once on threads of their own, as
+    // when comparing two versions of a program side by side. The threads are spawned with the
+    // default stack, which is all that a machine should need, even to run commands in a debug build.
+    #[test]
+    fn machines_run_independently_on_their_own_threads() {
+        let handles = [1, 2].map(|increment| {
+            thread::spawn(move || {
+                let mut app = App::new(Config::default()).unwrap();
+                let source = format!("loop: ADDI r01, r01, {}\nBEQ r00, r00, loop", increment);
+                app.cpu.ram.copy_from_slice(&ram_of(&source));
+                app.execute_command_with_result("SET r02 1").unwrap();
+                app.execute_command_with_result("FILL 0x0100 5 0xffff")
+                    .unwrap();
+                app.execute_command_with_result("STEP 10").unwrap();
+                app.execute_command_with_result("PRINT r01").unwrap()
+            })
+        });
+        let results = handles.map(|handle| handle.join().unwrap());
+        assert_eq!(results, ["r01 = 0x0005 (5)", "r01 = 0x000a (10)"]);
+    }
+
+    // Stepping into a handler stops at its entry, unless interrupts are held off while stepping,
+    // in which case they're left pending until the simulation runs.
+    #[test]
+    fn stepping_stops_at_handler_entry_unless_interrupts_are_masked() {
+        // The timer expires on the fourth instruction, once interrupts have been enabled.
+        let source = "ADDI r01, r00, 1\n\
+            STIO r01, r00, 0xffe8\n\
+            STIO r01, r00, 0x8000\n\
+            STIO r01, r00, 0x8001\n\
+            loop: ADDI r02, r02, 1\n\
+            BEQ r00, r00, loop\n\
+            .org 0x0100\n\
+            handler: BEQ r00, r00, handler";
+        for mode in ["STOP", "MASK"] {
+            let mut config = Config::default();
+            config.machine.interrupts = Some(InterruptConfig {
+                vector: 0x0100,
+                mask_on_entry: true,
+                threshold: 0,
+                nmi: None,
+            });
+            config.machine.devices = vec![DeviceConfig {
+                name: "timer0".into(),
+                kind: "timer".into(),
+                base: 0x8000,
+                irq: Some(2),
+                file: None,
+            }];
+            let mut app = App::new(config).unwrap();
+            app.cpu.ram.copy_from_slice(&ram_of(source));
+            app.execute_command_with_result(&format!("STEPIRQ {}", mode))
+                .unwrap();
+            let result = app.execute_command_with_result("STEP 16").unwrap();
+            if mode == "STOP" {
+                assert!(result.contains("entry of the handler for irq 2"));
+                assert_eq!(app.steps, 4);
+                assert_eq!(app.cpu.program_counter, 0x0100);
+            } else {
+                assert_eq!(app.steps, 16);
+                assert!(app.cpu.interrupts.handlers.is_empty());
+                // The interrupt is still pending, so it's taken as soon as the simulation runs.
+                app.execute_command_with_result("RUN").unwrap();
+                assert!(!app.step());
+                assert_eq!(app.cpu.program_counter, 0x0100);
+            }
         }
     }
 }
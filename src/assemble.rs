@@ -0,0 +1,292 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::disassemble::instruction_length;
+
+// Every mnemonic understood by the assembler, along with its opcode. The operands which each
+// instruction expects can be inferred from the length of the instruction, with JSH being the sole
+// special case.
+pub const MNEMONICS: [(&str, u16); 26] = [
+    ("ADD", 0b000000),
+    ("SUB", 0b000001),
+    ("AND", 0b000010),
+    ("OR", 0b000011),
+    ("XOR", 0b000100),
+    ("SLL", 0b000101),
+    ("SRL", 0b000110),
+    ("SRA", 0b000111),
+    ("ADDI", 0b001000),
+    ("ANDI", 0b001010),
+    ("ORI", 0b001011),
+    ("XORI", 0b001100),
+    ("SFTI", 0b001101),
+    ("SRAI", 0b001111),
+    ("LD", 0b010000),
+    ("ST", 0b010001),
+    ("LDIO", 0b011000),
+    ("STIO", 0b011001),
+    ("JAL", 0b101000),
+    ("JSH", 0b101001),
+    ("BEQ", 0b101010),
+    ("BNE", 0b101011),
+    ("BLT", 0b101100),
+    ("BGE", 0b101101),
+    ("BLTU", 0b101110),
+    ("BGEU", 0b101111),
+];
+
+// A single statement of assembly source, stripped of its label and comment.
+enum Statement<'a> {
+    Org(&'a str),
+    Word(Vec<&'a str>),
+    Instruction(u16, Vec<&'a str>),
+}
+
+// Assemble a program, producing a list of the words which it occupies along with their addresses.
+pub fn assemble(source: &str) -> Result<Vec<(u16, u16)>> {
+    let mut words = Vec::new();
+    for (_, start, encoded) in assemble_lines(source)? {
+        for (offset, word) in encoded.into_iter().enumerate() {
+            words.push((start.wrapping_add(u16::try_from(offset)?), word));
+        }
+    }
+    Ok(words)
+}
+
+// Assemble a program, producing the (zero-indexed) number of every line which holds a statement,
+// along with the address at which the statement starts and the words which encode it. This is what
+// a listing of the program is made from. Assembly happens in two passes; the first determines the
+// address of every label and the second actually encodes each statement.
+pub fn assemble_lines(source: &str) -> Result<Vec<(usize, u16, Vec<u16>)>> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+
+    // The first pass.
+    let mut address: u16 = 0x0000;
+    for (number, line) in source.lines().enumerate() {
+        let statement = parse_line(line, address, &mut labels).map_err(|e| at_line(number, e))?;
+        if let Some(statement) = statement {
+            match &statement {
+                Statement::Org(origin) => {
+                    address = parse_literal(origin).map_err(|e| at_line(number, e))?;
+                }
+                Statement::Word(values) => {
+                    address = address.wrapping_add(u16::try_from(values.len())?);
+                }
+                Statement::Instruction(opcode, _) => {
+                    address = address.wrapping_add(instruction_length(*opcode));
+                }
+            }
+            statements.push((number, address, statement));
+        }
+    }
+
+    // The second pass. Since we've already advanced the address past each statement, we need to
+    // work out where each one started.
+    let mut lines = Vec::new();
+    for (number, end, statement) in statements {
+        let encoded = encode(&statement, end, &labels).map_err(|e| at_line(number, e))?;
+        let start = end.wrapping_sub(u16::try_from(encoded.len())?);
+        lines.push((number, start, encoded));
+    }
+
+    Ok(lines)
+}
+
+// An error in a line of source. The line is kept apart from the message, so that whoever asked for
+// the source to be assembled can point at it.
+#[derive(Debug)]
+pub struct LineError {
+    // The number of the offending line, counting from one.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}.", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+// Attach the (zero-indexed) number of the offending line to an error.
+fn at_line(number: usize, error: anyhow::Error) -> anyhow::Error {
+    anyhow::Error::new(LineError {
+        line: number + 1,
+        message: error.to_string(),
+    })
+}
+
+// Break a line of source down into a statement, recording the address of its label if it has one.
+fn parse_line<'a>(
+    line: &'a str,
+    address: u16,
+    labels: &mut HashMap<&'a str, u16>,
+) -> Result<Option<Statement<'a>>> {
+    // Comments extend from a semicolon to the end of the line.
+    let mut line = line.split(';').next().unwrap().trim();
+
+    if let Some((label, rest)) = line.split_once(':') {
+        let label = label.trim();
+        if !is_label(label) {
+            bail!("\"{}\" is not a valid label", label);
+        }
+        if labels.insert(label, address).is_some() {
+            bail!("the label \"{}\" is defined more than once", label);
+        }
+        line = rest.trim();
+    }
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = if operands.trim().is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(str::trim).collect()
+    };
+
+    if mnemonic.eq_ignore_ascii_case(".org") {
+        match operands[..] {
+            [origin] => Ok(Some(Statement::Org(origin))),
+            _ => bail!(".org expects exactly one operand"),
+        }
+    } else if mnemonic.eq_ignore_ascii_case(".word") {
+        if operands.is_empty() {
+            bail!(".word expects at least one operand");
+        }
+        Ok(Some(Statement::Word(operands)))
+    } else {
+        let (_, opcode) = MNEMONICS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(mnemonic))
+            .ok_or_else(|| anyhow!("\"{}\" is not a valid mnemonic", mnemonic))?;
+        Ok(Some(Statement::Instruction(*opcode, operands)))
+    }
+}
+
+// Encode a single statement which ends at a given address.
+fn encode(statement: &Statement, end: u16, labels: &HashMap<&str, u16>) -> Result<Vec<u16>> {
+    match statement {
+        Statement::Org(_) => Ok(Vec::new()),
+        Statement::Word(values) => values.iter().map(|v| parse_value(v, labels)).collect(),
+        Statement::Instruction(0b101001, operands) => {
+            // JSH takes a 10-bit signed offset relative to its own address, which may be given
+            // either as a label or as a literal offset.
+            let [target] = operands[..] else {
+                bail!("JSH expects exactly one operand");
+            };
+            let start = end.wrapping_sub(1);
+            let offset = if is_label(target) {
+                parse_value(target, labels)?.wrapping_sub(start) as i16
+            } else {
+                parse_value(target, labels)? as i16
+            };
+            if !(-0x200..0x200).contains(&offset) {
+                bail!(
+                    "the offset {} does not fit in the 10 bits available to JSH",
+                    offset
+                );
+            }
+            Ok(vec![((offset as u16) << 6) | 0b101001])
+        }
+        Statement::Instruction(opcode, operands) => {
+            let mnemonic = MNEMONICS.iter().find(|(_, o)| o == opcode).unwrap().0;
+            match (instruction_length(*opcode), &operands[..]) {
+                (1, [destination, source]) => {
+                    Ok(vec![encode_registers(*opcode, destination, source)?])
+                }
+                (2, [destination, source, immediate]) => Ok(vec![
+                    encode_registers(*opcode, destination, source)?,
+                    parse_value(immediate, labels)?,
+                ]),
+                (1, _) => bail!("{} expects a destination and a source register", mnemonic),
+                _ => bail!(
+                    "{} expects a destination register, a source register, and an immediate",
+                    mnemonic
+                ),
+            }
+        }
+    }
+}
+
+// Encode the register fields of an instruction along with its opcode.
+fn encode_registers(opcode: u16, destination: &str, source: &str) -> Result<u16> {
+    Ok((parse_register(source)? << 11) | (parse_register(destination)? << 6) | opcode)
+}
+
+// Parse a register name such as r7 or r07.
+fn parse_register(register: &str) -> Result<u16> {
+    register
+        .strip_prefix(['r', 'R'])
+        .and_then(|index| index.parse::<u16>().ok())
+        .filter(|&index| index < 0x20)
+        .ok_or_else(|| anyhow!("\"{}\" is not a valid register", register))
+}
+
+// Parse a value which may be either a label or a (possibly negative) numeric literal.
+fn parse_value(value: &str, labels: &HashMap<&str, u16>) -> Result<u16> {
+    if let Some(negated) = value.strip_prefix('-') {
+        Ok(parse_literal(negated)?.wrapping_neg())
+    } else if is_label(value) {
+        labels
+            .get(value)
+            .copied()
+            .ok_or_else(|| anyhow!("the label \"{}\" is not defined", value))
+    } else {
+        parse_literal(value)
+    }
+}
+
+// Determine whether or not a string is a valid label. Labels may contain letters, digits,
+// underscores, and periods, but may not begin with a digit.
+fn is_label(label: &str) -> bool {
+    label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// Parse a numeric literal which may be negated with a leading minus sign, in which case it is
+// taken as a signed value and encoded in two's complement. Only values which fit in a signed word
+// may be negated, so that "-1" is 0xffff but "-0x8001" is rejected.
+pub fn parse_signed_literal(literal: &str) -> Result<u16> {
+    match literal.strip_prefix('-') {
+        Some(magnitude) => {
+            let magnitude = parse_literal(magnitude)?;
+            if magnitude > 0x8000 {
+                bail!("-{} does not fit in a signed word", magnitude);
+            }
+            Ok(magnitude.wrapping_neg())
+        }
+        None => parse_literal(literal),
+    }
+}
+
+// Parse a numeric literal, which may be given in decimal, or in hexadecimal or binary with a 0x or
+// 0b prefix respectively. Hexadecimal and binary may also be written with an h or b suffix, as in
+// 0ffffh or 1010b, in which case the literal must begin with a digit so that it can't be mistaken
+// for a label. Digits may be grouped with underscores, as in 0x8000_0000 or 1_000.
+pub fn parse_literal(literal: &str) -> Result<u16> {
+    let lowercase = literal.to_lowercase();
+    let (digits, radix, suffixed) = if let Some(hex_literal) = lowercase.strip_prefix("0x") {
+        (hex_literal, 16, false)
+    } else if let Some(binary_literal) = lowercase.strip_prefix("0b") {
+        (binary_literal, 2, false)
+    } else if let Some(hex_literal) = lowercase.strip_suffix('h') {
+        (hex_literal, 16, true)
+    } else if let Some(binary_literal) = lowercase.strip_suffix('b') {
+        (binary_literal, 2, true)
+    } else {
+        (lowercase.as_str(), 10, false)
+    };
+    if digits.starts_with('_') || (suffixed && !digits.starts_with(|c: char| c.is_ascii_digit())) {
+        bail!("\"{}\" must begin with a digit", literal);
+    }
+    Ok(u16::from_str_radix(&digits.replace('_', ""), radix)?)
+}
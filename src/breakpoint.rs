@@ -0,0 +1,171 @@
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+use std::ops::Index;
+
+use crate::command::quote;
+
+// Something kept for each of a set of addresses, such as the breakpoints or the tracepoints. These
+// are looked up after every step, so alongside the map is a bitmap of which addresses it holds,
+// through which the typical step, which arrives at none of them, is dealt with by a single load
+// however many there are. Nothing is ever patched into RAM, so guest code which checksums itself
+// and DUMPs of RAM are none the wiser.
+pub struct AddressMap<T> {
+    entries: BTreeMap<u16, T>,
+    bitmap: Box<[u64; 0x400]>,
+}
+
+impl<T> AddressMap<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            bitmap: Box::new([0; 0x400]),
+        }
+    }
+
+    pub fn contains_key(&self, address: &u16) -> bool {
+        self.bitmap[usize::from(*address >> 6)] & 1 << (*address & 0x3f) != 0
+    }
+
+    pub fn get(&self, address: &u16) -> Option<&T> {
+        match self.contains_key(address) {
+            true => self.entries.get(address),
+            false => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, address: &u16) -> Option<&mut T> {
+        match self.contains_key(address) {
+            true => self.entries.get_mut(address),
+            false => None,
+        }
+    }
+
+    pub fn insert(&mut self, address: u16, value: T) -> Option<T> {
+        self.bitmap[usize::from(address >> 6)] |= 1 << (address & 0x3f);
+        self.entries.insert(address, value)
+    }
+
+    pub fn remove(&mut self, address: &u16) -> Option<T> {
+        self.bitmap[usize::from(*address >> 6)] &= !(1 << (*address & 0x3f));
+        self.entries.remove(address)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bitmap.fill(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, u16, T> {
+        self.entries.iter()
+    }
+}
+
+impl<T> Index<&u16> for AddressMap<T> {
+    type Output = T;
+
+    fn index(&self, address: &u16) -> &T {
+        &self.entries[address]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AddressMap<T> {
+    type Item = (&'a u16, &'a T);
+    type IntoIter = btree_map::Iter<'a, u16, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+pub struct Breakpoint {
+    // A disabled breakpoint is retained, but never halts the simulation.
+    pub enabled: bool,
+    // A temporary breakpoint is deleted as soon as it halts the simulation.
+    pub temporary: bool,
+    // The number of upcoming hits which should be ignored before the simulation is halted.
+    pub ignore: u32,
+    // The number of times that the breakpoint has been reached while enabled, including any hits
+    // which were ignored.
+    pub hits: u32,
+    // A semicolon-separated list of commands to be executed whenever the breakpoint halts the
+    // simulation.
+    pub actions: Option<String>,
+}
+
+impl Breakpoint {
+    // Construct a new, enabled breakpoint.
+    pub fn new(temporary: bool, ignore: u32, actions: Option<String>) -> Self {
+        Self {
+            enabled: true,
+            temporary,
+            ignore,
+            hits: 0,
+            actions,
+        }
+    }
+
+    // Record that the breakpoint has been reached, returning whether or not the simulation should
+    // be halted.
+    pub fn hit(&mut self) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        self.hits += 1;
+        if self.ignore > 0 {
+            self.ignore -= 1;
+            false
+        } else {
+            true
+        }
+    }
+
+    // Produce the command which recreates this breakpoint at a given address, for use in session
+    // files.
+    pub fn command(&self, address: u16) -> String {
+        let mut command = format!(
+            "{} {:#06x}",
+            if self.temporary { "TBREAK" } else { "BREAK" },
+            address
+        );
+        if self.ignore > 0 {
+            command.push_str(&format!(" IGNORE {}", self.ignore));
+        }
+        if let Some(actions) = &self.actions {
+            command.push_str(&format!(" DO {}", quote(actions)));
+        }
+        if !self.enabled {
+            command.push_str(&format!("\nDISABLE {:#06x}", address));
+        }
+        command
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut attributes = Vec::new();
+        if !self.enabled {
+            attributes.push("disabled".to_string());
+        }
+        if self.temporary {
+            attributes.push("temporary".to_string());
+        }
+        if self.ignore > 0 {
+            attributes.push(format!("ignore {}", self.ignore));
+        }
+        attributes.push(format!("hits {}", self.hits));
+        if let Some(actions) = &self.actions {
+            attributes.push(format!("do \"{}\"", actions));
+        }
+
+        write!(f, "({})", attributes.join(", "))
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::diagnostic::Diagnostic;
+
+// An external build, such as a run of make, which is run in the background so that the UI stays
+// responsive while it runs. Its output is collected a line at a time from both of its streams, in
+// whatever order the lines arrive, and any line which reports a problem with the source is kept as
+// a diagnostic.
+pub struct PendingBuild {
+    pub command: String,
+    directory: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+    child: Child,
+    receiver: Receiver<String>,
+    // Whether or not both streams have been closed, so that every line of output has been
+    // collected.
+    closed: bool,
+}
+
+impl PendingBuild {
+    // Begin running a command with the shell, in a given directory.
+    pub fn spawn(command: &str, directory: &Path) -> io::Result<Self> {
+        #[cfg(unix)]
+        let mut shell = {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        #[cfg(not(unix))]
+        let mut shell = {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        };
+        let mut child = shell
+            .arg(command)
+            .current_dir(directory)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let stdout = child
+            .stdout
+            .take()
+            .map(|s| Box::new(s) as Box<dyn Read + Send>);
+        let stderr = child
+            .stderr
+            .take()
+            .map(|s| Box::new(s) as Box<dyn Read + Send>);
+        // The output has all been collected once every copy of the sender has been dropped, so
+        // each stream is given its own.
+        for stream in [stdout, stderr].into_iter().flatten() {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                // NOTE: Tools don't always write UTF-8, and a stray byte shouldn't cost the rest of
+                // the output.
+                for line in BufReader::new(stream).split(b'\n').map_while(Result::ok) {
+                    let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        Ok(Self {
+            command: command.to_string(),
+            directory: directory.to_path_buf(),
+            diagnostics: Vec::new(),
+            child,
+            receiver,
+            closed: false,
+        })
+    }
+
+    // Collect any output without blocking, returning the exit status of the build once it has
+    // exited and every line of its output has been collected.
+    pub fn poll(&mut self, output: &mut Vec<String>) -> Option<io::Result<ExitStatus>> {
+        while !self.closed {
+            match self.receiver.try_recv() {
+                Ok(line) => self.collect(line, output),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => self.closed = true,
+            }
+        }
+        self.child.try_wait().transpose()
+    }
+
+    // Wait for the build to finish, collecting all of its output, as for a BUILD which wasn't
+    // typed at the prompt.
+    pub fn wait(&mut self, output: &mut Vec<String>) -> io::Result<ExitStatus> {
+        while let Ok(line) = self.receiver.recv() {
+            self.collect(line, output);
+        }
+        self.child.wait()
+    }
+
+    fn collect(&mut self, line: String, output: &mut Vec<String>) {
+        self.diagnostics
+            .extend(Diagnostic::parse(&line, &self.directory));
+        output.push(line);
+    }
+
+    // Stop the build, and wait for it to exit.
+    pub fn cancel(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// A recording of everything drawn to the terminal over a session, as an asciicast version 2 file
+// which asciinema can play back, for tutorials and for attaching reproductions to bug reports.
+// Each frame is recorded as an output event once it has been flushed to the terminal, and a marker
+// is added whenever the simulation has moved on by some steps, so that a player can skip between
+// them.
+pub struct Cast {
+    pub filename: String,
+    file: BufWriter<File>,
+    start: Instant,
+    // Whatever has been written to the terminal since the last output event.
+    pending: Vec<u8>,
+    // The number of steps which had been taken at the last marker.
+    steps: Option<u64>,
+    // The first error met while writing the cast, after which nothing more is recorded, since a
+    // full disk is no reason to interrupt the session itself.
+    error: Option<io::Error>,
+}
+
+impl Cast {
+    // Create a cast for a terminal of a given size, writing its header.
+    pub fn create(filename: &str, (width, height): (u16, u16)) -> Result<Self> {
+        let mut file = BufWriter::new(
+            File::create(filename).map_err(|e| anyhow!("Failed to create {}: {}", filename, e))?,
+        );
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        writeln!(
+            file,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"title\": \"ilo\"}}",
+            width, height, timestamp
+        )?;
+        Ok(Self {
+            filename: filename.to_string(),
+            file,
+            start: Instant::now(),
+            pending: Vec::new(),
+            steps: None,
+            error: None,
+        })
+    }
+
+    // Add a marker for the number of steps taken so far, unless it hasn't changed since the last.
+    pub fn mark(&mut self, steps: u64) {
+        if self.steps != Some(steps) {
+            self.steps = Some(steps);
+            self.event("m", &format!("step {}", steps));
+        }
+    }
+
+    // Record that the terminal has been resized.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.event("r", &format!("{}x{}", width, height));
+    }
+
+    // Report the first error met while recording the cast, if there was one.
+    pub fn finish(&mut self) -> Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    // Record whatever has been written to the terminal since the last output event.
+    fn output(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let text = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+        self.event("o", &text);
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        // NOTE: The output must be written before anything else happens, so that a marker or a
+        // resize lands between the frames which it came between.
+        if kind != "o" {
+            self.output();
+        }
+        // Each event is flushed as it's written, so that the cast is still whole up to the last
+        // frame if ilo is killed.
+        let time = self.start.elapsed().as_secs_f64();
+        let written = writeln!(
+            self.file,
+            "[{:.6}, \"{}\", \"{}\"]",
+            time,
+            kind,
+            escape(data)
+        )
+        .and_then(|_| self.file.flush());
+        if let Err(error) = written {
+            self.error = Some(error);
+        }
+    }
+}
+
+// What the UI is drawn to, which is the standard output, teed into a cast if one is being recorded.
+pub struct Recorder {
+    stdout: Stdout,
+    cast: Option<Rc<RefCell<Cast>>>,
+}
+
+impl Recorder {
+    pub fn new(cast: Option<Rc<RefCell<Cast>>>) -> Self {
+        Self {
+            stdout: io::stdout(),
+            cast,
+        }
+    }
+}
+
+impl Write for Recorder {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(bytes)?;
+        if let Some(cast) = &self.cast {
+            cast.borrow_mut()
+                .pending
+                .extend_from_slice(&bytes[..written]);
+        }
+        Ok(written)
+    }
+
+    // The terminal is flushed once each frame has been drawn, which is when a frame is complete,
+    // so that's when each is recorded.
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        if let Some(cast) = &self.cast {
+            cast.borrow_mut().output();
+        }
+        Ok(())
+    }
+}
+
+// Escape text for use in a JSON string.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char < ' ' || char == '\u{7f}' => {
+                escaped.push_str(&format!("\\u{:04x}", u32::from(char)))
+            }
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,41 @@
+// The CRC-32 (as used by zlib, PNG, and friends) of every possible byte, so that a byte can be
+// folded into the checksum with a single lookup rather than eight shifts.
+const TABLE: [u32; 0x100] = {
+    let mut table = [0; 0x100];
+    let mut byte = 0;
+    while byte < 0x100 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+fn update(crc: u32, byte: u8) -> u32 {
+    (crc >> 8) ^ TABLE[usize::from(crc as u8 ^ byte)]
+}
+
+// Compute the CRC-32 of a sequence of bytes.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes
+        .iter()
+        .fold(0xffffffff, |crc, &byte| update(crc, byte))
+}
+
+// Compute the CRC-32 of a sequence of words, taken most significant byte first, just as they are
+// laid out in an image file. This is the same as the CRC-32 of the bytes of a DUMP of the words.
+pub fn crc32_words(words: &[u16]) -> u32 {
+    !words.iter().fold(0xffffffff, |crc, &word| {
+        let [high, low] = word.to_be_bytes();
+        update(update(crc, high), low)
+    })
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use std::io::{self, Write};
+
+// The alphabet used by base64, in order of the values which the characters encode.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Place text on the system clipboard, by way of the terminal. The OSC 52 escape sequence asks the
+// terminal to set its clipboard to the given text, which works without any clipboard library or
+// display server, and even when ilo is running on another machine over SSH.
+// NOTE: Not every terminal honours OSC 52, and some only do so once it has been enabled in their
+// settings. There's no way to tell whether it worked, so the sequence is simply sent.
+pub fn copy(text: &str) -> Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// Encode a sequence of bytes as base64, padded as OSC 52 requires.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64[(group >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
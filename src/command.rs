@@ -0,0 +1,403 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::str::FromStr;
+
+use crate::assemble::{parse_literal, parse_signed_literal};
+
+// A command understood by the prompt, along with a summary of the arguments which it accepts.
+// Optional arguments are given in brackets, alternatives are separated by bars, and keywords are
+// given in capitals.
+pub struct Spec {
+    pub name: &'static str,
+    pub usage: &'static str,
+}
+
+// Every command understood by the prompt, in the order in which they are listed by HELP.
+pub const COMMANDS: &[Spec] = &[
+    Spec { name: "RUN", usage: "RUN" },
+    Spec { name: "HALT", usage: "HALT" },
+    Spec { name: "RESET", usage: "RESET" },
+    Spec { name: "CANCEL", usage: "CANCEL" },
+    Spec { name: "STEP", usage: "STEP [count]" },
+    Spec { name: "STEPWHILE", usage: "STEPWHILE condition" },
+    Spec { name: "STEPUNTIL", usage: "STEPUNTIL condition" },
+    Spec {
+        name: "RUNUNTIL",
+        usage: "RUNUNTIL BRANCH|CALL|RET | RUNUNTIL LOAD|STORE [address [length]]",
+    },
+    Spec { name: "STEPIRQ", usage: "STEPIRQ [STOP|MASK]" },
+    Spec { name: "SET", usage: "SET register|PC value" },
+    Spec { name: "JUMP", usage: "JUMP address" },
+    Spec { name: "SKIP", usage: "SKIP [count]" },
+    Spec { name: "CALL", usage: "CALL address [register=value...]" },
+    Spec { name: "LOAD", usage: "LOAD [address] file" },
+    Spec { name: "RELOAD", usage: "RELOAD" },
+    Spec { name: "RECENT", usage: "RECENT [entry]" },
+    Spec { name: "BUILD", usage: "BUILD" },
+    Spec { name: "VERIFY", usage: "VERIFY [RAM checksum [address length]]" },
+    Spec { name: "DUMP", usage: "DUMP file address length [BIG|LITTLE]" },
+    Spec { name: "FILL", usage: "FILL address length value" },
+    Spec { name: "DIFF", usage: "DIFF address address length" },
+    Spec { name: "HASH", usage: "HASH [address length]" },
+    Spec {
+        name: "EXPORT",
+        usage: "EXPORT MEM|HEX|COE|MIF file [address length]",
+    },
+    Spec { name: "WAVE", usage: "WAVE file [register...] | WAVE OFF" },
+    Spec { name: "COPY", usage: "COPY HISTORY | REGISTERS | TERMINAL | RAM [address [length]] [TO destination]" },
+    Spec { name: "REPORT", usage: "REPORT file" },
+    Spec { name: "RAM", usage: "RAM [PIN address | FOLLOW]" },
+    Spec { name: "FREEZE", usage: "FREEZE" },
+    Spec { name: "THAW", usage: "THAW" },
+    Spec { name: "SEEK", usage: "SEEK [step]" },
+    Spec { name: "MINIMAP", usage: "MINIMAP ON|OFF" },
+    Spec { name: "REFRESH", usage: "REFRESH [RATE fps | pane FRAME|HALT]" },
+    Spec { name: "MARK", usage: "MARK name [address]" },
+    Spec { name: "UNMARK", usage: "UNMARK name" },
+    Spec { name: "GOTO", usage: "GOTO name|address" },
+    Spec { name: "MARKS", usage: "MARKS [ON|OFF]" },
+    Spec { name: "DIAGNOSTICS", usage: "DIAGNOSTICS [ON|OFF|entry]" },
+    Spec { name: "ACCESSES", usage: "ACCESSES [ON [address length] | OFF] | ACCESSES SAVE file" },
+    Spec { name: "STACK", usage: "STACK [ON|OFF]" },
+    Spec { name: "HEAP", usage: "HEAP [ON|OFF]" },
+    Spec { name: "TERMINAL", usage: "TERMINAL [ON|OFF|CLEAR] | TERMINAL SCROLL rows | TERMINAL SAVE file" },
+    Spec {
+        name: "PRINT",
+        usage: "PRINT expression | PRINT \"format\" [expression, ...]",
+    },
+    Spec { name: "TYPE", usage: "TYPE [name { field: type, ... }]" },
+    Spec { name: "WATCHEXPR", usage: "WATCHEXPR expression | DELETE number | CLEAR" },
+    Spec { name: "ASSERT", usage: "ASSERT [expression | DELETE number | CLEAR]" },
+    Spec { name: "SNAPSHOT", usage: "SNAPSHOT file [AGAINST baseline]" },
+    Spec { name: "RESTORE", usage: "RESTORE file" },
+    Spec { name: "COMPARE", usage: "COMPARE [address] file | COMPARE OFF" },
+    Spec { name: "COUNTERS", usage: "COUNTERS [ON|OFF|RESET]" },
+    Spec { name: "STRICT", usage: "STRICT [ON|OFF]" },
+    Spec { name: "WARNINGS", usage: "WARNINGS [ON|OFF]" },
+    Spec { name: "OVERFLOW", usage: "OVERFLOW [OFF|WARN|HALT]" },
+    Spec {
+        name: "TAINT",
+        usage: "TAINT [ON|OFF] | TAINT address [length] | TAINT FILE address file",
+    },
+    Spec { name: "TAINT?", usage: "TAINT? register|address" },
+    Spec { name: "WHOWROTE", usage: "WHOWROTE register|address" },
+    Spec { name: "ROM", usage: "ROM [ON|OFF]" },
+    Spec {
+        name: "DEVICE",
+        usage: "DEVICE ADD kind base [AS name] [IRQ line] [FILE file] | REMOVE name | INFO name | PANE ON|OFF | TRACE name ON|OFF | TRACE SAVE file | LIST",
+    },
+    Spec {
+        name: "INJECT",
+        usage: "INJECT BITFLIP address [bit] | UART-OVERRUN [name] | IRQ-STORM name [count]",
+    },
+    Spec { name: "COST", usage: "COST [RESET]" },
+    Spec { name: "MEASURE", usage: "MEASURE [start end | OFF]" },
+    Spec { name: "FOCUS", usage: "FOCUS register|OFF" },
+    Spec { name: "PRESENT", usage: "PRESENT ON|OFF" },
+    Spec { name: "EXPLAIN", usage: "EXPLAIN [address]" },
+    Spec { name: "LIVENESS", usage: "LIVENESS [address length]" },
+    Spec { name: "ANALYZE", usage: "ANALYZE start end" },
+    Spec { name: "DISASM", usage: "DISASM [entry] file [LISTING [WIDTH width]]" },
+    Spec { name: "ASM", usage: "ASM file [LISTING file [WIDTH width]]" },
+    Spec { name: "BREAK", usage: "BREAK address [IGNORE count] [DO \"commands\"]" },
+    Spec { name: "TBREAK", usage: "TBREAK address [IGNORE count] [DO \"commands\"]" },
+    Spec { name: "ENABLE", usage: "ENABLE address" },
+    Spec { name: "DISABLE", usage: "DISABLE address" },
+    Spec { name: "TRACEPOINT", usage: "TRACEPOINT address \"format\"" },
+    Spec { name: "DELETE", usage: "DELETE address" },
+    Spec { name: "BREAKS", usage: "BREAKS" },
+    Spec { name: "HELP", usage: "HELP [command]" },
+];
+
+// Look up a command by name, case-insensitively.
+pub fn find(name: &str) -> Option<&'static Spec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+// List every command understood by the prompt, for use in messages.
+pub fn supported() -> String {
+    let names = COMMANDS.iter().map(|spec| spec.name).collect::<Vec<_>>();
+    format!(
+        "Supported commands are /pattern, {}.",
+        alternatives(&names, "and")
+    )
+}
+
+// A command which has been split into its name and arguments, from which the arguments are taken
+// one at a time as the command is parsed. Anything which doesn't fit what the command expects is
+// reported along with the command's usage.
+pub struct Arguments {
+    spec: &'static Spec,
+    tokens: Vec<String>,
+    // Whether or not each token was wrapped in quotes.
+    quoted: Vec<bool>,
+    next: usize,
+}
+
+impl Arguments {
+    // Split a command into its name and arguments, checking that the command exists.
+    pub fn parse(command: &str) -> Result<Self> {
+        let (tokens, quoted): (Vec<_>, Vec<_>) = tokenize(command)?.into_iter().unzip();
+        let Some(name) = tokens.first() else {
+            bail!("Type a command and press Enter. {}", supported());
+        };
+        let spec = find(name)
+            .ok_or_else(|| anyhow!("\"{}\" is not a valid command. {}", name, supported()))?;
+        Ok(Self {
+            spec,
+            tokens,
+            quoted,
+            next: 1,
+        })
+    }
+
+    // The name of the command, in capitals.
+    pub fn name(&self) -> &'static str {
+        self.spec.name
+    }
+
+    // The number of arguments which have yet to be taken.
+    pub fn remaining(&self) -> usize {
+        self.tokens.len() - self.next
+    }
+
+    // Look at the next argument without taking it.
+    pub fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    // Whether or not the next argument was wrapped in quotes, which tells a string apart from
+    // something else spelled the same way.
+    pub fn next_is_quoted(&self) -> bool {
+        self.quoted.get(self.next).copied().unwrap_or(false)
+    }
+
+    // Take the next argument, whatever it is, if there is one.
+    pub fn optional_word(&mut self) -> Option<String> {
+        let word = self.tokens.get(self.next).cloned();
+        if word.is_some() {
+            self.next += 1;
+        }
+        word
+    }
+
+    // Take the next argument, whatever it is, describing what was expected if there isn't one.
+    pub fn word(&mut self, what: &str) -> Result<String> {
+        self.optional_word()
+            .ok_or_else(|| self.error(&format!("expected {}", what)))
+    }
+
+    // Take the next argument if it is the given keyword, returning whether or not it was.
+    pub fn keyword(&mut self, keyword: &str) -> bool {
+        let matches = self
+            .peek()
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword));
+        if matches {
+            self.next += 1;
+        }
+        matches
+    }
+
+    // Take the next argument as one of several keywords, returning the keyword as it was given in
+    // the list of choices, or None if there are no more arguments.
+    pub fn optional_choice(&mut self, choices: &[&'static str]) -> Result<Option<&'static str>> {
+        let Some(word) = self.peek() else {
+            return Ok(None);
+        };
+        match choices
+            .iter()
+            .find(|choice| choice.eq_ignore_ascii_case(word))
+        {
+            Some(&choice) => {
+                self.next += 1;
+                Ok(Some(choice))
+            }
+            None => Err(self.error(&format!(
+                "expected {}, got '{}'",
+                alternatives(choices, "or"),
+                word
+            ))),
+        }
+    }
+
+    // Take the next argument as one of several keywords.
+    pub fn choice(&mut self, choices: &[&'static str]) -> Result<&'static str> {
+        self.optional_choice(choices)?
+            .ok_or_else(|| self.error(&format!("expected {}", alternatives(choices, "or"))))
+    }
+
+    // Take the next argument as ON or OFF, if there is one.
+    pub fn optional_switch(&mut self) -> Result<Option<bool>> {
+        Ok(self
+            .optional_choice(&["ON", "OFF"])?
+            .map(|state| state == "ON"))
+    }
+
+    // Take the next argument as ON or OFF.
+    pub fn switch(&mut self) -> Result<bool> {
+        Ok(self.choice(&["ON", "OFF"])? == "ON")
+    }
+
+    // Take the next argument as a numeric literal, if there is one.
+    pub fn optional_literal(&mut self, what: &str) -> Result<Option<u16>> {
+        let Some(word) = self.peek() else {
+            return Ok(None);
+        };
+        match parse_literal(word) {
+            Ok(value) => {
+                self.next += 1;
+                Ok(Some(value))
+            }
+            Err(_) => Err(self.error(&format!("expected {}, got '{}'", what, word))),
+        }
+    }
+
+    // Take the next argument as a numeric literal, such as an address.
+    pub fn literal(&mut self, what: &str) -> Result<u16> {
+        self.optional_literal(what)?
+            .ok_or_else(|| self.error(&format!("expected {}", what)))
+    }
+
+    // Take the next argument as a value to be stored in a word, which may be negative, in which
+    // case it is encoded in two's complement.
+    pub fn value(&mut self) -> Result<u16> {
+        let word = self.word("a value")?;
+        parse_signed_literal(&word)
+            .map_err(|_| self.error(&format!("expected a value, got '{}'", word)))
+    }
+
+    // Take the next argument as a decimal number, if there is one. As in literals, the digits may
+    // be grouped with underscores.
+    pub fn optional_number<T: FromStr>(&mut self, what: &str) -> Result<Option<T>> {
+        let Some(word) = self.peek() else {
+            return Ok(None);
+        };
+        let digits = if word.starts_with('_') {
+            word.to_string()
+        } else {
+            word.replace('_', "")
+        };
+        match digits.parse() {
+            Ok(value) => {
+                self.next += 1;
+                Ok(Some(value))
+            }
+            Err(_) => Err(self.error(&format!("expected {}, got '{}'", what, word))),
+        }
+    }
+
+    // Take the next argument as a decimal number, such as a count.
+    pub fn number<T: FromStr>(&mut self, what: &str) -> Result<T> {
+        self.optional_number(what)?
+            .ok_or_else(|| self.error(&format!("expected {}", what)))
+    }
+
+    // Take the next argument as a register, written as r followed by its number.
+    pub fn register(&mut self) -> Result<usize> {
+        let word = self.word("a register")?;
+        word.strip_prefix(['r', 'R'])
+            .and_then(|number| number.parse::<usize>().ok())
+            .filter(|&register| register < 0x20)
+            .ok_or_else(|| {
+                self.error(&format!(
+                    "expected a register from r00 to r31, got '{}'",
+                    word
+                ))
+            })
+    }
+
+    // Check that every argument has been taken, since anything left over was not understood.
+    pub fn finish(&self) -> Result<()> {
+        match self.peek() {
+            Some(word) => Err(self.error(&format!("unexpected '{}'", word))),
+            None => Ok(()),
+        }
+    }
+
+    // Describe a problem with the arguments, along with the usage of the command.
+    pub fn error(&self, problem: &str) -> anyhow::Error {
+        anyhow!(
+            "{}: {}. Usage: {}",
+            self.spec.name,
+            problem,
+            self.spec.usage
+        )
+    }
+}
+
+// Describe a list of words as alternatives, as in "ON or OFF", or as a list, as in "a, b, and c".
+fn alternatives(words: &[&str], conjunction: &str) -> String {
+    match words {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [first, second] => format!("{} {} {}", first, conjunction, second),
+        [rest @ .., last] => format!("{}, {} {}", rest.join(", "), conjunction, last),
+    }
+}
+
+// Split a command into arguments at runs of whitespace. An argument may be wrapped in double
+// quotes so that it can contain whitespace, as in `LOAD "my file.bin"`, and within quotes a
+// backslash escapes the character after it, so that quotes and backslashes can be included too.
+// Outside of quotes, backslashes are taken literally, since they're common in Windows paths. Each
+// argument is given along with whether or not it was quoted.
+pub fn tokenize(command: &str) -> Result<Vec<(String, bool)>> {
+    let mut tokens = Vec::new();
+    let mut chars = command.chars().peekable();
+    loop {
+        while chars.next_if(|char| char.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(tokens);
+        };
+
+        let mut token = String::new();
+        let quoted = first == '"';
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(char) => token.push(char),
+                        None => bail!(
+                            "The quoted argument beginning \"{}\" is never closed.",
+                            token
+                        ),
+                    },
+                    Some(char) => token.push(char),
+                    None => bail!(
+                        "The quoted argument beginning \"{}\" is never closed.",
+                        token
+                    ),
+                }
+            }
+            // A closing quote must end the argument, or else it's unclear what was meant.
+            if chars.peek().is_some_and(|char| !char.is_whitespace()) {
+                bail!(
+                    "The quoted argument \"{}\" must be followed by a space.",
+                    token
+                );
+            }
+        } else {
+            while let Some(char) = chars.next_if(|char| !char.is_whitespace()) {
+                token.push(char);
+            }
+        }
+        tokens.push((token, quoted));
+    }
+}
+
+// Quote an argument so that tokenizing it gives back exactly the same text, for use when writing
+// commands into session files.
+pub fn quote(argument: &str) -> String {
+    let mut quoted = String::from("\"");
+    for char in argument.chars() {
+        if matches!(char, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(char);
+    }
+    quoted.push('"');
+    quoted
+}
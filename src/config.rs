@@ -0,0 +1,330 @@
+use anyhow::{anyhow, bail, Result};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::assemble::parse_literal;
+use crate::expr::Packing;
+use crate::interrupt::Stepping;
+use crate::load::ByteOrder;
+use crate::memory::{Decoding, RomWrites};
+use crate::warning::Overflow;
+
+// The path from which the configuration is loaded at startup.
+const CONFIG_PATH: &str = "ilo.toml";
+
+// The path from which the machine description is loaded at startup, unless another is given with
+// --machine.
+const MACHINE_PATH: &str = "machine.toml";
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // The cost of executing each instruction, keyed by mnemonic. Instructions which are not listed
+    // cost a single unit.
+    pub cost: HashMap<String, u64>,
+    // Whether or not instructions which write to r00 fault, as with STRICT ON.
+    pub strict: bool,
+    // Whether or not suspicious instructions are noted in the console, as with WARNINGS ON.
+    pub warnings: bool,
+    // What stepping does about interrupts, as with STEPIRQ, which is either `"stop"` to stop at
+    // the entry of a handler or `"mask"` to hold interrupts off.
+    pub interrupt_stepping: Stepping,
+    // What is done on signed overflow, as with OVERFLOW, which is one of `"off"`, `"warn"`, or
+    // `"halt"`.
+    pub overflow: Overflow,
+    // Whether or not usage statistics are recorded in the stats file, for `ilo stats` to show.
+    pub stats: bool,
+    // The profile which restricts the commands available, if any, as with --profile.
+    pub profile: Option<String>,
+    // Profiles defined in addition to the built-in ones, written as `[profiles.name]` tables.
+    pub profiles: HashMap<String, ProfileConfig>,
+    // The calling convention followed by the guest, which the call stack is unwound with.
+    pub abi: AbiConfig,
+    // The layout of the heap managed by the guest's allocator, if it has one, which the Heap pane
+    // walks.
+    pub heap: Option<HeapConfig>,
+    // The order of the bytes of each word in binary images and data files, either `"big"` or
+    // `"little"`, as LOAD reads them and as DUMP writes them, as with --byte-order.
+    pub byte_order: ByteOrder,
+    // How the guest holds strings, either as `"word"` with one character to a word or as
+    // `"packed"` with two characters to a word, which is how str[...] reads them.
+    pub strings: Packing,
+    // How often the UI is redrawn, for keeping it responsive over a slow connection.
+    pub refresh: RefreshConfig,
+    // Bindings of keys to actions, keyed by the name of the key, as in `"Shift-F10" = "STEP 16"`.
+    // These are applied on top of the default bindings.
+    pub keys: HashMap<String, String>,
+    // The machine being emulated. This is kept in a separate file from the rest of the
+    // configuration, so that several board variants can be kept side by side.
+    #[serde(skip)]
+    pub machine: Machine,
+    // Whether or not a summary of where the emulator's own time went is printed on exit, as with
+    // --self-profile.
+    #[serde(skip)]
+    pub self_profile: bool,
+    // The file into which everything drawn to the terminal is recorded as an asciicast, if any, as
+    // with --record-cast.
+    #[serde(skip)]
+    pub record_cast: Option<String>,
+    // The arguments passed to the guest, which are whatever follows -- on the command line, and
+    // which the guest reads through a hypercall port.
+    #[serde(skip)]
+    pub arguments: Vec<String>,
+}
+
+// A description of the hardware of the machine being emulated.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Machine {
+    pub memory: MemoryConfig,
+    // The boot ROM, if the machine has one.
+    pub rom: Option<RomConfig>,
+    // The address at which execution begins when the machine is reset.
+    pub reset_vector: u16,
+    // The interrupt controller, if the CPU takes interrupts. Otherwise, guest code must poll its
+    // devices to find out whether they are asserting their interrupt lines.
+    pub interrupts: Option<InterruptConfig>,
+    // The memory-mapped devices attached to the bus, written as an array of `[[device]]` tables.
+    #[serde(rename = "device")]
+    pub devices: Vec<DeviceConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    // The number of words of RAM which are fitted, which must be a power of two.
+    pub size: usize,
+    // Whether addresses beyond the end of RAM are left unmapped, or mirror the RAM beneath them.
+    pub decoding: Decoding,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomConfig {
+    // The file from which the contents of the boot ROM are read at startup.
+    pub image: String,
+    // Whether writes to the boot ROM land in the RAM beneath it or halt the simulation.
+    #[serde(default)]
+    pub writes: RomWrites,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    // The name by which the device is referred to in commands and in its output.
+    pub name: String,
+    // The kind of device, such as "uart" or "timer".
+    pub kind: String,
+    // The address of the first of the device's registers.
+    pub base: u16,
+    // The interrupt line which the device raises, if it is wired to one.
+    pub irq: Option<u8>,
+    // A file backing the device, whose meaning depends on the kind of device.
+    pub file: Option<String>,
+}
+
+// How the CPU takes interrupts, for modeling different designs of interrupt controller.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterruptConfig {
+    // The address of the handler, which every line shares.
+    pub vector: u16,
+    // Whether or not entering a handler masks every other line until it returns. If not, then a
+    // handler may be interrupted by a line more urgent than its own, so handlers nest.
+    #[serde(default = "default_mask_on_entry")]
+    pub mask_on_entry: bool,
+    // The least urgent line which is taken after a reset, where lines with greater numbers are
+    // more urgent. The guest may change it through the controller's threshold register.
+    #[serde(default)]
+    pub threshold: u8,
+    // The line which can't be masked, if any.
+    pub nmi: Option<u8>,
+}
+
+fn default_mask_on_entry() -> bool {
+    true
+}
+
+// A set of restrictions on the commands which may be used, as for a graded exercise.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    // The only commands which may be used, if given. Otherwise, every command may be used.
+    pub allow: Option<Vec<String>>,
+    // Commands which may not be used, even if they're allowed above.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+// The calling convention followed by the guest. A call is a JAL which links into the link
+// register, and the stack grows down from the stack pointer. If functions keep a frame pointer,
+// then it points at a record holding the return address and the caller's frame pointer, at the
+// given offsets from it; otherwise the stack is searched for return addresses. A function returns
+// its results in the result registers.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AbiConfig {
+    pub link: usize,
+    pub stack: usize,
+    pub frame: Option<usize>,
+    pub saved_return: i16,
+    pub saved_frame: i16,
+    pub results: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RefreshConfig {
+    // The most frames which are drawn each second.
+    pub rate: u32,
+    // The panes which are only redrawn once the simulation halts, rather than on every frame while
+    // it runs, such as `["ram", "history"]`.
+    pub halt: Vec<String>,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        Self {
+            rate: 60,
+            halt: Vec::new(),
+        }
+    }
+}
+
+// The layout of the heap managed by a simple allocator. The heap is a run of blocks from `start` up
+// to, but not including, `end`, each of which holds its length in words, header included, at the
+// offset `size` from its start. Free blocks are chained into a list through the word at the offset
+// `next` from their start, beginning with the pointer held at `head` and ending with `null`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeapConfig {
+    pub start: u16,
+    pub end: u16,
+    pub head: u16,
+    #[serde(default)]
+    pub size: i16,
+    #[serde(default = "default_next")]
+    pub next: i16,
+    #[serde(default)]
+    pub null: u16,
+}
+
+fn default_next() -> i16 {
+    1
+}
+
+impl Default for AbiConfig {
+    fn default() -> Self {
+        Self {
+            link: 31,
+            stack: 30,
+            frame: None,
+            saved_return: 1,
+            saved_frame: 0,
+            results: vec![1],
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            size: 0x10000,
+            decoding: Decoding::default(),
+        }
+    }
+}
+
+impl Config {
+    // Load the configuration and the machine description from the working directory. If either
+    // file is missing, then the defaults are used in its place.
+    pub fn load() -> Result<Self> {
+        let mut config: Self = read_toml(CONFIG_PATH)?.unwrap_or_default();
+        config.machine = read_toml(MACHINE_PATH)?.unwrap_or_default();
+        Ok(config)
+    }
+
+    // Override parts of the configuration with command line arguments. A machine description
+    // given with --machine is loaded before any other overrides are applied, regardless of the
+    // order in which the arguments appear.
+    pub fn apply_arguments(&mut self, arguments: impl Iterator<Item = String>) -> Result<()> {
+        self.apply_arguments_with(arguments, |argument, _| {
+            bail!("Unrecognized argument \"{}\".", argument)
+        })
+    }
+
+    // Override parts of the configuration with command line arguments, just as `apply_arguments`
+    // does, but handing any argument which isn't recognized to a subcommand which takes arguments
+    // of its own, along with the rest of the arguments, so that it can take any values which
+    // follow it.
+    pub fn apply_arguments_with<I: Iterator<Item = String>>(
+        &mut self,
+        mut arguments: I,
+        mut other: impl FnMut(String, &mut I) -> Result<()>,
+    ) -> Result<()> {
+        let mut reset_vector = None;
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--machine" => {
+                    let Some(path) = arguments.next() else {
+                        bail!("--machine must be followed by the path to a machine description.");
+                    };
+                    self.machine = read_toml(&path)?.ok_or_else(|| {
+                        anyhow!("The machine description {} does not exist.", path)
+                    })?;
+                }
+                "--profile" => {
+                    let Some(name) = arguments.next() else {
+                        bail!("--profile must be followed by the name of a profile.");
+                    };
+                    self.profile = Some(name);
+                }
+                "--self-profile" => self.self_profile = true,
+                "--record-cast" => {
+                    let Some(path) = arguments.next() else {
+                        bail!("--record-cast must be followed by the path to a cast.");
+                    };
+                    self.record_cast = Some(path);
+                }
+                "--" => self.arguments.extend(arguments.by_ref()),
+                "--byte-order" => {
+                    let order = arguments.next().unwrap_or_default();
+                    let Some(order) = ByteOrder::parse(&order) else {
+                        bail!("--byte-order must be followed by big or little.");
+                    };
+                    self.byte_order = order;
+                }
+                "--reset-vector" => {
+                    let Some(address) = arguments.next() else {
+                        bail!("--reset-vector must be followed by an address.");
+                    };
+                    reset_vector =
+                        Some(parse_literal(&address.to_lowercase()).map_err(|_| {
+                            anyhow!("\"{}\" is not a valid reset vector.", address)
+                        })?);
+                }
+                _ => other(argument, &mut arguments)?,
+            }
+        }
+        if let Some(reset_vector) = reset_vector {
+            self.machine.reset_vector = reset_vector;
+        }
+        Ok(())
+    }
+}
+
+// Read and parse a TOML file, returning `None` if it doesn't exist.
+pub fn read_toml<T: DeserializeOwned>(path: &str) -> Result<Option<T>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents)
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid {}: {}", path, e)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::HashMap;
+
+use crate::assemble::MNEMONICS;
+
+pub struct CostModel {
+    // The cost of each opcode, indexed by opcode.
+    table: [u64; 0x40],
+    pub total: u64,
+    // The cost accumulated by each function, keyed by the address of its entry point. Cost
+    // accumulated outside of any function is keyed by `None`.
+    pub functions: HashMap<Option<u16>, u64>,
+    // A shadow call stack, holding the entry point and return address of each function which has
+    // been called but has not yet returned.
+    call_stack: Vec<(u16, u16)>,
+}
+
+impl CostModel {
+    // Construct a new cost model from a table mapping mnemonics to costs.
+    pub fn new(costs: &HashMap<String, u64>) -> Result<Self> {
+        let mut table = [1; 0x40];
+        for (mnemonic, &cost) in costs {
+            let (_, opcode) = MNEMONICS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(mnemonic))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "\"{}\" in the cost table is not a valid mnemonic.",
+                        mnemonic
+                    )
+                })?;
+            table[usize::from(*opcode)] = cost;
+        }
+
+        Ok(Self {
+            table,
+            total: 0,
+            functions: HashMap::new(),
+            call_stack: Vec::new(),
+        })
+    }
+
+    // Account for the execution of an instruction at a given address, given the value of the
+    // program counter after it was executed.
+    pub fn record(&mut self, address: u16, instruction: u16, program_counter: u16) {
+        let cost = self.table[usize::from(instruction & 0b0000000000111111)];
+        // NOTE: Costs come straight from the configuration, so they may be large enough to
+        // overflow once added up, in which case the totals simply stop at the largest they can be.
+        self.total = self.total.saturating_add(cost);
+        let function = self
+            .functions
+            .entry(self.call_stack.last().map(|&(entry, _)| entry))
+            .or_insert(0);
+        *function = function.saturating_add(cost);
+
+        // A JAL which writes a link is treated as a call, and arriving at the return address of
+        // the innermost call is treated as a return from it.
+        let linked = instruction & 0b0000011111000000 != 0;
+        if instruction & 0b0000000000111111 == 0b101000 && linked {
+            self.call_stack
+                .push((program_counter, address.wrapping_add(2)));
+        } else if self
+            .call_stack
+            .last()
+            .is_some_and(|&(_, return_address)| return_address == program_counter)
+        {
+            self.call_stack.pop();
+        }
+    }
+
+    // Forget all accumulated costs.
+    pub fn reset(&mut self) {
+        self.total = 0;
+        self.functions.clear();
+        self.call_stack.clear();
+    }
+}
@@ -0,0 +1,76 @@
+// The base address of the performance counters, when they are mapped into the address space. Each
+// counter is 32 bits wide, and so occupies two consecutive addresses, with the low word first.
+pub const COUNTERS_BASE: u16 = 0xfff0;
+
+// The number of addresses occupied by the performance counters.
+const COUNTERS_LENGTH: u16 = 6;
+
+#[derive(Clone, Default)]
+pub struct Counters {
+    // Whether or not the counters are visible to guest code. When they are mapped, loads and stores
+    // to their addresses access the counters rather than RAM.
+    pub mapped: bool,
+    // NOTE: The emulator doesn't model any sort of pipeline, so a cycle is simply counted for each
+    // word fetched from memory, whether as an instruction, an immediate, or data.
+    pub cycles: u32,
+    pub instructions: u32,
+    // Only conditional branches count towards this total, and only when they are taken.
+    pub branches: u32,
+    // Reading the low word of a counter latches its high word, so that guest code can read the
+    // full 32-bit value consistently, even though the counter may be incremented between the two
+    // reads.
+    latch: u16,
+}
+
+impl Counters {
+    // Read from the counters, returning `None` if the address doesn't belong to them.
+    pub fn read(&mut self, address: u16) -> Option<u16> {
+        let offset = self.offset(address)?;
+        let counter = self.counter(offset / 2);
+        if offset % 2 == 0 {
+            self.latch = (counter >> 16) as u16;
+            Some(counter as u16)
+        } else {
+            Some(self.latch)
+        }
+    }
+
+    // Write to the counters, returning whether or not the address belongs to them. Writing any
+    // value to either word of a counter resets it to zero.
+    pub fn write(&mut self, address: u16) -> bool {
+        match self.offset(address) {
+            Some(offset) => {
+                *self.counter_mut(offset / 2) = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Whether or not an address belongs to the counters, while they are mapped.
+    pub fn contains(&self, address: u16) -> bool {
+        self.offset(address).is_some()
+    }
+
+    // Determine the offset of an address into the counters, if it falls within them.
+    fn offset(&self, address: u16) -> Option<u16> {
+        let offset = address.wrapping_sub(COUNTERS_BASE);
+        (self.mapped && offset < COUNTERS_LENGTH).then_some(offset)
+    }
+
+    fn counter(&self, index: u16) -> u32 {
+        match index {
+            0 => self.cycles,
+            1 => self.instructions,
+            _ => self.branches,
+        }
+    }
+
+    fn counter_mut(&mut self, index: u16) -> &mut u32 {
+        match index {
+            0 => &mut self.cycles,
+            1 => &mut self.instructions,
+            _ => &mut self.branches,
+        }
+    }
+}
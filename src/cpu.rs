@@ -1,15 +1,48 @@
+use crate::counters::Counters;
+use crate::device::Devices;
+use crate::disassemble::{disassemble, instruction_length};
+use crate::interrupt::Interrupts;
+use crate::memory::MemoryMap;
+use crate::usage::{Usage, EXECUTED, READ, WRITTEN};
+
+#[derive(Clone)]
 pub struct Cpu {
-    // The CPU has 32 registers. The zero register, or `registers[0]`, always outputs a value of
-    // 0x0000 when it is read. The only reason that we allocate space for 32 registers here, rather
-    // than 31, is just so that we can read from and write to registers with slightly more reckless
-    // abandon.
+    // The CPU has 32 registers. The zero register, or `registers[0]`, always holds a value of
+    // 0x0000, since writes to it are discarded as they are made. The only reason that we allocate
+    // space for 32 registers here, rather than 31, is just so that we can index registers directly.
+    // Anything which modifies the registers from outside of the CPU should go through
+    // `set_register`, so that the zero register stays zero.
     pub registers: [u16; 0x20],
     // The program counter points to the address in ram containing the next instruction to be
     // executed.
     pub program_counter: u16,
     // The RAM is somewhat unusual, in that its word size is 16 bits, rather than the more typical
     // 8 bits. Consequently, an address refers to a 16-bit value in RAM, rather than an 8-bit one.
-    pub ram: [u16; 0x10000],
+    // It's kept on the heap, since the CPU is copied around by value, and 128 KiB at a time soon
+    // overflows the stack of anything but the main thread.
+    pub ram: Box<[u16; 0x10000]>,
+    // The memory map determines how addresses are decoded when less RAM is fitted than the address
+    // space allows for. The backing store above is always full-sized regardless, but words beyond
+    // the fitted RAM are never accessed by the CPU.
+    pub memory: MemoryMap,
+    // The performance counters keep track of the work done by the CPU, and may optionally be
+    // mapped into the address space, where they shadow the RAM beneath them.
+    pub counters: Counters,
+    // A description of why the last instruction could not be completed, if it couldn't. The
+    // instruction has no effect in this case, other than advancing the program counter.
+    pub fault: Option<String>,
+    // The memory-mapped devices attached to the bus, which shadow the RAM beneath them.
+    pub devices: Devices,
+    // The interrupt controller, through which devices interrupt the CPU, if it takes interrupts.
+    // Like the performance counters, its registers shadow the RAM beneath them.
+    pub interrupts: Interrupts,
+    // The address at which execution begins after a reset.
+    pub reset_vector: u16,
+    // How the program has used each word of RAM, which is purely for display purposes.
+    pub usage: Usage,
+    // Whether or not an instruction which writes to r00 faults, rather than having its result
+    // silently discarded. Such an instruction is harmless, but it almost always indicates a bug.
+    pub strict: bool,
 }
 
 impl Cpu {
@@ -20,11 +53,78 @@ impl Cpu {
             // hardware is unlikely to offer such a guarantee, so software should not rely on
             // these values.
             registers: [0x0000; 0x20],
-            ram: [0x0000; 0x10000],
+            ram: vec![0x0000; 0x10000].try_into().unwrap(),
 
-            // The program counter is guaranteed to always be initialized to 0x0000. Hardware must
-            // also offer this guarantee.
+            // The program counter is guaranteed to always be initialized to the reset vector,
+            // which is 0x0000 unless the machine is configured otherwise. Hardware must also offer
+            // this guarantee.
             program_counter: 0x0000,
+            reset_vector: 0x0000,
+
+            counters: Counters::default(),
+            memory: MemoryMap::default(),
+            fault: None,
+            devices: Devices::default(),
+            interrupts: Interrupts::default(),
+            strict: false,
+            usage: Usage::default(),
+        }
+    }
+
+    // Write to a register. Writes to r00 are discarded, as they are in hardware.
+    pub fn set_register(&mut self, register: usize, value: u16) {
+        if register != 0 {
+            self.registers[register] = value;
+        }
+    }
+
+    // Assert the reset line. The registers are cleared and execution resumes from the reset
+    // vector, with the boot ROM mapped back in and the performance counters zeroed, but the
+    // contents of RAM survive, just as they would on a real board.
+    pub fn reset(&mut self) {
+        self.registers = [0x0000; 0x20];
+        self.program_counter = self.reset_vector;
+        self.fault = None;
+        self.counters.cycles = 0;
+        self.counters.instructions = 0;
+        self.counters.branches = 0;
+        if let Some(rom) = &mut self.memory.rom {
+            rom.mapped = true;
+        }
+        self.devices.reset();
+        self.interrupts.reset();
+    }
+
+    // Read from an address as the CPU would see it, respecting the memory map. Unlike a load, this
+    // has no side effects, and so the performance counters are not consulted.
+    pub fn peek(&self, address: u16) -> u16 {
+        match self
+            .interrupts
+            .read(address)
+            .or_else(|| self.devices.peek(address))
+        {
+            Some(value) => value,
+            None => self.memory.read(&self.ram[..], address),
+        }
+    }
+
+    // Read the whole of the address space as the CPU would see it, for anything which works on an
+    // image of memory at once, rather than a word at a time.
+    pub fn image(&self) -> Vec<u16> {
+        (0..=u16::MAX).map(|address| self.peek(address)).collect()
+    }
+
+    // Record that the program has used the word of RAM at an address, unless the address is
+    // shadowed by something other than RAM.
+    fn touch(&mut self, address: u16, flag: u8) {
+        if self.devices.peek(address).is_some()
+            || self.interrupts.contains(address)
+            || self.memory.shadowing_rom(address).is_some()
+        {
+            return;
+        }
+        if let Some(index) = self.memory.decode(address) {
+            self.usage.mark(index, flag, self.counters.instructions);
         }
     }
 
@@ -37,19 +137,24 @@ impl Cpu {
         // next address. Technically, the JSH instruction takes an immediate operand which is held
         // at the address indicated by the program counter, but this is a special case which we may
         // treat separately.
-        let instruction = self.ram[usize::from(self.program_counter)];
-        let immediate = self.ram[usize::from(self.program_counter.wrapping_add(1))];
+        let instruction = self.peek(self.program_counter);
+        let immediate = self.peek(self.program_counter.wrapping_add(1));
+        self.touch(self.program_counter, EXECUTED);
+        if instruction_length(instruction) == 2 {
+            self.touch(self.program_counter.wrapping_add(1), EXECUTED);
+        }
+        self.devices.context = (self.program_counter, self.counters.cycles);
+        self.interrupts.taken = None;
 
         // Break up the instruction into its constituent parts for ease of access. Observe that it
         // is valuable to have a mutable reference to the destination register, but an instruction
-        // will never write to its source register, so we only need the value. r0 is hardwired to
-        // contain 0x0000.
-        let source = if instruction & 0b1111100000000000 == 0x0000 {
-            0x0000
-        } else {
-            self.registers[usize::from((instruction & 0b1111100000000000) >> 11)]
-        };
-        let destination = &mut self.registers[usize::from((instruction & 0b0000011111000000) >> 6)];
+        // will never write to its source register, so we only need the value. The destination is
+        // worked on in a copy, which is only written back to the register file at the end of the
+        // instruction if it isn't r00.
+        let source = self.registers[usize::from((instruction & 0b1111100000000000) >> 11)];
+        let register = usize::from((instruction & 0b0000011111000000) >> 6);
+        let mut result = self.registers[register];
+        let destination = &mut result;
         let opcode = instruction & 0b0000000000111111;
 
         // At the end of the day, what is an emulator but socially acceptable trappings on a
@@ -83,27 +188,27 @@ impl Cpu {
             0b000101 => {
                 // SLL
                 *destination = if (source as i16).is_positive() {
-                    *destination << source
+                    shift_left(*destination, source)
                 } else {
-                    *destination >> -(source as i16)
+                    shift_right(*destination, source)
                 };
                 self.program_counter = self.program_counter.wrapping_add(1);
             }
             0b000110 => {
                 // SRL
                 *destination = if (source as i16).is_positive() {
-                    *destination >> source
+                    shift_right(*destination, source)
                 } else {
-                    *destination << -(source as i16)
+                    shift_left(*destination, source)
                 };
                 self.program_counter = self.program_counter.wrapping_add(1);
             }
             0b000111 => {
                 // SRA
                 *destination = if (source as i16).is_positive() {
-                    ((*destination as i16) >> source) as u16
+                    shift_right_arithmetic(*destination, source)
                 } else {
-                    *destination << -(source as i16)
+                    shift_left(*destination, source)
                 };
                 self.program_counter = self.program_counter.wrapping_add(1);
             }
@@ -130,39 +235,83 @@ impl Cpu {
             0b001101 => {
                 // SFTI
                 *destination = if (immediate as i16).is_positive() {
-                    source << immediate
+                    shift_left(source, immediate)
                 } else {
-                    source >> -(immediate as i16)
+                    shift_right(source, immediate)
                 };
                 self.program_counter = self.program_counter.wrapping_add(2);
             }
             0b001111 => {
                 // SRAI
                 *destination = if (immediate as i16).is_positive() {
-                    ((source as i16) >> immediate) as u16
+                    shift_right_arithmetic(source, immediate)
                 } else {
-                    source << -(immediate as i16)
+                    shift_left(source, immediate)
                 };
                 self.program_counter = self.program_counter.wrapping_add(2);
             }
             0b010000 => {
                 // LD
-                *destination = self.ram[usize::from(source)];
+                *destination = match self
+                    .interrupts
+                    .read(source)
+                    .or_else(|| self.counters.read(source))
+                {
+                    Some(value) => value,
+                    None => match self.devices.read(source) {
+                        Some(value) => value,
+                        None => {
+                            self.touch(source, READ);
+                            self.memory.read(&self.ram[..], source)
+                        }
+                    },
+                };
                 self.program_counter = self.program_counter.wrapping_add(1);
             }
             0b010001 => {
                 // ST
-                self.ram[usize::from(source)] = *destination;
+                if !self.interrupts.write(source, *destination)
+                    && !self.counters.write(source)
+                    && !self.devices.write(source, *destination)
+                {
+                    self.touch(source, WRITTEN);
+                    if !self.memory.write(&mut self.ram[..], source, *destination) {
+                        self.fault = Some(format!("write to the boot ROM at {:#06x}", source));
+                    }
+                }
                 self.program_counter = self.program_counter.wrapping_add(1);
             }
             0b011000 => {
                 // LDIO
-                *destination = self.ram[usize::from(source.wrapping_add(immediate))];
+                let address = source.wrapping_add(immediate);
+                *destination = match self
+                    .interrupts
+                    .read(address)
+                    .or_else(|| self.counters.read(address))
+                {
+                    Some(value) => value,
+                    None => match self.devices.read(address) {
+                        Some(value) => value,
+                        None => {
+                            self.touch(address, READ);
+                            self.memory.read(&self.ram[..], address)
+                        }
+                    },
+                };
                 self.program_counter = self.program_counter.wrapping_add(2);
             }
             0b011001 => {
                 // STIO
-                self.ram[usize::from(source.wrapping_add(immediate))] = *destination;
+                let address = source.wrapping_add(immediate);
+                if !self.interrupts.write(address, *destination)
+                    && !self.counters.write(address)
+                    && !self.devices.write(address, *destination)
+                {
+                    self.touch(address, WRITTEN);
+                    if !self.memory.write(&mut self.ram[..], address, *destination) {
+                        self.fault = Some(format!("write to the boot ROM at {:#06x}", address));
+                    }
+                }
                 self.program_counter = self.program_counter.wrapping_add(2);
             }
             0b101000 => {
@@ -179,6 +328,7 @@ impl Cpu {
                 // BEQ
                 if *destination == source {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -187,6 +337,7 @@ impl Cpu {
                 // BNE
                 if *destination != source {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -195,6 +346,7 @@ impl Cpu {
                 // BLT
                 if (*destination as i16) < (source as i16) {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -203,6 +355,7 @@ impl Cpu {
                 // BGE
                 if (*destination as i16) >= (source as i16) {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -211,6 +364,7 @@ impl Cpu {
                 // BLTU
                 if *destination < source {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -219,6 +373,7 @@ impl Cpu {
                 // BGEU
                 if *destination >= source {
                     self.program_counter = immediate;
+                    self.counters.branches = self.counters.branches.wrapping_add(1);
                 } else {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
@@ -229,5 +384,235 @@ impl Cpu {
                 // not rely on unused instrustions behaving as NOPs.
             }
         }
+
+        // A store to the return register of the interrupt controller returns from the handler, in
+        // place of carrying on to the next instruction.
+        if let Some(resume) = self.interrupts.take_resume() {
+            self.program_counter = resume;
+        }
+
+        if register != 0 {
+            self.registers[register] = result;
+        } else if self.strict && writes_destination(opcode) {
+            self.fault = Some(format!(
+                "write to r00 by {}",
+                disassemble(instruction, immediate).trim()
+            ));
+        }
+
+        // Update the performance counters. Loads and stores take an extra cycle to access memory.
+        let accesses = match opcode {
+            0b010000 | 0b010001 | 0b011000 | 0b011001 => 1,
+            _ => 0,
+        };
+        self.counters.instructions = self.counters.instructions.wrapping_add(1);
+        self.counters.cycles = self
+            .counters
+            .cycles
+            .wrapping_add(u32::from(instruction_length(instruction)) + accesses);
+        self.devices.tick();
+
+        // Once the instruction has completed, the most urgent line which isn't masked is taken, so
+        // that the next instruction executed is the first of its handler.
+        if let Some(line) = self.interrupts.pending(&self.devices) {
+            if let Some(vector) = self.interrupts.enter(line, self.program_counter) {
+                self.program_counter = vector;
+            }
+        }
+    }
+}
+
+// Determine whether or not an instruction writes to its destination register, for the purposes of
+// strict mode. JAL is deliberately excluded, since discarding the link is how a plain jump or
+// return is written.
+fn writes_destination(opcode: u16) -> bool {
+    matches!(
+        opcode,
+        0b000000..=0b001000 | 0b001010..=0b001101 | 0b001111 | 0b010000 | 0b011000
+    )
+}
+
+// Shift amounts are signed, and it is the instruction which determines the direction of the shift
+// for an amount of either sign, so these helpers only consider the magnitude of the amount. Shifting
+// by the width of a word or more shifts out every bit, rather than panicking as Rust's shifts would,
+// since guest code is free to shift by any amount it pleases.
+fn magnitude(amount: u16) -> u32 {
+    u32::from((amount as i16).unsigned_abs())
+}
+
+fn shift_left(value: u16, amount: u16) -> u16 {
+    value.checked_shl(magnitude(amount)).unwrap_or(0x0000)
+}
+
+fn shift_right(value: u16, amount: u16) -> u16 {
+    value.checked_shr(magnitude(amount)).unwrap_or(0x0000)
+}
+
+// An arithmetic shift by the width of a word or more leaves only copies of the sign bit.
+fn shift_right_arithmetic(value: u16, amount: u16) -> u16 {
+    ((value as i16) >> magnitude(amount).min(15)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeviceConfig, InterruptConfig};
+    use crate::disassemble::tests::ram_of;
+    use crate::interrupt::INTERRUPTS_BASE;
+
+    // Assemble a program into the RAM of a fresh CPU, and execute a given number of instructions.
+    fn run(source: &str, strict: bool, steps: usize) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.strict = strict;
+        cpu.ram.copy_from_slice(&ram_of(source));
+        for _ in 0..steps {
+            cpu.step();
+        }
+        cpu
+    }
+
+    #[test]
+    fn r00_stays_zero_after_alu_writes() {
+        let cpu = run(
+            "ADDI r00, r00, 0x1234\nADDI r01, r00, 1\nADD r00, r01",
+            false,
+            3,
+        );
+        assert_eq!(cpu.registers[0], 0x0000);
+        assert_eq!(cpu.fault, None);
+    }
+
+    #[test]
+    fn r00_stays_zero_after_loads() {
+        let source = "ADDI r01, r00, data\nLD r00, r01\nLDIO r00, r00, data\ndata: .word 0xbeef";
+        let cpu = run(source, false, 3);
+        assert_eq!(cpu.registers[0], 0x0000);
+    }
+
+    #[test]
+    fn r00_stays_zero_after_jal() {
+        let cpu = run("JAL r00, r00, next\nnext: JSH 0", false, 1);
+        assert_eq!(cpu.registers[0], 0x0000);
+        assert_eq!(cpu.program_counter, 0x0002);
+    }
+
+    #[test]
+    fn set_register_discards_writes_to_r00() {
+        let mut cpu = Cpu::new();
+        cpu.set_register(0, 0xffff);
+        cpu.set_register(1, 0xffff);
+        assert_eq!(cpu.registers[0], 0x0000);
+        assert_eq!(cpu.registers[1], 0xffff);
+    }
+
+    #[test]
+    fn strict_faults_on_writes_to_r00() {
+        let cpu = run("ADDI r00, r00, 1", true, 1);
+        assert!(cpu.fault.is_some());
+        let cpu = run("LDIO r00, r00, 0x0000", true, 1);
+        assert!(cpu.fault.is_some());
+        assert_eq!(cpu.registers[0], 0x0000);
+    }
+
+    #[test]
+    fn strict_allows_jal_to_discard_its_link() {
+        let cpu = run("JAL r00, r00, next\nnext: JSH 0", true, 1);
+        assert_eq!(cpu.fault, None);
+    }
+
+    // Attach a timer to each of the given interrupt lines, holding every line asserted for a given
+    // number of instructions, and give the CPU an interrupt controller whose handler is at 0x0100.
+    fn interrupting(cpu: &mut Cpu, mask_on_entry: bool, lines: &[(u8, u32)]) {
+        let configs = lines
+            .iter()
+            .map(|&(line, _)| DeviceConfig {
+                name: format!("timer{}", line),
+                kind: "timer".into(),
+                base: 0x8000 + u16::from(line) * 2,
+                irq: Some(line),
+                file: None,
+            })
+            .collect::<Vec<_>>();
+        cpu.devices = Devices::new(&configs, &[]).unwrap();
+        for (attached, &(_, storm)) in cpu.devices.attached.iter_mut().zip(lines) {
+            attached.storm = storm;
+        }
+        cpu.interrupts = Interrupts::new(Some(&InterruptConfig {
+            vector: 0x0100,
+            mask_on_entry,
+            threshold: 0,
+            nmi: Some(7),
+        }));
+        cpu.interrupts.enabled = true;
+    }
+
+    const HANDLER: &str = "ADDI r01, r00, 1\nADDI r02, r00, 2\n.org 0x0100\nSTIO r00, r00, 0xffea";
+
+    #[test]
+    fn interrupts_are_taken_between_instructions_and_return_to_them() {
+        let mut cpu = run(HANDLER, false, 0);
+        interrupting(&mut cpu, true, &[(2, 2)]);
+        cpu.step();
+        assert_eq!(cpu.registers[1], 0x0001);
+        assert_eq!(cpu.program_counter, 0x0100);
+        assert_eq!(cpu.interrupts.taken, Some(2));
+        assert_eq!(cpu.peek(INTERRUPTS_BASE + 1), 0x0002);
+        // The handler returns once the line is no longer asserted.
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0002);
+        assert!(cpu.interrupts.handlers.is_empty());
+        cpu.step();
+        assert_eq!(cpu.registers[2], 0x0002);
+    }
+
+    #[test]
+    fn interrupts_are_masked_while_disabled_or_below_the_threshold() {
+        let mut cpu = run(HANDLER, false, 0);
+        interrupting(&mut cpu, true, &[(2, 8)]);
+        cpu.interrupts.enabled = false;
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0002);
+        cpu.interrupts.enabled = true;
+        cpu.interrupts.threshold = 3;
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0004);
+    }
+
+    #[test]
+    fn handlers_nest_only_if_entry_does_not_mask() {
+        for mask_on_entry in [true, false] {
+            let mut cpu = run(HANDLER, false, 0);
+            interrupting(&mut cpu, mask_on_entry, &[(1, 8), (3, 8)]);
+            cpu.devices.attached[1].storm = 0;
+            cpu.step();
+            assert_eq!(cpu.interrupts.taken, Some(1));
+            // A more urgent line is asserted while the first handler is running.
+            cpu.devices.attached[1].storm = 8;
+            cpu.program_counter = 0x0000;
+            cpu.step();
+            let nested = (!mask_on_entry).then_some(3);
+            assert_eq!(cpu.interrupts.taken, nested);
+        }
+    }
+
+    #[test]
+    fn the_nmi_is_taken_even_while_interrupts_are_disabled() {
+        let mut cpu = run(HANDLER, false, 0);
+        interrupting(&mut cpu, true, &[(1, 8), (7, 8)]);
+        cpu.interrupts.enabled = false;
+        cpu.step();
+        assert_eq!(cpu.interrupts.taken, Some(7));
+        // Nothing interrupts the NMI handler, not even the NMI again.
+        cpu.interrupts.enabled = true;
+        cpu.program_counter = 0x0000;
+        cpu.step();
+        assert_eq!(cpu.interrupts.taken, None);
+    }
+
+    #[test]
+    fn strict_allows_writes_to_other_registers() {
+        let cpu = run("ADDI r01, r00, 1", true, 1);
+        assert_eq!(cpu.fault, None);
+        assert_eq!(cpu.registers[1], 0x0001);
     }
 }
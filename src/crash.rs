@@ -0,0 +1,61 @@
+use anyhow::Result;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::app::App;
+use crate::report::report;
+use crate::snapshot;
+
+// Write a crash dump describing the state of the session when ilo failed, returning the path to
+// the report. The report is the same one written by REPORT, along with what went wrong and the
+// commands which led up to it, and the state of the machine is saved as a snapshot alongside it,
+// so that it can be restored with RESTORE and examined.
+pub fn dump(app: &App, cause: &str) -> Result<String> {
+    // Name the files for the time of the crash, so that one crash never overwrites another.
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let report_path = format!("ilo-crash-{}.md", time);
+    let snapshot_path = format!("ilo-crash-{}.snapshot", time);
+
+    let mut dump = String::from("# ilo crash dump\n\n");
+    dump.push_str("## Cause\n\n```\n");
+    dump.push_str(cause.trim_end());
+    dump.push_str("\n```\n\n");
+    // NOTE: The snapshot is saved first, so that the dump can say whether or not it worked. Even a
+    // dump without a snapshot is worth having.
+    match snapshot::save(&app.cpu, &snapshot_path, None) {
+        Ok(_) => dump.push_str(&format!(
+            "The state of the machine was saved to `{}`, and can be restored with `RESTORE {}`.\n\n",
+            snapshot_path, snapshot_path
+        )),
+        Err(error) => dump.push_str(&format!(
+            "The state of the machine could not be saved: {}\n\n",
+            error
+        )),
+    }
+
+    // The report has a title of its own, which is left out.
+    let report = report(app);
+    dump.push_str(
+        report
+            .split_once('\n')
+            .map_or("", |(_, rest)| rest.trim_start()),
+    );
+
+    dump.push_str("\n## Recent commands\n\n");
+    if app.recent_commands.is_empty() {
+        dump.push_str("No commands were executed.\n");
+    } else {
+        dump.push_str("The oldest comes first.\n\n```\n");
+        for command in &app.recent_commands {
+            dump.push_str(command);
+            dump.push('\n');
+        }
+        dump.push_str("```\n");
+    }
+
+    fs::write(&report_path, dump)?;
+    Ok(report_path)
+}
@@ -0,0 +1,91 @@
+use crate::device::Device;
+
+// The offsets of the debug port's registers.
+const CHAR: u16 = 0;
+const STRING: u16 = 1;
+const VALUE: u16 = 2;
+
+// Something which the guest has asked the debug port to print.
+#[derive(Clone)]
+pub enum Output {
+    // A complete line of text.
+    Line(String),
+    // The string in RAM at an address. The port can't read RAM itself, so the string is read by
+    // the app once the instruction which wrote the address has completed.
+    String(u16),
+}
+
+// A semihosted debug port, through which guest code can print to the console without a driver for
+// the UART. Writing to the char register prints the low byte of the value, with lines completed by
+// a newline, just as with the UART. Writing an address to the string register prints the string
+// held there as a line of its own, and writing to the value register prints the value as a line of
+// its own, in hexadecimal and in decimal. Every register reads as 0x0000.
+#[derive(Clone, Default)]
+pub struct Debug {
+    // Characters printed since the last complete line was collected.
+    pub line: Vec<u8>,
+    output: Vec<Output>,
+}
+
+impl Device for Debug {
+    fn kind(&self) -> &'static str {
+        "debug"
+    }
+
+    fn length(&self) -> u16 {
+        3
+    }
+
+    fn register_name(&self, offset: u16) -> &str {
+        match offset {
+            CHAR => "char",
+            STRING => "string",
+            _ => "value",
+        }
+    }
+
+    fn read(&mut self, offset: u16) -> u16 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u16) {
+        match offset {
+            CHAR => match value as u8 {
+                b'\n' => {
+                    let line = String::from_utf8_lossy(&self.line).into_owned();
+                    self.output.push(Output::Line(line));
+                    self.line.clear();
+                }
+                byte => self.line.push(byte),
+            },
+            STRING => self.output.push(Output::String(value)),
+            VALUE => self
+                .output
+                .push(Output::Line(format!("{:#06x} ({})", value, value as i16))),
+            _ => {}
+        }
+    }
+
+    fn peek(&self, _offset: u16) -> u16 {
+        0x0000
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![format!(
+            "printed, awaiting newline: {:?}",
+            String::from_utf8_lossy(&self.line)
+        )]
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+    }
+
+    fn take_debug(&mut self) -> Vec<Output> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
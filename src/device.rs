@@ -0,0 +1,464 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::config::DeviceConfig;
+use crate::debug::{Debug, Output};
+use crate::hypercall::{Hypercall, Request};
+use crate::script::Scripted;
+use crate::timer::Timer;
+use crate::uart::Uart;
+
+// A memory-mapped peripheral. Each device occupies a contiguous range of addresses, and its
+// registers are addressed by their offset from the start of that range.
+//
+// NOTE: Devices must never perform host I/O themselves, since the CPU (and everything attached to
+// it) is routinely cloned in order to predict the effects of an instruction. Anything which a
+// device produces for the host is buffered, and collected by the app after each step. Devices must
+// be Send as well, so that a whole machine can be moved to a thread of its own.
+pub trait Device: Send {
+    // The kind of device, as written in the machine description.
+    fn kind(&self) -> &'static str;
+    // The number of registers which the device occupies.
+    fn length(&self) -> u16;
+    // The name of the register at a given offset.
+    fn register_name(&self, offset: u16) -> &str;
+    fn read(&mut self, offset: u16) -> u16;
+    fn write(&mut self, offset: u16, value: u16);
+    // Read a register without any side effects, for display purposes.
+    fn peek(&self, offset: u16) -> u16;
+    // Return the device to its power-on state.
+    fn reset(&mut self);
+    // Describe the internal state of the device, one line at a time.
+    fn describe(&self) -> Vec<String>;
+    // Whether or not the device is currently asserting its interrupt line.
+    fn interrupt(&self) -> bool {
+        false
+    }
+    // Advance the device by a single instruction's worth of time.
+    fn tick(&mut self) {}
+    // Inject a fault specific to this kind of device, returning a description of what was done, or
+    // `None` if the device doesn't support the fault.
+    fn inject(&mut self, _fault: &str) -> Option<String> {
+        None
+    }
+    // Collect any complete lines of text which the device has produced for the host.
+    fn take_output(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+    // Collect every byte which the device has transmitted, for the guest's terminal.
+    fn take_transmitted(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+    // Collect anything which the guest has asked the device to print as debug output.
+    fn take_debug(&mut self) -> Vec<Output> {
+        Vec::new()
+    }
+    // Collect anything which the guest has asked of the emulator itself through the device.
+    fn take_requests(&mut self) -> Vec<Request> {
+        Vec::new()
+    }
+    fn clone_box(&self) -> Box<dyn Device>;
+}
+
+// A device, along with where it is attached to the bus.
+pub struct Attached {
+    pub name: String,
+    pub base: u16,
+    // The interrupt line which the device is wired to, if any.
+    // NOTE: Unless the machine has an interrupt controller, the CPU doesn't take interrupts, so
+    // this is purely descriptive, and guest code must poll devices to find out whether they are
+    // asserting their interrupt lines.
+    pub irq: Option<u8>,
+    // Whether or not every access to the device's registers is logged.
+    pub traced: bool,
+    // The number of instructions for which the device's interrupt is held asserted regardless of
+    // its state, in order to simulate a storm of spurious interrupts.
+    pub storm: u32,
+    pub device: Box<dyn Device>,
+}
+
+impl Clone for Attached {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            base: self.base,
+            irq: self.irq,
+            traced: self.traced,
+            storm: self.storm,
+            device: self.device.clone_box(),
+        }
+    }
+}
+
+impl Attached {
+    // Describe the device and its internal state, beginning with a summary of where it is
+    // attached.
+    pub fn describe(&self) -> Vec<String> {
+        let irq = match self.irq {
+            Some(irq) if self.interrupt() => format!(", irq {} asserted", irq),
+            Some(irq) => format!(", irq {}", irq),
+            None if self.interrupt() => ", interrupt pending".to_string(),
+            None => String::new(),
+        };
+        let mut lines = vec![format!(
+            "{} ({} at {:#06x}{})",
+            self.name,
+            self.device.kind(),
+            self.base,
+            irq
+        )];
+        lines.extend(
+            self.device
+                .describe()
+                .into_iter()
+                .map(|line| format!("  {}", line)),
+        );
+        if self.storm > 0 {
+            lines.push(format!(
+                "  interrupt storm for {} more instructions",
+                self.storm
+            ));
+        }
+        lines
+    }
+
+    // Whether or not the device is asserting its interrupt line, taking any injected interrupt
+    // storm into account.
+    pub fn interrupt(&self) -> bool {
+        self.storm > 0 || self.device.interrupt()
+    }
+
+    // Determine the offset of an address into the device's registers, if it falls within them.
+    fn offset(&self, address: u16) -> Option<u16> {
+        let offset = address.wrapping_sub(self.base);
+        (offset < self.device.length()).then_some(offset)
+    }
+}
+
+// The devices attached to the bus. Like the performance counters, they shadow the memory beneath
+// them.
+#[derive(Clone, Default)]
+pub struct Devices {
+    pub attached: Vec<Attached>,
+    // The program counter and cycle count of the instruction being executed, which are recorded
+    // alongside each traced access.
+    pub context: (u16, u32),
+    // Traced accesses which have yet to be collected.
+    trace: Vec<String>,
+}
+
+impl Devices {
+    // Construct the devices described by a machine description, making sure that none of them
+    // collide with one another. The arguments passed to the guest are given to any device which
+    // offers them to it.
+    pub fn new(configs: &[DeviceConfig], arguments: &[String]) -> Result<Self> {
+        let mut devices = Self::default();
+        for config in configs {
+            devices.attach(create(config, arguments)?)?;
+        }
+        Ok(devices)
+    }
+
+    // The interrupt lines which are currently asserted, in ascending order. Devices which aren't
+    // wired to a line don't appear, even if they are interrupting.
+    pub fn asserted(&self) -> Vec<u8> {
+        let lines = self
+            .attached
+            .iter()
+            .filter(|attached| attached.interrupt())
+            .filter_map(|attached| attached.irq)
+            .collect::<BTreeSet<_>>();
+        lines.into_iter().collect()
+    }
+
+    // Attach a device to the bus.
+    pub fn attach(&mut self, attached: Attached) -> Result<()> {
+        let end = u32::from(attached.base) + u32::from(attached.device.length());
+        if end > 0x10000 {
+            bail!(
+                "{} at {:#06x} extends past the end of the address space.",
+                attached.name,
+                attached.base
+            );
+        }
+        for other in &self.attached {
+            if other.name.eq_ignore_ascii_case(&attached.name) {
+                bail!("There is already a device named {}.", attached.name);
+            }
+            let other_end = u32::from(other.base) + u32::from(other.device.length());
+            if u32::from(attached.base) < other_end && u32::from(other.base) < end {
+                bail!(
+                    "{} at {:#06x} overlaps {} at {:#06x}.",
+                    attached.name,
+                    attached.base,
+                    other.name,
+                    other.base
+                );
+            }
+        }
+        self.attached.push(attached);
+        Ok(())
+    }
+
+    // Detach the device with a given name from the bus, returning it if there was one.
+    pub fn detach(&mut self, name: &str) -> Option<Attached> {
+        let index = self
+            .attached
+            .iter()
+            .position(|attached| attached.name.eq_ignore_ascii_case(name))?;
+        Some(self.attached.remove(index))
+    }
+
+    // Find the device with a given name.
+    pub fn find(&self, name: &str) -> Option<&Attached> {
+        self.attached
+            .iter()
+            .find(|attached| attached.name.eq_ignore_ascii_case(name))
+    }
+
+    // Choose a name for a new device of a given kind which isn't already taken, by numbering
+    // devices of the same kind from zero.
+    pub fn unused_name(&self, kind: &str) -> String {
+        (0..)
+            .map(|i| format!("{}{}", kind.to_lowercase(), i))
+            .find(|name| {
+                !self
+                    .attached
+                    .iter()
+                    .any(|attached| attached.name.eq_ignore_ascii_case(name))
+            })
+            .unwrap()
+    }
+
+    // Read from the devices, returning `None` if the address doesn't belong to any of them.
+    pub fn read(&mut self, address: u16) -> Option<u16> {
+        let attached = self
+            .attached
+            .iter_mut()
+            .find(|attached| attached.offset(address).is_some())?;
+        let offset = attached.offset(address).unwrap();
+        let value = attached.device.read(offset);
+        if attached.traced {
+            let line = trace_line(attached, self.context, "read", offset, value);
+            self.trace.push(line);
+        }
+        Some(value)
+    }
+
+    // Write to the devices, returning whether or not the address belongs to any of them.
+    pub fn write(&mut self, address: u16, value: u16) -> bool {
+        let Some(attached) = self
+            .attached
+            .iter_mut()
+            .find(|attached| attached.offset(address).is_some())
+        else {
+            return false;
+        };
+        let offset = attached.offset(address).unwrap();
+        attached.device.write(offset, value);
+        if attached.traced {
+            let line = trace_line(attached, self.context, "write", offset, value);
+            self.trace.push(line);
+        }
+        true
+    }
+
+    // Collect the traced accesses made since the last collection.
+    pub fn take_trace(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.trace)
+    }
+
+    // Find the device with a given name, so that it may be modified.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Attached> {
+        self.attached
+            .iter_mut()
+            .find(|attached| attached.name.eq_ignore_ascii_case(name))
+    }
+
+    // Read from the devices without any side effects.
+    pub fn peek(&self, address: u16) -> Option<u16> {
+        self.attached.iter().find_map(|attached| {
+            let offset = attached.offset(address)?;
+            Some(attached.device.peek(offset))
+        })
+    }
+
+    pub fn reset(&mut self) {
+        for attached in &mut self.attached {
+            attached.device.reset();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for attached in &mut self.attached {
+            attached.device.tick();
+            attached.storm = attached.storm.saturating_sub(1);
+        }
+    }
+
+    // Find the device which a fault should be injected into, given either its name or its kind.
+    // If neither is given, then the only device of the given kind is chosen, if there is exactly
+    // one.
+    pub fn target(&mut self, name: Option<&str>, kind: Option<&str>) -> Result<&mut Attached> {
+        // A device whose name matches exactly always wins over devices of a matching kind.
+        let exact = name.and_then(|name| {
+            self.attached
+                .iter()
+                .position(|attached| attached.name.eq_ignore_ascii_case(name))
+        });
+        let candidates = self
+            .attached
+            .iter()
+            .enumerate()
+            .filter(|(_, attached)| {
+                let wanted = name.or(kind);
+                wanted.is_none_or(|wanted| attached.device.kind().eq_ignore_ascii_case(wanted))
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        match (exact, candidates.as_slice()) {
+            (Some(index), _) | (None, &[index]) => Ok(&mut self.attached[index]),
+            (None, []) => Err(anyhow!(
+                "There is no device named or of kind {}.",
+                name.or(kind).unwrap_or("any")
+            )),
+            (None, _) => Err(anyhow!(
+                "Several devices could be meant, so the device must be named."
+            )),
+        }
+    }
+
+    // Collect the output of every device, with each line prefixed by the name of its device.
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.attached
+            .iter_mut()
+            .flat_map(|attached| {
+                let name = attached.name.clone();
+                attached
+                    .device
+                    .take_output()
+                    .into_iter()
+                    .map(move |line| format!("{}: {}", name, line))
+            })
+            .collect()
+    }
+
+    // Collect the bytes transmitted by every device since the last collection.
+    pub fn take_transmitted(&mut self) -> Vec<u8> {
+        self.attached
+            .iter_mut()
+            .flat_map(|attached| attached.device.take_transmitted())
+            .collect()
+    }
+
+    // Collect the debug output printed by every device since the last collection, along with the
+    // name of the device which printed it.
+    pub fn take_debug(&mut self) -> Vec<(String, Output)> {
+        self.attached
+            .iter_mut()
+            .flat_map(|attached| {
+                let name = attached.name.clone();
+                attached
+                    .device
+                    .take_debug()
+                    .into_iter()
+                    .map(move |output| (name.clone(), output))
+            })
+            .collect()
+    }
+
+    // Collect the requests made of the emulator through every device since the last collection,
+    // along with the name of the device through which each was made.
+    pub fn take_requests(&mut self) -> Vec<(String, Request)> {
+        self.attached
+            .iter_mut()
+            .flat_map(|attached| {
+                let name = attached.name.clone();
+                attached
+                    .device
+                    .take_requests()
+                    .into_iter()
+                    .map(move |request| (name.clone(), request))
+            })
+            .collect()
+    }
+}
+
+// Describe a single access to a device's registers.
+fn trace_line(
+    attached: &Attached,
+    (program_counter, cycle): (u16, u32),
+    access: &str,
+    offset: u16,
+    value: u16,
+) -> String {
+    format!(
+        "{}: {} {} {:#06x} ({:#06x}) at pc {:#06x}, cycle {}",
+        attached.name,
+        access,
+        attached.device.register_name(offset),
+        value,
+        attached.base + offset,
+        program_counter,
+        cycle
+    )
+}
+
+// Construct a device from its description, given the arguments passed to the guest.
+pub fn create(config: &DeviceConfig, arguments: &[String]) -> Result<Attached> {
+    let file = match &config.file {
+        Some(file) => Some(fs::read(file).map_err(|e| {
+            anyhow!(
+                "Failed to read {}, the file backing {}: {}",
+                file,
+                config.name,
+                e
+            )
+        })?),
+        None => None,
+    };
+
+    let device: Box<dyn Device> = match config.kind.to_lowercase().as_str() {
+        "uart" => Box::new(Uart::new(file.unwrap_or_default())),
+        "timer" if file.is_some() => {
+            bail!("{} is a timer, which takes no backing file.", config.name)
+        }
+        "timer" => Box::new(Timer::default()),
+        "debug" if file.is_some() => {
+            bail!("{} is a debug port, which takes no backing file.", config.name)
+        }
+        "debug" => Box::new(Debug::default()),
+        "hypercall" if file.is_some() => {
+            bail!(
+                "{} is a hypercall port, which takes no backing file.",
+                config.name
+            )
+        }
+        "hypercall" => Box::new(Hypercall::new(arguments)),
+        "script" => match file {
+            Some(file) => Box::new(
+                Scripted::new(&file)
+                    .map_err(|e| anyhow!("Failed to set up {}: {}", config.name, e))?,
+            ),
+            None => bail!(
+                "{} is a scripted device, so it needs a file containing its script.",
+                config.name
+            ),
+        },
+        _ => bail!(
+            "\"{}\" is not a known kind of device. The known kinds are uart, timer, script, debug, and hypercall.",
+            config.kind
+        ),
+    };
+
+    Ok(Attached {
+        name: config.name.clone(),
+        base: config.base,
+        irq: config.irq,
+        traced: false,
+        storm: 0,
+        device,
+    })
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The number of lines of source shown on either side of the line at fault.
+const CONTEXT: usize = 2;
+
+// A problem with a line of source, as reported by the assembler or by an external build, or with
+// the code at an address in RAM, as reported by ANALYZE.
+pub struct Diagnostic {
+    // The file as it was named in the report, or nothing if the problem is with code in RAM.
+    pub file: Option<String>,
+    source: Source,
+    // The number of the line at fault, counting from one, or else the address at fault.
+    pub line: usize,
+    pub message: String,
+}
+
+// Where the lines around a problem are found.
+enum Source {
+    // The path at which the file can be read, which is read afresh whenever its excerpt is shown.
+    File(PathBuf),
+    // The code in RAM around the address at fault, as it was disassembled when the problem was
+    // found, with each line numbered by its address.
+    Disassembly(Vec<(usize, String)>),
+}
+
+impl Diagnostic {
+    pub fn new(file: &str, line: usize, message: &str) -> Self {
+        Self {
+            file: Some(file.to_string()),
+            source: Source::File(PathBuf::from(file)),
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    // A problem with the code at an address in RAM, along with the code around it.
+    pub fn at(address: u16, message: &str, disassembly: Vec<(usize, String)>) -> Self {
+        Self {
+            file: None,
+            source: Source::Disassembly(disassembly),
+            line: usize::from(address),
+            message: message.to_string(),
+        }
+    }
+
+    // Recognize a line of output from a tool which reports a problem as `file:line: message` or
+    // `file:line:column: message`, as most compilers and assemblers do. The file is relative to the
+    // directory in which the tool was run.
+    pub fn parse(output: &str, directory: &Path) -> Option<Self> {
+        let (file, rest) = output.split_once(':')?;
+        let (line, rest) = rest.split_once(':')?;
+        let line = line.trim().parse().ok().filter(|&line| line > 0)?;
+        let message = match rest.split_once(':') {
+            Some((column, message)) if column.trim().parse::<usize>().is_ok() => message,
+            _ => rest,
+        }
+        .trim();
+        if file.trim().is_empty() || message.is_empty() {
+            return None;
+        }
+        Some(Self {
+            file: Some(file.to_string()),
+            source: Source::File(directory.join(file)),
+            line,
+            message: message.to_string(),
+        })
+    }
+
+    // The lines of source around the line at fault, along with their numbers, or nothing if the
+    // file can't be read.
+    pub fn excerpt(&self) -> Vec<(usize, String)> {
+        let path = match &self.source {
+            Source::File(path) => path,
+            Source::Disassembly(lines) => return lines.clone(),
+        };
+        let Ok(source) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        let first = self.line.saturating_sub(CONTEXT).max(1);
+        source
+            .lines()
+            .enumerate()
+            .map(|(number, text)| (number + 1, text.to_string()))
+            .skip(first - 1)
+            .take(self.line + CONTEXT + 1 - first)
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}: {}", file, self.line, self.message),
+            None => write!(f, "{:#06x}: {}", self.line, self.message),
+        }
+    }
+}
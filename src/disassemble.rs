@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use crate::listing::{self, Line};
+
 pub fn disassemble(instruction: u16, immediate: u16) -> String {
     let source = format!("{:02}", (instruction & 0b1111100000000000) >> 11);
     let destination = format!("{:02}", (instruction & 0b0000011111000000) >> 6);
@@ -91,3 +95,297 @@ pub fn disassemble(instruction: u16, immediate: u16) -> String {
         _ => String::new(),
     }
 }
+
+// Determine whether or not an instruction corresponds to one of the opcodes which is actually
+// defined by the ISA. Undefined opcodes happen to behave as NOPs in the emulator, but no sensible
+// program should contain them, so they make a good indicator that we are looking at data.
+pub fn is_defined(instruction: u16) -> bool {
+    matches!(
+        instruction & 0b0000000000111111,
+        0b000000..=0b001000
+            | 0b001010..=0b001101
+            | 0b001111
+            | 0b010000
+            | 0b010001
+            | 0b011000
+            | 0b011001
+            | 0b101000..=0b101111
+    )
+}
+
+// Determine the number of words occupied by an instruction. Every instruction with a set fourth
+// bit in its opcode takes an immediate operand in the following word, with the sole exception of
+// JSH, which packs its offset into the instruction itself.
+pub fn instruction_length(instruction: u16) -> u16 {
+    let opcode = instruction & 0b0000000000111111;
+    if opcode & 0b001000 != 0 && opcode != 0b101001 {
+        2
+    } else {
+        1
+    }
+}
+
+// Determine the addresses to which control may flow after executing the instruction at a given
+// address. This is necessarily somewhat conservative, as the targets of register-indirect jumps
+// can't be known without actually running the program.
+pub fn successors(address: u16, instruction: u16, immediate: u16) -> Vec<u16> {
+    let source = (instruction & 0b1111100000000000) >> 11;
+    let destination = (instruction & 0b0000011111000000) >> 6;
+    let next = address.wrapping_add(instruction_length(instruction));
+
+    match instruction & 0b0000000000111111 {
+        0b101000 => {
+            // JAL. If the source is r00, then the target is known statically. If a link is
+            // written, then we assume that the callee will eventually return to the next
+            // instruction.
+            let mut successors = Vec::new();
+            if source == 0 {
+                successors.push(immediate);
+            }
+            if destination != 0 {
+                successors.push(next);
+            }
+            successors
+        }
+        0b101001 => {
+            // JSH
+            let offset = (instruction & 0b1111111111000000) as i16 >> 6;
+            vec![address.wrapping_add_signed(offset)]
+        }
+        0b101010 | 0b101101 | 0b101111 if source == destination => {
+            // BEQ, BGE, and BGEU comparing a register against itself are always taken, and are
+            // commonly used as unconditional jumps.
+            vec![immediate]
+        }
+        0b101011 | 0b101100 | 0b101110 if source == destination => {
+            // Similarly, BNE, BLT, and BLTU comparing a register against itself are never taken.
+            vec![next]
+        }
+        0b101010..=0b101111 => vec![immediate, next],
+        _ => vec![next],
+    }
+}
+
+// Perform a simple reachability analysis, starting from an entry point and following every
+// statically known path of execution. The returned vector holds, for every address in RAM, whether
+// or not an instruction which may be executed begins at that address.
+pub fn find_reachable(ram: &[u16], entry: u16) -> Vec<bool> {
+    let mut reachable = vec![false; ram.len()];
+    let mut pending = vec![entry];
+
+    while let Some(address) = pending.pop() {
+        if reachable[usize::from(address)] {
+            continue;
+        }
+
+        // If we stumble upon an undefined opcode, then it is far more likely that we've
+        // wandered off into data than that the program actually intends to execute a NOP.
+        let instruction = ram[usize::from(address)];
+        if !is_defined(instruction) {
+            continue;
+        }
+        let immediate = ram[usize::from(address.wrapping_add(1))];
+
+        reachable[usize::from(address)] = true;
+        pending.extend(successors(address, instruction, immediate));
+    }
+
+    reachable
+}
+
+// Determine the address to which a control flow instruction transfers control, if that address
+// is known statically.
+pub fn static_target(address: u16, instruction: u16, immediate: u16) -> Option<u16> {
+    match instruction & 0b0000000000111111 {
+        0b101000 if instruction & 0b1111100000000000 == 0 => Some(immediate),
+        0b101001 => {
+            let offset = (instruction & 0b1111111111000000) as i16 >> 6;
+            Some(address.wrapping_add_signed(offset))
+        }
+        0b101010..=0b101111 => Some(immediate),
+        _ => None,
+    }
+}
+
+// Determine whether or not an instruction is a call, which is a JAL that writes a link.
+pub fn is_call(instruction: u16) -> bool {
+    instruction & 0b0000000000111111 == 0b101000 && (instruction & 0b0000011111000000) >> 6 != 0
+}
+
+// Determine whether or not an instruction, along with its immediate, is a return, which under the
+// calling convention is a JAL that neither writes a link nor adds an offset to the link register.
+pub fn is_return(instruction: u16, immediate: u16, link: usize) -> bool {
+    instruction & 0b0000000000111111 == 0b101000
+        && (instruction & 0b0000011111000000) >> 6 == 0
+        && usize::from(instruction >> 11) == link
+        && immediate == 0x0000
+}
+
+// Produce a listing of a region of RAM in the syntax accepted by the assembler, such that
+// assembling the listing reproduces the region exactly. Words which are reachable as code from the
+// entry point are disassembled, and everything else is rendered as a `.word` directive, so that
+// data isn't presented as a string of nonsensical instructions. Labels are generated for the
+// targets of branches and jumps which fall within the region.
+pub fn disassemble_region(ram: &[u16], entry: u16, start: u16, end: u16) -> String {
+    let mut listing = format!(
+        "; Disassembled by ilo from entry point {:#06x}.\n.org {:#06x}\n",
+        entry, start
+    );
+    for line in region_lines(ram, entry, start, end) {
+        match line.address {
+            Some(address) => listing.push_str(&format!("{:<32}; {:#06x}\n", line.code, address)),
+            None => listing.push_str(&format!("{}\n", line.code)),
+        }
+    }
+    listing
+}
+
+// Produce a listing of a region of RAM, just as `disassemble_region` does, but laid out with the
+// address and encoding of every line beside it, and with the source column given a fixed width.
+pub fn list_region(ram: &[u16], entry: u16, start: u16, end: u16, width: usize) -> String {
+    listing::format(
+        &format!("Disassembled by ilo from entry point {:#06x}.", entry),
+        &region_lines(ram, entry, start, end),
+        width,
+    )
+}
+
+// Disassemble a region of RAM into lines of source, with a line of its own for each label.
+fn region_lines(ram: &[u16], entry: u16, start: u16, end: u16) -> Vec<Line> {
+    let reachable = find_reachable(ram, entry);
+
+    // Begin by determining where each line of the listing starts, whether it holds code, and the
+    // words that it covers.
+    let mut lines = Vec::new();
+    let mut address = usize::from(start);
+    while address <= usize::from(end) {
+        let instruction = ram[address];
+        let immediate = ram[(address + 1) % ram.len()];
+        let length = if reachable[address] {
+            usize::from(instruction_length(instruction))
+        } else {
+            1
+        };
+        lines.push((address as u16, reachable[address], instruction, immediate));
+        address += length;
+    }
+
+    // Labels can only be placed at the start of a line, so any target which lands in the middle
+    // of an instruction, or outside of the region altogether, is left as a numeric literal.
+    let mut labels = HashMap::new();
+    for &(address, code, instruction, immediate) in &lines {
+        if !code {
+            continue;
+        }
+        if let Some(target) = static_target(address, instruction, immediate) {
+            if lines.binary_search_by_key(&target, |line| line.0).is_ok() {
+                labels.insert(target, format!("l_{:04x}", target));
+            }
+        }
+    }
+
+    let mut source = Vec::new();
+    for (address, code, instruction, immediate) in lines {
+        if let Some(label) = labels.get(&address) {
+            source.push(Line {
+                address: None,
+                words: Vec::new(),
+                code: format!("{}:", label),
+                comment: None,
+            });
+        }
+
+        // Immediates are given in decimal in the comments of a listing, since that's usually how
+        // they were written.
+        let (text, words, comment) = if code {
+            let text = disassemble(instruction, immediate);
+            let label = static_target(address, instruction, immediate).and_then(|t| labels.get(&t));
+            let comment = match instruction & 0b0000000000111111 {
+                _ if label.is_some() => None,
+                0b001000 | 0b001101 => Some((immediate as i16).to_string()),
+                _ if instruction_length(instruction) == 2 => Some(immediate.to_string()),
+                _ => None,
+            };
+            let words = if instruction_length(instruction) == 2 {
+                vec![instruction, immediate]
+            } else {
+                vec![instruction]
+            };
+            // The target is always the final operand of a control flow instruction, so we can
+            // simply swap it out for the label.
+            match label {
+                Some(label) => (
+                    format!("{} {}", text.rsplit_once(' ').unwrap().0, label),
+                    words,
+                    comment,
+                ),
+                None => (text, words, comment),
+            }
+        } else {
+            let comment = Some(instruction.to_string());
+            (
+                format!(".word {:#06x}", instruction),
+                vec![instruction],
+                comment,
+            )
+        };
+        source.push(Line {
+            address: Some(address),
+            words,
+            code: format!("    {}", text),
+            comment,
+        });
+    }
+
+    source
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::assemble::assemble;
+
+    // A program with a backward branch, a forward call, and data after the code, none of which
+    // can be reached, so that the listing has labels and `.word` directives as well as code.
+    const PROGRAM: &str = "
+            ADDI r01, r00, 3
+    loop:   ADDI r01, r01, -1
+            BNE r01, r00, loop
+            JAL r31, r00, func
+    done:   JSH 0
+    func:   JAL r00, r31, 0
+            .word 0x1234
+            .word 0xffff
+    ";
+
+    // Assemble a program into a full-sized image of RAM.
+    pub(crate) fn ram_of(source: &str) -> Vec<u16> {
+        let mut ram = vec![0x0000; 0x10000];
+        for (address, word) in assemble(source).unwrap() {
+            ram[usize::from(address)] = word;
+        }
+        ram
+    }
+
+    #[test]
+    fn disassembled_region_reassembles_to_the_original_image() {
+        let ram = ram_of(PROGRAM);
+        let end = assemble(PROGRAM).unwrap().last().unwrap().0;
+        let listing = disassemble_region(&ram, 0x0000, 0x0000, end);
+
+        assert!(listing.contains("l_0002:"), "{}", listing);
+        assert!(listing.contains(".word 0x1234"), "{}", listing);
+        assert_eq!(ram_of(&listing), ram, "{}", listing);
+    }
+
+    #[test]
+    fn disassembled_region_reassembles_away_from_the_start_of_ram() {
+        let source = format!(".org 0x0100\n{}", PROGRAM);
+        let ram = ram_of(&source);
+        let end = assemble(&source).unwrap().last().unwrap().0;
+        let listing = disassemble_region(&ram, 0x0100, 0x0100, end);
+
+        assert!(listing.contains(".org 0x0100"), "{}", listing);
+        assert_eq!(ram_of(&listing), ram, "{}", listing);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::abi::{Abi, Frame};
+use crate::cpu::Cpu;
+
+// The state of the machine at the moment that it faulted or failed an assertion, which is shown in
+// full until it is dismissed, since the one-line message alone rarely says enough to work out what
+// went wrong.
+pub struct Exception {
+    // The address of the instruction which was executed last, and the words which encode it.
+    pub address: u16,
+    pub words: Vec<u16>,
+    pub instruction: String,
+    // What went wrong, as a sentence of its own.
+    pub message: String,
+    // The registers as the faulting instruction left them.
+    pub registers: [u16; 0x20],
+    pub frames: Vec<Frame>,
+}
+
+impl Exception {
+    // Capture the state of a machine which has just faulted or failed an assertion, given the
+    // instruction which was executed last.
+    pub fn new(
+        cpu: &Cpu,
+        abi: &Abi,
+        address: u16,
+        words: Vec<u16>,
+        instruction: String,
+        message: String,
+    ) -> Self {
+        // NOTE: The program counter has already moved past the faulting instruction, but the
+        // innermost frame is where the fault happened.
+        let mut frames = abi.unwind(cpu);
+        frames[0].address = address;
+        Self {
+            address,
+            words,
+            instruction,
+            message,
+            registers: cpu.registers,
+            frames,
+        }
+    }
+}
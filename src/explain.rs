@@ -0,0 +1,256 @@
+use crate::assemble::MNEMONICS;
+use crate::cpu::Cpu;
+use crate::disassemble::{disassemble, instruction_length, is_defined};
+
+// Describe the semantics of an instruction in plain notation, for the benefit of people who are
+// still learning the ISA.
+pub fn semantics(instruction: u16, immediate: u16) -> String {
+    let s = format!("r{:02}", (instruction & 0b1111100000000000) >> 11);
+    let d = format!("r{:02}", (instruction & 0b0000011111000000) >> 6);
+    let i = format!("{:#06x}", immediate);
+
+    match instruction & 0b0000000000111111 {
+        0b000000 => format!("{d} ← {d} + {s}"),
+        0b000001 => format!("{d} ← {d} - {s}"),
+        0b000010 => format!("{d} ← {d} & {s}"),
+        0b000011 => format!("{d} ← {d} | {s}"),
+        0b000100 => format!("{d} ← {d} ^ {s}"),
+        0b000101 => format!("{d} ← {d} << {s} (logical)"),
+        0b000110 => format!("{d} ← {d} >> {s} (logical)"),
+        0b000111 => format!("{d} ← {d} >> {s} (arithmetic)"),
+        0b001000 => format!("{d} ← {s} + {i}"),
+        0b001010 => format!("{d} ← {s} & {i}"),
+        0b001011 => format!("{d} ← {s} | {i}"),
+        0b001100 => format!("{d} ← {s} ^ {i}"),
+        0b001101 => format!("{d} ← {s} << {i} (logical)"),
+        0b001111 => format!("{d} ← {s} >> {i} (arithmetic)"),
+        0b010000 => format!("{d} ← [{s}]"),
+        0b010001 => format!("[{s}] ← {d}"),
+        0b011000 => format!("{d} ← [{s} + {i}]"),
+        0b011001 => format!("[{s} + {i}] ← {d}"),
+        0b101000 => format!("{d} ← pc + 2; pc ← {s} + {i}"),
+        0b101001 => {
+            let offset = (instruction & 0b1111111111000000) as i16 >> 6;
+            format!("pc ← pc + {}", offset)
+        }
+        0b101010 => format!("if {d} = {s} then pc ← {i}"),
+        0b101011 => format!("if {d} ≠ {s} then pc ← {i}"),
+        0b101100 => format!("if {d} < {s} (signed) then pc ← {i}"),
+        0b101101 => format!("if {d} ≥ {s} (signed) then pc ← {i}"),
+        0b101110 => format!("if {d} < {s} (unsigned) then pc ← {i}"),
+        0b101111 => format!("if {d} ≥ {s} (unsigned) then pc ← {i}"),
+        _ => "no operation (undefined opcode)".into(),
+    }
+}
+
+// The registers and memory which an instruction reads from and writes to.
+#[derive(Default)]
+pub struct Dataflow {
+    pub reads: Vec<usize>,
+    pub writes: Vec<usize>,
+    // The address of RAM which is accessed, if any, along with whether it is written to.
+    pub memory: Option<(u16, bool)>,
+}
+
+// Determine the dataflow of the instruction to which the program counter currently points.
+pub fn dataflow(cpu: &Cpu) -> Dataflow {
+    dataflow_at(cpu, cpu.program_counter)
+}
+
+// Determine the dataflow of the instruction at a given address, were it to be executed with the
+// current register state. Reads from and writes to r00 are omitted, since they don't do anything.
+pub fn dataflow_at(cpu: &Cpu, address: u16) -> Dataflow {
+    let instruction = cpu.peek(address);
+    let immediate = cpu.peek(address.wrapping_add(1));
+    let s = usize::from((instruction & 0b1111100000000000) >> 11);
+    let d = usize::from((instruction & 0b0000011111000000) >> 6);
+    let value = |r: usize| if r == 0 { 0x0000 } else { cpu.registers[r] };
+    if !is_defined(instruction) {
+        return Dataflow::default();
+    }
+
+    let (reads, writes, memory) = match instruction & 0b0000000000111111 {
+        0b000000..=0b000111 => (vec![d, s], vec![d], None),
+        0b001000..=0b001111 => (vec![s], vec![d], None),
+        0b010000 => (vec![s], vec![d], Some((value(s), false))),
+        0b010001 => (vec![s, d], vec![], Some((value(s), true))),
+        0b011000 => (
+            vec![s],
+            vec![d],
+            Some((value(s).wrapping_add(immediate), false)),
+        ),
+        0b011001 => (
+            vec![s, d],
+            vec![],
+            Some((value(s).wrapping_add(immediate), true)),
+        ),
+        0b101000 => (vec![s], vec![d], None),
+        0b101010..=0b101111 => (vec![d, s], vec![], None),
+        _ => (vec![], vec![], None),
+    };
+
+    let mut dataflow = Dataflow {
+        reads: reads.into_iter().filter(|&r| r != 0).collect(),
+        writes: writes.into_iter().filter(|&r| r != 0).collect(),
+        memory,
+    };
+    dataflow.reads.dedup();
+    dataflow
+}
+
+// Something which an instruction will do when it's executed.
+enum Effect {
+    // A register which will change, with its value before and after.
+    Register(usize, u16, u16),
+    // A word of memory which will be stored to, with its value before and after, which may be the
+    // same.
+    Store(u16, u16, u16),
+    // Whether or not a conditional branch will be taken, or where anything else which doesn't
+    // simply fall through to the next instruction will go, which is described the same way
+    // however the rest is.
+    Flow(String),
+}
+
+// Predict the effects of executing the instruction at a given address with the current register
+// state, without committing any of them.
+fn predict(cpu: &Cpu, address: u16) -> Vec<Effect> {
+    // The simplest way to be certain that the prediction matches reality is to actually execute
+    // the instruction on a copy of the CPU.
+    let mut after = cpu.clone();
+    after.program_counter = address;
+    after.step();
+
+    let mut effects = Vec::new();
+    for r in 1..0x20 {
+        if after.registers[r] != cpu.registers[r] {
+            effects.push(Effect::Register(r, cpu.registers[r], after.registers[r]));
+        }
+    }
+    if let Some((memory, true)) = dataflow_at(cpu, address).memory {
+        effects.push(Effect::Store(memory, cpu.peek(memory), after.peek(memory)));
+    }
+
+    // For conditional branches, the interesting part is whether or not the branch is taken.
+    let instruction = cpu.peek(address);
+    let next = address.wrapping_add(instruction_length(instruction));
+    if (0b101010..=0b101111).contains(&(instruction & 0b0000000000111111)) {
+        if after.program_counter == next {
+            effects.push(Effect::Flow("branch not taken".into()));
+        } else {
+            effects.push(Effect::Flow(format!(
+                "branch taken to {:#06x}",
+                after.program_counter
+            )));
+        }
+    } else if after.program_counter != next {
+        effects.push(Effect::Flow(format!(
+            "jump to {:#06x}",
+            after.program_counter
+        )));
+    }
+
+    effects
+}
+
+// Describe what will change when the instruction at a given address is executed with the current
+// register state, with each register and word of memory given as its value before and after.
+pub fn effects(cpu: &Cpu, address: u16) -> Vec<String> {
+    predict(cpu, address)
+        .into_iter()
+        .filter_map(|effect| match effect {
+            Effect::Register(r, before, after) => {
+                Some(format!("r{:02}: {:#06x} → {:#06x}", r, before, after))
+            }
+            Effect::Store(memory, before, after) => (before != after)
+                .then(|| format!("[{:#06x}]: {:#06x} → {:#06x}", memory, before, after)),
+            Effect::Flow(flow) => Some(flow),
+        })
+        .collect()
+}
+
+// Describe what the instruction at a given address will do in as little space as possible, for
+// previewing it beneath the history, with each register or word of memory written given just as
+// its new value. Stores are given even if they don't change anything.
+pub fn preview(cpu: &Cpu, address: u16) -> Vec<String> {
+    predict(cpu, address)
+        .into_iter()
+        .map(|effect| match effect {
+            Effect::Register(r, _, after) => format!("r{:02} ← {:#06x}", r, after),
+            Effect::Store(memory, _, after) => format!("[{:#06x}] ← {:#06x}", memory, after),
+            Effect::Flow(flow) => flow,
+        })
+        .collect()
+}
+
+// Produce a detailed breakdown of the instruction at a given address, one line at a time.
+pub fn explain(cpu: &Cpu, address: u16) -> Vec<String> {
+    let instruction = cpu.peek(address);
+    let immediate = cpu.peek(address.wrapping_add(1));
+    let opcode = instruction & 0b0000000000111111;
+    let s = usize::from((instruction & 0b1111100000000000) >> 11);
+    let d = usize::from((instruction & 0b0000011111000000) >> 6);
+    let value = |r: usize| if r == 0 { 0x0000 } else { cpu.registers[r] };
+
+    if !is_defined(instruction) {
+        return vec![
+            format!("{:#06x}: {:#06x}", address, instruction),
+            format!(
+                "opcode {:06b} is undefined, and is treated as a NOP by the emulator.",
+                opcode
+            ),
+        ];
+    }
+
+    let mnemonic = MNEMONICS.iter().find(|(_, o)| *o == opcode).unwrap().0;
+    let length = instruction_length(instruction);
+    let mut lines = vec![if length == 2 {
+        format!(
+            "{:#06x}: {:#06x} {:#06x}  {}",
+            address,
+            instruction,
+            immediate,
+            disassemble(instruction, immediate)
+        )
+    } else {
+        format!(
+            "{:#06x}: {:#06x}         {}",
+            address,
+            instruction,
+            disassemble(instruction, immediate)
+        )
+    }];
+
+    if opcode == 0b101001 {
+        let offset = (instruction & 0b1111111111000000) as i16 >> 6;
+        lines.push(format!(
+            "bits: offset={:010b} ({}) opcode={:06b} ({})",
+            instruction >> 6,
+            offset,
+            opcode,
+            mnemonic
+        ));
+    } else {
+        lines.push(format!(
+            "bits: source={:05b} (r{:02}) destination={:05b} (r{:02}) opcode={:06b} ({})",
+            s, s, d, d, opcode, mnemonic
+        ));
+        let mut operands = vec![
+            format!("r{:02} = {:#06x}", d, value(d)),
+            format!("r{:02} = {:#06x}", s, value(s)),
+        ];
+        if length == 2 {
+            operands.push(format!("immediate = {:#06x}", immediate));
+        }
+        lines.push(format!("operands: {}", operands.join(", ")));
+    }
+    lines.push(format!("semantics: {}", semantics(instruction, immediate)));
+
+    let effects = effects(cpu, address);
+    if effects.is_empty() {
+        lines.push("effects: none".into());
+    } else {
+        lines.push(format!("effects: {}", effects.join("; ")));
+    }
+
+    lines
+}
@@ -0,0 +1,34 @@
+// Format words of memory as a memory initialization file, for loading into the block RAM of an
+// FPGA implementation. The first word is the one at address 0x0000 of the block RAM, whatever
+// address it was taken from.
+//
+// MEM and HEX are the same format, of one word in hexadecimal per line, as read by Verilog's
+// $readmemh. COE is the format read by the Xilinx block memory generator, and MIF is the format
+// read by Intel's Quartus.
+pub fn export(format: &str, words: &[u16]) -> String {
+    match format {
+        "COE" => {
+            let vector = words
+                .iter()
+                .map(|word| format!("{:04x}", word))
+                .collect::<Vec<_>>()
+                .join(",\n");
+            format!(
+                "memory_initialization_radix=16;\nmemory_initialization_vector=\n{};\n",
+                vector
+            )
+        }
+        "MIF" => {
+            let mut mif = format!(
+                "DEPTH = {};\nWIDTH = 16;\nADDRESS_RADIX = HEX;\nDATA_RADIX = HEX;\nCONTENT\nBEGIN\n",
+                words.len()
+            );
+            for (address, word) in words.iter().enumerate() {
+                mif.push_str(&format!("{:04x} : {:04x};\n", address, word));
+            }
+            mif.push_str("END;\n");
+            mif
+        }
+        _ => words.iter().map(|word| format!("{:04x}\n", word)).collect(),
+    }
+}
@@ -0,0 +1,641 @@
+use anyhow::{anyhow, bail, Result};
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::assemble::parse_literal;
+use crate::cpu::Cpu;
+use crate::record::{Field, Record};
+
+// A binary operator, in the order of their precedence from loosest to tightest, with operators of
+// the same precedence sharing a level.
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*"],
+];
+
+// The most characters which are read from a string, in case it's missing its terminator.
+const MAX_STRING: usize = 0x100;
+
+// The most elements which an array may have, so that a mistyped count can't flood the console.
+const MAX_ARRAY: u16 = 0x400;
+
+// An expression over the state of the CPU, such as `r01 == 0 && [r02 + 1] != 0xffff`, for use as a
+// condition. Registers are written as in tracepoints, the program counter as pc, and a word of
+// memory as an address in brackets. The operators are those of C, working on words with wrapping
+// arithmetic. Comparisons are unsigned and give 1 or 0, and any nonzero value counts as true.
+//
+// A word of memory may also be read as a particular type by naming it before the brackets, which
+// changes how it's shown and compared. signed[...] is a two's complement number, and comparisons
+// involving one are signed. char[...] is a character, held in the low byte of the word, which may
+// be compared with a character literal such as 'a'. str[...] is a string, and since it isn't a
+// number it can only be the whole expression. A string is held either one character to a word, up
+// to a word of 0x0000, or packed two characters to a word, up to a byte of 0x00. wstr[...] and
+// pstr[...] read a string in each of these ways, while str[...] reads one in whichever way is
+// configured.
+// Dereferences may be chained, so that `[[r05] + 2]` follows a pointer.
+//
+// Memory may also be read as a record type defined with TYPE, as in `point@[r05]`, which shows
+// each of its fields, or as an array of any type, as in `i16[16]@0x8000`. Like a string, neither
+// is a number, so they can only be the whole expression.
+pub struct Expression {
+    pub text: String,
+    // The type which the expression reads, and how many of them if it's an array, in which case
+    // the node gives their address.
+    layout: Option<(Field, Option<u16>)>,
+    // The way in which strings are held, for the text shown beside arrays of numbers.
+    packing: Packing,
+    node: Node,
+}
+
+// How a word of memory is read.
+#[derive(Clone, Copy)]
+enum Type {
+    Word,
+    Signed,
+    Char,
+    Str(Packing),
+}
+
+// How the characters of a string are held in memory. Since memory is addressed by the word rather
+// than the byte, either one or two characters might be held in each word, depending upon the
+// program.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Packing {
+    // One character to a word, in the low byte, up to a word of 0x0000.
+    #[default]
+    Word,
+    // Two characters to a word, the first in the high byte, up to a byte of 0x00.
+    Packed,
+}
+
+// The value of an expression, which keeps the type with which it was read so that it can be shown
+// in the most useful way.
+pub enum Value {
+    Word(u16),
+    Signed(u16),
+    Char(u16),
+    Str(String),
+    Record(Vec<(String, Value)>),
+    Array(Vec<Value>),
+}
+
+enum Node {
+    Literal(Type, u16),
+    Register(usize),
+    ProgramCounter,
+    Memory(Type, Box<Node>),
+    Unary(char, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+impl Expression {
+    // Parse an expression, given the record types which have been defined and the way in which
+    // str[...] reads strings.
+    pub fn parse(
+        text: &str,
+        types: &BTreeMap<String, Arc<Record>>,
+        packing: Packing,
+    ) -> Result<Self> {
+        // A type, and perhaps a count, comes before the address at which memory is read.
+        let (layout, address) = match text.split_once('@') {
+            Some((kind, address)) => (Some(layout(kind.trim(), types, packing)?), address),
+            None => (None, text),
+        };
+
+        let tokens = tokenize(address)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            packing,
+        };
+        let node = parser.binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            bail!("Unexpected \"{}\" in \"{}\".", token, text);
+        }
+        // Strings can't be used as numbers, so they may only be read at the top.
+        let operand = match &node {
+            Node::Memory(Type::Str(_), address) => address,
+            node => node,
+        };
+        if operand.reads_string() || (layout.is_some() && node.reads_string()) {
+            bail!("A str[...] can't be used within another expression, since it isn't a number.");
+        }
+        Ok(Self {
+            text: text.to_string(),
+            layout,
+            packing,
+            node,
+        })
+    }
+
+    pub fn evaluate(&self, cpu: &Cpu) -> Value {
+        if let Some((field, count)) = &self.layout {
+            let address = evaluate(&self.node, cpu).word();
+            return match count {
+                Some(count) => Value::Array(
+                    (0..*count)
+                        .map(|index| {
+                            read_field(field, cpu, address.wrapping_add(index * field.size()))
+                        })
+                        .collect(),
+                ),
+                None => read_field(field, cpu, address),
+            };
+        }
+        match &self.node {
+            Node::Memory(Type::Str(packing), address) => {
+                Value::Str(read_string(cpu, evaluate(address, cpu).word(), *packing))
+            }
+            node => evaluate(node, cpu),
+        }
+    }
+
+    // Whether or not the expression is currently true. A string is true if it isn't empty, and a
+    // record or an array is always true.
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        match self.evaluate(cpu) {
+            Value::Str(string) => !string.is_empty(),
+            Value::Record(_) | Value::Array(_) => true,
+            value => value.word() != 0,
+        }
+    }
+
+    // Lay an array out over several rows, each beginning with the address of its first element,
+    // for dumping buffers and tables readably. Numbers are shown eight to a row, followed by their
+    // words as text in the way that strings are held as in a hex dump, characters sixteen to a row
+    // as text, and strings and records one to a row. Anything else is shown on a single row.
+    pub fn rows(&self, cpu: &Cpu) -> Vec<String> {
+        let value = self.evaluate(cpu);
+        let (Some((field, Some(_))), Value::Array(elements)) = (&self.layout, &value) else {
+            return vec![format!("{} = {}", self.text, value.describe())];
+        };
+        let per_row = match field {
+            Field::U16 | Field::I16 => 8,
+            Field::Char => 16,
+            Field::Str(_) | Field::Record(_) => 1,
+        };
+        let address = evaluate(&self.node, cpu).word();
+        let mut rows = vec![format!("{} =", self.text)];
+        for (row, elements) in elements.chunks(per_row).enumerate() {
+            let start = address.wrapping_add((row * per_row) as u16 * field.size());
+            let elements = match field {
+                Field::Char => elements
+                    .iter()
+                    .map(|element| printable(element.word() as u8))
+                    .collect(),
+                Field::U16 | Field::I16 => {
+                    let numbers = elements
+                        .iter()
+                        .map(|element| format!("{:>6}", element.show()))
+                        .collect::<Vec<_>>();
+                    let text = elements
+                        .iter()
+                        .flat_map(|element| match self.packing {
+                            Packing::Word => vec![element.word() as u8],
+                            Packing::Packed => element.word().to_be_bytes().to_vec(),
+                        })
+                        .map(printable)
+                        .collect::<String>();
+                    // Short final rows are padded, so that the text lines up with the rows above.
+                    let width = per_row * 7 - 1;
+                    format!("{:<width$}  |{}|", numbers.join(" "), text, width = width)
+                }
+                _ => elements
+                    .iter()
+                    .map(Value::show)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            rows.push(format!("{:04x}  {}", start, elements));
+        }
+        rows
+    }
+}
+
+impl Value {
+    fn word(&self) -> u16 {
+        match self {
+            Value::Word(word) | Value::Signed(word) | Value::Char(word) => *word,
+            Value::Str(_) | Value::Record(_) | Value::Array(_) => {
+                unreachable!("strings, records, and arrays are never used as numbers")
+            }
+        }
+    }
+
+    // Show the value just as its type would have it.
+    fn show(&self) -> String {
+        match self {
+            Value::Word(word) => format!("{:#06x}", word),
+            Value::Signed(word) => (*word as i16).to_string(),
+            Value::Char(word) => format!("'{}'", char::from(*word as u8).escape_default()),
+            Value::Str(string) => format!("\"{}\"", string.escape_default()),
+            Value::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value.show()))
+                    .collect::<Vec<_>>();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            Value::Array(elements) => {
+                let elements = elements.iter().map(Value::show).collect::<Vec<_>>();
+                format!("[{}]", elements.join(", "))
+            }
+        }
+    }
+
+    // Describe the value, both as its type would have it and as the word which holds it.
+    pub fn describe(&self) -> String {
+        match self {
+            Value::Word(word) => format!("{:#06x} ({})", word, word),
+            Value::Signed(word) => format!("{} ({:#06x})", *word as i16, word),
+            Value::Char(word) => format!(
+                "'{}' ({:#06x})",
+                char::from(*word as u8).escape_default(),
+                word
+            ),
+            value => value.show(),
+        }
+    }
+}
+
+impl Node {
+    fn reads_string(&self) -> bool {
+        match self {
+            Node::Memory(Type::Str(_), _) => true,
+            Node::Memory(_, operand) | Node::Unary(_, operand) => operand.reads_string(),
+            Node::Binary(_, left, right) => left.reads_string() || right.reads_string(),
+            _ => false,
+        }
+    }
+}
+
+// Parse the type which memory is read as, perhaps followed by a count in brackets for an array.
+fn layout(
+    kind: &str,
+    types: &BTreeMap<String, Arc<Record>>,
+    packing: Packing,
+) -> Result<(Field, Option<u16>)> {
+    let Some((kind, count)) = kind.split_once('[') else {
+        return Ok((Field::parse(kind, types, packing)?, None));
+    };
+    let Some(count) = count.strip_suffix(']') else {
+        bail!(
+            "The count of {}[...] is missing its closing \"]\".",
+            kind.trim()
+        );
+    };
+    let count = parse_literal(count.trim())
+        .map_err(|_| anyhow!("\"{}\" is not a count of elements.", count.trim()))?;
+    if !(1..=MAX_ARRAY).contains(&count) {
+        bail!("An array must have between 1 and {} elements.", MAX_ARRAY);
+    }
+    Ok((Field::parse(kind.trim(), types, packing)?, Some(count)))
+}
+
+// Read a value of a type at an address.
+fn read_field(field: &Field, cpu: &Cpu, address: u16) -> Value {
+    let word = cpu.peek(address);
+    match field {
+        Field::U16 => Value::Word(word),
+        Field::I16 => Value::Signed(word),
+        Field::Char => Value::Char(word & 0x00ff),
+        Field::Str(packing) => Value::Str(read_string(cpu, word, *packing)),
+        Field::Record(record) => read_record(record, cpu, address),
+    }
+}
+
+// Read a record at an address, field by field.
+fn read_record(record: &Record, cpu: &Cpu, mut address: u16) -> Value {
+    let mut fields = Vec::new();
+    for (name, field) in &record.fields {
+        fields.push((name.clone(), read_field(field, cpu, address)));
+        address = address.wrapping_add(field.size());
+    }
+    Value::Record(fields)
+}
+
+// Show a byte as a character, with bytes which aren't printable ASCII characters as a period.
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        char::from(byte)
+    } else {
+        '.'
+    }
+}
+
+// Read a string, held in either of the ways which a string might be.
+pub fn read_string(cpu: &Cpu, address: u16, packing: Packing) -> String {
+    match packing {
+        Packing::Word => (0..MAX_STRING as u16)
+            .map(|offset| cpu.peek(address.wrapping_add(offset)))
+            .take_while(|&word| word != 0x0000)
+            .map(|word| char::from(word as u8))
+            .collect(),
+        Packing::Packed => (0..MAX_STRING.div_ceil(2) as u16)
+            .flat_map(|offset| cpu.peek(address.wrapping_add(offset)).to_be_bytes())
+            .take_while(|&byte| byte != 0x00)
+            .take(MAX_STRING)
+            .map(char::from)
+            .collect(),
+    }
+}
+
+// Expand a format string against a list of values, as PRINT does. Each placeholder is replaced by
+// the next value: `{}` shows it just as its type would have it, `{:x}` in hexadecimal, `{:d}` in
+// decimal, `{:i}` in signed decimal, `{:c}` as a character, and `{:s}` as the string at the address
+// which it gives, held in the configured way. Literal braces may be written as `{{` and `}}`, just
+// as in tracepoints.
+pub fn format_values(
+    format: &str,
+    values: &[Value],
+    cpu: &Cpu,
+    packing: Packing,
+) -> Result<String> {
+    let mut output = String::new();
+    let mut values = values.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => bail!("Unterminated placeholder in \"{}\".", format),
+                    }
+                }
+                let value = values.next().ok_or_else(|| {
+                    anyhow!(
+                        "\"{}\" has more placeholders than there are values.",
+                        format
+                    )
+                })?;
+                // Without a conversion, a value is shown just as its type would have it.
+                let conversion = match (placeholder.strip_prefix(':'), value) {
+                    (Some(conversion), _) => conversion,
+                    (None, Value::Word(_)) if placeholder.is_empty() => "x",
+                    (None, Value::Signed(_)) if placeholder.is_empty() => "i",
+                    (None, Value::Char(_)) if placeholder.is_empty() => "c",
+                    (None, Value::Str(_)) if placeholder.is_empty() => "s",
+                    _ => "",
+                };
+                let word = match value {
+                    Value::Str(string) if conversion == "s" => {
+                        output.push_str(string);
+                        continue;
+                    }
+                    Value::Str(_) => bail!(
+                        "A string can only be formatted with {{}} or {{:s}}, not {{{}}}.",
+                        placeholder
+                    ),
+                    Value::Record(_) | Value::Array(_) if placeholder.is_empty() => {
+                        output.push_str(&value.show());
+                        continue;
+                    }
+                    Value::Record(_) | Value::Array(_) => bail!(
+                        "A record or an array can only be formatted with {{}}, not {{{}}}.",
+                        placeholder
+                    ),
+                    value => value.word(),
+                };
+                output.push_str(&match conversion {
+                    "x" => format!("{:#06x}", word),
+                    "d" => word.to_string(),
+                    "i" => (word as i16).to_string(),
+                    "c" => char::from(word as u8).escape_default().to_string(),
+                    "s" => read_string(cpu, word, packing),
+                    _ => bail!(
+                        "{{{}}} is not a placeholder. Use {{}}, {{:x}}, {{:d}}, {{:i}}, {{:c}}, or {{:s}}.",
+                        placeholder
+                    ),
+                });
+            }
+            '}' => bail!("Unmatched \"}}\" in \"{}\".", format),
+            c => output.push(c),
+        }
+    }
+    if values.next().is_some() {
+        bail!(
+            "\"{}\" has fewer placeholders than there are values.",
+            format
+        );
+    }
+    Ok(output)
+}
+
+fn evaluate(node: &Node, cpu: &Cpu) -> Value {
+    match node {
+        Node::Literal(Type::Char, value) => Value::Char(*value),
+        Node::Literal(_, value) => Value::Word(*value),
+        Node::Register(register) => Value::Word(cpu.registers[*register]),
+        Node::ProgramCounter => Value::Word(cpu.program_counter),
+        Node::Memory(kind, address) => {
+            let word = cpu.peek(evaluate(address, cpu).word());
+            match kind {
+                Type::Signed => Value::Signed(word),
+                Type::Char => Value::Char(word & 0x00ff),
+                _ => Value::Word(word),
+            }
+        }
+        Node::Unary(operator, operand) => {
+            let operand = evaluate(operand, cpu).word();
+            Value::Word(match operator {
+                '-' => operand.wrapping_neg(),
+                '~' => !operand,
+                _ => u16::from(operand == 0),
+            })
+        }
+        Node::Binary(operator, left, right) => {
+            let left = evaluate(left, cpu);
+            // NOTE: As in C, the right side of || and && is only evaluated if it's needed, though
+            // since reading memory never has side effects here, this only saves time.
+            match *operator {
+                "||" if left.word() != 0 => return Value::Word(1),
+                "&&" if left.word() == 0 => return Value::Word(0),
+                _ => {}
+            }
+            let right = evaluate(right, cpu);
+            let signed = matches!(left, Value::Signed(_)) || matches!(right, Value::Signed(_));
+            let ordering = if signed {
+                (left.word() as i16).cmp(&(right.word() as i16))
+            } else {
+                left.word().cmp(&right.word())
+            };
+            let (left, right) = (left.word(), right.word());
+            Value::Word(match *operator {
+                "||" | "&&" => u16::from(right != 0),
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "==" => u16::from(left == right),
+                "!=" => u16::from(left != right),
+                "<=" => u16::from(ordering.is_le()),
+                ">=" => u16::from(ordering.is_ge()),
+                "<" => u16::from(ordering.is_lt()),
+                ">" => u16::from(ordering.is_gt()),
+                "<<" => left.checked_shl(u32::from(right)).unwrap_or(0),
+                ">>" => left.checked_shr(u32::from(right)).unwrap_or(0),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                _ => left.wrapping_mul(right),
+            })
+        }
+    }
+}
+
+// Split an expression into numbers, names, character literals, operators, and brackets.
+fn tokenize(text: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut token = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        } else if c == '\'' {
+            chars.next();
+            match (chars.next(), chars.next()) {
+                (Some(character), Some('\'')) if character.is_ascii() => {
+                    tokens.push(format!("'{}'", character))
+                }
+                _ => bail!(
+                    "Unterminated character literal in \"{}\". Only a single ASCII character may be quoted.",
+                    text
+                ),
+            }
+        } else {
+            chars.next();
+            let pair = chars.peek().map(|&next| format!("{}{}", c, next));
+            match pair.as_deref() {
+                Some("||" | "&&" | "==" | "!=" | "<=" | ">=" | "<<" | ">>") => {
+                    tokens.push(pair.unwrap());
+                    chars.next();
+                }
+                _ if "|&^<>+-*!~()[]".contains(c) => tokens.push(c.to_string()),
+                _ => bail!("Unexpected \"{}\" in \"{}\".", c, text),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+    packing: Packing,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected \"{}\", but found \"{}\".", expected, token),
+            None => bail!("Expected \"{}\" at the end of the expression.", expected),
+        }
+    }
+
+    // Parse a chain of binary operators at a level of precedence, along with anything which binds
+    // more tightly.
+    fn binary(&mut self, level: usize) -> Result<Node> {
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut node = self.binary(level + 1)?;
+        while let Some(&operator) = self
+            .tokens
+            .get(self.position)
+            .and_then(|token| operators.iter().find(|&&operator| operator == token))
+        {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            node = Node::Binary(operator, Box::new(node), Box::new(right));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        let token = self
+            .next()
+            .ok_or_else(|| anyhow!("The expression ended unexpectedly."))?
+            .to_string();
+        let kind = match token.to_lowercase().as_str() {
+            "word" => Some(Type::Word),
+            "signed" => Some(Type::Signed),
+            "char" => Some(Type::Char),
+            "str" => Some(Type::Str(self.packing)),
+            "wstr" => Some(Type::Str(Packing::Word)),
+            "pstr" => Some(Type::Str(Packing::Packed)),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.expect("[")?;
+            return self.dereference(kind);
+        }
+        match token.as_str() {
+            "-" | "~" | "!" => Ok(Node::Unary(
+                token.chars().next().unwrap(),
+                Box::new(self.unary()?),
+            )),
+            "(" => {
+                let node = self.binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            "[" => self.dereference(Type::Word),
+            _ if token.starts_with('\'') => {
+                Ok(Node::Literal(Type::Char, u16::from(token.as_bytes()[1])))
+            }
+            _ if token.eq_ignore_ascii_case("pc") => Ok(Node::ProgramCounter),
+            _ if token.starts_with(['r', 'R']) => match token[1..].parse::<usize>() {
+                Ok(0) => Ok(Node::Literal(Type::Word, 0x0000)),
+                Ok(register) if register < 0x20 => Ok(Node::Register(register)),
+                _ => bail!("\"{}\" is not a valid register.", token),
+            },
+            _ => parse_literal(&token.to_lowercase())
+                .map(|value| Node::Literal(Type::Word, value))
+                .map_err(|_| anyhow!("\"{}\" is not a valid value.", token)),
+        }
+    }
+
+    // Parse the address of a dereference, whose opening bracket has already been taken.
+    fn dereference(&mut self, kind: Type) -> Result<Node> {
+        let node = self.binary(0)?;
+        self.expect("]")?;
+        Ok(Node::Memory(kind, Box::new(node)))
+    }
+}
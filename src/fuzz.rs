@@ -0,0 +1,272 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cpu::Cpu;
+use crate::device::{Attached, Device};
+use crate::disassemble::{disassemble, instruction_length};
+use crate::memory::{Decoding, MemoryMap, Rom, RomWrites};
+use crate::random::Random;
+use crate::timer::Timer;
+use crate::uart::Uart;
+
+// The number of instructions executed on each randomized machine before another is constructed.
+// This is long enough for the guest to get the devices and the boot ROM into interesting states,
+// but short enough that a good variety of machines is tried.
+const ROUND_LENGTH: u64 = 0x1000;
+
+// The number of instructions executed if --steps isn't given.
+const DEFAULT_STEPS: u64 = 10_000_000;
+
+// How often progress is reported, in instructions.
+const PROGRESS_INTERVAL: u64 = 1_000_000;
+
+// The state of the CPU before an instruction, which is kept around in order to check the effects
+// of the instruction against it, and to report the instruction if anything goes wrong.
+// NOTE: Cloning the whole CPU before every instruction would make fuzzing far too slow, since the
+// RAM alone is 128KiB, so only what is actually needed is kept.
+struct Before {
+    program_counter: u16,
+    instruction: u16,
+    immediate: u16,
+    registers: [u16; 0x20],
+    instructions: u32,
+    cycles: u32,
+}
+
+// Execute random instruction words over randomized machines, checking after every instruction
+// that the emulator hasn't panicked and that its invariants still hold. This is what `ilo fuzz`
+// does, and it accepts --steps to set the number of instructions executed, and --seed to reproduce
+// an earlier run.
+pub fn fuzz(mut arguments: impl Iterator<Item = String>) -> Result<()> {
+    let mut steps = DEFAULT_STEPS;
+    let mut seed = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--steps" => {
+                steps = parse_number(arguments.next(), "--steps must be followed by a number.")?
+            }
+            "--seed" => {
+                seed = Some(parse_number(
+                    arguments.next(),
+                    "--seed must be followed by a number.",
+                )?)
+            }
+            _ => bail!("Unrecognized argument \"{}\" to fuzz.", argument),
+        }
+    }
+    let seed = seed.unwrap_or_else(|| Random::from_time().next_u64());
+    let mut random = Random::new(seed);
+    println!("Fuzzing for {} instructions with seed {:#x}.", steps, seed);
+
+    let mut executed = 0;
+    let mut round = 0;
+    while executed < steps {
+        let mut cpu = machine(&mut random)?;
+        // Nothing is allowed to touch the backing store beyond the fitted RAM, so it is checked
+        // at the end of every round.
+        let beyond = cpu.ram[cpu.memory.size..].to_vec();
+
+        for step in 0..ROUND_LENGTH.min(steps - executed) {
+            // Rather than letting the guest wander off into whatever the RAM happens to contain,
+            // every instruction is fresh, along with its immediate operand. If the program counter
+            // points at something other than RAM, then these writes are simply lost, and the guest
+            // executes whatever is there instead.
+            for address in [cpu.program_counter, cpu.program_counter.wrapping_add(1)] {
+                if let Some(index) = cpu.memory.decode(address) {
+                    cpu.ram[index] = random.next_u16();
+                }
+            }
+
+            let before = Before {
+                program_counter: cpu.program_counter,
+                instruction: cpu.peek(cpu.program_counter),
+                immediate: cpu.peek(cpu.program_counter.wrapping_add(1)),
+                registers: cpu.registers,
+                instructions: cpu.counters.instructions,
+                cycles: cpu.counters.cycles,
+            };
+            let violation = match panic::catch_unwind(AssertUnwindSafe(|| cpu.step())) {
+                Ok(()) => check(&before, &mut cpu),
+                Err(_) => Some("the emulator panicked".into()),
+            };
+            if let Some(violation) = violation {
+                return Err(report(seed, round, step, &before, &violation));
+            }
+
+            executed += 1;
+            if executed % PROGRESS_INTERVAL == 0 {
+                println!("Executed {} of {} instructions.", executed, steps);
+            }
+        }
+
+        if cpu.ram[cpu.memory.size..] != beyond[..] {
+            bail!(
+                "With seed {:#x}, round {} wrote to the backing store beyond the {:#x} words of fitted RAM.",
+                seed,
+                round,
+                cpu.memory.size
+            );
+        }
+        round += 1;
+    }
+
+    println!("No problems found in {} instructions.", steps);
+    Ok(())
+}
+
+// Construct a machine with a random memory map, boot ROM, and set of devices, with every register
+// and word of RAM randomized.
+fn machine(random: &mut Random) -> Result<Cpu> {
+    let mut cpu = Cpu::new();
+    let decoding = if random.chance() {
+        Decoding::Mirrored
+    } else {
+        Decoding::Unmapped
+    };
+    cpu.memory = MemoryMap::new(0x10 << random.below(13), decoding)?;
+    if random.chance() {
+        cpu.memory.rom = Some(Rom {
+            words: (0..random.below(0x100))
+                .map(|_| random.next_u16())
+                .collect(),
+            mapped: true,
+            writes: if random.chance() {
+                RomWrites::Trap
+            } else {
+                RomWrites::Through
+            },
+        });
+    }
+    cpu.counters.mapped = random.chance();
+
+    if random.chance() {
+        let input = (0..random.below(0x40))
+            .map(|_| random.next_u16() as u8)
+            .collect();
+        attach(
+            &mut cpu,
+            "uart0",
+            random.next_u16(),
+            Box::new(Uart::new(input)),
+        );
+    }
+    if random.chance() {
+        attach(
+            &mut cpu,
+            "timer0",
+            random.next_u16(),
+            Box::<Timer>::default(),
+        );
+    }
+
+    for word in cpu.ram.iter_mut() {
+        *word = random.next_u16();
+    }
+    for register in cpu.registers.iter_mut().skip(1) {
+        *register = random.next_u16();
+    }
+    cpu.program_counter = random.next_u16();
+    Ok(cpu)
+}
+
+// Attach a device to a randomized machine. Devices which would collide with another, or which
+// would extend past the end of the address space, are simply left out.
+fn attach(cpu: &mut Cpu, name: &str, base: u16, device: Box<dyn Device>) {
+    let _ = cpu.devices.attach(Attached {
+        name: name.into(),
+        base,
+        irq: None,
+        traced: false,
+        storm: 0,
+        device,
+    });
+}
+
+// Check the invariants which every instruction must uphold, returning a description of the first
+// one to be violated, if any.
+fn check(before: &Before, cpu: &mut Cpu) -> Option<String> {
+    let opcode = before.instruction & 0b0000000000111111;
+    let store = matches!(opcode, 0b010001 | 0b011001);
+
+    if cpu.registers[0] != 0x0000 {
+        return Some(format!("r00 holds {:#06x}", cpu.registers[0]));
+    }
+
+    // A store to the performance counters resets the counter, and may do so before the
+    // instruction itself is counted.
+    let length = u32::from(instruction_length(before.instruction));
+    let counted = cpu.counters.instructions == before.instructions.wrapping_add(1)
+        || (store && cpu.counters.instructions == 1);
+    if !counted {
+        return Some(format!(
+            "the instruction count went from {} to {}",
+            before.instructions, cpu.counters.instructions
+        ));
+    }
+    let cycles = cpu.counters.cycles.wrapping_sub(before.cycles);
+    let reset = store && cpu.counters.cycles == length + 1;
+    if !(length..=length + 1).contains(&cycles) && !reset {
+        return Some(format!(
+            "the cycle count went from {} to {}",
+            before.cycles, cpu.counters.cycles
+        ));
+    }
+
+    // Only stores can fault, by writing to a boot ROM which traps writes.
+    if let Some(fault) = cpu.fault.take() {
+        if !store {
+            return Some(format!(
+                "a non-store instruction faulted with \"{}\"",
+                fault
+            ));
+        }
+    }
+
+    // Every instruction other than a jump or branch either falls through to the next instruction,
+    // or, if it is undefined, leaves the program counter where it is.
+    let control_flow = matches!(opcode, 0b101000..=0b101111);
+    let next = before.program_counter.wrapping_add(length as u16);
+    if !control_flow && cpu.program_counter != next && cpu.program_counter != before.program_counter
+    {
+        return Some(format!(
+            "the program counter moved from {:#06x} to {:#06x}",
+            before.program_counter, cpu.program_counter
+        ));
+    }
+
+    None
+}
+
+// Describe a violated invariant, along with everything needed to reproduce it.
+fn report(seed: u64, round: u64, step: u64, before: &Before, violation: &str) -> anyhow::Error {
+    let registers = before
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("r{:02} {:#06x}", i, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    anyhow!(
+        "With seed {:#x}, at step {} of round {}, {} after executing {:#06x} {:#06x} ({}) at {:#06x}. Beforehand, the registers held {}.",
+        seed,
+        step,
+        round,
+        violation,
+        before.instruction,
+        before.immediate,
+        disassemble(before.instruction, before.immediate).trim(),
+        before.program_counter,
+        registers
+    )
+}
+
+// Parse a decimal or hexadecimal number given as an argument.
+fn parse_number(argument: Option<String>, missing: &str) -> Result<u64> {
+    let argument = argument.ok_or_else(|| anyhow!("{}", missing))?;
+    let parsed = match argument.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => argument.parse(),
+    };
+    parsed.map_err(|_| anyhow!("\"{}\" is not a valid number.", argument))
+}
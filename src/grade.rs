@@ -0,0 +1,224 @@
+use anyhow::{anyhow, bail, Result};
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::app::write_image;
+use crate::assemble::parse_literal;
+use crate::cpu::Cpu;
+use crate::load::ByteOrder;
+
+// The number of steps after which a test is abandoned, if the exercise doesn't set its own limit.
+const DEFAULT_STEPS: u64 = 1_000_000;
+
+// An exercise, which is read from a TOML file and checks a program against a series of tests. For
+// example, an exercise asking for the sum of an array, whose address and length are passed in r01
+// and r02, with the sum returned in r03, might be written as follows.
+//
+//     name = "Sum of an array"
+//     end = 0x0040
+//
+//     [[test]]
+//     name = "three numbers"
+//     registers = { r01 = 0x0100, r02 = 3 }
+//     memory = { "0x0100" = [1, 2, 3] }
+//     expect = { registers = { r03 = 6 } }
+//
+// Each test starts from a freshly loaded program, with the registers and memory set up as given,
+// and runs until the program counter reaches the end address. Without an end address, a test runs
+// until the program jumps to itself, which is the usual way for a program to stop.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Exercise {
+    name: Option<String>,
+    // The address at which the program is loaded.
+    #[serde(default)]
+    load: u16,
+    // The address at which execution begins, which is where the program is loaded unless given.
+    entry: Option<u16>,
+    end: Option<u16>,
+    // The number of steps which each test may take before it fails.
+    steps: Option<u64>,
+    // The order of the bytes of each word in the program, either `"big"` or `"little"`.
+    #[serde(default)]
+    byte_order: ByteOrder,
+    #[serde(rename = "test")]
+    tests: Vec<Test>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Test {
+    name: String,
+    #[serde(default)]
+    registers: BTreeMap<String, i64>,
+    #[serde(default)]
+    memory: BTreeMap<String, Vec<i64>>,
+    #[serde(default)]
+    expect: Expect,
+}
+
+// The values of registers and runs of words of memory expected after a test, written just as they
+// are when setting them up before the test. Values may be negative, in which case they're in two's
+// complement.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Expect {
+    registers: BTreeMap<String, i64>,
+    memory: BTreeMap<String, Vec<i64>>,
+}
+
+// Grade a program against an exercise, printing whether each test passed or failed. This is what
+// `ilo grade exercise.toml program.bin` does, and it fails unless every test passes, so that it can
+// be used as the backend of an autograder.
+pub fn grade(mut arguments: impl Iterator<Item = String>) -> Result<()> {
+    let (Some(exercise_path), Some(program_path)) = (arguments.next(), arguments.next()) else {
+        bail!("grade must be followed by the path to an exercise and the path to a program.");
+    };
+    if let Some(argument) = arguments.next() {
+        bail!("Unrecognized argument \"{}\" to grade.", argument);
+    }
+
+    let exercise: Exercise = toml::from_str(&fs::read_to_string(&exercise_path)?)
+        .map_err(|e| anyhow!("Invalid {}: {}", exercise_path, e))?;
+    let program = fs::read(&program_path)?;
+
+    println!(
+        "Grading {} against {}.",
+        program_path,
+        exercise.name.as_deref().unwrap_or(&exercise_path)
+    );
+    let mut passed = 0;
+    for test in &exercise.tests {
+        match run(&exercise, test, &program_path, &program)? {
+            Ok(steps) => {
+                passed += 1;
+                println!("PASS {} ({} steps)", test.name, steps);
+            }
+            Err(problems) => println!("FAIL {}: {}", test.name, problems.join("; ")),
+        }
+    }
+
+    let total = exercise.tests.len();
+    println!("{} of {} tests passed.", passed, total);
+    if passed < total {
+        bail!("{} of {} tests failed.", total - passed, total);
+    }
+    Ok(())
+}
+
+// Run a single test, returning either the number of steps which the program took, or every way in
+// which the end state differed from what was expected. An error means that the exercise itself is
+// broken, rather than the program.
+fn run(
+    exercise: &Exercise,
+    test: &Test,
+    filename: &str,
+    program: &[u8],
+) -> Result<Result<u64, Vec<String>>> {
+    let mut cpu = Cpu::new();
+    write_image(
+        &mut cpu,
+        exercise.load,
+        filename,
+        program,
+        exercise.byte_order,
+    )?;
+    cpu.program_counter = exercise.entry.unwrap_or(exercise.load);
+    for (register, value) in registers(&test.registers)? {
+        cpu.set_register(register, value);
+    }
+    for (address, value) in memory(&test.memory)? {
+        let index = cpu.memory.decode(address).ok_or_else(|| {
+            anyhow!(
+                "Test \"{}\" sets {:#06x}, where no RAM is fitted.",
+                test.name,
+                address
+            )
+        })?;
+        cpu.ram[index] = value;
+    }
+
+    let limit = exercise.steps.unwrap_or(DEFAULT_STEPS);
+    let mut steps = 0;
+    loop {
+        if Some(cpu.program_counter) == exercise.end {
+            break;
+        }
+        if steps == limit {
+            return Ok(Err(vec![format!("did not finish within {} steps", limit)]));
+        }
+        let address = cpu.program_counter;
+        cpu.step();
+        steps += 1;
+        if let Some(fault) = cpu.fault.take() {
+            return Ok(Err(vec![format!("faulted at {:#06x}: {}", address, fault)]));
+        }
+        if exercise.end.is_none() && cpu.program_counter == address {
+            break;
+        }
+    }
+
+    let mut problems = Vec::new();
+    for (register, expected) in registers(&test.expect.registers)? {
+        let actual = cpu.registers[register];
+        if actual != expected {
+            problems.push(format!(
+                "r{:02} is {:#06x}, but {:#06x} was expected",
+                register, actual, expected
+            ));
+        }
+    }
+    for (address, expected) in memory(&test.expect.memory)? {
+        let actual = cpu.peek(address);
+        if actual != expected {
+            problems.push(format!(
+                "{:#06x} holds {:#06x}, but {:#06x} was expected",
+                address, actual, expected
+            ));
+        }
+    }
+    Ok(if problems.is_empty() {
+        Ok(steps)
+    } else {
+        Err(problems)
+    })
+}
+
+// Look up the registers named as keys, along with their values.
+fn registers(registers: &BTreeMap<String, i64>) -> Result<Vec<(usize, u16)>> {
+    registers
+        .iter()
+        .map(|(name, &value)| {
+            let register = name
+                .strip_prefix(['r', 'R'])
+                .and_then(|number| number.parse::<usize>().ok())
+                .filter(|&register| (1..0x20).contains(&register))
+                .ok_or_else(|| anyhow!("\"{}\" is not a register from r01 to r31.", name))?;
+            Ok((register, word(value)?))
+        })
+        .collect()
+}
+
+// Expand runs of words of memory, keyed by the addresses at which they start, into every word
+// along with its address.
+fn memory(memory: &BTreeMap<String, Vec<i64>>) -> Result<Vec<(u16, u16)>> {
+    let mut words = Vec::new();
+    for (start, values) in memory {
+        let start =
+            parse_literal(start).map_err(|_| anyhow!("\"{}\" is not a valid address.", start))?;
+        for (offset, &value) in values.iter().enumerate() {
+            words.push((start.wrapping_add(offset as u16), word(value)?));
+        }
+    }
+    Ok(words)
+}
+
+// Convert a value to a word, encoding it in two's complement if it is negative.
+fn word(value: i64) -> Result<u16> {
+    u16::try_from(value)
+        .or_else(|_| i16::try_from(value).map(|value| value as u16))
+        .map_err(|_| anyhow!("{} does not fit in a word.", value))
+}
@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+
+use std::collections::BTreeSet;
+
+use crate::config::HeapConfig;
+use crate::cpu::Cpu;
+
+// The most blocks which are walked, so that a heap of tiny blocks can't hold up every frame.
+const MAX_BLOCKS: usize = 0x1000;
+
+// A block of the guest's heap.
+pub struct Block {
+    pub address: u16,
+    // The length of the block in words, header included.
+    pub size: u16,
+    // Whether or not the block was found on the free list.
+    pub free: bool,
+    // Anything wrong with the header of the block, or with its link to the next free block.
+    pub problems: Vec<String>,
+}
+
+impl Block {
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{:#06x} {:#06x} words {}",
+            self.address,
+            self.size,
+            if self.free { "free" } else { "used" }
+        );
+        for problem in &self.problems {
+            description.push_str(&format!("; {}", problem));
+        }
+        description
+    }
+}
+
+// The blocks of the heap, in order of address, along with anything wrong with the heap as a whole.
+pub struct Walk {
+    pub blocks: Vec<Block>,
+    pub problems: Vec<String>,
+}
+
+impl Walk {
+    // The number of words in free blocks and in used blocks.
+    pub fn totals(&self) -> (u32, u32) {
+        self.blocks
+            .iter()
+            .fold((0, 0), |(free, used), block| match block.free {
+                true => (free + u32::from(block.size), used),
+                false => (free, used + u32::from(block.size)),
+            })
+    }
+}
+
+// The layout of the heap managed by the guest's allocator, by which its blocks are walked.
+pub struct Heap {
+    start: u16,
+    end: u16,
+    head: u16,
+    size: i16,
+    next: i16,
+    null: u16,
+}
+
+impl Heap {
+    pub fn new(heap: &HeapConfig) -> Result<Self> {
+        if heap.start >= heap.end {
+            bail!(
+                "The heap must end after it starts, but it runs from {:#06x} to {:#06x}.",
+                heap.start,
+                heap.end
+            );
+        }
+        Ok(Self {
+            start: heap.start,
+            end: heap.end,
+            head: heap.head,
+            size: heap.size,
+            next: heap.next,
+            null: heap.null,
+        })
+    }
+
+    // Walk the heap from one block to the next by their sizes, and then follow the free list to
+    // find which of them are free. Like unwinding the call stack, this only looks at memory as it
+    // is, so it can't be misled by an allocator which has lost track of its own blocks.
+    pub fn walk(&self, cpu: &Cpu) -> Walk {
+        let mut walk = Walk {
+            blocks: Vec::new(),
+            problems: Vec::new(),
+        };
+
+        let mut address = self.start;
+        while address < self.end && walk.blocks.len() < MAX_BLOCKS {
+            let size = cpu.peek(address.wrapping_add_signed(self.size));
+            let mut block = Block {
+                address,
+                size,
+                free: false,
+                problems: Vec::new(),
+            };
+            // A block which is empty or which overruns the heap means that the header has been
+            // overwritten, and the blocks beyond it can't be found.
+            let past = u32::from(address) + u32::from(size);
+            if size == 0 {
+                block.problems.push("the size is zero".into());
+            } else if past > u32::from(self.end) {
+                block.problems.push(format!(
+                    "runs past the end of the heap at {:#06x}",
+                    self.end
+                ));
+            }
+            let corrupt = !block.problems.is_empty();
+            walk.blocks.push(block);
+            if corrupt {
+                break;
+            }
+            address = past as u16;
+        }
+
+        // Follow the free list, which should only ever link to the start of a block, and which
+        // should never come back around to a block which it has already visited.
+        let mut visited = BTreeSet::new();
+        let mut previous: Option<usize> = None;
+        let mut link = cpu.peek(self.head);
+        while link != self.null {
+            let problem = if visited.contains(&link) {
+                format!("links back to {:#06x}, so the free list loops", link)
+            } else {
+                match walk
+                    .blocks
+                    .binary_search_by_key(&link, |block| block.address)
+                {
+                    Ok(index) => {
+                        visited.insert(link);
+                        walk.blocks[index].free = true;
+                        previous = Some(index);
+                        link = cpu.peek(link.wrapping_add_signed(self.next));
+                        continue;
+                    }
+                    Err(_) => format!("links to {:#06x}, which doesn't begin a block", link),
+                }
+            };
+            match previous {
+                Some(index) => walk.blocks[index].problems.push(problem),
+                None => walk.problems.push(format!(
+                    "The head of the free list at {:#06x} {}.",
+                    self.head, problem
+                )),
+            }
+            break;
+        }
+        walk
+    }
+}
@@ -0,0 +1,54 @@
+// The most entries which are kept in the instruction history.
+pub const HISTORY_LENGTH: usize = 0xff;
+
+// An entry in the instruction history.
+#[derive(Clone)]
+pub enum History {
+    // An instruction which was executed, as its word and the word after it.
+    Executed(u16, u16),
+    // A move of the program counter by the debugger rather than by the program, as with JUMP or
+    // SKIP, described as it's shown in the history.
+    Intervention(String),
+    // An interrupt which was taken once the instruction before it had completed, as its line.
+    Interrupt(u8),
+}
+
+// The instruction history, as a ring buffer which is allocated once, and in which each new entry
+// takes the place of the oldest once it is full. Every step adds an entry, so over a long run this
+// keeps the allocator out of the way entirely.
+#[derive(Clone)]
+pub struct InstructionHistory {
+    entries: Vec<History>,
+    // The index of the oldest entry, which is only ever anything but zero once the buffer is full.
+    oldest: usize,
+}
+
+impl InstructionHistory {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::with_capacity(HISTORY_LENGTH),
+            oldest: 0,
+        }
+    }
+
+    pub fn push(&mut self, entry: History) {
+        if self.entries.len() < HISTORY_LENGTH {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.oldest] = entry;
+            self.oldest = (self.oldest + 1) % HISTORY_LENGTH;
+        }
+    }
+
+    // Forget every entry, keeping the buffer for the entries to come.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.oldest = 0;
+    }
+
+    // The entries, from the oldest to the most recent.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &History> {
+        let (newer, older) = self.entries.split_at(self.oldest);
+        older.iter().chain(newer)
+    }
+}
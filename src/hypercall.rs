@@ -0,0 +1,195 @@
+use crate::device::Device;
+
+// The version of the hypercall interface, which guest code should check before relying on any of
+// the services below. It is bumped whenever a service changes in a way that a guest could notice.
+pub const HYPERCALL_VERSION: u16 = 1;
+
+// The offsets of the hypercall port's registers.
+const VERSION: u16 = 0;
+const FEATURES: u16 = 1;
+const PARAMETER: u16 = 2;
+const CALL: u16 = 3;
+const DATA: u16 = 4;
+
+// The services which may be called, by writing their number to the call register.
+const ARGUMENTS: u16 = 0x0001;
+const ARGUMENT: u16 = 0x0002;
+const SNAPSHOT: u16 = 0x0003;
+const EXIT: u16 = 0x0004;
+
+// The bits of the features register, each of which is set if the corresponding service is offered.
+const FEATURE_ARGUMENTS: u16 = 0b0001;
+const FEATURE_SNAPSHOT: u16 = 0b0010;
+const FEATURE_EXIT: u16 = 0b0100;
+
+// The result of calling a service which doesn't exist, or of selecting an argument which doesn't.
+const UNSUPPORTED: u16 = 0xffff;
+
+// The most characters of text which are kept for a call, beyond which any more are dropped, so that
+// a guest which writes to the data register without ever making a call can't grow it forever.
+const MAX_TEXT: usize = 0x100;
+
+// Something which the guest has asked of the emulator itself, rather than of the machine, which
+// the app carries out once the instruction which asked for it has completed.
+#[derive(Clone)]
+pub enum Request {
+    // Save a snapshot of the machine to a file with the given name.
+    Snapshot(String),
+    // Halt the simulation, since the guest has finished, with a status which is zero on success
+    // and a message saying why it finished.
+    Exit { status: u16, message: String },
+}
+
+// A port through which guest code can make use of the emulator, so that test programs and demos
+// can cooperate with it rather than just running on it. The version register reads as the version
+// of the interface, and the features register as the services which are offered. A service is
+// called by writing its parameter, if it takes one, to the parameter register, and then its number
+// to the call register, after which reading the call register gives its result. Text is passed to
+// a service by writing it to the data register a character at a time beforehand.
+//
+// ARGUMENTS gives the number of arguments which were passed to the guest on the command line, after
+// --. ARGUMENT selects the argument whose index is its parameter, giving its length, after which
+// each read of the data register gives its next character, and 0x0000 once there are none left.
+// SNAPSHOT saves a snapshot of the machine to the file named by its text, which must be a plain
+// file name which doesn't already exist, so that the guest can't write anywhere but the working
+// directory, nor replace anything there. EXIT halts the simulation with its parameter as the
+// status, and its text as a message saying why. Calling a service which doesn't exist gives
+// 0xffff.
+//
+// NOTE: Arguments are read from the port itself, so that a run which reads them replays exactly,
+// but snapshots and exits can only be carried out by the app, so they are buffered like debug
+// output, and never happen for a CPU which has been cloned to predict or replay a step.
+#[derive(Clone)]
+pub struct Hypercall {
+    arguments: Vec<String>,
+    parameter: u16,
+    result: u16,
+    // The characters of the argument selected last which have yet to be read.
+    selected: Vec<u8>,
+    // The text written to the data register since the last call.
+    text: Vec<u8>,
+    requests: Vec<Request>,
+}
+
+impl Hypercall {
+    pub fn new(arguments: &[String]) -> Self {
+        Self {
+            arguments: arguments.to_vec(),
+            parameter: 0x0000,
+            result: 0x0000,
+            selected: Vec::new(),
+            text: Vec::new(),
+            requests: Vec::new(),
+        }
+    }
+
+    // Carry out a call to a service, returning its result.
+    fn call(&mut self, service: u16) -> u16 {
+        let text = String::from_utf8_lossy(&std::mem::take(&mut self.text)).into_owned();
+        match service {
+            ARGUMENTS => self.arguments.len() as u16,
+            ARGUMENT => match self.arguments.get(usize::from(self.parameter)) {
+                Some(argument) => {
+                    self.selected = argument.bytes().rev().collect();
+                    argument.len() as u16
+                }
+                None => {
+                    self.selected.clear();
+                    UNSUPPORTED
+                }
+            },
+            SNAPSHOT => {
+                self.requests.push(Request::Snapshot(text));
+                0x0000
+            }
+            EXIT => {
+                self.requests.push(Request::Exit {
+                    status: self.parameter,
+                    message: text,
+                });
+                0x0000
+            }
+            _ => UNSUPPORTED,
+        }
+    }
+}
+
+impl Device for Hypercall {
+    fn kind(&self) -> &'static str {
+        "hypercall"
+    }
+
+    fn length(&self) -> u16 {
+        5
+    }
+
+    fn register_name(&self, offset: u16) -> &str {
+        match offset {
+            VERSION => "version",
+            FEATURES => "features",
+            PARAMETER => "parameter",
+            CALL => "call",
+            _ => "data",
+        }
+    }
+
+    fn read(&mut self, offset: u16) -> u16 {
+        match offset {
+            DATA => self.selected.pop().map_or(0x0000, u16::from),
+            _ => self.peek(offset),
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u16) {
+        match offset {
+            PARAMETER => self.parameter = value,
+            CALL => self.result = self.call(value),
+            DATA if self.text.len() < MAX_TEXT => self.text.push(value as u8),
+            _ => {}
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u16 {
+        match offset {
+            VERSION => HYPERCALL_VERSION,
+            FEATURES => FEATURE_ARGUMENTS | FEATURE_SNAPSHOT | FEATURE_EXIT,
+            PARAMETER => self.parameter,
+            CALL => self.result,
+            _ => self.selected.last().map_or(0x0000, |&byte| u16::from(byte)),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.parameter = 0x0000;
+        self.result = 0x0000;
+        self.selected.clear();
+        self.text.clear();
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            format!(
+                "version {}, {} argument(s): {:?}",
+                HYPERCALL_VERSION,
+                self.arguments.len(),
+                self.arguments
+            ),
+            format!(
+                "parameter {:#06x}, last result {:#06x}",
+                self.parameter, self.result
+            ),
+            format!(
+                "text awaiting a call: {:?}",
+                String::from_utf8_lossy(&self.text)
+            ),
+        ]
+    }
+
+    fn take_requests(&mut self) -> Vec<Request> {
+        std::mem::take(&mut self.requests)
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,231 @@
+use serde::Deserialize;
+
+use crate::config::InterruptConfig;
+use crate::device::Devices;
+
+// The base address of the interrupt controller's registers, which are mapped into the address space
+// whenever the CPU takes interrupts.
+pub const INTERRUPTS_BASE: u16 = 0xffe8;
+
+// The offsets of the interrupt controller's registers.
+const ENABLE: u16 = 0;
+const LINE: u16 = 1;
+const RETURN: u16 = 2;
+const THRESHOLD: u16 = 3;
+
+// The number of addresses occupied by the interrupt controller.
+const INTERRUPTS_LENGTH: u16 = 4;
+
+// What the line register reads as while no handler is running.
+const NO_LINE: u16 = 0xffff;
+
+// What stepping does about interrupts. Running is never affected, so a handler can always be caught
+// with a breakpoint at the vector.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stepping {
+    // Interrupts are taken as usual, and stepping stops once one is, at the entry of its handler.
+    #[default]
+    Stop,
+    // Interrupts are held off while stepping, so that stepping stays within the code being stepped
+    // through, and any which are pending are taken once the simulation runs.
+    Mask,
+}
+
+// A handler which has been entered, and has yet to return.
+#[derive(Clone)]
+pub struct Handler {
+    pub line: u8,
+    // The address of the instruction which was interrupted, to which the handler returns.
+    pub return_address: u16,
+}
+
+// The interrupt controller, which decides when the CPU stops what it's doing to run a handler for an
+// interrupt line which a device is asserting. Every line shares a single handler at the vector,
+// which reads the line register to find out which it is handling. A line is taken after the
+// instruction in progress completes, by noting where execution was to resume and jumping to the
+// vector, and the handler returns by writing any value to the return register, which jumps back.
+//
+// Lines with greater numbers are more urgent. While interrupts are enabled, a line is taken if it
+// is at or above the threshold, and if no handler is running. If the controller doesn't mask
+// interrupts on entry, then a handler may itself be interrupted by a more urgent line than the one
+// it's handling, so handlers nest. The NMI line, if there is one, is taken regardless of whether
+// interrupts are enabled, or of the threshold, or of any handler other than its own.
+//
+// NOTE: Lines are level-triggered, so a handler must acknowledge its device before returning, or
+// the line will just be taken again straight away.
+#[derive(Clone, Default)]
+pub struct Interrupts {
+    // Where the handler begins, or `None` if the CPU doesn't take interrupts at all, in which case
+    // the controller's registers aren't mapped either, and guest code must poll its devices.
+    pub vector: Option<u16>,
+    // Whether or not entering a handler masks every other line until it returns. Otherwise, a more
+    // urgent line may interrupt a handler.
+    pub mask_on_entry: bool,
+    // The line which can't be masked, if any.
+    pub nmi: Option<u8>,
+    // The threshold which the controller starts out with after a reset.
+    pub initial_threshold: u8,
+    // Whether or not interrupts other than the NMI are taken. These are disabled by a reset, so
+    // that the guest can set up whatever its handler depends on before enabling them.
+    pub enabled: bool,
+    // The least urgent line which is taken.
+    pub threshold: u8,
+    // The handlers which have been entered and have yet to return, innermost last.
+    pub handlers: Vec<Handler>,
+    // The line which was taken at the end of the last step, if one was.
+    pub taken: Option<u8>,
+    // Whether or not the debugger is holding off every interrupt, the NMI included, as it does
+    // while stepping if it has been asked to. This isn't part of the machine, so a reset leaves it
+    // alone.
+    pub held: bool,
+    // Where the CPU resumes once the current instruction completes, if it has returned from a
+    // handler.
+    resume: Option<u16>,
+}
+
+impl Interrupts {
+    // Construct an interrupt controller from its description in the machine description, if the
+    // machine has one.
+    pub fn new(config: Option<&InterruptConfig>) -> Self {
+        match config {
+            Some(config) => Self {
+                vector: Some(config.vector),
+                mask_on_entry: config.mask_on_entry,
+                nmi: config.nmi,
+                initial_threshold: config.threshold,
+                threshold: config.threshold,
+                ..Self::default()
+            },
+            None => Self::default(),
+        }
+    }
+
+    // Return the controller to its state after a reset, with interrupts disabled and no handler
+    // running.
+    pub fn reset(&mut self) {
+        self.enabled = false;
+        self.threshold = self.initial_threshold;
+        self.handlers.clear();
+        self.taken = None;
+        self.resume = None;
+    }
+
+    // Read from the controller, returning `None` if the address doesn't belong to it.
+    pub fn read(&self, address: u16) -> Option<u16> {
+        Some(match self.offset(address)? {
+            ENABLE => u16::from(self.enabled),
+            LINE => self
+                .handlers
+                .last()
+                .map_or(NO_LINE, |handler| u16::from(handler.line)),
+            RETURN => self
+                .handlers
+                .last()
+                .map_or(0x0000, |handler| handler.return_address),
+            _ => u16::from(self.threshold),
+        })
+    }
+
+    // Write to the controller, returning whether or not the address belongs to it. Writing to the
+    // enable register enables interrupts if the low bit is set and disables them otherwise, writing
+    // to the threshold register sets the threshold, and writing any value to the return register
+    // returns from the innermost handler, or does nothing if no handler is running. The line
+    // register can't be written.
+    pub fn write(&mut self, address: u16, value: u16) -> bool {
+        let Some(offset) = self.offset(address) else {
+            return false;
+        };
+        match offset {
+            ENABLE => self.enabled = value & 0x0001 != 0,
+            RETURN => {
+                if let Some(handler) = self.handlers.pop() {
+                    self.resume = Some(handler.return_address);
+                }
+            }
+            THRESHOLD => self.threshold = value.min(0x00ff) as u8,
+            _ => {}
+        }
+        true
+    }
+
+    // Whether or not an address belongs to the controller, while it is mapped.
+    pub fn contains(&self, address: u16) -> bool {
+        self.offset(address).is_some()
+    }
+
+    // Take where the CPU resumes after returning from a handler, if the instruction just executed
+    // returned from one.
+    pub fn take_resume(&mut self) -> Option<u16> {
+        self.resume.take()
+    }
+
+    // Determine the line which should be taken next, given the lines asserted by the devices, if
+    // any should be.
+    pub fn pending(&self, devices: &Devices) -> Option<u8> {
+        self.vector?;
+        if self.held {
+            return None;
+        }
+        // An NMI handler can't be interrupted by anything.
+        if self
+            .handlers
+            .iter()
+            .any(|handler| Some(handler.line) == self.nmi)
+        {
+            return None;
+        }
+        let innermost = self.handlers.last().map(|handler| handler.line);
+        devices.asserted().into_iter().rev().find(|&line| {
+            if Some(line) == self.nmi {
+                return true;
+            }
+            let masked = match innermost {
+                Some(_) if self.mask_on_entry => true,
+                Some(handling) => line <= handling,
+                None => false,
+            };
+            self.enabled && line >= self.threshold && !masked
+        })
+    }
+
+    // Enter the handler for a line, given the address of the instruction which it interrupts,
+    // returning the address of the handler.
+    pub fn enter(&mut self, line: u8, return_address: u16) -> Option<u16> {
+        let vector = self.vector?;
+        self.handlers.push(Handler {
+            line,
+            return_address,
+        });
+        self.taken = Some(line);
+        Some(vector)
+    }
+
+    // Describe the state of the controller tersely, for display along the border of the Registers
+    // pane, or `None` if the CPU doesn't take interrupts. Inside handlers, the lines being handled
+    // are listed from the outermost in, and otherwise whether interrupts are enabled is given. Any
+    // threshold follows.
+    pub fn summary(&self) -> Option<String> {
+        self.vector?;
+        let mut summary = if self.handlers.is_empty() {
+            String::from(if self.enabled { "int on" } else { "int off" })
+        } else {
+            let lines = self
+                .handlers
+                .iter()
+                .map(|handler| handler.line.to_string())
+                .collect::<Vec<_>>();
+            format!("in {}", lines.join(">"))
+        };
+        if self.threshold > 0 {
+            summary.push_str(&format!(" ≥{}", self.threshold));
+        }
+        Some(summary)
+    }
+
+    // Determine the offset of an address into the controller, if it falls within it.
+    fn offset(&self, address: u16) -> Option<u16> {
+        let offset = address.wrapping_sub(INTERRUPTS_BASE);
+        (self.vector.is_some() && offset < INTERRUPTS_LENGTH).then_some(offset)
+    }
+}
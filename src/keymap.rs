@@ -0,0 +1,141 @@
+use anyhow::{anyhow, bail, Result};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use std::collections::HashMap;
+
+// Something which a key can be bound to.
+#[derive(Clone)]
+pub enum Action {
+    Quit,
+    Suspend,
+    ToggleFreeze,
+    // Step continuously for as long as the key is held.
+    TurboStep,
+    // A command, executed as though it had been typed at the prompt, but without disturbing
+    // whatever is being typed there.
+    Command(String),
+}
+
+// The keys which are bound when ilo starts, before the configuration is applied. Each can be
+// rebound, or unbound by binding it to an empty string, in the `[keys]` table of ilo.toml.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("Ctrl-C", "quit"),
+    ("Ctrl-Z", "suspend"),
+    ("Ctrl-F", "toggle-freeze"),
+    ("F5", "RUN"),
+    ("F7", "BUILD"),
+    ("F10", "STEP"),
+    ("Shift-F10", "STEP 16"),
+    ("Ctrl-F10", "turbo-step"),
+];
+
+// The keys which are bound to actions, as opposed to being typed at the prompt. A key is written
+// as its name, such as "F10", "PageDown", or "s", preceded by any of "Ctrl-", "Alt-", and "Shift-".
+pub struct Keymap {
+    bindings: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Keymap {
+    // Bind the default keys, and then apply the bindings from the configuration on top of them.
+    pub fn new(overrides: &HashMap<String, String>) -> Result<Self> {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        let defaults = DEFAULT_BINDINGS
+            .iter()
+            .map(|&(key, action)| (key.to_string(), action.to_string()));
+        for (key, action) in defaults.chain(overrides.clone()) {
+            let key =
+                parse_key(&key).map_err(|e| anyhow!("Invalid key binding \"{}\": {}", key, e))?;
+            match action.trim() {
+                "" => keymap.bindings.remove(&key),
+                action if action.eq_ignore_ascii_case("quit") => {
+                    keymap.bindings.insert(key, Action::Quit)
+                }
+                action if action.eq_ignore_ascii_case("suspend") => {
+                    keymap.bindings.insert(key, Action::Suspend)
+                }
+                action if action.eq_ignore_ascii_case("toggle-freeze") => {
+                    keymap.bindings.insert(key, Action::ToggleFreeze)
+                }
+                action if action.eq_ignore_ascii_case("turbo-step") => {
+                    keymap.bindings.insert(key, Action::TurboStep)
+                }
+                command => keymap
+                    .bindings
+                    .insert(key, Action::Command(command.to_string())),
+            };
+        }
+        Ok(keymap)
+    }
+
+    // Find the action bound to a key press, if there is one.
+    pub fn lookup(&self, key: &KeyEvent) -> Option<&Action> {
+        self.bindings.get(&normalize(key.code, key.modifiers))
+    }
+}
+
+// Parse the name of a key, along with its modifiers.
+fn parse_key(text: &str) -> Result<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text.trim();
+    // NOTE: A trailing dash is the minus key itself rather than a separator, as in "Ctrl--".
+    while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+        modifiers |= match prefix.to_ascii_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => bail!("\"{}\" is not a modifier. Use Ctrl, Alt, or Shift.", prefix),
+        };
+        rest = key;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(char), None) => KeyCode::Char(char),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(number @ 1..=24) => KeyCode::F(number),
+                _ => bail!("\"{}\" is not the name of a key.", rest),
+            },
+        },
+    };
+
+    // Without Ctrl or Alt, a key which types a character is needed for typing at the prompt.
+    if matches!(code, KeyCode::Char(_))
+        && !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        bail!("keys which type characters must be bound with Ctrl or Alt");
+    }
+    Ok(normalize(code, modifiers))
+}
+
+// Bring a key into the form in which it is bound. Terminals disagree about whether Shift is
+// reported alongside a character which it has already changed, so for characters it is dropped,
+// and letters are compared without regard to case. Shift-Tab is reported as a key of its own.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(char) => (
+            KeyCode::Char(char.to_ascii_lowercase()),
+            modifiers - KeyModifiers::SHIFT,
+        ),
+        KeyCode::BackTab => (KeyCode::Tab, modifiers | KeyModifiers::SHIFT),
+        code => (code, modifiers),
+    }
+}
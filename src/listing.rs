@@ -0,0 +1,99 @@
+// The width of the source column used when none is given, which leaves room for the longest
+// disassembled instruction along with a label.
+pub const DEFAULT_WIDTH: usize = 28;
+
+// The most words of encoding shown on a single row of a listing. Every instruction fits, and
+// longer runs of data continue onto rows of their own.
+const WORDS_PER_ROW: usize = 2;
+
+// A line of source, along with the address at which it was placed and the words which it occupies,
+// if it occupies any at all.
+pub struct Line {
+    pub address: Option<u16>,
+    pub words: Vec<u16>,
+    pub code: String,
+    pub comment: Option<String>,
+}
+
+// Lay lines of source out as a classic listing, which puts the address and encoding of every line
+// beside it, for printing out for code review or teaching. Comments are aligned in a column after
+// the code, which is given a fixed width.
+//
+//     0000  2848 0005  start:  ADDI r01, r01, 5    ; The first term.
+//     0002  0001                SUB  r00, r01
+pub fn format(title: &str, lines: &[Line], width: usize) -> String {
+    let mut listing = format!("; {}\n", title);
+    for line in lines {
+        let mut rows = line.words.chunks(WORDS_PER_ROW);
+        let encoding = rows.next().map(encode).unwrap_or_default();
+        let source = match &line.comment {
+            Some(comment) if line.code.trim().is_empty() => format!("; {}", comment),
+            Some(comment) => format!("{:<width$} ; {}", line.code, comment, width = width),
+            None => line.code.clone(),
+        };
+        push_row(&mut listing, line.address, &encoding, &source);
+
+        // Any further words are shown beneath, each row at its own address.
+        for (row, words) in rows.enumerate() {
+            let address = line
+                .address
+                .map(|address| address.wrapping_add(((row + 1) * WORDS_PER_ROW) as u16));
+            push_row(&mut listing, address, &encode(words), "");
+        }
+    }
+    listing
+}
+
+fn push_row(listing: &mut String, address: Option<u16>, encoding: &str, source: &str) {
+    let address = match address {
+        Some(address) => format!("{:04x}", address),
+        None => String::new(),
+    };
+    let row = format!("{:<4}  {:<9}  {}", address, encoding, source);
+    listing.push_str(row.trim_end());
+    listing.push('\n');
+}
+
+fn encode(words: &[u16]) -> String {
+    words
+        .iter()
+        .map(|word| format!("{:04x}", word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Split a line of assembly source at its comment, if it has one, keeping the code as it was
+// written, indentation included.
+fn split_comment(line: &str) -> (String, Option<String>) {
+    match line.split_once(';') {
+        Some((code, comment)) => (
+            code.trim_end().to_string(),
+            Some(comment.trim().to_string()),
+        ),
+        None => (line.trim_end().to_string(), None),
+    }
+}
+
+// Build the lines of a listing of assembly source, given the statements which it was assembled
+// into. Lines which hold no statement, such as those with only a label or a comment, are listed
+// without an address.
+pub fn assembled(source: &str, statements: &[(usize, u16, Vec<u16>)]) -> Vec<Line> {
+    let mut statements = statements.iter().peekable();
+    source
+        .lines()
+        .enumerate()
+        .map(|(number, line)| {
+            let (code, comment) = split_comment(line);
+            let (address, words) = match statements.next_if(|(n, _, _)| *n == number) {
+                Some((_, start, words)) => (Some(*start), words.clone()),
+                None => (None, Vec::new()),
+            };
+            Line {
+                address,
+                words,
+                code,
+                comment,
+            }
+        })
+        .collect()
+}
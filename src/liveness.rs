@@ -0,0 +1,260 @@
+use std::collections::BTreeSet;
+
+use crate::cpu::Cpu;
+use crate::disassemble::{instruction_length, successors};
+use crate::explain::dataflow_at;
+
+// The most stretches of addresses which are listed for each register, so that a register which is
+// live all over a long run doesn't flood the console.
+const MAX_STRETCHES: usize = 8;
+
+// How a register is used over a run or over a region of code, for allocating registers by hand
+// and for spotting those which are never used or whose values are thrown away.
+#[derive(Clone, Default)]
+pub struct RegisterUsage {
+    // The number of instructions which read and write the register, counting each time that an
+    // instruction was executed over a run, or each instruction once over a region.
+    pub reads: usize,
+    pub writes: usize,
+    // The addresses of the instructions before which the register holds a value which is read
+    // later, before anything overwrites it.
+    pub live: BTreeSet<u16>,
+    // The addresses of the instructions which write a value to the register which is always
+    // overwritten before it's read.
+    pub dead: BTreeSet<u16>,
+}
+
+impl RegisterUsage {
+    pub fn used(&self) -> bool {
+        self.reads > 0 || self.writes > 0
+    }
+
+    // Describe the usage of a register in a line, with each stretch of consecutive instructions
+    // over which it's live given as the addresses of the first and the last of them.
+    pub fn describe(&self, cpu: &Cpu, register: usize) -> String {
+        let mut line = format!(
+            "r{:02}: read {} time(s), written {} time(s)",
+            register, self.reads, self.writes
+        );
+        if !self.live.is_empty() {
+            line.push_str(&format!(", live at {}", stretches(cpu, &self.live)));
+        }
+        if !self.dead.is_empty() {
+            line.push_str(&format!(
+                ", overwritten before being read after {}",
+                stretches(cpu, &self.dead)
+            ));
+        }
+        line
+    }
+}
+
+// An instruction which was executed over a run, or which is part of a region of code, as its
+// address along with the registers which it reads and writes, as bitmasks.
+struct Instruction {
+    address: u16,
+    reads: u32,
+    writes: u32,
+}
+
+impl Instruction {
+    fn at(cpu: &Cpu, address: u16) -> Self {
+        let dataflow = dataflow_at(cpu, address);
+        Self {
+            address,
+            reads: mask(&dataflow.reads),
+            writes: mask(&dataflow.writes),
+        }
+    }
+}
+
+// What happens next to the value held in a register, looking forward from some point.
+#[derive(Clone, Copy, PartialEq)]
+enum Next {
+    Read,
+    Overwritten,
+    // The run or the region ends first, so it can't be said.
+    Unknown,
+}
+
+// Collects the instructions executed over a run, as they are executed, and then works out how each
+// register was used over it. Working backwards from the end of the run, a register is live before
+// an instruction if the next thing that happens to it is a read.
+pub struct Trace {
+    instructions: Vec<Instruction>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self {
+            instructions: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    // Record the instruction to which the program counter points, before it is executed.
+    pub fn record(&mut self, cpu: &Cpu) {
+        self.instructions
+            .push(Instruction::at(cpu, cpu.program_counter));
+    }
+
+    pub fn usage(&self) -> [RegisterUsage; 0x20] {
+        let mut usage: [RegisterUsage; 0x20] = Default::default();
+        let mut next = [Next::Unknown; 0x20];
+        for instruction in self.instructions.iter().rev() {
+            for (register, usage) in usage.iter_mut().enumerate().skip(1) {
+                let bit = 1 << register;
+                if instruction.writes & bit != 0 {
+                    usage.writes += 1;
+                    if next[register] == Next::Overwritten {
+                        usage.dead.insert(instruction.address);
+                    }
+                    next[register] = Next::Overwritten;
+                }
+                if instruction.reads & bit != 0 {
+                    usage.reads += 1;
+                    next[register] = Next::Read;
+                }
+                if next[register] == Next::Read {
+                    usage.live.insert(instruction.address);
+                }
+            }
+        }
+        usage
+    }
+}
+
+// Work out how each register is used by the code in a region of RAM, decoding it as consecutive
+// instructions from its start, without running it. Liveness flows backwards along every path that
+// execution may take through the region, so a register is live before an instruction if it may be
+// read before it's overwritten. Wherever execution may leave the region, whether by a jump, a
+// call, or a return, anything may happen to the registers, so a write is only reported as
+// overwritten if it's overwritten before being read along every path within the region.
+pub fn region(cpu: &Cpu, start: u16, length: u16) -> [RegisterUsage; 0x20] {
+    let end = u32::from(start) + u32::from(length);
+    let mut instructions = Vec::new();
+    let mut address = u32::from(start);
+    while address < end {
+        instructions.push(Instruction::at(cpu, address as u16));
+        address += u32::from(instruction_length(cpu.peek(address as u16)));
+    }
+    let index = |address: u16| {
+        instructions
+            .binary_search_by_key(&address, |instruction| instruction.address)
+            .ok()
+    };
+
+    // For each instruction, the successors which are instructions in the region, and whether or
+    // not execution may leave the region from it.
+    let edges = instructions
+        .iter()
+        .map(|instruction| {
+            let word = cpu.peek(instruction.address);
+            let immediate = cpu.peek(instruction.address.wrapping_add(1));
+            let targets = successors(instruction.address, word, immediate);
+            let inside = targets
+                .iter()
+                .filter_map(|&target| index(target))
+                .collect::<Vec<_>>();
+            // A register-indirect jump has no successor that can be known, and a call may do
+            // anything at all before it returns.
+            let call = word & 0b0000000000111111 == 0b101000 && instruction.writes != 0;
+            let leaves = targets.is_empty() || inside.len() < targets.len() || call;
+            (inside, leaves)
+        })
+        .collect::<Vec<_>>();
+
+    // The registers which may be read, and which may leave the region, before being overwritten,
+    // looking forward from just before each instruction, which are found by iterating to a fixed
+    // point.
+    let mut live = vec![0u32; instructions.len()];
+    let mut escapes = vec![0u32; instructions.len()];
+    let after = |live: &[u32], escapes: &[u32], index: usize| {
+        let (inside, leaves) = &edges[index];
+        let mut after = inside
+            .iter()
+            .fold((0, 0), |(l, e), &next| (l | live[next], e | escapes[next]));
+        if *leaves {
+            after.1 = u32::MAX;
+        }
+        after
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, instruction) in instructions.iter().enumerate().rev() {
+            let (live_after, escapes_after) = after(&live, &escapes, index);
+            let live_before = (live_after & !instruction.writes) | instruction.reads;
+            let escapes_before = escapes_after & !instruction.writes;
+            if live_before != live[index] || escapes_before != escapes[index] {
+                live[index] = live_before;
+                escapes[index] = escapes_before;
+                changed = true;
+            }
+        }
+    }
+
+    let mut usage: [RegisterUsage; 0x20] = Default::default();
+    for (index, instruction) in instructions.iter().enumerate() {
+        let (live_after, escapes_after) = after(&live, &escapes, index);
+        for (register, usage) in usage.iter_mut().enumerate().skip(1) {
+            let bit = 1 << register;
+            if instruction.reads & bit != 0 {
+                usage.reads += 1;
+            }
+            if instruction.writes & bit != 0 {
+                usage.writes += 1;
+                if (live_after | escapes_after) & bit == 0 {
+                    usage.dead.insert(instruction.address);
+                }
+            }
+            if live[index] & bit != 0 {
+                usage.live.insert(instruction.address);
+            }
+        }
+    }
+    usage
+}
+
+fn mask(registers: &[usize]) -> u32 {
+    registers
+        .iter()
+        .fold(0, |mask, &register| mask | 1 << register)
+}
+
+// List a set of addresses of instructions as stretches of consecutive instructions, each given as
+// the addresses of its first and last instructions, or just one address if it's just one
+// instruction.
+fn stretches(cpu: &Cpu, addresses: &BTreeSet<u16>) -> String {
+    let mut stretches: Vec<(u16, u16)> = Vec::new();
+    for &address in addresses {
+        match stretches.last_mut() {
+            Some((_, last))
+                if last.wrapping_add(instruction_length(cpu.peek(*last))) == address =>
+            {
+                *last = address;
+            }
+            _ => stretches.push((address, address)),
+        }
+    }
+    let count = stretches.len();
+    let mut listed = stretches
+        .into_iter()
+        .take(MAX_STRETCHES)
+        .map(|(first, last)| {
+            if first == last {
+                format!("{:#06x}", first)
+            } else {
+                format!("{:#06x}-{:#06x}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    if count > MAX_STRETCHES {
+        listed.push_str(&format!(" and {} more", count - MAX_STRETCHES));
+    }
+    listed
+}
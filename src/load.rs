@@ -0,0 +1,198 @@
+use anyhow::{anyhow, Result};
+
+use serde::Deserialize;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+// The order in which the two bytes of each word are kept in a binary image or data file. Most
+// assemblers for the machine write the most significant byte first, but some hardware tools expect
+// the least significant first.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+impl ByteOrder {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "big" => Some(ByteOrder::Big),
+            "little" => Some(ByteOrder::Little),
+            _ => None,
+        }
+    }
+
+    // Read every whole word from a sequence of bytes. A trailing odd byte is ignored.
+    pub fn words(self, bytes: &[u8]) -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|pair| match self {
+                ByteOrder::Big => u16::from_be_bytes([pair[0], pair[1]]),
+                ByteOrder::Little => u16::from_le_bytes([pair[0], pair[1]]),
+            })
+            .collect()
+    }
+
+    pub fn bytes(self, word: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Big => word.to_be_bytes(),
+            ByteOrder::Little => word.to_le_bytes(),
+        }
+    }
+}
+
+// The number of bytes read between progress reports.
+const CHUNK_SIZE: usize = 0x1000;
+
+// A message sent from the worker thread to the UI thread.
+enum Message {
+    Progress(u64),
+    Done(io::Result<Vec<u8>>),
+}
+
+// A file which is being read on a worker thread, so that reading large files (or files on slow
+// network filesystems) doesn't block rendering and input.
+pub struct PendingLoad {
+    pub filename: String,
+    pub address: u16,
+    // The number of bytes read so far, and the total size of the file if it is known.
+    pub read: u64,
+    pub total: Option<u64>,
+    receiver: Receiver<Message>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PendingLoad {
+    // Begin reading a file on a worker thread.
+    pub fn spawn(filename: &str, address: u16) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let total = std::fs::metadata(filename).ok().map(|m| m.len());
+
+        let path = filename.to_string();
+        let flag = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let result = (|| {
+                let mut file = File::open(path)?;
+                let mut bytes = Vec::new();
+                let mut chunk = [0; CHUNK_SIZE];
+                loop {
+                    if flag.load(Ordering::Relaxed) {
+                        return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                    }
+                    let count = file.read(&mut chunk)?;
+                    if count == 0 {
+                        return Ok(bytes);
+                    }
+                    bytes.extend_from_slice(&chunk[..count]);
+                    // NOTE: If the UI thread has stopped listening, then there's nobody left to
+                    // care about progress reports, and the read will end shortly anyway.
+                    let _ = sender.send(Message::Progress(bytes.len() as u64));
+                }
+            })();
+            let _ = sender.send(Message::Done(result));
+        });
+
+        Self {
+            filename: filename.to_string(),
+            address,
+            read: 0,
+            total,
+            receiver,
+            cancelled,
+        }
+    }
+
+    // Collect any messages from the worker thread without blocking, returning the contents of the
+    // file once it has been read in its entirety.
+    pub fn poll(&mut self) -> Option<io::Result<Vec<u8>>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Progress(read)) => self.read = read,
+                Ok(Message::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(io::Error::other(
+                        "the loading thread exited unexpectedly",
+                    )))
+                }
+            }
+        }
+    }
+
+    // Ask the worker thread to stop reading the file.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+// Whether or not a file is a hex image, as read by Verilog's $readmemh, rather than a binary
+// image. This is decided by the extension, since a binary image could happen to look like text.
+pub fn is_hex_image(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("mem") || extension.eq_ignore_ascii_case("hex")
+        })
+}
+
+// Parse a hex image, returning each word along with its offset from the start of the image. Words
+// are written in hexadecimal, separated by whitespace, and are placed one after another except
+// where an @ followed by an offset in hexadecimal moves on to somewhere else. Underscores may be
+// used to separate digits, and comments are written as in Verilog.
+pub fn parse_hex_image(text: &str) -> Result<Vec<(usize, u16)>> {
+    // Comments are replaced with spaces, so that they still separate whatever is either side.
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+            stripped.push(' ');
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            let end = after
+                .find("*/")
+                .ok_or_else(|| anyhow!("a comment is never closed"))?;
+            rest = &after[end + 2..];
+            stripped.push(' ');
+        } else {
+            let char = rest.chars().next().unwrap();
+            stripped.push(char);
+            rest = &rest[char.len_utf8()..];
+        }
+    }
+
+    let mut words = Vec::new();
+    let mut offset = 0;
+    for token in stripped.split_whitespace() {
+        let (digits, is_offset) = match token.strip_prefix('@') {
+            Some(digits) => (digits, true),
+            None => (token, false),
+        };
+        let value = usize::from_str_radix(&digits.replace('_', ""), 16)
+            .map_err(|_| anyhow!("\"{}\" is not a word in hexadecimal", token))?;
+        if is_offset {
+            // No offset beyond the address space could ever fit, wherever the image is loaded.
+            offset = usize::from(
+                u16::try_from(value)
+                    .map_err(|_| anyhow!("\"{}\" is beyond the address space", token))?,
+            );
+        } else {
+            let word = u16::try_from(value)
+                .map_err(|_| anyhow!("\"{}\" does not fit in a word", token))?;
+            words.push((offset, word));
+            offset = offset
+                .checked_add(1)
+                .ok_or_else(|| anyhow!("the image is too large"))?;
+        }
+    }
+    Ok(words)
+}
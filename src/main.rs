@@ -1,83 +1,476 @@
+mod abi;
+mod accesses;
+mod analyze;
 mod app;
+mod assemble;
+mod breakpoint;
+mod build;
+mod cast;
+mod checksum;
+mod clipboard;
+mod command;
+mod config;
+mod cost;
+mod counters;
 mod cpu;
+mod crash;
+mod debug;
+mod device;
+mod diagnostic;
 mod disassemble;
+mod exception;
+mod explain;
+mod export;
+mod expr;
+mod fuzz;
+mod grade;
+mod heap;
+mod history;
+mod hypercall;
+mod interrupt;
+mod keymap;
+mod listing;
+mod liveness;
+mod load;
+mod measure;
+mod memory;
+mod profile;
+mod project;
+mod random;
+mod recent;
+mod record;
+mod region;
+mod replay;
+mod report;
+mod script;
+mod snapshot;
+mod starts;
+mod stats;
+mod taint;
+mod terminal;
+mod timeline;
+mod timer;
+mod timing;
+mod trace;
+mod uart;
 mod ui;
+mod until;
+mod usage;
+mod warning;
+mod wave;
+mod writers;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
+use crossterm::cursor::Show;
 use crossterm::event::{
-    self, poll, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers,
+    self, poll, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+    EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, MouseButton,
+    MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
 };
 use crossterm::execute;
 use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
 };
 
 use ratatui::prelude::*;
 
+use std::cell::RefCell;
 use std::io;
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::app::App;
-use crate::ui::ui;
+use crate::cast::{Cast, Recorder};
+use crate::config::Config;
+use crate::fuzz::fuzz;
+use crate::grade::grade;
+use crate::keymap::Action;
+use crate::project::Project;
+use crate::replay::compare;
+use crate::stats::stats;
+use crate::timing::{Phase, Timing};
+use crate::ui::{animating, ui};
 
 fn main() -> Result<()> {
-    // Begin by setting up the terminal.
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // The fuzzer, the grader, the trace comparison, and the statistics summary run headless, and
+    // load whatever configuration they need for themselves.
+    let mut arguments = std::env::args().skip(1).peekable();
+    if arguments.next_if(|argument| argument == "fuzz").is_some() {
+        return fuzz(arguments);
+    }
+    if arguments
+        .next_if(|argument| argument == "compare")
+        .is_some()
+    {
+        return compare(arguments);
+    }
+    if arguments.next_if(|argument| argument == "grade").is_some() {
+        return grade(arguments);
+    }
+    if arguments.next_if(|argument| argument == "stats").is_some() {
+        return stats(arguments);
+    }
+
+    // A project is opened as a unit with `ilo open project.toml`, which may be followed by the
+    // usual arguments.
+    let mut project = match arguments.next_if(|argument| argument == "open") {
+        Some(_) => {
+            let Some(path) = arguments.next() else {
+                bail!("open must be followed by the path to a project.");
+            };
+            Some(Project::read(&path)?)
+        }
+        None => None,
+    };
 
-    // Obtain a rendering handle for the terminal.
-    let backend = CrosstermBackend::new(stdout);
+    // Construct the app before touching the terminal, so that any problem with the configuration
+    // or the project is reported normally.
+    let mut config = Config::load()?;
+    if let Some(project) = &mut project {
+        project.configure(&mut config)?;
+    }
+    config.apply_arguments(arguments)?;
+    let mut timing = config.self_profile.then(Timing::new);
+    let cast = match &config.record_cast {
+        Some(path) => Some(Rc::new(RefCell::new(Cast::create(
+            path,
+            crossterm::terminal::size()?,
+        )?))),
+        None => None,
+    };
+    let mut app = App::new(config)?;
+    if let Some(project) = project {
+        app.command_result = Ok(app.open(project)?);
+    }
+
+    // Set up the terminal, and obtain a rendering handle for it. Whatever is drawn to it is
+    // recorded in the cast as well, if there is one.
+    let mut enhanced = enter_terminal()?;
+    let backend = CrosstermBackend::new(Recorder::new(cast.clone()));
     let mut terminal = Terminal::new(backend)?;
 
-    // Construct and run app.
-    let mut app = App::new();
-    let result = run_app(&mut terminal, &mut app);
+    // Run the app. A panic is caught rather than left to unwind out of main, so that the terminal
+    // can be restored and a crash dump written. The panic message is kept by the hook, since it
+    // would be lost on the alternate screen if it were printed straight away.
+    let panic = Arc::new(Mutex::new(None));
+    let hook_panic = Arc::clone(&panic);
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut panic) = hook_panic.lock() {
+            *panic = Some(info.to_string());
+        }
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_app(
+            &mut terminal,
+            &mut app,
+            &mut enhanced,
+            &mut timing,
+            cast.as_deref(),
+        )
+    }));
+    let _ = panic::take_hook();
 
     // Return the terminal to its normal operating state.
+    // NOTE: We don't want to propagate a potential error from running the app before this, or we
+    // might not properly return the terminal to its normal operating state.
+    leave_terminal(enhanced)?;
+
+    // Likewise, the self-profile is reported even after a crash, since a crash after a slow run
+    // doesn't make the run any less slow.
+    if let Some(timing) = &timing {
+        eprint!("{}", timing.report());
+    }
+
+    // The cast is kept even after a crash, since that's when a recording of the session is most
+    // useful.
+    if let Some(cast) = &cast {
+        let mut cast = cast.borrow_mut();
+        if let Err(error) = cast.finish() {
+            eprintln!(
+                "The cast {} could not be recorded: {}",
+                cast.filename, error
+            );
+        }
+    }
+
+    // Statistics are saved even after a crash, since the session still happened.
+    if let Some(stats) = &app.stats {
+        if let Err(error) = stats.save() {
+            eprintln!("The usage statistics could not be saved: {}", error);
+        }
+    }
+
+    let cause = match result {
+        Ok(Ok(())) => return Ok(()),
+        Ok(Err(error)) => format!("{:?}", error),
+        Err(_) => panic
+            .lock()
+            .ok()
+            .and_then(|mut panic| panic.take())
+            .unwrap_or_else(|| "ilo panicked.".into()),
+    };
+    match crash::dump(&app, &cause) {
+        Ok(path) => eprintln!("ilo crashed. A crash dump was written to {}.", path),
+        Err(error) => eprintln!(
+            "ilo crashed, and the crash dump could not be written: {}",
+            error
+        ),
+    }
+    bail!("{}", cause)
+}
+
+// Put the terminal into the state in which the UI is drawn, returning whether or not the keyboard
+// enhancements were enabled, since they must be disabled again afterwards.
+fn enter_terminal() -> Result<bool> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    // Bracketed paste delivers pasted text all at once, rather than as a series of key presses in
+    // which any newline would execute whatever had been pasted so far.
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    // Where the terminal can tell them apart, held keys are reported as repeats rather than as
+    // fresh presses, so that repeats can be dropped while the simulation is busy.
+    let enhanced = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            )
+        )?;
+    }
+    Ok(enhanced)
+}
+
+// Return the terminal to its normal operating state.
+fn leave_terminal(enhanced: bool) -> Result<()> {
+    let mut stdout = io::stdout();
+    if enhanced {
+        execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        stdout,
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        Show
     )?;
-    terminal.show_cursor()?;
+    Ok(())
+}
 
-    // We don't want to propagate a potential error from running the error earlier, or we might not
-    // properly return the terminal to its normal operating state.
-    result
+// Drop to the shell from which ilo was started, just as Ctrl-Z would if the terminal weren't in raw
+// mode, and pick up where things were left once ilo is brought back to the foreground.
+#[cfg(unix)]
+fn suspend<B: Backend>(terminal: &mut Terminal<B>, enhanced: &mut bool) -> Result<()> {
+    leave_terminal(*enhanced)?;
+    // SAFETY: raise has no preconditions. With the default disposition, SIGTSTP stops the process
+    // here until the shell continues it.
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    *enhanced = enter_terminal()?;
+    // Whatever the shell left on the screen must not show through the next frame.
+    terminal.clear()?;
+    Ok(())
+}
+
+// How long it is until the next frame may be drawn. Between frames, a running simulation carries on
+// without being drawn, since nobody could read the panes any faster than the frame rate anyway.
+fn until_next_frame(app: &App, last_frame: Option<Instant>) -> Duration {
+    let interval = Duration::from_micros(1_000_000 / u64::from(app.frame_rate));
+    last_frame.map_or(Duration::ZERO, |last| {
+        interval.saturating_sub(last.elapsed())
+    })
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+// Account the time since an instant to a phase of the main loop, if the emulator is profiling
+// itself.
+fn record(timing: &mut Option<Timing>, phase: Phase, since: Instant) {
+    if let Some(timing) = timing {
+        timing.record(phase, since);
+    }
+}
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    enhanced: &mut bool,
+    timing: &mut Option<Timing>,
+    cast: Option<&RefCell<Cast>>,
+) -> Result<()> {
+    // The UI is only redrawn when something may have changed, and then no more often than the
+    // frame rate allows, so that an idle ilo doesn't keep a core busy.
+    let mut dirty = true;
+    let mut last_frame: Option<Instant> = None;
     loop {
-        terminal.draw(|f| ui(f, app))?;
-
-        if poll(Duration::from_nanos(1))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char(char) => {
-                            // HACK: This is a super quick and dirty way to exit the application.
-                            if char == 'c' && key.modifiers == KeyModifiers::CONTROL {
-                                return Ok(());
+        if (dirty || animating(app)) && until_next_frame(app, last_frame).is_zero() {
+            let start = Instant::now();
+            terminal.draw(|f| ui(f, app))?;
+            record(timing, Phase::Rendering, start);
+            if let Some(cast) = cast {
+                cast.borrow_mut().mark(app.steps);
+            }
+            last_frame = Some(Instant::now());
+            dirty = false;
+        }
+
+        // While work is in progress, events are only checked for between ticks. Otherwise, wait
+        // for the next frame if one is due, or else block until there's an event to handle.
+        let start = Instant::now();
+        let ready = if app.busy() {
+            poll(Duration::ZERO)?
+        } else if dirty || animating(app) {
+            poll(until_next_frame(app, last_frame).max(Duration::from_millis(1)))?
+        } else {
+            true
+        };
+        record(timing, Phase::Polling, start);
+
+        if ready {
+            // When nothing is going on, this is where the loop blocks until there is an event.
+            let start = Instant::now();
+            let event = event::read()?;
+            record(timing, Phase::Polling, start);
+            let start = Instant::now();
+            dirty = true;
+            // The next frame is laid out for the new size regardless, but shrinking a terminal can
+            // leave stale output wherever it reflowed, so the screen is cleared first.
+            if let Event::Resize(width, height) = event {
+                if let Some(cast) = cast {
+                    cast.borrow_mut().resize(width, height);
+                }
+                terminal.clear()?;
+            }
+            // Text pasted while the Registers pane is selected is ignored, rather than executing
+            // commands at a prompt which isn't even shown.
+            if let Event::Paste(text) = &event {
+                if app.register_cursor.is_none() {
+                    app.paste(text);
+                }
+            }
+            if let Event::Mouse(mouse) = event {
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                    app.click(mouse.column, mouse.row);
+                }
+            }
+            if let Event::Key(key) = event {
+                let action = app.keymap.lookup(&key).cloned();
+                // Held keys repeat, but won't pile up behind a long STEP or LOAD. Terminals which
+                // don't report repeats send them as presses instead, which are never dropped. The
+                // turbo step key is meant to be held, though, so its repeats are what keep it
+                // going, and letting go of it is what stops it.
+                let turbo = matches!(action, Some(Action::TurboStep));
+                let repeat = key.kind == KeyEventKind::Repeat && app.busy() && !turbo;
+                if key.kind == KeyEventKind::Release {
+                    if turbo {
+                        app.release_turbo();
+                    }
+                } else if !repeat {
+                    // Keys bound in the keymap work everywhere. Otherwise, while the Registers
+                    // pane is selected, keys edit the registers instead of being typed at the
+                    // prompt. Tab always toggles the selection.
+                    if let Some(action) = action {
+                        match action {
+                            Action::Quit => return Ok(()),
+                            #[cfg(unix)]
+                            Action::Suspend => suspend(terminal, enhanced)?,
+                            #[cfg(not(unix))]
+                            Action::Suspend => {
+                                app.command_result =
+                                    Err(anyhow::anyhow!("Suspending is only supported on Unix."))
                             }
-                            app.command_buffer.push(char);
+                            Action::ToggleFreeze => app.toggle_frozen(),
+                            Action::TurboStep => app.hold_turbo(),
+                            Action::Command(command) => app.execute_hotkey(&command),
                         }
-                        KeyCode::Backspace => {
-                            app.command_buffer.pop();
-                        }
-                        KeyCode::Enter => {
-                            app.execute_command();
+                    } else if app.register_cursor.is_some() && key.code != KeyCode::Tab {
+                        handle_register_key(app, key.code);
+                    } else {
+                        match key.code {
+                            // NOTE: No command begins with an n, so n and N typed at an empty prompt
+                            // can safely be taken to navigate between search matches, as in less.
+                            KeyCode::Char(char @ ('n' | 'N'))
+                                if app.command_buffer.is_empty() && app.search.is_some() =>
+                            {
+                                app.command_result = app.search_step(char == 'n');
+                            }
+                            KeyCode::Char(char) => {
+                                app.insert_char(char);
+                            }
+                            KeyCode::Backspace => {
+                                app.delete_before_cursor();
+                            }
+                            KeyCode::Delete => {
+                                app.delete_at_cursor();
+                            }
+                            KeyCode::Left => {
+                                app.move_command_cursor(false);
+                            }
+                            KeyCode::Right => {
+                                app.move_command_cursor(true);
+                            }
+                            KeyCode::Home => {
+                                app.command_cursor = 0;
+                            }
+                            KeyCode::End => {
+                                app.command_cursor = app.command_buffer.len();
+                            }
+                            KeyCode::Enter => {
+                                app.execute_command();
+                            }
+                            KeyCode::Esc => {
+                                app.cancel();
+                            }
+                            KeyCode::Tab => {
+                                app.toggle_register_pane();
+                            }
+                            _ => {}
                         }
-                        _ => {}
                     }
                 }
             }
+            record(timing, Phase::Handling, start);
+        }
+
+        // A tick also follows every event, so that it sees any change which a command made.
+        if ready || app.busy() {
+            let start = Instant::now();
+            app.tick();
+            record(timing, Phase::Stepping, start);
+            dirty = true;
         }
+    }
+}
 
-        if app.running {
-            app.step();
+// Handle a key pressed while the Registers pane is selected. The arrow keys move between registers,
+// and Enter begins editing one, after which the new value is typed and Enter pressed again to
+// write it. Esc abandons the edit, or deselects the pane if there is no edit, and y copies every
+// register to the clipboard.
+fn handle_register_key(app: &mut App, code: KeyCode) {
+    match (&mut app.register_edit, code) {
+        (Some(edit), KeyCode::Char(char)) => edit.push(char),
+        (Some(edit), KeyCode::Backspace) => {
+            edit.pop();
+        }
+        (Some(_), KeyCode::Enter) => app.commit_register_edit(),
+        (Some(_), KeyCode::Esc) => app.register_edit = None,
+        (None, KeyCode::Up) => app.move_register_cursor(-1),
+        (None, KeyCode::Down) => app.move_register_cursor(1),
+        (None, KeyCode::Enter) => app.begin_register_edit(),
+        (None, KeyCode::Esc) => app.toggle_register_pane(),
+        (None, KeyCode::Char('y')) => {
+            app.command_result = app.execute_command_with_result("COPY REGISTERS")
         }
+        _ => {}
     }
 }
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::cpu::Cpu;
+
+// A measurement of the work done to get from one address to another, for benchmarking a routine
+// without doing arithmetic on the performance counters by hand. The measurement is armed when the
+// program counter arrives at the start, and each traversal is completed when it next arrives at
+// the end. The start and the end may be the same address, in which case each trip around a loop
+// is a traversal.
+pub struct Measure {
+    pub start: u16,
+    pub end: u16,
+    // The instructions retired and the cycles counted when the start was last arrived at, if the
+    // end hasn't been arrived at since.
+    armed: Option<(u32, u32)>,
+    pub instructions: Summary,
+    pub cycles: Summary,
+}
+
+// The smallest, largest, and total amounts measured over a number of traversals.
+#[derive(Clone, Copy, Default)]
+pub struct Summary {
+    pub count: u64,
+    pub min: u32,
+    pub max: u32,
+    pub total: u64,
+}
+
+impl Summary {
+    fn add(&mut self, amount: u32) {
+        if self.count == 0 {
+            self.min = amount;
+            self.max = amount;
+        } else {
+            self.min = self.min.min(amount);
+            self.max = self.max.max(amount);
+        }
+        self.count += 1;
+        self.total += u64::from(amount);
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {}, avg {:.1}, max {}",
+            self.min,
+            self.total as f64 / self.count as f64,
+            self.max
+        )
+    }
+}
+
+impl Measure {
+    // Begin measuring between two addresses, arming the measurement straight away if the program
+    // counter is already at the start.
+    pub fn new(start: u16, end: u16, cpu: &Cpu) -> Self {
+        let mut measure = Self {
+            start,
+            end,
+            armed: None,
+            instructions: Summary::default(),
+            cycles: Summary::default(),
+        };
+        if cpu.program_counter == start {
+            measure.arm(cpu);
+        }
+        measure
+    }
+
+    pub fn armed(&self) -> bool {
+        self.armed.is_some()
+    }
+
+    // Abandon the traversal in progress, as when the counters are zeroed out from under it.
+    pub fn disarm(&mut self) {
+        self.armed = None;
+    }
+
+    fn arm(&mut self, cpu: &Cpu) {
+        self.armed = Some((cpu.counters.instructions, cpu.counters.cycles));
+    }
+
+    // Account for the program counter having arrived where it now points, describing the traversal
+    // if one has just been completed.
+    pub fn arrive(&mut self, cpu: &Cpu) -> Option<String> {
+        let program_counter = cpu.program_counter;
+        let mut line = None;
+        if program_counter == self.end {
+            if let Some((instructions, cycles)) = self.armed.take() {
+                // NOTE: The counters can be zeroed by the program as well as by the debugger, so a
+                // traversal over which they went backwards can't be measured.
+                line = Some(
+                    match (
+                        cpu.counters.instructions.checked_sub(instructions),
+                        cpu.counters.cycles.checked_sub(cycles),
+                    ) {
+                        (Some(instructions), Some(cycles)) => {
+                            self.instructions.add(instructions);
+                            self.cycles.add(cycles);
+                            format!(
+                                "Measured {:#06x} to {:#06x}: {} instructions, {} cycles. {}",
+                                self.start,
+                                self.end,
+                                instructions,
+                                cycles,
+                                self.summary()
+                            )
+                        }
+                        _ => format!(
+                            "Measured {:#06x} to {:#06x}, but the performance counters were reset along the way, so the traversal was discarded.",
+                            self.start, self.end
+                        ),
+                    },
+                );
+            }
+        }
+        if program_counter == self.start {
+            self.arm(cpu);
+        }
+        line
+    }
+
+    // Summarize every traversal measured so far.
+    pub fn summary(&self) -> String {
+        match self.instructions.count {
+            0 => "No traversals have been measured yet.".into(),
+            count => format!(
+                "Over {} traversal(s), instructions: {}; cycles: {}.",
+                count, self.instructions, self.cycles
+            ),
+        }
+    }
+}
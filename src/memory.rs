@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+
+use serde::Deserialize;
+
+// The value read from an address which isn't connected to anything. With nothing driving the bus,
+// the pull-up resistors on a typical board leave every line high.
+pub const OPEN_BUS: u16 = 0xffff;
+
+// The address of the boot ROM's control register. While the boot ROM is mapped, reading this
+// address returns 0x0001, and writing any value to it unmaps the boot ROM. Once the boot ROM is
+// unmapped, the register disappears along with it, and the address refers to RAM as usual.
+pub const ROM_CONTROL: u16 = 0xfff8;
+
+// How addresses beyond the end of a RAM smaller than the address space are decoded.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Decoding {
+    // The addresses are not connected to anything, so reads see the open bus and writes are lost.
+    #[default]
+    Unmapped,
+    // Only the low address lines are decoded, so RAM repeats throughout the address space.
+    Mirrored,
+}
+
+// What happens when the CPU writes to an address shadowed by the boot ROM.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RomWrites {
+    // The write lands in the RAM underneath the boot ROM, where it becomes visible once the boot
+    // ROM is unmapped. This allows boot code to copy itself into RAM in place.
+    #[default]
+    Through,
+    // The write is refused, and the simulation is halted.
+    Trap,
+}
+
+// A boot ROM, which shadows the lowest addresses of RAM until it is unmapped.
+#[derive(Clone)]
+pub struct Rom {
+    pub words: Vec<u16>,
+    pub mapped: bool,
+    pub writes: RomWrites,
+}
+
+#[derive(Clone)]
+pub struct MemoryMap {
+    // The number of words of RAM which are actually fitted. This is always a power of two, as it
+    // is on any real board built from ordinary memory chips.
+    pub size: usize,
+    pub decoding: Decoding,
+    pub rom: Option<Rom>,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self {
+            size: 0x10000,
+            decoding: Decoding::Unmapped,
+            rom: None,
+        }
+    }
+}
+
+impl MemoryMap {
+    pub fn new(size: usize, decoding: Decoding) -> Result<Self> {
+        if !size.is_power_of_two() || !(0x10..=0x10000).contains(&size) {
+            return Err(anyhow!(
+                "{:#x} is not a valid RAM size. The RAM size must be a power of two between 0x10 and 0x10000 words.",
+                size
+            ));
+        }
+        Ok(Self {
+            size,
+            decoding,
+            rom: None,
+        })
+    }
+
+    // Determine which word of RAM, if any, an address refers to.
+    pub fn decode(&self, address: u16) -> Option<usize> {
+        let address = usize::from(address);
+        match self.decoding {
+            _ if address < self.size => Some(address),
+            Decoding::Unmapped => None,
+            Decoding::Mirrored => Some(address & (self.size - 1)),
+        }
+    }
+
+    // Determine the boot ROM word, if any, which shadows an address.
+    pub fn shadowing_rom(&self, address: u16) -> Option<u16> {
+        let rom = self.rom.as_ref().filter(|rom| rom.mapped)?;
+        rom.words.get(usize::from(address)).copied()
+    }
+
+    // Read from an address, given the backing store of the RAM. The boot ROM takes priority over
+    // RAM while it is mapped.
+    pub fn read(&self, ram: &[u16], address: u16) -> u16 {
+        let mapped = self.rom.as_ref().is_some_and(|rom| rom.mapped);
+        if let Some(word) = self.shadowing_rom(address) {
+            word
+        } else if mapped && address == ROM_CONTROL {
+            0x0001
+        } else {
+            self.decode(address).map_or(OPEN_BUS, |index| ram[index])
+        }
+    }
+
+    // Write to an address, given the backing store of the RAM, returning whether or not the write
+    // was accepted. Writes to unmapped addresses are silently discarded, but writes to the boot
+    // ROM may be refused, depending on how it is configured.
+    pub fn write(&mut self, ram: &mut [u16], address: u16, value: u16) -> bool {
+        if let Some(rom) = self.rom.as_mut().filter(|rom| rom.mapped) {
+            if address == ROM_CONTROL {
+                rom.mapped = false;
+                return true;
+            }
+            if usize::from(address) < rom.words.len() && rom.writes == RomWrites::Trap {
+                return false;
+            }
+        }
+        if let Some(index) = self.decode(address) {
+            ram[index] = value;
+        }
+        true
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::command::{self, COMMANDS};
+use crate::config::ProfileConfig;
+
+// The only commands which the instructor profile allows. These are LOAD, RUN, STEP, and RESET,
+// along with their variants, and every command which leaves the state of the machine alone, so
+// that a graded exercise can only be passed by fixing the program rather than patching the memory
+// or registers it runs on. Being an allowlist, any command added later is denied until it's been
+// judged safe to add here. Commands which only write memory in some of their forms, such as
+// COPY RAM ... TO, check FILL for those, which isn't allowed.
+const INSTRUCTOR_ALLOWED: &[&str] = &[
+    "RUN",
+    "HALT",
+    "RESET",
+    "CANCEL",
+    "STEP",
+    "STEPWHILE",
+    "STEPUNTIL",
+    "RUNUNTIL",
+    "STEPIRQ",
+    "CALL",
+    "LOAD",
+    "RELOAD",
+    "RECENT",
+    "BUILD",
+    "VERIFY",
+    "DUMP",
+    "DIFF",
+    "HASH",
+    "EXPORT",
+    "WAVE",
+    "COPY",
+    "REPORT",
+    "RAM",
+    "FREEZE",
+    "THAW",
+    "MINIMAP",
+    "REFRESH",
+    "MARK",
+    "UNMARK",
+    "GOTO",
+    "MARKS",
+    "DIAGNOSTICS",
+    "ACCESSES",
+    "STACK",
+    "HEAP",
+    "TERMINAL",
+    "PRINT",
+    "TYPE",
+    "WATCHEXPR",
+    "ASSERT",
+    "SNAPSHOT",
+    "COMPARE",
+    "WARNINGS",
+    "OVERFLOW",
+    "TAINT",
+    "TAINT?",
+    "WHOWROTE",
+    "COST",
+    "MEASURE",
+    "FOCUS",
+    "PRESENT",
+    "EXPLAIN",
+    "LIVENESS",
+    "ANALYZE",
+    "DISASM",
+    "BREAK",
+    "TBREAK",
+    "ENABLE",
+    "DISABLE",
+    "TRACEPOINT",
+    "DELETE",
+    "BREAKS",
+    "HELP",
+];
+
+// The commands which may be used during a session, as restricted by a profile.
+pub struct Profile {
+    pub name: String,
+    denied: BTreeSet<&'static str>,
+}
+
+impl Profile {
+    // Look up a profile by name, among those defined in the configuration and then among the
+    // built-in ones. A profile in the configuration replaces a built-in profile of the same name.
+    pub fn new(name: &str, profiles: &HashMap<String, ProfileConfig>) -> Result<Self> {
+        let denied = match profiles.get(name) {
+            Some(profile) => {
+                let allowed = match &profile.allow {
+                    Some(allow) => Some(commands(allow)?),
+                    None => None,
+                };
+                let denied = commands(&profile.deny)?;
+                COMMANDS
+                    .iter()
+                    .map(|spec| spec.name)
+                    .filter(|name| {
+                        denied.contains(name)
+                            || allowed.as_ref().is_some_and(|allowed| !allowed.contains(name))
+                    })
+                    .collect()
+            }
+            None if name == "instructor" => COMMANDS
+                .iter()
+                .map(|spec| spec.name)
+                .filter(|name| !INSTRUCTOR_ALLOWED.contains(name))
+                .collect(),
+            None => bail!(
+                "There is no profile named \"{}\". Define it as [profiles.{}] in ilo.toml, or use the built-in instructor profile.",
+                name,
+                name
+            ),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            denied,
+        })
+    }
+
+    // Whether or not a command, given by its name in capitals, may be used.
+    pub fn allows(&self, name: &str) -> bool {
+        !self.denied.contains(name)
+    }
+
+    // Check that a command may be used, explaining why not if it can't.
+    pub fn check(&self, name: &str) -> Result<()> {
+        if self.allows(name) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} is disabled by the {} profile.",
+                name,
+                self.name
+            ))
+        }
+    }
+}
+
+// Look up the names of commands given in a profile, as the names by which they're known.
+fn commands(names: &[String]) -> Result<BTreeSet<&'static str>> {
+    names
+        .iter()
+        .map(|name| {
+            command::find(name).map(|spec| spec.name).ok_or_else(|| {
+                anyhow!(
+                    "\"{}\" is not a command, so it can't be in a profile.",
+                    name
+                )
+            })
+        })
+        .collect()
+}
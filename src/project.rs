@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+
+use serde::Deserialize;
+
+use std::path::Path;
+
+use crate::config::{read_toml, AbiConfig, Config};
+use crate::load::ByteOrder;
+
+// A project, which ties together the files that go with a program so that they can be opened as a
+// unit with `ilo open project.toml`, rather than each being set up by hand. For example, a program
+// built for a board with its own machine description might be written as follows.
+//
+//     image = "build/game.bin"
+//     load = 0x0100
+//     machine = "boards/handheld.toml"
+//     symbols = "build/game.sym"
+//     session = "game.ilo-session"
+//     byte_order = "little"
+//     build.cmd = "make build/game.bin"
+//
+//     [abi]
+//     link = 29
+//     stack = 28
+//
+// Only the image is required. Paths are relative to the directory holding the project file, so
+// that a project can be opened from anywhere.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    // The path from which the project was read.
+    #[serde(skip)]
+    pub path: String,
+    pub image: String,
+    // The address at which the image is loaded.
+    #[serde(default)]
+    pub load: u16,
+    // The machine description, in place of the one in the working directory.
+    pub machine: Option<String>,
+    // A file of symbols, which are bookmarked once the image is loaded. Each line holds a name
+    // followed by an address, just as they are given to MARK, and lines beginning with a semicolon
+    // are comments.
+    pub symbols: Option<String>,
+    // The session file, in place of the one kept beside the image.
+    pub session: Option<String>,
+    // The calling convention followed by the guest, in place of the one in the configuration.
+    pub abi: Option<AbiConfig>,
+    // The order of the bytes of each word in the image, in place of the one in the configuration.
+    pub byte_order: Option<ByteOrder>,
+    // How the image is built, for BUILD.
+    pub build: Option<BuildConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    // The command which builds the image, which is run by the shell in the directory holding the
+    // project file.
+    pub cmd: String,
+}
+
+impl Project {
+    pub fn read(path: &str) -> Result<Self> {
+        let mut project: Self =
+            read_toml(path)?.ok_or_else(|| anyhow!("The project {} does not exist.", path))?;
+
+        project.path = path.to_string();
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        let files = [
+            &mut project.machine,
+            &mut project.symbols,
+            &mut project.session,
+        ];
+        for file in files.into_iter().flatten().chain([&mut project.image]) {
+            *file = directory.join(&file).to_string_lossy().into_owned();
+        }
+        Ok(project)
+    }
+
+    // The directory holding the project file, in which its build is run.
+    pub fn directory(&self) -> &Path {
+        match Path::new(&self.path).parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory,
+            _ => Path::new("."),
+        }
+    }
+
+    // Apply the parts of the project which describe the machine to the configuration, before the
+    // app is constructed from it. Anything given on the command line is applied afterwards, and so
+    // takes precedence.
+    pub fn configure(&mut self, config: &mut Config) -> Result<()> {
+        if let Some(machine) = &self.machine {
+            config.machine = read_toml(machine)?
+                .ok_or_else(|| anyhow!("The machine description {} does not exist.", machine))?;
+        }
+        if let Some(abi) = self.abi.take() {
+            config.abi = abi;
+        }
+        if let Some(order) = self.byte_order {
+            config.byte_order = order;
+        }
+        Ok(())
+    }
+}
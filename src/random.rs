@@ -0,0 +1,46 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A small xorshift pseudorandom number generator. Nothing here needs random numbers of any great
+// quality, so it isn't worth pulling in a dependency for them.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    // Construct a generator with a given seed, so that a sequence can be reproduced.
+    pub fn new(seed: u64) -> Self {
+        // NOTE: Xorshift gets stuck on a state of zero, so a seed of zero is quietly replaced.
+        Self {
+            state: if seed == 0 { 0x2545f4914f6cdd1d } else { seed },
+        }
+    }
+
+    // Construct a generator seeded from the current time.
+    pub fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::new(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    pub fn next_u16(&mut self) -> u16 {
+        (self.next_u64() >> 48) as u16
+    }
+
+    // Flip a coin.
+    pub fn chance(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    // Produce a number in the range 0..bound.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+
+// The path to which the images and projects most recently opened are written, beside ilo.toml, so
+// that they can be opened again with RELOAD and RECENT, even in a later session.
+const RECENT_PATH: &str = "ilo-recent.toml";
+
+// The most entries which are kept.
+const RECENT_LENGTH: usize = 0x10;
+
+// Something which was recently opened, which is opened again in just the same way.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Recent {
+    // An image, along with the address at which it was loaded.
+    Image { path: String, load: u16 },
+    // A project, as opened with `ilo open`.
+    Project { path: String },
+}
+
+impl Recent {
+    pub fn path(&self) -> &str {
+        match self {
+            Recent::Image { path, .. } | Recent::Project { path } => path,
+        }
+    }
+}
+
+impl fmt::Display for Recent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recent::Image { path, load } => write!(f, "{} at {:#06x}", path, load),
+            Recent::Project { path } => write!(f, "project {}", path),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct RecentFile {
+    // The entries from the most recent to the least recent.
+    #[serde(rename = "entry")]
+    entries: Vec<Recent>,
+}
+
+// Read the recent files, from the most recent to the least recent, which are empty if none have
+// been written yet.
+pub fn load() -> Result<Vec<Recent>> {
+    match fs::read_to_string(RECENT_PATH) {
+        Ok(contents) => toml::from_str::<RecentFile>(&contents)
+            .map(|file| file.entries)
+            .map_err(|e| anyhow!("Invalid {}: {}", RECENT_PATH, e)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// Move an entry to the front of the recent files, replacing any other entry for the same file. The
+// file is read afresh every time, so that several sessions running side by side don't lose each
+// other's entries.
+pub fn remember(recent: Recent) -> Result<()> {
+    let mut entries = load()?;
+    entries.retain(|entry| entry.path() != recent.path());
+    entries.insert(0, recent);
+    entries.truncate(RECENT_LENGTH);
+    fs::write(RECENT_PATH, toml::to_string(&RecentFile { entries })?)?;
+    Ok(())
+}
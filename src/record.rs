@@ -0,0 +1,144 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::expr::Packing;
+
+// A record type, defined with TYPE, which lays out named fields in consecutive words of memory so
+// that a structure can be shown field by field rather than as raw words.
+pub struct Record {
+    pub name: String,
+    pub fields: Vec<(String, Field)>,
+}
+
+// The type of a field of a record.
+#[derive(Clone)]
+pub enum Field {
+    // An unsigned word.
+    U16,
+    // A signed word, in two's complement.
+    I16,
+    // A character, held in the low byte of a word.
+    Char,
+    // The address of a string, held in the given way.
+    Str(Packing),
+    // Another record, which is laid out within this one.
+    Record(Arc<Record>),
+}
+
+impl Record {
+    // Parse the definition of a record type, as in `point { x: i16, y: i16 }`, given the record
+    // types which have already been defined, since fields may be records themselves, and the way
+    // in which str fields hold their strings.
+    pub fn parse(
+        definition: &str,
+        types: &BTreeMap<String, Arc<Record>>,
+        packing: Packing,
+    ) -> Result<Self> {
+        let (name, body) = definition
+            .split_once('{')
+            .ok_or_else(|| anyhow!("A type is defined as name {{ field: type, ... }}."))?;
+        let name = name.trim();
+        let Some(body) = body.trim().strip_suffix('}') else {
+            bail!("The definition of {} is missing its closing \"}}\".", name);
+        };
+        if !is_name(name) || ["u16", "i16", "char", "str", "wstr", "pstr"].contains(&name) {
+            bail!("\"{}\" is not a valid name for a type.", name);
+        }
+
+        let mut fields: Vec<(String, Field)> = Vec::new();
+        for field in body
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+        {
+            let Some((field_name, kind)) = field.split_once(':') else {
+                bail!("\"{}\" is not a field such as x: i16.", field);
+            };
+            let (field_name, kind) = (field_name.trim(), kind.trim());
+            if !is_name(field_name) {
+                bail!("\"{}\" is not a valid name for a field.", field_name);
+            }
+            if fields.iter().any(|(name, _)| name == field_name) {
+                bail!("{} has more than one field named {}.", name, field_name);
+            }
+            fields.push((field_name.to_string(), Field::parse(kind, types, packing)?));
+        }
+        if fields.is_empty() {
+            bail!("{} must have at least one field.", name);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    // The number of words which the record occupies.
+    pub fn size(&self) -> u16 {
+        self.fields
+            .iter()
+            .map(|(_, field)| field.size())
+            .fold(0, u16::wrapping_add)
+    }
+
+    // Write the record type back out as the TYPE command which defines it.
+    pub fn definition(&self) -> String {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, field)| format!("{}: {}", name, field.name()))
+            .collect::<Vec<_>>();
+        format!("{} {{ {} }}", self.name, fields.join(", "))
+    }
+}
+
+impl Field {
+    pub fn parse(
+        kind: &str,
+        types: &BTreeMap<String, Arc<Record>>,
+        packing: Packing,
+    ) -> Result<Self> {
+        Ok(match kind {
+            "u16" => Field::U16,
+            "i16" => Field::I16,
+            "char" => Field::Char,
+            "str" => Field::Str(packing),
+            "wstr" => Field::Str(Packing::Word),
+            "pstr" => Field::Str(Packing::Packed),
+            _ => Field::Record(types.get(kind).cloned().ok_or_else(|| {
+                anyhow!(
+                    "\"{}\" is not a type. Types may be u16, i16, char, str, wstr, pstr, or one defined with TYPE.",
+                    kind
+                )
+            })?),
+        })
+    }
+
+    // The number of words which a value of the type occupies.
+    pub fn size(&self) -> u16 {
+        match self {
+            Field::Record(record) => record.size(),
+            _ => 1,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Field::U16 => "u16",
+            Field::I16 => "i16",
+            Field::Char => "char",
+            // NOTE: The way in which the string is held is always written out, in case the
+            // configured way has changed by the time that the definition is read back.
+            Field::Str(Packing::Word) => "wstr",
+            Field::Str(Packing::Packed) => "pstr",
+            Field::Record(record) => &record.name,
+        }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+
+use std::ops::Range;
+
+use crate::cpu::Cpu;
+
+// How many words are compared at a time when looking for differences. Whole chunks are compared
+// as slices, which the compiler turns into a memcmp, and only the chunks which differ are searched
+// word by word, so that comparing large regions which are mostly the same is quick.
+const CHUNK: usize = 0x40;
+
+// Find the words of RAM making up a region, which must lie entirely within the RAM which is
+// fitted. These operations work on RAM directly, as LOAD does, so the boot ROM doesn't get in the
+// way of them and no mirror of RAM is written twice.
+pub fn region(cpu: &Cpu, address: u16, length: u16) -> Result<Range<usize>> {
+    let start = usize::from(address);
+    let end = start + usize::from(length);
+    if end > cpu.memory.size {
+        bail!(
+            "{:#06x} words starting at {:#06x} extend past the end of RAM at {:#06x}.",
+            length,
+            address,
+            cpu.memory.size
+        );
+    }
+    Ok(start..end)
+}
+
+// Set every word of a region to a value.
+pub fn fill(cpu: &mut Cpu, address: u16, length: u16, value: u16) -> Result<()> {
+    let region = region(cpu, address, length)?;
+    cpu.ram[region].fill(value);
+    Ok(())
+}
+
+// Copy a region to another address, as if through a temporary buffer, so the regions may overlap.
+pub fn copy(cpu: &mut Cpu, source: u16, destination: u16, length: u16) -> Result<()> {
+    let source = region(cpu, source, length)?;
+    region(cpu, destination, length)?;
+    cpu.ram.copy_within(source, usize::from(destination));
+    Ok(())
+}
+
+// Find the offsets at which two regions of the same length differ.
+pub fn differences(cpu: &Cpu, first: u16, second: u16, length: u16) -> Result<Vec<usize>> {
+    let first = &cpu.ram[region(cpu, first, length)?];
+    let second = &cpu.ram[region(cpu, second, length)?];
+    if first == second {
+        return Ok(Vec::new());
+    }
+    let mut offsets = Vec::new();
+    for (chunk, (a, b)) in first.chunks(CHUNK).zip(second.chunks(CHUNK)).enumerate() {
+        if a == b {
+            continue;
+        }
+        let differing = a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b);
+        offsets.extend(differing.map(|(offset, _)| chunk * CHUNK + offset));
+    }
+    Ok(offsets)
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::VecDeque;
+use std::fs;
+
+use crate::app::{build_cpu, write_image};
+use crate::assemble::parse_literal;
+use crate::config::Config;
+use crate::disassemble::disassemble;
+
+// The number of matching instructions shown before a divergence, for context.
+const CONTEXT: usize = 8;
+
+// A column of a trace, which records some part of the state of the machine.
+#[derive(Clone, Copy)]
+enum Column {
+    // The address of the instruction which was retired.
+    ProgramCounter,
+    // The instruction word which was retired.
+    Instruction,
+    // The value of a register after the instruction was retired.
+    Register(usize),
+    // Anything else, such as a cycle count, which has no counterpart in the emulator.
+    Ignored,
+}
+
+// Replay a program and compare every instruction which it retires against a trace captured from
+// another implementation of the ISA, such as one on an FPGA, reporting the first point at which
+// they diverge. This is what `ilo compare --trace trace.csv program.bin` does. The program is
+// loaded at --load, which defaults to 0x0000, and any other arguments describe the machine, just
+// as they do when running ilo normally.
+//
+// The trace is a CSV file with a header naming its columns, and a row for each retired instruction.
+// The pc column is required, and gives the address of the instruction. An instruction column gives
+// the instruction word, and columns named r01 through r31 give registers after the instruction was
+// retired, and only these registers are compared. Other columns are ignored. Values may be written
+// in hexadecimal or binary with 0x or 0b, or otherwise in decimal.
+pub fn compare(arguments: impl Iterator<Item = String>) -> Result<()> {
+    let mut trace_path = None;
+    let mut load = 0x0000;
+    let mut program_path = None;
+    // The arguments which describe the machine are left to the configuration, which knows which
+    // of them take values.
+    let mut config = Config::load()?;
+    config.apply_arguments_with(arguments, |argument, arguments| {
+        match argument.as_str() {
+            "--trace" => {
+                trace_path =
+                    Some(arguments.next().ok_or_else(|| {
+                        anyhow!("--trace must be followed by the path to a trace.")
+                    })?)
+            }
+            "--load" => {
+                let address = arguments
+                    .next()
+                    .ok_or_else(|| anyhow!("--load must be followed by an address."))?;
+                load = parse_literal(&address)
+                    .map_err(|_| anyhow!("\"{}\" is not a valid address.", address))?;
+            }
+            _ if program_path.is_none() && !argument.starts_with("--") => {
+                program_path = Some(argument)
+            }
+            _ => bail!("Unrecognized argument \"{}\" to compare.", argument),
+        }
+        Ok(())
+    })?;
+    let (Some(trace_path), Some(program_path)) = (trace_path, program_path) else {
+        bail!("compare must be given --trace and the path to a trace, and the path to a program.");
+    };
+
+    let mut cpu = build_cpu(&config)?;
+    write_image(
+        &mut cpu,
+        load,
+        &program_path,
+        &fs::read(&program_path)?,
+        config.byte_order,
+    )?;
+
+    let trace = fs::read_to_string(&trace_path)?;
+    let mut lines = trace
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    let Some((_, header)) = lines.next() else {
+        bail!("{} is empty.", trace_path);
+    };
+    let columns = header
+        .split(',')
+        .map(|name| column(name.trim()))
+        .collect::<Result<Vec<_>>>()?;
+    if !columns
+        .iter()
+        .any(|column| matches!(column, Column::ProgramCounter))
+    {
+        bail!("{} has no pc column.", trace_path);
+    }
+
+    // The instructions which matched most recently, as they were disassembled.
+    let mut recent = VecDeque::new();
+    let mut retired = 0;
+    for (number, line) in lines {
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        if fields.len() != columns.len() {
+            bail!(
+                "Line {} of {} has {} values, but the header names {} columns.",
+                number,
+                trace_path,
+                fields.len(),
+                columns.len()
+            );
+        }
+        // Ignored columns are never parsed, since they might not even be numbers.
+        let values = columns
+            .iter()
+            .zip(fields)
+            .filter(|(column, _)| !matches!(column, Column::Ignored))
+            .map(|(&column, field)| match parse_literal(field) {
+                Ok(value) => Ok((column, value)),
+                Err(_) => Err(anyhow!(
+                    "Line {} of {} has \"{}\", which is not a valid value.",
+                    number,
+                    trace_path,
+                    field
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let address = cpu.program_counter;
+        let instruction = cpu.peek(address);
+        let immediate = cpu.peek(address.wrapping_add(1));
+        let mut differences = Vec::new();
+        for &(column, value) in &values {
+            match column {
+                Column::ProgramCounter if address != value => differences.push(format!(
+                    "pc is {:#06x} in the emulator, but {:#06x} in the trace",
+                    address, value
+                )),
+                Column::Instruction if instruction != value => differences.push(format!(
+                    "the instruction is {:#06x} in the emulator, but {:#06x} in the trace",
+                    instruction, value
+                )),
+                _ => {}
+            }
+        }
+
+        // Registers are compared after the instruction is retired, and only if the emulator agreed
+        // on which instruction that was.
+        if differences.is_empty() {
+            cpu.step();
+            for &(column, value) in &values {
+                if let Column::Register(register) = column {
+                    if cpu.registers[register] != value {
+                        differences.push(format!(
+                            "r{:02} is {:#06x} in the emulator, but {:#06x} in the trace",
+                            register, cpu.registers[register], value
+                        ));
+                    }
+                }
+            }
+        }
+
+        let disassembly = format!(
+            "{:#06x}: {}",
+            address,
+            disassemble(instruction, immediate).trim_end()
+        );
+        if !differences.is_empty() {
+            println!(
+                "The emulator diverged from the trace after {} matching instructions.",
+                retired
+            );
+            if !recent.is_empty() {
+                println!("The last instructions which matched were:");
+                for line in &recent {
+                    println!("  {}", line);
+                }
+            }
+            println!(
+                "At line {} of {}, with the emulator at {}:",
+                number, trace_path, disassembly
+            );
+            for difference in &differences {
+                println!("  {}", difference);
+            }
+            bail!("The emulator diverged from {}.", trace_path);
+        }
+
+        recent.push_back(disassembly);
+        if recent.len() > CONTEXT {
+            recent.pop_front();
+        }
+        retired += 1;
+    }
+
+    println!(
+        "All {} instructions in {} match the emulator.",
+        retired, trace_path
+    );
+    Ok(())
+}
+
+// Identify a column of a trace by its name in the header.
+fn column(name: &str) -> Result<Column> {
+    let lowercase = name.to_lowercase();
+    if lowercase == "pc" {
+        return Ok(Column::ProgramCounter);
+    }
+    if lowercase == "instruction" {
+        return Ok(Column::Instruction);
+    }
+    match lowercase
+        .strip_prefix('r')
+        .and_then(|number| number.parse::<usize>().ok())
+    {
+        Some(register @ 1..=0x1f) => Ok(Column::Register(register)),
+        Some(_) => bail!("The column {} is not a register from r01 to r31.", name),
+        None => Ok(Column::Ignored),
+    }
+}
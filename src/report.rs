@@ -0,0 +1,77 @@
+use crate::app::App;
+
+// The number of lines of the instruction history and of the console which are included in a
+// report. These are the most recent, since they're the ones most likely to matter.
+const RECENT_LINES: usize = 0x20;
+
+// Summarize the state of a session as a Markdown document, suitable for attaching to an issue
+// report or a lab submission.
+pub fn report(app: &App) -> String {
+    let mut report = String::from("# ilo session report\n\n");
+
+    report.push_str("## Images\n\n");
+    match (&app.image, app.image_checksum) {
+        (Some(image), Some(checksum)) => {
+            report.push_str(&format!("- `{}` (CRC-32 {:#010x})\n", image, checksum))
+        }
+        (Some(image), None) => report.push_str(&format!("- `{}`\n", image)),
+        _ => report.push_str("- No image has been loaded.\n"),
+    }
+    if let Some(rom) = &app.cpu.memory.rom {
+        report.push_str(&format!(
+            "- Boot ROM of {:#06x} words, {}\n",
+            rom.words.len(),
+            if rom.mapped { "mapped" } else { "unmapped" }
+        ));
+    }
+
+    report.push_str("\n## Registers\n\n```\n");
+    report.push_str(&app.registers_text());
+    report.push_str("```\n");
+
+    report.push_str("\n## Recent disassembly\n\n");
+    report.push_str("The next instruction comes first, followed by those executed before it, most recent first.\n\n```\n");
+    for line in app.history_text().lines().take(RECENT_LINES) {
+        report.push_str(line);
+        report.push('\n');
+    }
+    report.push_str("```\n");
+
+    report.push_str("\n## Breakpoints\n\n");
+    if app.breakpoints.is_empty() && app.tracepoints.is_empty() {
+        report.push_str("No breakpoints or tracepoints are set.\n");
+    }
+    for (address, breakpoint) in &app.breakpoints {
+        report.push_str(&format!("- `{:#06x}` {}\n", address, breakpoint));
+    }
+    for (address, format) in &app.tracepoints {
+        report.push_str(&format!("- `{:#06x}` tracepoint \"{}\"\n", address, format));
+    }
+
+    let counters = &app.cpu.counters;
+    report.push_str("\n## Statistics\n\n");
+    report.push_str(&format!("- Cycles: {}\n", counters.cycles));
+    report.push_str(&format!(
+        "- Instructions retired: {}\n",
+        counters.instructions
+    ));
+    report.push_str(&format!("- Taken branches: {}\n", counters.branches));
+    if let Some(fault) = &app.cpu.fault {
+        report.push_str(&format!("- Faulted: {}\n", fault));
+    }
+
+    report.push_str("\n## Recent console output\n\n");
+    if app.console.is_empty() {
+        report.push_str("The console is empty.\n");
+    } else {
+        report.push_str("```\n");
+        let skip = app.console.len().saturating_sub(RECENT_LINES);
+        for line in app.console.iter().skip(skip) {
+            report.push_str(line);
+            report.push('\n');
+        }
+        report.push_str("```\n");
+    }
+
+    report
+}
@@ -0,0 +1,250 @@
+use anyhow::{anyhow, bail, Result};
+
+use serde::Deserialize;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::device::Device;
+
+// The specification of a scripted device, which is read from the TOML file backing the device.
+// For example, a device which reports that it is busy for the first two polls, and which raises
+// an interrupt a while after being sent a command, might be written as follows.
+//
+//     [[register]]
+//     name = "status"
+//     reads = [0x0000, 0x0000]
+//     value = 0x0001
+//
+//     [[register]]
+//     name = "command"
+//
+//     [[rule]]
+//     write = "command"
+//     value = 0x0001
+//     after = 100
+//     set = { status = 0x0003 }
+//     interrupt = true
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Spec {
+    #[serde(rename = "register")]
+    registers: Vec<RegisterSpec>,
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleSpec>,
+}
+
+// A register, which occupies the offset given by its position in the list of registers.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegisterSpec {
+    name: String,
+    // The value which the register holds after a reset.
+    #[serde(default)]
+    value: u16,
+    // Values which are returned by successive reads after a reset, before the register settles
+    // on its value.
+    #[serde(default)]
+    reads: Vec<u16>,
+    // Whether or not writes to the register are discarded, rather than changing its value.
+    #[serde(default)]
+    read_only: bool,
+}
+
+// A response to a write, which fires a given number of instructions after the write.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    // The register which must be written to in order to trigger the rule.
+    write: String,
+    // The value which must be written in order to trigger the rule. If there isn't one, then any
+    // value will do.
+    value: Option<u16>,
+    #[serde(default)]
+    after: u32,
+    // The values which registers are set to when the rule fires, keyed by name.
+    #[serde(default)]
+    set: HashMap<String, u16>,
+    // Whether the device's interrupt is asserted or deasserted when the rule fires, if either.
+    interrupt: Option<bool>,
+}
+
+// A rule, with register names resolved to offsets.
+struct Rule {
+    write: u16,
+    value: Option<u16>,
+    after: u32,
+    set: Vec<(u16, u16)>,
+    interrupt: Option<bool>,
+}
+
+#[derive(Clone)]
+pub struct Scripted {
+    // NOTE: The specification is shared between clones, since the CPU is cloned frequently.
+    registers: Arc<Vec<RegisterSpec>>,
+    rules: Arc<Vec<Rule>>,
+    values: Vec<u16>,
+    reads: Vec<VecDeque<u16>>,
+    // Rules which have been triggered, along with the number of instructions until they fire.
+    pending: Vec<(u32, usize)>,
+    interrupt: bool,
+}
+
+impl Scripted {
+    pub fn new(spec: &[u8]) -> Result<Self> {
+        let spec = std::str::from_utf8(spec).map_err(|_| anyhow!("The script is not UTF-8."))?;
+        let spec: Spec = toml::from_str(spec).map_err(|e| anyhow!("Invalid script: {}", e))?;
+        if spec.registers.is_empty() || spec.registers.len() > 0x100 {
+            bail!("A scripted device must have between 1 and 256 registers.");
+        }
+
+        let offset = |name: &str| {
+            spec.registers
+                .iter()
+                .position(|register| register.name.eq_ignore_ascii_case(name))
+                .map(|offset| offset as u16)
+                .ok_or_else(|| anyhow!("The script has no register named {}.", name))
+        };
+        let rules = spec
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(Rule {
+                    write: offset(&rule.write)?,
+                    value: rule.value,
+                    after: rule.after,
+                    set: rule
+                        .set
+                        .iter()
+                        .map(|(name, &value)| Ok((offset(name)?, value)))
+                        .collect::<Result<_>>()?,
+                    interrupt: rule.interrupt,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut scripted = Self {
+            registers: Arc::new(spec.registers),
+            rules: Arc::new(rules),
+            values: Vec::new(),
+            reads: Vec::new(),
+            pending: Vec::new(),
+            interrupt: false,
+        };
+        scripted.reset();
+        Ok(scripted)
+    }
+
+    // Count down the pending rules by a given number of instructions, firing any which are due.
+    fn tick_by(&mut self, instructions: u32) {
+        let mut due = Vec::new();
+        self.pending.retain_mut(|(remaining, rule)| {
+            *remaining = remaining.saturating_sub(instructions);
+            if *remaining == 0 {
+                due.push(*rule);
+                false
+            } else {
+                true
+            }
+        });
+        for rule in due {
+            self.fire(rule);
+        }
+    }
+
+    // Apply the effects of a rule.
+    fn fire(&mut self, rule: usize) {
+        let rule = &self.rules[rule];
+        for &(offset, value) in &rule.set {
+            self.values[usize::from(offset)] = value;
+        }
+        if let Some(interrupt) = rule.interrupt {
+            self.interrupt = interrupt;
+        }
+    }
+}
+
+impl Device for Scripted {
+    fn kind(&self) -> &'static str {
+        "script"
+    }
+
+    fn length(&self) -> u16 {
+        self.registers.len() as u16
+    }
+
+    fn register_name(&self, offset: u16) -> &str {
+        &self.registers[usize::from(offset)].name
+    }
+
+    fn read(&mut self, offset: u16) -> u16 {
+        let offset = usize::from(offset);
+        self.reads[offset]
+            .pop_front()
+            .unwrap_or(self.values[offset])
+    }
+
+    fn write(&mut self, offset: u16, value: u16) {
+        if !self.registers[usize::from(offset)].read_only {
+            self.values[usize::from(offset)] = value;
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.write == offset && rule.value.is_none_or(|v| v == value) {
+                self.pending.push((rule.after, i));
+            }
+        }
+        // Rules without a delay take effect immediately, rather than after the next instruction.
+        self.tick_by(0);
+    }
+
+    fn peek(&self, offset: u16) -> u16 {
+        let offset = usize::from(offset);
+        self.reads[offset]
+            .front()
+            .copied()
+            .unwrap_or(self.values[offset])
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let mut lines = vec![self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(offset, register)| {
+                format!("{} {:#06x}", register.name, self.peek(offset as u16))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")];
+        for &(remaining, rule) in &self.pending {
+            lines.push(format!(
+                "rule {} fires in {} instructions",
+                rule + 1,
+                remaining
+            ));
+        }
+        lines
+    }
+
+    fn interrupt(&self) -> bool {
+        self.interrupt
+    }
+
+    fn reset(&mut self) {
+        self.values = self.registers.iter().map(|r| r.value).collect();
+        self.reads = self
+            .registers
+            .iter()
+            .map(|r| r.reads.iter().copied().collect())
+            .collect();
+        self.pending.clear();
+        self.interrupt = false;
+    }
+
+    fn tick(&mut self) {
+        self.tick_by(1);
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
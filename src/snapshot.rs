@@ -0,0 +1,239 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::fs;
+
+use crate::checksum::crc32;
+use crate::cpu::Cpu;
+
+// Every snapshot file begins with these bytes, followed by a byte giving the kind of snapshot.
+const MAGIC: &[u8; 4] = b"ILOS";
+const FULL: u8 = 0;
+const DELTA: u8 = 1;
+
+// Runs of at least this many identical words are worth encoding as a run rather than as literals.
+const MINIMUM_RUN: usize = 3;
+
+// The architectural state of the CPU, as recorded in a snapshot. The performance counters are not
+// included, since they describe the history of the simulation rather than its state.
+pub struct Snapshot {
+    pub registers: [u16; 0x20],
+    pub program_counter: u16,
+    pub ram: Vec<u16>,
+}
+
+impl Snapshot {
+    // Apply the snapshot to a CPU, replacing its registers, program counter, and RAM.
+    pub fn apply(&self, cpu: &mut Cpu) {
+        for (register, &value) in self.registers.iter().enumerate() {
+            cpu.set_register(register, value);
+        }
+        cpu.program_counter = self.program_counter;
+        cpu.ram.copy_from_slice(&self.ram);
+    }
+}
+
+// Save the state of a CPU to a snapshot file, returning the size of the file in bytes.
+//
+// RAM is run-length encoded, which is very effective for the typical image, consisting of a little
+// code and data in a sea of zeroes. If a baseline snapshot is given, then only the difference from
+// the baseline's RAM is recorded, as the XOR of the two, which is zero wherever the RAM is
+// unchanged and so compresses even better. The baseline must be a full snapshot, and the path to
+// it is recorded as given, along with its CRC-32 so that a modified baseline can be detected.
+pub fn save(cpu: &Cpu, filename: &str, baseline: Option<&str>) -> Result<usize> {
+    let mut bytes = MAGIC.to_vec();
+    let ram = match baseline {
+        Some(baseline) => {
+            let baseline_bytes = fs::read(baseline)?;
+            let baseline_snapshot = parse(&baseline_bytes, baseline, None)?;
+            bytes.push(DELTA);
+            bytes.extend(crc32(&baseline_bytes).to_be_bytes());
+            bytes.extend((baseline.len() as u16).to_be_bytes());
+            bytes.extend(baseline.as_bytes());
+            cpu.ram
+                .iter()
+                .zip(&baseline_snapshot.ram)
+                .map(|(word, base)| word ^ base)
+                .collect()
+        }
+        None => {
+            bytes.push(FULL);
+            cpu.ram.to_vec()
+        }
+    };
+
+    let words = cpu
+        .registers
+        .iter()
+        .copied()
+        .chain([cpu.program_counter])
+        .chain(compress(&ram));
+    bytes.extend(words.flat_map(|word| word.to_be_bytes()));
+
+    fs::write(filename, &bytes)?;
+    Ok(bytes.len())
+}
+
+// Load a snapshot file, reading its baseline as well if it is a delta snapshot.
+pub fn load(filename: &str) -> Result<Snapshot> {
+    let bytes = fs::read(filename)?;
+    let mut reader = Reader::new(&bytes, filename);
+    reader.header()?;
+
+    let baseline = if reader.byte()? == DELTA {
+        let checksum = u32::from(reader.word()?) << 16 | u32::from(reader.word()?);
+        let length = usize::from(reader.word()?);
+        let path = String::from_utf8(reader.bytes(length)?.to_vec())
+            .map_err(|_| anyhow!("{} names a baseline which is not valid UTF-8.", filename))?;
+        let baseline_bytes = fs::read(&path).map_err(|e| {
+            anyhow!(
+                "Failed to read {}, the baseline of {}: {}",
+                path,
+                filename,
+                e
+            )
+        })?;
+        if crc32(&baseline_bytes) != checksum {
+            bail!(
+                "{}, the baseline of {}, has changed since the snapshot was saved.",
+                path,
+                filename
+            );
+        }
+        Some(parse(&baseline_bytes, &path, None)?)
+    } else {
+        None
+    };
+
+    parse(&bytes, filename, baseline.as_ref())
+}
+
+// Parse the contents of a snapshot file. Delta snapshots must be accompanied by their baseline.
+fn parse(bytes: &[u8], filename: &str, baseline: Option<&Snapshot>) -> Result<Snapshot> {
+    let mut reader = Reader::new(bytes, filename);
+    reader.header()?;
+    match (reader.byte()?, baseline) {
+        (FULL, _) => (),
+        (DELTA, Some(_)) => {
+            reader.bytes(4)?;
+            let length = usize::from(reader.word()?);
+            reader.bytes(length)?;
+        }
+        (DELTA, None) => bail!(
+            "{} is a delta snapshot, and so cannot be used as a baseline.",
+            filename
+        ),
+        (kind, _) => bail!("{} is a snapshot of unknown kind {}.", filename, kind),
+    }
+
+    let mut registers = [0x0000; 0x20];
+    for register in registers.iter_mut() {
+        *register = reader.word()?;
+    }
+    let program_counter = reader.word()?;
+    let mut ram = reader.decompress(0x10000)?;
+    if let Some(baseline) = baseline {
+        for (word, base) in ram.iter_mut().zip(&baseline.ram) {
+            *word ^= base;
+        }
+    }
+
+    Ok(Snapshot {
+        registers,
+        program_counter,
+        ram,
+    })
+}
+
+// Run-length encode a sequence of words. Each packet begins with a control word, whose high bit
+// distinguishes between a run, which is followed by a single word to be repeated, and a sequence
+// of literals, which is followed by the literal words themselves. The low 15 bits give the length.
+fn compress(words: &[u16]) -> Vec<u16> {
+    let mut output = Vec::new();
+    let mut literals = 0;
+    let mut i = 0;
+
+    while i < words.len() {
+        let run = words[i..]
+            .iter()
+            .take(0x7fff)
+            .take_while(|&&word| word == words[i])
+            .count();
+        if run >= MINIMUM_RUN {
+            push_literals(&mut output, &words[literals..i]);
+            output.extend([0x8000 | run as u16, words[i]]);
+            i += run;
+            literals = i;
+        } else {
+            i += run;
+        }
+    }
+    push_literals(&mut output, &words[literals..]);
+
+    output
+}
+
+fn push_literals(output: &mut Vec<u16>, literals: &[u16]) {
+    for chunk in literals.chunks(0x7fff) {
+        output.push(chunk.len() as u16);
+        output.extend_from_slice(chunk);
+    }
+}
+
+// A cursor over the contents of a snapshot file, which reports truncation as an error.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    filename: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], filename: &'a str) -> Self {
+        Self { bytes, filename }
+    }
+
+    fn header(&mut self) -> Result<()> {
+        if !self.bytes.starts_with(MAGIC) {
+            bail!("{} is not a snapshot file.", self.filename);
+        }
+        self.bytes(MAGIC.len())?;
+        Ok(())
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < count {
+            bail!("{} is truncated.", self.filename);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Decode run-length encoded words until exactly a given number have been produced.
+    fn decompress(&mut self, length: usize) -> Result<Vec<u16>> {
+        let mut output = Vec::with_capacity(length);
+        while output.len() < length {
+            let control = self.word()?;
+            let count = usize::from(control & 0x7fff);
+            if control & 0x8000 != 0 {
+                let word = self.word()?;
+                output.extend(std::iter::repeat_n(word, count));
+            } else {
+                for _ in 0..count {
+                    output.push(self.word()?);
+                }
+            }
+        }
+        if output.len() != length {
+            bail!("{} is corrupt.", self.filename);
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,53 @@
+use crate::cpu::Cpu;
+use crate::disassemble::instruction_length;
+
+// The roles in which a word of RAM is known to have been used. A word may be known in both, if
+// execution has been misaligned at some point, so these are combined as flags.
+const START: u8 = 0b01;
+const IMMEDIATE: u8 = 0b10;
+
+// Which words of RAM are known to begin an instruction, and which are known to hold the immediate
+// of the instruction before them, for telling where instructions begin in code which is a mix of
+// one-word and two-word instructions. Words are learned about as they are executed, and from
+// static analysis, so a word which hasn't been reached either way is simply unknown.
+pub struct Starts {
+    // Roles, indexed as the fitted RAM is, so that mirrored addresses agree.
+    roles: Vec<u8>,
+}
+
+impl Starts {
+    pub fn new() -> Self {
+        Self {
+            roles: vec![0; 0x10000],
+        }
+    }
+
+    // Forget everything, as when a new image is loaded.
+    pub fn clear(&mut self) {
+        self.roles.fill(0);
+    }
+
+    // Forget what is known about a word of RAM, as when something new is assembled over it.
+    pub fn forget(&mut self, index: usize) {
+        self.roles[index] = 0;
+    }
+
+    // Record that an instruction begins at an address, along with its immediate if it has one.
+    pub fn mark(&mut self, cpu: &Cpu, address: u16) {
+        if let Some(index) = cpu.memory.decode(address) {
+            self.roles[index] |= START;
+        }
+        if instruction_length(cpu.peek(address)) == 2 {
+            if let Some(index) = cpu.memory.decode(address.wrapping_add(1)) {
+                self.roles[index] |= IMMEDIATE;
+            }
+        }
+    }
+
+    // The address of the instruction whose immediate is held at an address, if that's the only
+    // role in which the word is known.
+    pub fn immediate_of(&self, cpu: &Cpu, address: u16) -> Option<u16> {
+        let index = cpu.memory.decode(address)?;
+        (self.roles[index] == IMMEDIATE).then(|| address.wrapping_sub(1))
+    }
+}
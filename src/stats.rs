@@ -0,0 +1,89 @@
+use anyhow::{anyhow, bail, Result};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::command::COMMANDS;
+
+// The path to which usage statistics are written, if they have been enabled with `stats = true`
+// in ilo.toml. Nothing ever leaves this file, which is meant to be collected by hand, as when an
+// instructor wants to know which features of the debugger a lab actually used.
+const STATS_PATH: &str = "ilo-stats.toml";
+
+// Aggregate usage over every session which recorded statistics. Only counts are kept, and never
+// the arguments to commands, since these might include the names of files.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Stats {
+    pub sessions: u64,
+    pub instructions: u64,
+    // The number of times that each command was typed, keyed by its name.
+    pub commands: BTreeMap<String, u64>,
+}
+
+impl Stats {
+    // Count a use of a command.
+    pub fn record(&mut self, name: &str) {
+        *self.commands.entry(name.to_string()).or_default() += 1;
+    }
+
+    // Add the statistics of this session to those already in the stats file.
+    pub fn save(&self) -> Result<()> {
+        let mut total = load()?;
+        total.sessions += self.sessions;
+        total.instructions += self.instructions;
+        for (name, count) in &self.commands {
+            *total.commands.entry(name.clone()).or_default() += count;
+        }
+        fs::write(STATS_PATH, toml::to_string(&total)?)?;
+        Ok(())
+    }
+}
+
+// Read the stats file, which is empty if it doesn't exist yet.
+fn load() -> Result<Stats> {
+    match fs::read_to_string(STATS_PATH) {
+        Ok(contents) => {
+            toml::from_str(&contents).map_err(|e| anyhow!("Invalid {}: {}", STATS_PATH, e))
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Stats::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// Summarize the stats file. This is what `ilo stats` does.
+pub fn stats(mut arguments: impl Iterator<Item = String>) -> Result<()> {
+    if let Some(argument) = arguments.next() {
+        bail!("Unrecognized argument \"{}\".", argument);
+    }
+
+    let stats = load()?;
+    if stats.sessions == 0 {
+        println!(
+            "No statistics have been recorded in {}. Set stats = true in ilo.toml to record them.",
+            STATS_PATH
+        );
+        return Ok(());
+    }
+
+    println!("Sessions: {}", stats.sessions);
+    println!("Instructions executed: {}", stats.instructions);
+    let mut used = stats.commands.iter().collect::<Vec<_>>();
+    used.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    println!("Commands, most used first:");
+    for (name, count) in used {
+        println!("  {:<12} {}", name, count);
+    }
+    let unused = COMMANDS
+        .iter()
+        .map(|spec| spec.name)
+        .filter(|name| !stats.commands.contains_key(*name))
+        .collect::<Vec<_>>();
+    if !unused.is_empty() {
+        println!("Never used: {}", unused.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::cpu::Cpu;
+
+// Which registers and words of RAM hold values derived from the program's input, for following
+// how input data flows through a program. Input is anything read from a device, along with any
+// words which are marked by hand, such as a file of data loaded into RAM.
+//
+// A value is tainted if any value that it was computed from is. Only the data which flows into a
+// value is considered, so the address of a load or a store doesn't taint what is loaded or stored,
+// and a branch doesn't taint anything.
+pub struct Taint {
+    registers: [bool; 0x20],
+    // Tainted words, indexed as the fitted RAM is, so that mirrored addresses agree.
+    memory: Vec<bool>,
+}
+
+impl Taint {
+    pub fn new() -> Self {
+        Self {
+            registers: [false; 0x20],
+            memory: vec![false; 0x10000],
+        }
+    }
+
+    pub fn register(&self, register: usize) -> bool {
+        self.registers[register]
+    }
+
+    pub fn memory(&self, cpu: &Cpu, address: u16) -> bool {
+        cpu.memory
+            .decode(address)
+            .is_some_and(|index| self.memory[index])
+    }
+
+    // Mark a range of RAM as tainted, returning the number of words which were actually RAM.
+    pub fn mark(&mut self, cpu: &Cpu, address: u16, length: u16) -> usize {
+        (0..length)
+            .filter_map(|offset| cpu.memory.decode(address.wrapping_add(offset)))
+            .map(|index| self.memory[index] = true)
+            .count()
+    }
+
+    // The number of tainted registers and words of RAM.
+    pub fn count(&self) -> (usize, usize) {
+        (
+            self.registers.iter().filter(|&&tainted| tainted).count(),
+            self.memory.iter().filter(|&&tainted| tainted).count(),
+        )
+    }
+
+    // Propagate taint through the instruction to which the program counter currently points. This
+    // must be done before the instruction is executed, while its operands still hold the values
+    // that it reads.
+    pub fn propagate(&mut self, cpu: &Cpu) {
+        let instruction = cpu.peek(cpu.program_counter);
+        let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+        let s = usize::from((instruction & 0b1111100000000000) >> 11);
+        let d = usize::from((instruction & 0b0000011111000000) >> 6);
+        let source = cpu.registers[s];
+
+        // A load is tainted if it reads from a device, or from a tainted word of RAM.
+        let load = |address: u16| cpu.devices.peek(address).is_some() || self.memory(cpu, address);
+        let written = match instruction & 0b0000000000111111 {
+            0b000000..=0b000111 => Some(self.registers[d] || self.registers[s]),
+            0b001000 | 0b001010..=0b001101 | 0b001111 => Some(self.registers[s]),
+            0b010000 => Some(load(source)),
+            0b011000 => Some(load(source.wrapping_add(immediate))),
+            0b010001 => {
+                self.store(cpu, source, d);
+                None
+            }
+            0b011001 => {
+                self.store(cpu, source.wrapping_add(immediate), d);
+                None
+            }
+            // The link which JAL writes is just an address, which is never tainted.
+            0b101000 => Some(false),
+            _ => None,
+        };
+        if let Some(tainted) = written {
+            if d != 0 {
+                self.registers[d] = tainted;
+            }
+        }
+    }
+
+    // A store taints the word which it writes if the register which it stores is tainted, and
+    // clears the taint of the word otherwise. Stores to devices don't land in RAM, so they can't
+    // taint it.
+    fn store(&mut self, cpu: &Cpu, address: u16, register: usize) {
+        if cpu.devices.peek(address).is_some() {
+            return;
+        }
+        if let Some(index) = cpu.memory.decode(address) {
+            self.memory[index] = self.registers[register];
+        }
+    }
+}
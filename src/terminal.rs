@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+
+// The size of the guest's terminal screen, which is that of a VT100.
+pub const ROWS: usize = 24;
+pub const COLUMNS: usize = 80;
+
+// The most rows which are kept once they have scrolled off the top of the screen.
+const SCROLLBACK_LENGTH: usize = 0x400;
+
+// How a character is drawn. Colors are numbered as in ANSI escape sequences, from 0 to 7, with 8
+// to 15 being their bright counterparts.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Attributes {
+    pub foreground: Option<u8>,
+    pub background: Option<u8>,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct Cell {
+    pub character: char,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            character: ' ',
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+// Where the parser is in an escape sequence.
+enum State {
+    // Nothing out of the ordinary is going on.
+    Ground,
+    // An escape character has been received.
+    Escape,
+    // A control sequence introducer has been received, followed by these parameters so far.
+    Csi(String),
+}
+
+// The guest's terminal, which interprets what the UART transmits as a VT100 would, so that
+// text-mode programs can draw simple interfaces. Only the common control sequences are understood:
+// moving the cursor, clearing the screen or a line, and setting colors and attributes. Anything
+// else is quietly ignored, as a terminal would.
+pub struct Terminal {
+    screen: Vec<Vec<Cell>>,
+    // Rows which have scrolled off the top of the screen, from the oldest to the newest.
+    scrollback: VecDeque<Vec<Cell>>,
+    // The row and column of the cursor, counting from zero.
+    pub cursor: (usize, usize),
+    // The cursor as saved by ESC [ s, to be restored by ESC [ u.
+    saved: (usize, usize),
+    attributes: Attributes,
+    state: State,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self {
+            screen: vec![vec![Cell::default(); COLUMNS]; ROWS],
+            scrollback: VecDeque::new(),
+            cursor: (0, 0),
+            saved: (0, 0),
+            attributes: Attributes::default(),
+            state: State::Ground,
+        }
+    }
+
+    // Clear the screen and the scrollback, and return the terminal to its initial state.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    // Every row which the terminal holds, from the oldest row of the scrollback to the bottom row
+    // of the screen.
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.scrollback
+            .iter()
+            .chain(self.screen.iter())
+            .map(|row| row.as_slice())
+    }
+
+    // The number of rows of scrollback above the screen.
+    pub fn scrollback(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    // The text of the scrollback and the screen, one row to a line, without trailing blanks. Colors
+    // and attributes are left out, and so are the blank rows at the bottom of the screen.
+    pub fn text(&self) -> String {
+        let lines = self
+            .rows()
+            .map(|row| {
+                let line = row.iter().map(|cell| cell.character).collect::<String>();
+                line.trim_end().to_string()
+            })
+            .collect::<Vec<_>>();
+        let length = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |last| last + 1);
+        lines[..length]
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    // Interpret bytes transmitted by the guest.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.byte(byte);
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        match std::mem::replace(&mut self.state, State::Ground) {
+            State::Ground => self.control(byte),
+            State::Escape => match byte {
+                b'[' => self.state = State::Csi(String::new()),
+                b'c' => self.clear(),
+                _ => {}
+            },
+            State::Csi(mut parameters) => match byte {
+                b'0'..=b'9' | b';' | b'?' => {
+                    // A sequence which goes on forever is abandoned rather than buffered.
+                    if parameters.len() < 0x20 {
+                        parameters.push(char::from(byte));
+                        self.state = State::Csi(parameters);
+                    }
+                }
+                _ => self.sequence(&parameters, byte),
+            },
+        }
+    }
+
+    // Handle a byte which isn't part of an escape sequence.
+    fn control(&mut self, byte: u8) {
+        let column = self.cursor.1;
+        match byte {
+            0x1b => self.state = State::Escape,
+            // NOTE: A newline returns the carriage as well, since most guest programs end their
+            // lines with a bare newline, just as the UART's lines in the console do.
+            b'\n' => self.line_feed(),
+            b'\r' => self.cursor.1 = 0,
+            0x08 => self.cursor.1 = column.saturating_sub(1),
+            b'\t' => self.cursor.1 = ((column / 8 + 1) * 8).min(COLUMNS - 1),
+            0x20..=0x7e => {
+                // The cursor rests past the last column once it is filled, and the line only wraps
+                // when another character is printed.
+                if column == COLUMNS {
+                    self.line_feed();
+                }
+                let (row, column) = self.cursor;
+                self.screen[row][column] = Cell {
+                    character: char::from(byte),
+                    attributes: self.attributes,
+                };
+                self.cursor = (row, column + 1);
+            }
+            _ => {}
+        }
+    }
+
+    // Move to the start of the next line, scrolling the screen up if the cursor is on the last.
+    fn line_feed(&mut self) {
+        let (row, _) = self.cursor;
+        if row + 1 < ROWS {
+            self.cursor = (row + 1, 0);
+            return;
+        }
+        let top = self.screen.remove(0);
+        self.scrollback.push_back(top);
+        if self.scrollback.len() > SCROLLBACK_LENGTH {
+            self.scrollback.pop_front();
+        }
+        self.screen.push(vec![Cell::default(); COLUMNS]);
+        self.cursor = (row, 0);
+    }
+
+    // Handle a complete control sequence, given its parameters and the byte which ended it.
+    fn sequence(&mut self, parameters: &str, command: u8) {
+        let numbers = parameters
+            .trim_start_matches('?')
+            .split(';')
+            .map(|number| number.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        // The nth parameter, or a default if it was left out.
+        let parameter =
+            |n: usize, default: usize| numbers.get(n).copied().flatten().unwrap_or(default);
+        // A count of zero means the same as a count of one.
+        let count = parameter(0, 1).max(1);
+        let (row, column) = self.cursor;
+        let column = column.min(COLUMNS - 1);
+
+        match command {
+            b'A' => self.cursor = (row.saturating_sub(count), column),
+            b'B' => self.cursor = ((row + count).min(ROWS - 1), column),
+            b'C' => self.cursor = (row, (column + count).min(COLUMNS - 1)),
+            b'D' => self.cursor = (row, column.saturating_sub(count)),
+            b'H' | b'f' => {
+                self.cursor = (
+                    parameter(0, 1).clamp(1, ROWS) - 1,
+                    parameter(1, 1).clamp(1, COLUMNS) - 1,
+                )
+            }
+            b'J' => match parameter(0, 0) {
+                0 => {
+                    self.erase(row, column..COLUMNS);
+                    for row in row + 1..ROWS {
+                        self.erase(row, 0..COLUMNS);
+                    }
+                }
+                1 => {
+                    for row in 0..row {
+                        self.erase(row, 0..COLUMNS);
+                    }
+                    self.erase(row, 0..column + 1);
+                }
+                2 => {
+                    for row in 0..ROWS {
+                        self.erase(row, 0..COLUMNS);
+                    }
+                }
+                3 => self.scrollback.clear(),
+                _ => {}
+            },
+            b'K' => match parameter(0, 0) {
+                0 => self.erase(row, column..COLUMNS),
+                1 => self.erase(row, 0..column + 1),
+                2 => self.erase(row, 0..COLUMNS),
+                _ => {}
+            },
+            b'm' => {
+                for n in 0..numbers.len() {
+                    self.select_graphic_rendition(parameter(n, 0));
+                }
+            }
+            b's' => self.saved = self.cursor,
+            b'u' => self.cursor = self.saved,
+            _ => {}
+        }
+    }
+
+    // Erase part of a row, leaving the current background color behind, as a VT100 does.
+    fn erase(&mut self, row: usize, columns: std::ops::Range<usize>) {
+        let blank = Cell {
+            character: ' ',
+            attributes: Attributes {
+                background: self.attributes.background,
+                ..Attributes::default()
+            },
+        };
+        self.screen[row][columns].fill(blank);
+    }
+
+    fn select_graphic_rendition(&mut self, parameter: usize) {
+        let attributes = &mut self.attributes;
+        match parameter {
+            0 => *attributes = Attributes::default(),
+            1 => attributes.bold = true,
+            4 => attributes.underline = true,
+            7 => attributes.reverse = true,
+            22 => attributes.bold = false,
+            24 => attributes.underline = false,
+            27 => attributes.reverse = false,
+            30..=37 => attributes.foreground = Some((parameter - 30) as u8),
+            39 => attributes.foreground = None,
+            40..=47 => attributes.background = Some((parameter - 40) as u8),
+            49 => attributes.background = None,
+            90..=97 => attributes.foreground = Some((parameter - 90 + 8) as u8),
+            100..=107 => attributes.background = Some((parameter - 100 + 8) as u8),
+            _ => {}
+        }
+    }
+}
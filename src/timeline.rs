@@ -0,0 +1,120 @@
+use anyhow::{bail, Result};
+
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::history::{History, InstructionHistory};
+
+// The most steps which are taken between one checkpoint and the next, which bounds how many steps
+// must be taken again to reach any point in the recording.
+const CHECKPOINT_INTERVAL: u64 = 0x1000;
+
+// The most checkpoints which are kept. Once there are this many, the oldest is dropped to make room
+// for the next, so the recording only reaches back so far.
+const MAX_CHECKPOINTS: usize = 0x40;
+
+// A recording of the run so far, as copies of the machine taken every so often, from which any
+// step of the run can be reconstructed by stepping a copy forward from the checkpoint before it.
+// This is what SEEK travels through time with.
+pub struct Timeline {
+    // Copies of the machine, each along with the number of steps which had been taken when it was
+    // taken, from the oldest to the newest.
+    checkpoints: VecDeque<(u64, Box<Cpu>)>,
+    // Whether or not something other than stepping has changed the machine since the last
+    // checkpoint, in which case stepping forward from it wouldn't arrive where the run did.
+    dirty: bool,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            checkpoints: VecDeque::new(),
+            dirty: true,
+        }
+    }
+
+    // Note that the machine was changed other than by stepping it, so that the next step begins
+    // with a new checkpoint.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    // Forget the whole recording, as when a new image is loaded and the count of steps begins again.
+    pub fn clear(&mut self) {
+        self.checkpoints.clear();
+        self.dirty = true;
+    }
+
+    // Record the machine as it is before a step is taken, if it's due for a checkpoint.
+    pub fn record(&mut self, step: u64, cpu: &Cpu) {
+        let due = self
+            .checkpoints
+            .back()
+            .is_none_or(|&(last, _)| step - last >= CHECKPOINT_INTERVAL);
+        if !self.dirty && !due {
+            return;
+        }
+        self.checkpoints.push_back((step, Box::new(cpu.clone())));
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            self.checkpoints.pop_front();
+        }
+        self.dirty = false;
+    }
+
+    // The earliest step which can still be reconstructed, if any can be.
+    pub fn earliest(&self) -> Option<u64> {
+        self.checkpoints.front().map(|&(step, _)| step)
+    }
+
+    // Reconstruct the machine as it was once a given number of steps had been taken, along with
+    // the instructions which led up to it since the checkpoint that it was reconstructed from.
+    // NOTE: Only the machine itself is stepped, so anything which was done to it by the debugger
+    // without a command in between, such as input typed into a device, isn't seen again.
+    pub fn seek(&self, step: u64) -> Result<(Box<Cpu>, InstructionHistory)> {
+        let Some((start, checkpoint)) = self.checkpoints.iter().rev().find(|&&(s, _)| s <= step)
+        else {
+            match self.earliest() {
+                Some(earliest) => bail!(
+                    "Step {} is too long ago. The recording only reaches back to step {}.",
+                    step,
+                    earliest
+                ),
+                None => bail!("Nothing has been recorded yet."),
+            }
+        };
+        let mut cpu = checkpoint.clone();
+        let mut history = InstructionHistory::new();
+        for _ in *start..step {
+            let instruction = cpu.peek(cpu.program_counter);
+            let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+            history.push(History::Executed(instruction, immediate));
+            cpu.step();
+            if let Some(line) = cpu.interrupts.taken {
+                history.push(History::Interrupt(line));
+            }
+            // A fault is reported when it happens, rather than being found in the reconstruction.
+            cpu.fault = None;
+        }
+        Ok((cpu, history))
+    }
+
+    // Step through the whole recording, up to a given number of steps, showing the machine to a
+    // visitor before each step is taken. Each stretch between one checkpoint and the next begins
+    // afresh from its checkpoint, just as SEEK does. Returns the step at which the recording
+    // begins.
+    pub fn replay(&self, step: u64, mut visit: impl FnMut(&Cpu)) -> Result<u64> {
+        let Some(earliest) = self.earliest() else {
+            bail!("Nothing has been recorded yet.");
+        };
+        let ends = self.checkpoints.iter().skip(1).map(|&(start, _)| start);
+        for ((start, checkpoint), end) in self.checkpoints.iter().zip(ends.chain([step])) {
+            let mut cpu = checkpoint.clone();
+            for _ in *start..end.min(step) {
+                visit(&cpu);
+                cpu.step();
+                cpu.fault = None;
+            }
+        }
+        Ok(earliest)
+    }
+}
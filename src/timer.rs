@@ -0,0 +1,106 @@
+use crate::device::Device;
+
+// The offsets of the timer's registers.
+const COUNT: u16 = 0;
+const CONTROL: u16 = 1;
+
+// The bits of the control register.
+const ENABLED: u16 = 0b01;
+const EXPIRED: u16 = 0b10;
+
+// A countdown timer, which counts in instructions executed. Writing to the count register sets
+// both the current count and the value to which it is reloaded upon expiry. While the timer is
+// enabled, the count is decremented after every instruction, and when it reaches zero the expired
+// bit of the control register is set. Writing to the control register enables or disables the
+// timer, and writing a one to the expired bit acknowledges the expiry by clearing it.
+#[derive(Clone, Default)]
+pub struct Timer {
+    pub count: u16,
+    pub reload: u16,
+    pub enabled: bool,
+    pub expired: bool,
+}
+
+impl Device for Timer {
+    fn kind(&self) -> &'static str {
+        "timer"
+    }
+
+    fn length(&self) -> u16 {
+        2
+    }
+
+    fn register_name(&self, offset: u16) -> &str {
+        match offset {
+            COUNT => "count",
+            _ => "control",
+        }
+    }
+
+    fn read(&mut self, offset: u16) -> u16 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u16) {
+        match offset {
+            COUNT => {
+                self.count = value;
+                self.reload = value;
+            }
+            _ => {
+                self.enabled = value & ENABLED != 0;
+                if value & EXPIRED != 0 {
+                    self.expired = false;
+                }
+            }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u16 {
+        match offset {
+            COUNT => self.count,
+            CONTROL => {
+                (if self.enabled { ENABLED } else { 0 }) | (if self.expired { EXPIRED } else { 0 })
+            }
+            _ => 0x0000,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            format!("count {:#06x}, reload {:#06x}", self.count, self.reload),
+            format!(
+                "{}, {}",
+                if self.enabled { "enabled" } else { "disabled" },
+                if self.expired {
+                    "expired"
+                } else {
+                    "not expired"
+                }
+            ),
+        ]
+    }
+
+    // The timer interrupts from the moment that it expires until the expiry is acknowledged.
+    fn interrupt(&self) -> bool {
+        self.expired
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn tick(&mut self) {
+        if self.enabled && self.count > 0 {
+            self.count -= 1;
+            if self.count == 0 {
+                self.expired = true;
+                self.count = self.reload;
+            }
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
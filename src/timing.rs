@@ -0,0 +1,81 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// The parts of the main loop which host time is accounted to.
+#[derive(Clone, Copy)]
+pub enum Phase {
+    // Stepping the simulation, along with everything else done in a tick.
+    Stepping,
+    // Laying out and drawing the UI.
+    Rendering,
+    // Waiting for input, and reading it.
+    Polling,
+    // Handling input, including executing any commands that it entered.
+    Handling,
+}
+
+const PHASES: [(Phase, &str); 4] = [
+    (Phase::Stepping, "stepping"),
+    (Phase::Rendering, "rendering"),
+    (Phase::Polling, "polling"),
+    (Phase::Handling, "handling"),
+];
+
+// Where the emulator's own time goes, as measured with --self-profile, for finding out why a run
+// is slow on a given machine.
+pub struct Timing {
+    start: Instant,
+    // The total time spent in each phase, and the number of times that it was entered, indexed by
+    // phase.
+    phases: [(Duration, u64); 4],
+}
+
+impl Timing {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            phases: [(Duration::ZERO, 0); 4],
+        }
+    }
+
+    // Account the time since an instant to a phase.
+    pub fn record(&mut self, phase: Phase, since: Instant) {
+        let (total, count) = &mut self.phases[phase as usize];
+        *total += since.elapsed();
+        *count += 1;
+    }
+
+    // Summarize where the time went, with one line for each phase, and with whatever wasn't
+    // accounted to any phase at the end.
+    pub fn report(&self) -> String {
+        let elapsed = self.start.elapsed();
+        let share = |duration: Duration| 100.0 * duration.as_secs_f64() / elapsed.as_secs_f64();
+        let mut report = format!("ilo ran for {:.3} s.\n", elapsed.as_secs_f64());
+        let mut accounted = Duration::ZERO;
+        for (phase, name) in PHASES {
+            let (total, count) = self.phases[phase as usize];
+            accounted += total;
+            let _ = writeln!(
+                report,
+                "  {:<10} {:>10.3} s {:>6.1}%  {} times, {:.3} ms each",
+                name,
+                total.as_secs_f64(),
+                share(total),
+                count,
+                match count {
+                    0 => 0.0,
+                    count => total.as_secs_f64() * 1000.0 / count as f64,
+                }
+            );
+        }
+        let other = elapsed.saturating_sub(accounted);
+        let _ = writeln!(
+            report,
+            "  {:<10} {:>10.3} s {:>6.1}%",
+            "other",
+            other.as_secs_f64(),
+            share(other)
+        );
+        report
+    }
+}
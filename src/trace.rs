@@ -0,0 +1,63 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::assemble::parse_literal;
+use crate::cpu::Cpu;
+
+// Expand a tracepoint format string against the current state of the CPU. Placeholders are
+// enclosed in braces, and may refer to a register (`{r1}`), the program counter (`{pc}`), or a word
+// of RAM at either a literal address (`{[0x8000]}`) or the address held in a register (`{[r5]}`).
+// Literal braces may be written as `{{` and `}}`.
+pub fn format_trace(format: &str, cpu: &Cpu) -> Result<String> {
+    let mut output = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => bail!("Unterminated placeholder in \"{}\".", format),
+                    }
+                }
+                output.push_str(&format!("{:#06x}", evaluate(placeholder.trim(), cpu)?));
+            }
+            '}' => bail!("Unmatched \"}}\" in \"{}\".", format),
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+// Evaluate the contents of a single placeholder.
+fn evaluate(placeholder: &str, cpu: &Cpu) -> Result<u16> {
+    if placeholder.eq_ignore_ascii_case("pc") {
+        Ok(cpu.program_counter)
+    } else if let Some(address) = placeholder
+        .strip_prefix('[')
+        .and_then(|p| p.strip_suffix(']'))
+    {
+        let address = evaluate(address.trim(), cpu)?;
+        Ok(cpu.peek(address))
+    } else if let Some(index) = placeholder.strip_prefix(['r', 'R']) {
+        match index.parse::<usize>() {
+            Ok(0) => Ok(0x0000),
+            Ok(index) if index < 0x20 => Ok(cpu.registers[index]),
+            _ => Err(anyhow!("\"{}\" is not a valid register.", placeholder)),
+        }
+    } else {
+        parse_literal(placeholder)
+            .map_err(|_| anyhow!("\"{{{}}}\" is not a valid placeholder.", placeholder))
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+
+use crate::device::Device;
+
+// The offsets of the UART's registers.
+const DATA: u16 = 0;
+const STATUS: u16 = 1;
+
+// The bits of the status register.
+const RECEIVE_READY: u16 = 0b01;
+const TRANSMIT_READY: u16 = 0b10;
+const OVERRUN: u16 = 0b100;
+
+// A simple serial port. Writing to the data register transmits the low byte of the value, and
+// reading from it receives the next byte waiting in the receive FIFO, or 0x0000 if there isn't
+// one. The status register reports whether there is anything to receive, and whether a byte may
+// be transmitted, which is always the case since transmission is instantaneous. If received data
+// has been lost, then the status register also reports an overrun, until the status is next read.
+#[derive(Clone)]
+pub struct Uart {
+    // The bytes which are delivered to the receive FIFO whenever the UART is reset, taken from the
+    // file backing the UART.
+    input: Vec<u8>,
+    pub receive: VecDeque<u8>,
+    // Bytes transmitted since the last complete line was collected.
+    pub transmit: Vec<u8>,
+    lines: Vec<String>,
+    // Every byte transmitted since the last collection, for the guest's terminal.
+    raw: Vec<u8>,
+    overrun: bool,
+}
+
+impl Uart {
+    pub fn new(input: Vec<u8>) -> Self {
+        Self {
+            receive: input.iter().copied().collect(),
+            input,
+            transmit: Vec::new(),
+            lines: Vec::new(),
+            raw: Vec::new(),
+            overrun: false,
+        }
+    }
+}
+
+impl Device for Uart {
+    fn kind(&self) -> &'static str {
+        "uart"
+    }
+
+    fn length(&self) -> u16 {
+        2
+    }
+
+    fn register_name(&self, offset: u16) -> &str {
+        match offset {
+            DATA => "data",
+            _ => "status",
+        }
+    }
+
+    fn read(&mut self, offset: u16) -> u16 {
+        match offset {
+            DATA => self.receive.pop_front().map_or(0x0000, u16::from),
+            _ => {
+                let status = self.peek(offset);
+                self.overrun = false;
+                status
+            }
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u16) {
+        if offset == DATA {
+            self.raw.push(value as u8);
+            match value as u8 {
+                b'\n' => {
+                    let line = String::from_utf8_lossy(&self.transmit).into_owned();
+                    self.lines.push(line);
+                    self.transmit.clear();
+                }
+                byte => self.transmit.push(byte),
+            }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u16 {
+        match offset {
+            DATA => self.receive.front().map_or(0x0000, |&byte| u16::from(byte)),
+            _ => {
+                let mut status = TRANSMIT_READY;
+                if !self.receive.is_empty() {
+                    status |= RECEIVE_READY;
+                }
+                if self.overrun {
+                    status |= OVERRUN;
+                }
+                status
+            }
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let receive = self.receive.iter().copied().collect::<Vec<_>>();
+        vec![
+            format!(
+                "data {:#06x}, status {:#06x}",
+                self.peek(DATA),
+                self.peek(STATUS)
+            ),
+            format!(
+                "receive FIFO: {} bytes {:?}",
+                receive.len(),
+                String::from_utf8_lossy(&receive)
+            ),
+            format!(
+                "transmitted, awaiting newline: {:?}",
+                String::from_utf8_lossy(&self.transmit)
+            ),
+        ]
+    }
+
+    // The UART interrupts whenever there is something waiting to be received.
+    fn interrupt(&self) -> bool {
+        !self.receive.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.receive = self.input.iter().copied().collect();
+        self.transmit.clear();
+        self.overrun = false;
+    }
+
+    // An overrun loses the next byte waiting to be received, as though it had been overwritten by
+    // the byte after it before the guest got around to reading it.
+    fn inject(&mut self, fault: &str) -> Option<String> {
+        (fault == "overrun").then(|| {
+            self.overrun = true;
+            match self.receive.pop_front() {
+                Some(byte) => format!("lost received byte {:#04x}", byte),
+                None => "flagged an overrun with nothing to lose".into(),
+            }
+        })
+    }
+
+    fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.lines)
+    }
+
+    fn take_transmitted(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.raw)
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
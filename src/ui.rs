@@ -1,64 +1,548 @@
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, BorderType, Borders, Padding, Paragraph};
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap};
 
-use crate::app::App;
-use crate::disassemble::disassemble;
+use std::time::Duration;
+
+use crate::abi;
+use crate::app::{search_text, App, Condition, MINIMAP_COLUMNS, MINIMAP_LABEL_WIDTH};
+use crate::counters::COUNTERS_BASE;
+use crate::cpu::Cpu;
+use crate::exception::Exception;
+use crate::explain::{dataflow, preview, semantics, Dataflow};
+use crate::heap::Walk;
+use crate::history::History;
+use crate::memory::ROM_CONTROL;
+use crate::taint::Taint;
+use crate::terminal::{Attributes, ROWS};
+use crate::usage::{BLOCK_SIZE, EXECUTED, READ, WRITTEN};
+
+// The smallest terminal in which the UI can be laid out. The Registers pane, the command prompt,
+// and a few rows of a few words of RAM must always fit, and everything else is optional.
+const MIN_WIDTH: u16 = REGISTERS_WIDTH + RAM_MIN_WIDTH;
+const MIN_HEIGHT: u16 = PROMPT_HEIGHT + RAM_MIN_HEIGHT;
+
+// The sizes of the panes around which the layout is built.
+const REGISTERS_WIDTH: u16 = 15;
+const HISTORY_WIDTH: u16 = 25;
+const PROMPT_HEIGHT: u16 = 4;
+const CONSOLE_HEIGHT: u16 = 8;
+const CURRENT_INSTRUCTION_HEIGHT: u16 = 5;
+const FOCUS_HEIGHT: u16 = 10;
+
+// The number of registers shown on each row of an exception.
+const EXCEPTION_REGISTER_COLUMNS: usize = 4;
+
+// The most problems which the diagnostics pane lists at once, above the source of the selected one.
+const DIAGNOSTICS_ROWS: u16 = 4;
+
+// The number of ranges of addresses which the accesses pane plots, each on a row labelled by the
+// address at which it begins.
+const ACCESSES_ROWS: u16 = 8;
+const ACCESSES_LABEL_WIDTH: u16 = 7;
+
+// The RAM pane is given at least enough room for three words on each of three rows, and optional
+// panes are collapsed rather than squeezing it any further.
+const RAM_MIN_WIDTH: u16 = 4 + RAM_LABEL_WIDTH + 3 * RAM_WORD_WIDTH;
+const RAM_MIN_HEIGHT: u16 = 5;
+
+// Each row of the RAM pane begins with its address, followed by the words on that row, each of
+// which is preceded by a space.
+const RAM_LABEL_WIDTH: u16 = 7;
+const RAM_WORD_WIDTH: u16 = 7;
 
 pub fn ui(f: &mut Frame, app: &App) {
+    // The minimap records where it was drawn, so if it isn't drawn this frame, clicks mustn't land
+    // on where it used to be.
+    app.minimap_area.set(Rect::default());
+
+    let area = f.size();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        render_too_small(f, area);
+        return;
+    }
+
+    // Optional panes are shown only if there is room for them, in order of importance, so that as
+    // the terminal shrinks they collapse one at a time. Vertically, the console or the current
+    // instruction goes first, once it would leave the registers with too few rows.
+    let banner_height = if app.presenting {
+        CURRENT_INSTRUCTION_HEIGHT
+    } else {
+        CONSOLE_HEIGHT
+    };
+    let show_banner = area.height - MIN_HEIGHT >= banner_height + RAM_MIN_HEIGHT;
+    let mut spare_width = area.width - MIN_WIDTH;
+    let mut fits = |width: u16| {
+        spare_width >= width && {
+            spare_width -= width;
+            true
+        }
+    };
+    let show_history = fits(HISTORY_WIDTH);
+    let show_reference = app.shown_reference().is_some() && fits(REGISTERS_WIDTH);
+    let show_minimap = app.minimap && fits(MINIMAP_LABEL_WIDTH + MINIMAP_COLUMNS + 4);
+
     // Begin by splitting the terminals into the chunks that we will use to display various parts
     // of the ui.
     let vertical_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(2), Constraint::Length(4)])
-        .split(f.size());
+        .constraints([
+            Constraint::Length(if app.presenting && show_banner {
+                banner_height
+            } else {
+                0
+            }),
+            Constraint::Min(RAM_MIN_HEIGHT),
+            Constraint::Length(if !app.presenting && show_banner {
+                banner_height
+            } else {
+                0
+            }),
+            Constraint::Length(PROMPT_HEIGHT),
+        ])
+        .split(area);
     let horizontal_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Length(25),
-            Constraint::Min(16),
-            Constraint::Length(15),
+            Constraint::Length(if show_history { HISTORY_WIDTH } else { 0 }),
+            Constraint::Min(RAM_MIN_WIDTH),
+            Constraint::Length(REGISTERS_WIDTH),
+            Constraint::Length(if show_reference { REGISTERS_WIDTH } else { 0 }),
         ])
-        .split(vertical_chunks[0]);
+        .split(vertical_chunks[1]);
 
     // The command chunk will display the command prompt.
-    let command_prompt_chunk = vertical_chunks[1];
+    let command_prompt_chunk = vertical_chunks[3];
+    // The console chunk will display output logged by tracepoints. It is hidden while presenting,
+    // to leave more room for everything else.
+    let console_chunk = vertical_chunks[2];
+    // The current instruction chunk will display the instruction which is about to be executed
+    // along with an explanation of its semantics, but only while presenting.
+    let current_instruction_chunk = vertical_chunks[0];
     // The registers chunk will display the contents of the CPU's registers.
     let registers_chunk = horizontal_chunks[2];
+    // The reference registers chunk will display the contents of the reference CPU's registers,
+    // if there is one.
+    let reference_registers_chunk = horizontal_chunks[3];
+    // The focus chunk will display the focused register in large digits, if a register is
+    // focused, taking space from the top of the RAM chunk.
+    // The devices chunk will display the state of every device, if the devices pane is shown,
+    // taking space from the bottom of the RAM chunk.
+    let device_lines = if app.device_pane {
+        app.shown_cpu()
+            .devices
+            .attached
+            .iter()
+            .map(|attached| attached.describe().len() as u16)
+            .sum::<u16>()
+            .max(1)
+    } else {
+        0
+    };
+    // The minimap chunk will display an overview of the whole address space, if the minimap is
+    // shown, taking space from the right of the RAM chunk.
+    let minimap_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(RAM_MIN_WIDTH),
+            Constraint::Length(if show_minimap {
+                MINIMAP_LABEL_WIDTH + MINIMAP_COLUMNS + 4
+            } else {
+                0
+            }),
+        ])
+        .split(horizontal_chunks[1]);
+    let minimap_chunk = Rect {
+        height: minimap_chunks[1].height.min(MINIMAP_COLUMNS + 2),
+        ..minimap_chunks[1]
+    };
+    // The panes which share the middle column with the RAM pane are likewise collapsed if they
+    // would leave it too short.
+    let mut spare_height = minimap_chunks[0].height.saturating_sub(RAM_MIN_HEIGHT);
+    let mut fits = |height: u16| {
+        spare_height >= height && {
+            spare_height -= height;
+            true
+        }
+    };
+    let focus_height = if app.focus.is_some() { FOCUS_HEIGHT } else { 0 };
+    let show_focus = app.focus.is_some() && fits(focus_height);
+    let devices_height = device_lines.min(12) + 2;
+    let show_devices = app.device_pane && fits(devices_height);
+    let bookmarks_height = (app.bookmarks.len() as u16).clamp(1, 8) + 2;
+    let show_bookmarks = app.bookmark_pane && fits(bookmarks_height);
+    let frames = if app.stack_pane {
+        app.abi.unwind(app.shown_cpu())
+    } else {
+        Vec::new()
+    };
+    let stack_height = (frames.len() as u16).min(8) + 2;
+    let show_stack = app.stack_pane && fits(stack_height);
+    let walk = match (&app.heap, app.heap_pane) {
+        (Some(heap), true) => Some(heap.walk(app.shown_cpu())),
+        _ => None,
+    };
+    let heap_height = walk.as_ref().map_or(0, |walk| {
+        ((walk.problems.len() + walk.blocks.len()) as u16).clamp(1, 8) + 2
+    });
+    let show_heap = walk.is_some() && fits(heap_height);
+    let watches_height = (app.watches.len() as u16).min(8) + 2;
+    let show_watches = !app.watches.is_empty() && fits(watches_height);
+    let diagnostics_height =
+        (app.diagnostics.len() as u16).clamp(1, DIAGNOSTICS_ROWS) + app.excerpt.len() as u16 + 2;
+    let show_diagnostics = app.diagnostics_pane && fits(diagnostics_height);
+    let show_accesses = app.accesses_pane && fits(ACCESSES_ROWS + 2);
+    // The terminal pane is shown with as many rows of the screen as there is room for, down to a
+    // few, since it is large enough that it would rarely fit otherwise.
+    let terminal_height = match app.terminal_pane {
+        true => (5..=ROWS as u16 + 2).rev().find(|&height| fits(height)),
+        false => None,
+    };
+    let show_terminal = terminal_height.is_some();
+    let middle_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(if show_focus { focus_height } else { 0 }),
+            Constraint::Min(RAM_MIN_HEIGHT),
+            Constraint::Length(if show_devices { devices_height } else { 0 }),
+            Constraint::Length(if show_bookmarks { bookmarks_height } else { 0 }),
+            Constraint::Length(if show_stack { stack_height } else { 0 }),
+            Constraint::Length(if show_heap { heap_height } else { 0 }),
+            Constraint::Length(if show_watches { watches_height } else { 0 }),
+            Constraint::Length(if show_diagnostics {
+                diagnostics_height
+            } else {
+                0
+            }),
+            Constraint::Length(if show_accesses { ACCESSES_ROWS + 2 } else { 0 }),
+            Constraint::Length(terminal_height.unwrap_or(0)),
+        ])
+        .split(minimap_chunks[0]);
+    let focus_chunk = middle_chunks[0];
     // The RAM chunk will display the contents of RAM in the vicinity of the program counter.
-    let ram_chunk = horizontal_chunks[1];
+    let ram_chunk = middle_chunks[1];
+    let devices_chunk = middle_chunks[2];
+    // The bookmarks chunk will list the bookmarks, if the bookmarks pane is shown, beneath the
+    // devices chunk.
+    let bookmarks_chunk = middle_chunks[3];
+    // The call stack chunk will display the frames of the guest's call stack, if the call stack
+    // pane is shown, beneath the bookmarks chunk.
+    let stack_chunk = middle_chunks[4];
+    // The heap chunk will display the blocks of the guest's heap, if the heap pane is shown,
+    // beneath the call stack chunk.
+    let heap_chunk = middle_chunks[5];
+    // The watches chunk will display the value of every watched expression, if there are any,
+    // beneath the heap chunk.
+    let watches_chunk = middle_chunks[6];
+    // The diagnostics chunk will display the problems reported by the assembler or by a build, if
+    // the diagnostics pane is shown, beneath the watches chunk.
+    let diagnostics_chunk = middle_chunks[7];
+    // The accesses chunk will plot the program's recent loads and stores, if the accesses pane is
+    // shown, beneath the diagnostics chunk.
+    let accesses_chunk = middle_chunks[8];
+    // The terminal chunk will display the guest's terminal, if the terminal pane is shown, beneath
+    // the accesses chunk.
+    let terminal_chunk = middle_chunks[9];
     // The instructions chunk will display a list of recently executed instructions, as well as the
     // instruction which will be executed on the next step.
     let instruction_history_chunk = horizontal_chunks[0];
 
     // Call all of the rendering functions.
     render_command_prompt(f, app, command_prompt_chunk);
-    render_registers(f, app, registers_chunk);
-    render_ram(f, app, ram_chunk);
-    render_instruction_history(f, app, instruction_history_chunk);
+    if show_banner {
+        if app.presenting {
+            pane(f, app, "instruction", current_instruction_chunk, |f| {
+                render_current_instruction(f, app, current_instruction_chunk)
+            });
+        } else {
+            pane(f, app, "console", console_chunk, |f| {
+                render_console(f, app, console_chunk)
+            });
+        }
+    }
+    pane(f, app, "registers", registers_chunk, |f| {
+        render_registers(f, app, registers_chunk)
+    });
+    if show_reference {
+        pane(f, app, "reference", reference_registers_chunk, |f| {
+            render_reference_registers(f, app, reference_registers_chunk)
+        });
+    }
+    if show_focus {
+        pane(f, app, "focus", focus_chunk, |f| {
+            render_focus(f, app, focus_chunk)
+        });
+    }
+    pane(f, app, "ram", ram_chunk, |f| render_ram(f, app, ram_chunk));
+    if show_minimap {
+        let drawn = pane(f, app, "minimap", minimap_chunk, |f| {
+            render_minimap(f, app, minimap_chunk)
+        });
+        // The minimap must still take clicks when what it showed is drawn again.
+        if !drawn {
+            app.minimap_area.set(minimap_block().inner(minimap_chunk));
+        }
+    }
+    if show_devices {
+        pane(f, app, "devices", devices_chunk, |f| {
+            render_devices(f, app, devices_chunk)
+        });
+    }
+    if show_bookmarks {
+        pane(f, app, "bookmarks", bookmarks_chunk, |f| {
+            render_bookmarks(f, app, bookmarks_chunk)
+        });
+    }
+    if show_stack {
+        pane(f, app, "stack", stack_chunk, |f| {
+            render_call_stack(f, &frames, stack_chunk)
+        });
+    }
+    if let Some(walk) = walk.as_ref().filter(|_| show_heap) {
+        pane(f, app, "heap", heap_chunk, |f| {
+            render_heap(f, walk, heap_chunk)
+        });
+    }
+    if show_watches {
+        pane(f, app, "watches", watches_chunk, |f| {
+            render_watches(f, app, watches_chunk)
+        });
+    }
+    if show_diagnostics {
+        pane(f, app, "diagnostics", diagnostics_chunk, |f| {
+            render_diagnostics(f, app, diagnostics_chunk)
+        });
+    }
+    if show_accesses {
+        pane(f, app, "accesses", accesses_chunk, |f| {
+            render_accesses(f, app, accesses_chunk)
+        });
+    }
+    if show_terminal {
+        pane(f, app, "terminal", terminal_chunk, |f| {
+            render_terminal(f, app, terminal_chunk)
+        });
+    }
+    if show_history {
+        pane(f, app, "history", instruction_history_chunk, |f| {
+            render_instruction_history(f, app, instruction_history_chunk)
+        });
+    }
+    // An exception is drawn over everything but the command prompt, until it is dismissed.
+    if let Some(exception) = &app.exception {
+        let rect = Rect {
+            height: area.height - PROMPT_HEIGHT,
+            ..area
+        };
+        render_exception(f, exception, rect);
+    }
+}
+
+// Render a pane, unless it is only redrawn once the simulation halts and the simulation is running,
+// in which case what it showed when it was last rendered is drawn again in its place. Returns
+// whether or not the pane was rendered afresh.
+fn pane(
+    f: &mut Frame,
+    app: &App,
+    name: &'static str,
+    rect: Rect,
+    render: impl FnOnce(&mut Frame),
+) -> bool {
+    if !app.halted_panes.contains(name) {
+        render(f);
+        return true;
+    }
+    let mut cache = app.pane_cache.borrow_mut();
+    // A pane which has moved or changed size since it was last rendered has to be rendered again,
+    // since what it showed no longer fits.
+    if app.running {
+        if let Some((cached, cells)) = cache.get(name).filter(|(cached, _)| *cached == rect) {
+            let buffer = f.buffer_mut();
+            for (i, cell) in cells.iter().enumerate() {
+                let x = cached.x + i as u16 % cached.width;
+                let y = cached.y + i as u16 / cached.width;
+                *buffer.get_mut(x, y) = cell.clone();
+            }
+            return false;
+        }
+    }
+    render(f);
+    let buffer = f.buffer_mut();
+    let cells = rect
+        .rows()
+        .flat_map(|row| row.columns())
+        .map(|position| buffer.get(position.x, position.y).clone())
+        .collect();
+    cache.insert(name, (rect, cells));
+    true
+}
+
+// Explain that the terminal is too small to lay out the UI, in place of the UI itself.
+fn render_too_small(f: &mut Frame, area: Rect) {
+    let lines = vec![
+        Line::styled(
+            "Terminal too small",
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::from(format!("{}x{}", area.width, area.height)),
+        Line::from(format!("ilo needs at least {}x{}", MIN_WIDTH, MIN_HEIGHT)),
+        Line::from("Ctrl-C quits"),
+    ];
+    // The message is centered vertically as well as horizontally, as far as it fits.
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    let rect = Rect {
+        y: area.y + top,
+        height: area.height - top,
+        ..area
+    };
+    let paragraph = Paragraph::new(lines).centered().wrap(Wrap { trim: true });
+    f.render_widget(paragraph, rect);
 }
 
 // Render the current status of the registers in a given area of the frame.
 pub fn render_registers(f: &mut Frame, app: &App, rect: Rect) {
-    // The block in which the registers are displayed.
-    let block = Block::default()
-        .title("Registers")
+    let dataflow = app.presenting.then(|| dataflow(app.shown_cpu()));
+    let cursor = app
+        .register_cursor
+        .map(|register| (register, app.register_edit.as_deref()));
+    render_cpu_registers(
+        f,
+        "Registers",
+        app.shown_cpu(),
+        app.shown_reference(),
+        dataflow.as_ref(),
+        shown_taint(app),
+        cursor,
+        rect,
+    );
+}
+
+// Render the current status of the reference CPU's registers in a given area of the frame.
+pub fn render_reference_registers(f: &mut Frame, app: &App, rect: Rect) {
+    if let Some(reference) = app.shown_reference() {
+        render_cpu_registers(
+            f,
+            "Reference",
+            reference,
+            Some(app.shown_cpu()),
+            None,
+            None,
+            None,
+            rect,
+        );
+    }
+}
+
+// Render the registers of a CPU, highlighting any which differ from those of another CPU that it
+// is being compared against, as well as any which are read or written by the next instruction. If
+// the pane is selected, then the register under the cursor is highlighted, along with the value
+// being typed into it, if it is being edited. Tainted registers are underlined.
+#[allow(clippy::too_many_arguments)]
+fn render_cpu_registers(
+    f: &mut Frame,
+    title: &str,
+    cpu: &Cpu,
+    other: Option<&Cpu>,
+    dataflow: Option<&Dataflow>,
+    taint: Option<&Taint>,
+    cursor: Option<(usize, Option<&str>)>,
+    rect: Rect,
+) {
+    // The block in which the registers are displayed, whose border is highlighted while the pane
+    // is selected.
+    let mut block = Block::default()
+        .title(title.to_string())
         .borders(Borders::ALL)
+        .border_style(if cursor.is_some() {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        })
         .padding(Padding::horizontal(1));
+    // The interrupt lines which are asserted are noted along the bottom border, since nothing else
+    // shows that a device is waiting to be polled, or that its handler is about to be entered.
+    // After them comes the state of the interrupt controller, if there is one.
+    let mut spans = Vec::new();
+    let asserted = cpu.devices.asserted();
+    if !asserted.is_empty() {
+        let lines = asserted
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        spans.push(Span::styled(
+            format!("irq {}", lines.join(" ")),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(summary) = cpu.interrupts.summary() {
+        let color = if cpu.interrupts.handlers.is_empty() {
+            Color::DarkGray
+        } else {
+            Color::Magenta
+        };
+        if !spans.is_empty() {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(summary, Style::default().fg(color)));
+    }
+    if !spans.is_empty() {
+        block = block.title(Title::from(Line::from(spans)).position(Position::Bottom));
+    }
+
+    // Registers which differ from the other CPU are highlighted in red.
+    let style = |differs: bool, tainted: bool| {
+        let style = if differs {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        };
+        if tainted {
+            style.patch(TAINTED_STYLE)
+        } else {
+            style
+        }
+    };
 
     // These lines contain the actual data within the general purpose registers.
-    let mut lines: Vec<Line> = app
-        .cpu
+    let mut lines: Vec<Line> = cpu
         .registers
         .iter()
         .zip(0..32)
         .map(|(c, a)| {
+            let differs = a != 0 && other.is_some_and(|o| o.registers[a] != *c);
+            let tainted = taint.is_some_and(|taint| taint.register(a));
+            // Registers written by the next instruction are highlighted in green, and registers
+            // which are only read are highlighted in yellow.
+            let name_style = match dataflow {
+                Some(dataflow) if dataflow.writes.contains(&a) => {
+                    Style::default().fg(Color::Black).bg(Color::Green)
+                }
+                Some(dataflow) if dataflow.reads.contains(&a) => {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                }
+                _ => Style::default(),
+            };
+            let (name_style, value) = match cursor {
+                Some((register, Some(edit))) if register == a => (
+                    name_style.add_modifier(Modifier::REVERSED),
+                    Span::styled(
+                        format!("{:<6}", format!("{}█", edit)),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                ),
+                Some((register, None)) if register == a => (
+                    name_style.add_modifier(Modifier::REVERSED),
+                    Span::styled(format!("{:#06x}", c), style(differs, tainted)),
+                ),
+                _ => (
+                    name_style,
+                    Span::styled(format!("{:#06x}", c), style(differs, tainted)),
+                ),
+            };
             Line::from(vec![
-                Span::styled(format!("r{:02}: ", a), Style::default()),
-                if a == 0 {
-                    Span::styled("0x0000", Style::default())
-                } else {
-                    Span::styled(format!("{:#06x}", c), Style::default())
-                },
+                Span::styled(format!("r{:02}:", a), name_style),
+                Span::raw(" "),
+                value,
             ])
         })
         .collect();
@@ -68,81 +552,1022 @@ pub fn render_registers(f: &mut Frame, app: &App, rect: Rect) {
     lines.push(Line::from(vec![
         Span::styled("pc:  ", Style::default()),
         Span::styled(
-            format!("{:#06x}", app.cpu.program_counter),
-            Style::default(),
+            format!("{:#06x}", cpu.program_counter),
+            style(
+                other.is_some_and(|o| o.program_counter != cpu.program_counter),
+                false,
+            ),
         ),
     ]));
 
+    // If the pane is too short to show every register, then it is scrolled just far enough to keep
+    // the cursor in view.
+    let height = usize::from(rect.height.saturating_sub(2)).max(1);
+    let scroll = cursor.map_or(0, |(register, _)| (register + 1).saturating_sub(height));
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((scroll as u16, 0))
+        .centered();
+    f.render_widget(paragraph, rect);
+}
+
+// Glyphs for each hexadecimal digit, three columns wide and five rows tall, used to display the
+// focused register in large digits.
+const GLYPHS: [[&str; 5]; 0x10] = [
+    ["███", "█ █", "█ █", "█ █", "███"],
+    [" █ ", "██ ", " █ ", " █ ", "███"],
+    ["███", "  █", "███", "█  ", "███"],
+    ["███", "  █", "███", "  █", "███"],
+    ["█ █", "█ █", "███", "  █", "  █"],
+    ["███", "█  ", "███", "  █", "███"],
+    ["███", "█  ", "███", "█ █", "███"],
+    ["███", "  █", "  █", "  █", "  █"],
+    ["███", "█ █", "███", "█ █", "███"],
+    ["███", "█ █", "███", "  █", "███"],
+    ["███", "█ █", "███", "█ █", "█ █"],
+    ["██ ", "█ █", "██ ", "█ █", "██ "],
+    ["███", "█  ", "█  ", "█  ", "███"],
+    ["██ ", "█ █", "█ █", "█ █", "██ "],
+    ["███", "█  ", "███", "█  ", "███"],
+    ["███", "█  ", "███", "█  ", "█  "],
+];
+
+// Render the focused register in large digits, along with several interpretations of its value.
+pub fn render_focus(f: &mut Frame, app: &App, rect: Rect) {
+    let Some(register) = app.focus else {
+        return;
+    };
+    let value = app.shown_cpu().registers[register];
+
+    // The block in which the focused register is displayed.
+    let block = Block::default()
+        .title(format!("Focus: r{:02}", register))
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    // Each row of the large digits is built up from the corresponding row of each digit's glyph.
+    let mut lines: Vec<Line> = (0..5)
+        .map(|row| {
+            let digits = (0..4)
+                .rev()
+                .map(|digit| GLYPHS[usize::from((value >> (digit * 4)) & 0xf)][row])
+                .collect::<Vec<_>>();
+            Line::styled(
+                digits.join(" "),
+                Style::default().add_modifier(Modifier::BOLD),
+            )
+        })
+        .collect();
+
+    // Bytes which aren't printable ASCII characters are displayed as a period.
+    let ascii = value
+        .to_be_bytes()
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                char::from(byte)
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>();
+    let binary = format!("{:016b}", value);
+
+    lines.push(Line::default());
+    lines.push(Line::from(format!(
+        "binary: {} {} {} {}",
+        &binary[0..4],
+        &binary[4..8],
+        &binary[8..12],
+        &binary[12..16]
+    )));
+    lines.push(Line::from(format!(
+        "unsigned: {}  signed: {}  ascii: \"{}\"",
+        value, value as i16, ascii
+    )));
+
     let paragraph = Paragraph::new(lines).block(block).centered();
     f.render_widget(paragraph, rect);
 }
 
 // Render a snapshot of RAM containing the program counter in a given area of the frame.
 pub fn render_ram(f: &mut Frame, app: &App, rect: Rect) {
-    // The block in which the snapshot of RAM is displayed.
+    // The block in which the snapshot of RAM is displayed. The title notes whether the pane is
+    // pinned, since otherwise a pinned pane looks just like one which follows the program counter.
     let block = Block::default()
-        .title("RAM")
+        .title(match app.ram_pin {
+            Some(address) => format!("RAM (pinned at {:#06x})", address),
+            None => "RAM".to_string(),
+        })
+        .title(Title::from(legend(app)).position(Position::Bottom))
         .borders(Borders::ALL)
         .padding(Padding::horizontal(1));
 
     // There's a bit of annoying math to be done to determine which page of RAM ought to be
     // displayed.
     let inner = block.inner(rect);
-    let page_size = inner.height * ((inner.width - 7) / 7);
+    let columns = (inner.width.saturating_sub(RAM_LABEL_WIDTH) / RAM_WORD_WIDTH).max(1);
+    let page_size = inner.height.max(1) * columns;
+
+    // While presenting, the address accessed by the next instruction is highlighted, in green if it
+    // is written and in yellow if it is read.
+    let memory = if app.presenting {
+        dataflow(app.shown_cpu()).memory
+    } else {
+        None
+    };
+
+    // A pinned pane begins exactly at the pinned address, while one which follows the program
+    // counter shows whichever page the program counter falls within.
+    // NOTE: Pages needn't divide the address space evenly, so addresses wrap around past 0xffff.
+    let base = match app.ram_pin {
+        Some(address) => address,
+        None => (app.shown_cpu().program_counter / page_size) * page_size,
+    };
+    // Only the rows which look different from when they were last drawn are formatted again. On a
+    // large terminal, formatting every word of the page each frame adds up, but from one frame to
+    // the next hardly any of them change.
+    let mut rows = app.ram_rows.borrow_mut();
+    rows.resize_with(usize::from(inner.height), RamRow::default);
+    for (row, cached) in (0..inner.height).zip(rows.iter_mut()) {
+        let address = base.wrapping_add(row * columns);
+        let words = (0..columns)
+            .map(|column| ram_word(app, address.wrapping_add(column), memory))
+            .collect::<Vec<_>>();
+        if cached.address != address || cached.words != words {
+            *cached = RamRow {
+                address,
+                line: ram_line(address, &words),
+                words,
+            };
+        }
+    }
+
+    f.render_widget(block, rect);
+    for (row, cached) in (0..inner.height).zip(rows.iter()) {
+        // The rows are centered, as a paragraph would center them.
+        let width = cached.line.width() as u16;
+        let x = inner.x + inner.width.saturating_sub(width) / 2;
+        f.buffer_mut()
+            .set_line(x, inner.y + row, &cached.line, inner.width);
+    }
+}
+
+// A row of the RAM pane as it was last drawn, along with how each of its words looked, by which it
+// can be told whether the row needs to be formatted again.
+#[derive(Default)]
+pub struct RamRow {
+    address: u16,
+    words: Vec<Word>,
+    line: Line<'static>,
+}
+
+// How a word of the RAM pane looks: its value, unless nothing is connected at its address, and its
+// style. A highlighted word is set apart from the words beside it, by leaving the space before it
+// unstyled.
+#[derive(Clone, Copy, PartialEq)]
+struct Word {
+    value: Option<u16>,
+    style: Style,
+    highlighted: bool,
+}
+
+fn ram_word(app: &App, address: u16, memory: Option<(u16, bool)>) -> Word {
+    let cpu = app.shown_cpu();
+    let highlight = if address == cpu.program_counter {
+        Some(Style::default().fg(Color::Black).bg(Color::White))
+    } else if let Some((_, write)) = memory.filter(|&(a, _)| a == address) {
+        Some(
+            Style::default()
+                .fg(Color::Black)
+                .bg(if write { Color::Green } else { Color::Yellow }),
+        )
+    } else if app
+        .search
+        .as_ref()
+        .is_some_and(|&(_, current)| current == address)
+    {
+        Some(CURRENT_MATCH_STYLE)
+    } else if let Some(style) = change_style(app, address) {
+        Some(style)
+    } else if app.breakpoints.contains_key(&address) {
+        Some(BREAKPOINT_STYLE)
+    } else if app.search.as_ref().is_some_and(|(pattern, _)| {
+        cpu.memory.decode(address).is_some()
+            && pattern.is_match(&search_text(cpu, &app.starts, address))
+    }) {
+        Some(MATCH_STYLE)
+    } else {
+        None
+    };
+    Word {
+        // Addresses beyond the end of the fitted RAM are shown as dashes if they aren't connected
+        // to anything at all.
+        value: cpu.memory.decode(address).map(|_| cpu.peek(address)),
+        style: highlight.unwrap_or_else(|| region_style(app, address)),
+        highlighted: highlight.is_some(),
+    }
+}
+
+fn ram_line(address: u16, words: &[Word]) -> Line<'static> {
+    let mut spans = vec![Span::styled(format!("{:#06x}:", address), Style::default())];
+    for word in words {
+        let text = match word.value {
+            Some(value) => format!("{:#06x}", value),
+            None => "------".to_string(),
+        };
+        if word.highlighted {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(text, word.style));
+        } else {
+            spans.push(Span::styled(format!(" {}", text), word.style));
+        }
+    }
+    Line::from(spans)
+}
+
+// The styles in which words of RAM are displayed, according to how they have been used.
+const CODE_STYLE: Style = Style::new().fg(Color::Cyan);
+const WRITTEN_STYLE: Style = Style::new().fg(Color::Green);
+const READ_STYLE: Style = Style::new().fg(Color::Yellow);
+const MMIO_STYLE: Style = Style::new().fg(Color::LightRed);
+const UNTOUCHED_STYLE: Style = Style::new().fg(Color::DarkGray);
+const TAINTED_STYLE: Style = Style::new().add_modifier(Modifier::UNDERLINED);
+const BREAKPOINT_STYLE: Style = Style::new().fg(Color::White).bg(Color::Red);
+
+// The styles in which matches for the current search are displayed in the RAM pane.
+const MATCH_STYLE: Style = Style::new().fg(Color::Black).bg(Color::Blue);
+const CURRENT_MATCH_STYLE: Style = Style::new().fg(Color::Black).bg(Color::LightBlue);
+
+// Determine the style of a word of RAM from how the program has used it. Addresses beyond the end
+// of the fitted RAM are dimmed, whether they mirror the RAM beneath them or aren't connected.
+fn region_style(app: &App, address: u16) -> Style {
+    let cpu = app.shown_cpu();
+    let mmio = cpu.devices.peek(address).is_some()
+        || cpu.counters.contains(address)
+        || (address == ROM_CONTROL && cpu.memory.rom.as_ref().is_some_and(|rom| rom.mapped));
+    let mut style = match cpu.memory.decode(address).map(|index| cpu.usage.get(index)) {
+        _ if mmio => MMIO_STYLE,
+        Some(flags) if flags & EXECUTED != 0 => CODE_STYLE,
+        Some(flags) if flags & WRITTEN != 0 => WRITTEN_STYLE,
+        Some(flags) if flags & READ != 0 => READ_STYLE,
+        _ => UNTOUCHED_STYLE,
+    };
+    if shown_taint(app).is_some_and(|taint| taint.memory(cpu, address)) {
+        style = style.patch(TAINTED_STYLE);
+    }
+    if usize::from(address) >= cpu.memory.size {
+        style.add_modifier(Modifier::DIM)
+    } else {
+        style
+    }
+}
+
+// A legend explaining the colors of the RAM pane, which is displayed along its bottom border.
+fn legend(app: &App) -> Line<'static> {
+    let mut legend = Line::from(vec![
+        Span::styled("code", CODE_STYLE),
+        Span::raw(" "),
+        Span::styled("written", WRITTEN_STYLE),
+        Span::raw(" "),
+        Span::styled("read", READ_STYLE),
+        Span::raw(" "),
+        Span::styled("mmio", MMIO_STYLE),
+        Span::raw(" "),
+        Span::styled("break", BREAKPOINT_STYLE),
+        Span::raw(" "),
+        Span::styled("untouched", UNTOUCHED_STYLE),
+    ]);
+    if app.taint.is_some() {
+        legend.spans.push(Span::raw(" "));
+        legend.spans.push(Span::styled("tainted", TAINTED_STYLE));
+    }
+    legend
+}
+
+// The taint of the machine as it's shown, if taint is being tracked. Taint is only tracked in the
+// live machine, so it's meaningless while the view is frozen.
+fn shown_taint(app: &App) -> Option<&Taint> {
+    app.taint.as_ref().filter(|_| app.frozen.is_none())
+}
+
+// The block in which the minimap is displayed, which also determines where clicks on it land.
+fn minimap_block() -> Block<'static> {
+    Block::default()
+        .title("Minimap")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1))
+}
+
+// Render an overview of the whole address space, in which each cell summarizes a block of memory.
+// The color of a cell shows how the block has been used, in the same way as the RAM pane, and its
+// glyph shows how recently the block was used. The block containing the program counter is
+// highlighted.
+pub fn render_minimap(f: &mut Frame, app: &App, rect: Rect) {
+    let block = minimap_block();
+    let inner = block.inner(rect);
+    app.minimap_area.set(inner);
 
+    let cpu = app.shown_cpu();
     let mut lines = Vec::new();
-    for row in 0..inner.height {
-        let base = (app.cpu.program_counter / page_size) * page_size;
-        let mut spans = vec![Span::styled(
-            format!("{:#06x}:", base + row * ((inner.width - 7) / 7)),
-            Style::default(),
-        )];
-        for column in 0..((inner.width - 7) / 7) {
-            let address = base + row * ((inner.width - 7) / 7) + column;
-            if address == app.cpu.program_counter {
+    for row in 0..MINIMAP_COLUMNS {
+        // Each row covers 0x1000 words, so it is labelled with the address at which it begins.
+        let mut spans = vec![Span::raw(format!("{:x}000 ", row))];
+        for column in 0..MINIMAP_COLUMNS {
+            let start = (row * MINIMAP_COLUMNS + column) * BLOCK_SIZE as u16;
+            let end = start + (BLOCK_SIZE as u16 - 1);
+            let Some(index) = cpu.memory.decode(start) else {
                 spans.push(Span::raw(" "));
-                spans.push(Span::styled(
-                    format!("{:#06x}", app.cpu.ram[usize::from(address)]),
-                    Style::default().fg(Color::Black).bg(Color::White),
-                ));
-            } else {
-                spans.push(Span::styled(
-                    format!(" {:#06x}", app.cpu.ram[usize::from(address)]),
-                    Style::default(),
-                ));
+                continue;
+            };
+
+            let usage = index / BLOCK_SIZE;
+            let flags = cpu.usage.block(usage);
+            let mmio =
+                cpu.devices.attached.iter().any(|attached| {
+                    let last = u32::from(attached.base) + u32::from(attached.device.length()) - 1;
+                    u32::from(attached.base) <= u32::from(end) && u32::from(start) <= last
+                }) || (cpu.counters.mapped && start <= COUNTERS_BASE && COUNTERS_BASE <= end);
+            let mut style = match flags {
+                _ if mmio => MMIO_STYLE,
+                _ if flags & EXECUTED != 0 => CODE_STYLE,
+                _ if flags & WRITTEN != 0 => WRITTEN_STYLE,
+                _ if flags & READ != 0 => READ_STYLE,
+                _ => UNTOUCHED_STYLE,
+            };
+            if (start..=end).contains(&cpu.program_counter) {
+                style = style.add_modifier(Modifier::REVERSED);
             }
+
+            // The more recently the block was used, the more solid its glyph.
+            let glyph = match cpu.usage.last_used(usage) {
+                Some(last) => match cpu.counters.instructions.wrapping_sub(last) {
+                    0..=0x3ff => "█",
+                    0x400..=0xffff => "▓",
+                    _ => "▒",
+                },
+                None => "·",
+            };
+            spans.push(Span::styled(glyph, style));
         }
         lines.push(Line::from(spans));
     }
 
-    let paragraph = Paragraph::new(lines)
-        .block(block)
-        .alignment(Alignment::Center);
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+// Determine how a word in a pinned RAM pane should be highlighted, if it has changed recently. The
+// highlight fades in two stages, so that the most recent changes stand out from the rest.
+fn change_style(app: &App, address: u16) -> Option<Style> {
+    // Changes are tracked in the live RAM, so they are meaningless while the view is frozen.
+    if app.frozen.is_some() {
+        return None;
+    }
+    let index = app.shown_cpu().memory.decode(address)?;
+    let age = app.ram_changed.get(index).copied().flatten()?.elapsed();
+    if age < RECENT_CHANGE {
+        Some(Style::default().fg(Color::Black).bg(Color::Magenta))
+    } else if age < FADING_CHANGE {
+        Some(Style::default().fg(Color::Magenta))
+    } else {
+        None
+    }
+}
+
+// How long a changed word stays brightly highlighted, and how long it takes to fade away entirely.
+const RECENT_CHANGE: Duration = Duration::from_millis(250);
+const FADING_CHANGE: Duration = Duration::from_millis(1000);
+
+// Whether or not the UI is still changing by itself, so that it must be redrawn even though
+// nothing else has happened.
+pub fn animating(app: &App) -> bool {
+    app.ram_last_changed
+        .is_some_and(|changed| changed.elapsed() < FADING_CHANGE)
+}
+
+// Render the instruction which is about to be executed, along with an explanation of what it
+// does.
+pub fn render_current_instruction(f: &mut Frame, app: &App, rect: Rect) {
+    // The block in which the current instruction is displayed.
+    let block = Block::default()
+        .title("Current Instruction")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let cpu = app.shown_cpu();
+    let instruction = cpu.peek(cpu.program_counter);
+    let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+    // The line between the instruction and its explanation is left blank, unless the instruction
+    // is known to be the immediate of another, in which case that's called out there.
+    let misaligned = match app.starts.immediate_of(cpu, cpu.program_counter) {
+        Some(owner) => Line::styled(
+            format!(
+                "Misaligned: this is the immediate of {} at {:#06x}",
+                app.disassembly(cpu.peek(owner), instruction).trim(),
+                owner
+            ),
+            Style::default().fg(Color::Red),
+        ),
+        None => Line::default(),
+    };
+    let paragraph = Paragraph::new(vec![
+        Line::styled(
+            format!(
+                "{:#06x}: {}",
+                cpu.program_counter,
+                app.disassembly(instruction, immediate)
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        misaligned,
+        Line::from(semantics(instruction, immediate)),
+    ])
+    .block(block)
+    .centered();
     f.render_widget(paragraph, rect);
 }
 
 // Render the current command prompt.
 pub fn render_command_prompt(f: &mut Frame, app: &App, rect: Rect) {
-    // The block in which the command prompt is displayed.
+    // The block in which the command prompt is displayed, whose border is highlighted while the
+    // view has travelled back in time, so that it isn't mistaken for the present.
+    let travelled = app.frozen.as_ref().and_then(|frozen| frozen.step);
+    let mut title = match &app.profile {
+        Some(profile) => format!("Command Prompt ({} profile)", profile.name),
+        None => "Command Prompt".into(),
+    };
+    if let Some(step) = travelled {
+        title.push_str(&format!(" (time travel: step {} of {})", step, app.steps));
+    }
     let block = Block::default()
-        .title("Command Prompt")
-        .borders(Borders::ALL);
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(if travelled.is_some() {
+            Style::default().fg(Color::Magenta)
+        } else {
+            Style::default()
+        });
+
+    // While a LOAD, a BUILD, a long STEP, or a RUN is in progress, report on its progress in place
+    // of the result of the last command.
+    let progress = if let Some(load) = &app.pending_load {
+        Some(match load.total {
+            Some(total) => format!(
+                "Loading {}: {} of {} bytes read. Press Esc to cancel.",
+                load.filename, load.read, total
+            ),
+            None => format!(
+                "Loading {}: {} bytes read. Press Esc to cancel.",
+                load.filename, load.read
+            ),
+        })
+    } else if let Some(build) = &app.pending_build {
+        Some(format!(
+            "Building with {}. Press Esc to cancel.",
+            build.command
+        ))
+    } else if let Some((done, total)) = app.pending_steps {
+        Some(format!(
+            "Stepping simulation: {:#06x} of {:#06x} steps taken. Press Esc to cancel.",
+            done, total
+        ))
+    } else if let Some(pending) = &app.pending_condition {
+        let condition = match &pending.condition {
+            Condition::Expression { expression, until } => {
+                format!("until {} is {}", expression.text, until)
+            }
+            Condition::Next(until) => format!("to {}", until.describe()),
+        };
+        Some(format!(
+            "Stepping simulation {}: {:#06x} steps taken. Press Esc to cancel.",
+            condition, pending.steps
+        ))
+    } else if app.running && app.frozen.is_some() {
+        Some(format!(
+            "Running simulation: {} instructions executed. The view is frozen, so press Ctrl-F to make it live again, or Esc to halt.",
+            app.run_count
+        ))
+    } else if app.running {
+        Some(format!(
+            "Running simulation: {} instructions executed. Press Esc to halt, or Ctrl-F to freeze the view.",
+            app.run_count
+        ))
+    } else {
+        None
+    };
 
     let paragraph = Paragraph::new(vec![
-        match &app.command_result {
-            Ok(message) => Line::styled(format!("{}", message), Style::default().fg(Color::Green)),
-            Err(error) => Line::styled(format!("{}", error), Style::default().fg(Color::Red)),
+        match (&progress, &app.command_result) {
+            (Some(progress), _) => {
+                Line::styled(progress.clone(), Style::default().fg(Color::Yellow))
+            }
+            (None, Ok(message)) => {
+                Line::styled(message.to_string(), Style::default().fg(Color::Green))
+            }
+            (None, Err(error)) => {
+                Line::styled(format!("{}", error), Style::default().fg(Color::Red))
+            }
+        },
+        // While the Registers pane is selected, typing goes to the pane rather than the prompt,
+        // so the prompt explains how to use the pane instead.
+        match (app.register_cursor, &app.register_edit) {
+            (Some(_), Some(_)) => Line::styled(
+                "Type a value and press Enter to set the register, or Esc to abandon the edit.",
+                Style::default().fg(Color::DarkGray),
+            ),
+            (Some(_), None) => Line::styled(
+                "Use ↑ and ↓ to choose a register and Enter to edit it. Press Tab or Esc to return to the prompt.",
+                Style::default().fg(Color::DarkGray),
+            ),
+            (None, _) => command_line(app, block.inner(rect).width),
         },
-        Line::from(vec![
-            Span::raw(format!("> {}", app.command_buffer)),
-            Span::styled("█", Style::default().add_modifier(Modifier::SLOW_BLINK)),
-        ]),
     ])
     .block(block);
 
     f.render_widget(paragraph, rect);
 }
 
+// Lay out the command being typed, with the cursor drawn over the character which it is on, or
+// after the end of the command. If the command is too long to fit, then it is scrolled so that the
+// cursor stays in view. Widths are measured in columns rather than characters, since some
+// characters are twice as wide as others.
+fn command_line(app: &App, width: u16) -> Line<'static> {
+    let buffer = &app.command_buffer;
+    let (before, after) = buffer.split_at(app.command_cursor);
+    let mut chars = after.chars();
+    let cursor = chars.next().map_or(" ".to_string(), String::from);
+    let after = chars.as_str();
+
+    // Characters are dropped from the start of the command until the cursor fits, keeping a
+    // column free for it at the very end.
+    let room = usize::from(width).saturating_sub(2 + Span::raw(cursor.as_str()).width());
+    let mut before = before;
+    while Span::raw(before).width() > room {
+        let mut chars = before.chars();
+        chars.next();
+        before = chars.as_str();
+    }
+
+    Line::from(vec![
+        Span::raw(if before.len() < app.command_cursor {
+            "… ".to_string()
+        } else {
+            "> ".to_string()
+        }),
+        Span::raw(before.to_string()),
+        Span::styled(cursor, Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(after.to_string()),
+    ])
+}
+
+// Render the most recent lines of console output.
+pub fn render_console(f: &mut Frame, app: &App, rect: Rect) {
+    // The block in which the console is displayed.
+    let block = Block::default()
+        .title("Console")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    // Only the lines which fit in the block are displayed, with the most recent at the bottom.
+    let inner = block.inner(rect);
+    let lines: Vec<Line> = app
+        .shown_console()
+        .iter()
+        .skip(
+            app.shown_console()
+                .len()
+                .saturating_sub(usize::from(inner.height)),
+        )
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the state of every device attached to the bus.
+pub fn render_devices(f: &mut Frame, app: &App, rect: Rect) {
+    // The block in which the devices are displayed.
+    let block = Block::default()
+        .title("Devices")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let mut lines = Vec::new();
+    for attached in &app.shown_cpu().devices.attached {
+        for (i, line) in attached.describe().into_iter().enumerate() {
+            // The summary of each device is emphasized, to separate it from the one before.
+            lines.push(if i == 0 {
+                Line::styled(line, Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Line::from(line)
+            });
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No devices are attached.",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the list of bookmarks, along with the value and disassembly at each. Bookmarks at the
+// program counter are highlighted.
+pub fn render_bookmarks(f: &mut Frame, app: &App, rect: Rect) {
+    let block = Block::default()
+        .title("Bookmarks")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let mut lines = app
+        .bookmarks
+        .iter()
+        .map(|(name, &address)| {
+            let line = format!(
+                "{:#06x} {}: {}",
+                address,
+                name,
+                search_text(app.shown_cpu(), &app.starts, address)
+            );
+            if address == app.shown_cpu().program_counter {
+                Line::styled(line, Style::default().fg(Color::Black).bg(Color::White))
+            } else {
+                Line::from(line)
+            }
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No bookmarks are set. Use MARK to set one.",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the frames of the guest's call stack, innermost first. If there are more than fit, the
+// outermost frames are left off.
+pub fn render_call_stack(f: &mut Frame, frames: &[abi::Frame], rect: Rect) {
+    let block = Block::default()
+        .title("Call Stack")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let lines = frames
+        .iter()
+        .enumerate()
+        .map(|(depth, frame)| Line::from(frame.describe(depth)))
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the blocks of the guest's heap, with free blocks in green and anything corrupt in red.
+pub fn render_heap(f: &mut Frame, walk: &Walk, rect: Rect) {
+    let (free, used) = walk.totals();
+    let block = Block::default()
+        .title(format!("Heap ({:#06x} free, {:#06x} used)", free, used))
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let problem = Style::default().fg(Color::Red);
+    let lines = walk
+        .problems
+        .iter()
+        .map(|line| Line::styled(line.clone(), problem))
+        .chain(walk.blocks.iter().map(|heap_block| {
+            let style = if !heap_block.problems.is_empty() {
+                problem
+            } else if heap_block.free {
+                Style::default().fg(Color::Green)
+            } else {
+                Style::default()
+            };
+            Line::styled(heap_block.describe(), style)
+        }))
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the guest's terminal. Unless the pane is scrolled back, it shows as much of the screen as
+// fits, from the top, but always including the row with the cursor.
+pub fn render_terminal(f: &mut Frame, app: &App, rect: Rect) {
+    let terminal = &app.terminal;
+    let block = Block::default()
+        .title(match app.terminal_scroll {
+            0 => "Terminal".to_string(),
+            rows => format!("Terminal ({} rows back)", rows),
+        })
+        .borders(Borders::ALL);
+
+    let height = usize::from(block.inner(rect).height);
+    let (row, column) = terminal.cursor;
+    let end = match app.terminal_scroll {
+        0 => terminal.scrollback() + height.max(row + 1).min(ROWS),
+        rows => terminal.scrollback() + ROWS - rows,
+    };
+    let start = end.saturating_sub(height);
+    let cursor = (app.terminal_scroll == 0).then_some((terminal.scrollback() + row, column));
+
+    let lines = terminal
+        .rows()
+        .enumerate()
+        .skip(start)
+        .take(end - start)
+        .map(|(index, cells)| {
+            // Runs of cells drawn in the same way are gathered into a single span.
+            let mut spans: Vec<Span> = Vec::new();
+            let mut run = String::new();
+            let mut style = None;
+            for (i, cell) in cells.iter().enumerate() {
+                let mut cell_style = terminal_style(cell.attributes);
+                if cursor == Some((index, i)) {
+                    cell_style = cell_style.add_modifier(Modifier::REVERSED);
+                }
+                if style.is_some_and(|style| style != cell_style) {
+                    spans.push(Span::styled(std::mem::take(&mut run), style.unwrap()));
+                }
+                style = Some(cell_style);
+                run.push(cell.character);
+            }
+            if let Some(style) = style {
+                spans.push(Span::styled(run, style));
+            }
+            Line::from(spans)
+        })
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// The style in which a cell of the guest's terminal is drawn.
+fn terminal_style(attributes: Attributes) -> Style {
+    const COLORS: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ];
+    let mut style = Style::default();
+    if let Some(foreground) = attributes.foreground {
+        style = style.fg(COLORS[usize::from(foreground)]);
+    }
+    if let Some(background) = attributes.background {
+        style = style.bg(COLORS[usize::from(background)]);
+    }
+    if attributes.bold {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if attributes.underline {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    if attributes.reverse {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    style
+}
+
+// Render the value of every watched expression, numbered as WATCHEXPR DELETE refers to them.
+pub fn render_watches(f: &mut Frame, app: &App, rect: Rect) {
+    let block = Block::default()
+        .title("Watches")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let lines = app
+        .watches
+        .iter()
+        .enumerate()
+        .map(|(index, watch)| {
+            Line::from(format!(
+                "{}: {} = {}",
+                index + 1,
+                watch.text,
+                watch.evaluate(app.shown_cpu()).describe()
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the program's recent loads and stores as a ribbon, with time running from left to right
+// and addresses from top to bottom. The more accesses a cell stands for, the more solid its glyph,
+// and cells which were stored to are colored as written words are in the RAM pane.
+pub fn render_accesses(f: &mut Frame, app: &App, rect: Rect) {
+    let block = Block::default().borders(Borders::ALL);
+    let inner = block.inner(rect);
+    let columns = inner.width.saturating_sub(ACCESSES_LABEL_WIDTH);
+    let ribbon = app.accesses.ribbon(
+        app.steps,
+        usize::from(inner.height),
+        usize::from(columns),
+        app.accesses_range,
+    );
+    let block = block.title(format!(
+        "Accesses, {:#06x} steps by {:#06x} words",
+        ribbon.steps, ribbon.words
+    ));
+
+    let most = ribbon
+        .cells
+        .iter()
+        .flatten()
+        .map(|cell| cell.reads + cell.writes)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let lines = ribbon
+        .cells
+        .iter()
+        .enumerate()
+        .map(|(row, cells)| {
+            let start = u32::from(ribbon.start) + row as u32 * ribbon.words;
+            let mut spans = vec![Span::raw(match u16::try_from(start) {
+                Ok(start) => format!("{:#06x} ", start),
+                Err(_) => " ".repeat(usize::from(ACCESSES_LABEL_WIDTH)),
+            })];
+            spans.extend(cells.iter().map(|cell| {
+                let count = cell.reads + cell.writes;
+                let glyph = match count * 4 / most {
+                    _ if count == 0 => " ",
+                    0 => "░",
+                    1 => "▒",
+                    2 => "▓",
+                    _ => "█",
+                };
+                let style = if cell.writes > 0 {
+                    WRITTEN_STYLE
+                } else {
+                    READ_STYLE
+                };
+                Span::styled(glyph, style)
+            }));
+            Line::from(spans)
+        })
+        .collect::<Vec<_>>();
+
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+// Render the problems most recently reported, numbered as DIAGNOSTICS refers to them, followed by
+// the lines of source around the selected problem. If there are more problems than fit, only those
+// around the selected one are shown.
+pub fn render_diagnostics(f: &mut Frame, app: &App, rect: Rect) {
+    let block = Block::default()
+        .title("Diagnostics")
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1));
+
+    let first = app
+        .diagnostic
+        .saturating_sub(usize::from(DIAGNOSTICS_ROWS) - 1);
+    let mut lines = app
+        .diagnostics
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(usize::from(DIAGNOSTICS_ROWS))
+        .map(|(index, diagnostic)| {
+            let line = format!("{}: {}", index + 1, diagnostic);
+            if index == app.diagnostic {
+                Line::styled(line, Style::default().fg(Color::Black).bg(Color::White))
+            } else {
+                Line::from(line)
+            }
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No problems were reported.",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    // The width of the line numbers is that of the last of them, so that the source lines up. Code
+    // in RAM is numbered by address instead.
+    let selected = app.diagnostics.get(app.diagnostic);
+    let at_fault = selected.map(|d| d.line);
+    let in_ram = selected.is_some_and(|d| d.file.is_none());
+    let width = app
+        .excerpt
+        .last()
+        .map_or(0, |(number, _)| number.to_string().len());
+    lines.extend(app.excerpt.iter().map(|(number, text)| {
+        let line = if in_ram {
+            format!("{:#06x} | {}", number, text)
+        } else {
+            format!("{:>width$} | {}", number, text, width = width)
+        };
+        if Some(*number) == at_fault {
+            Line::styled(line, Style::default().fg(Color::Red))
+        } else {
+            Line::styled(line, Style::default().fg(Color::DarkGray))
+        }
+    }));
+
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, rect);
+}
+
+// Render the state of the machine when it faulted, in a box centered in a given area of the frame,
+// which is made as large as it needs to be, as far as it fits.
+pub fn render_exception(f: &mut Frame, exception: &Exception, rect: Rect) {
+    let block = Block::default()
+        .title("Exception")
+        .title(
+            Title::from("Esc to dismiss")
+                .position(Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red))
+        .padding(Padding::horizontal(1));
+
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let encoding = exception
+        .words
+        .iter()
+        .map(|word| format!("{:#06x}", word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut lines = vec![
+        Line::styled(exception.message.clone(), heading.fg(Color::Red)),
+        Line::from(format!(
+            "{:#06x}  {:<13}  {}",
+            exception.address, encoding, exception.instruction
+        )),
+        Line::default(),
+        Line::styled("Registers", heading),
+    ];
+    lines.extend(
+        exception
+            .registers
+            .chunks(EXCEPTION_REGISTER_COLUMNS)
+            .enumerate()
+            .map(|(row, registers)| {
+                let line = registers
+                    .iter()
+                    .enumerate()
+                    .map(|(column, value)| {
+                        let register = row * EXCEPTION_REGISTER_COLUMNS + column;
+                        format!("r{:02} {:#06x}", register, value)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                Line::from(line)
+            }),
+    );
+    lines.push(Line::default());
+    lines.push(Line::styled("Backtrace", heading));
+    lines.extend(
+        exception
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(depth, frame)| Line::from(frame.describe(depth))),
+    );
+
+    let width = lines
+        .iter()
+        .map(|line| line.width() as u16 + 4)
+        .max()
+        .unwrap_or(0)
+        .min(rect.width);
+    let height = (lines.len() as u16 + 2).min(rect.height);
+    let rect = Rect {
+        x: rect.x + (rect.width - width) / 2,
+        y: rect.y + (rect.height - height) / 2,
+        width,
+        height,
+    };
+    f.render_widget(Clear, rect);
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
 // Render the instruction history.
 pub fn render_instruction_history(f: &mut Frame, app: &App, rect: Rect) {
     // The block in which the command prompt is displayed.
@@ -155,24 +1580,42 @@ pub fn render_instruction_history(f: &mut Frame, app: &App, rect: Rect) {
     let inner = block.inner(rect);
 
     let mut lines = vec![Line::styled(
-        disassemble(
-            app.cpu.ram[usize::from(app.cpu.program_counter)],
-            app.cpu.ram[usize::from(app.cpu.program_counter.wrapping_add(1))],
+        app.disassembly(
+            app.shown_cpu().peek(app.shown_cpu().program_counter),
+            app.shown_cpu()
+                .peek(app.shown_cpu().program_counter.wrapping_add(1)),
         ),
         Style::default().add_modifier(Modifier::BOLD),
     )];
-    for i in 0..inner.height - 1 {
-        let history = app
-            .instruction_history
-            .get(app.instruction_history.len().wrapping_sub(usize::from(i)));
-        match history {
-            Some((instruction, immediate)) => {
-                lines.push(Line::from(disassemble(*instruction, *immediate)));
-            }
-            None => {
-                lines.push(Line::from(""));
+
+    // Beneath the next instruction, preview what it will do when it is executed.
+    for effect in preview(app.shown_cpu(), app.shown_cpu().program_counter) {
+        lines.push(Line::styled(
+            format!(" {}", effect),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    // Fill the rest of the block with the history, most recent first.
+    let remaining = usize::from(inner.height).saturating_sub(lines.len());
+    for entry in app.shown_history().iter().rev().take(remaining) {
+        lines.push(match entry {
+            History::Executed(instruction, immediate) => {
+                Line::from(app.disassembly(*instruction, *immediate))
             }
-        }
+            History::Intervention(description) => Line::styled(
+                description.clone(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::ITALIC),
+            ),
+            History::Interrupt(line) => Line::styled(
+                format!("irq {} taken", line),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::ITALIC),
+            ),
+        });
     }
 
     let paragraph = Paragraph::new(lines).block(block);
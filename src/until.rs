@@ -0,0 +1,59 @@
+use crate::cpu::Cpu;
+use crate::disassemble::{is_call, is_return};
+use crate::explain::dataflow;
+
+// A kind of instruction which RUNUNTIL runs to, for exploring unfamiliar code a step at a time
+// that's coarser than a single instruction.
+#[derive(Clone, Copy)]
+pub enum Until {
+    // Any instruction which may transfer control: a conditional branch, a JSH, or a JAL.
+    Branch,
+    // A call or a return, as the calling convention tells them apart.
+    Call,
+    Return,
+    // An instruction which reads from or writes to RAM, optionally only within a range of
+    // addresses, given as its start and its length.
+    Load(Option<(u16, u16)>),
+    Store(Option<(u16, u16)>),
+}
+
+impl Until {
+    // Whether or not the instruction to which the program counter points is of this kind. The
+    // register which holds return addresses is needed to tell returns apart from other jumps.
+    pub fn matches(&self, cpu: &Cpu, link: usize) -> bool {
+        let instruction = cpu.peek(cpu.program_counter);
+        let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+        match *self {
+            Until::Branch => matches!(instruction & 0b0000000000111111, 0b101000..=0b101111),
+            Until::Call => is_call(instruction),
+            Until::Return => is_return(instruction, immediate, link),
+            Until::Load(range) => Self::accesses(cpu, false, range),
+            Until::Store(range) => Self::accesses(cpu, true, range),
+        }
+    }
+
+    fn accesses(cpu: &Cpu, write: bool, range: Option<(u16, u16)>) -> bool {
+        dataflow(cpu).memory.is_some_and(|(address, written)| {
+            written == write
+                && range.is_none_or(|(start, length)| address.wrapping_sub(start) < length)
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let (kind, preposition, range) = match *self {
+            Until::Branch => ("branch", "", None),
+            Until::Call => ("call", "", None),
+            Until::Return => ("return", "", None),
+            Until::Load(range) => ("load", "from", range),
+            Until::Store(range) => ("store", "to", range),
+        };
+        match range {
+            Some((start, 1)) => format!("the next {} {} {:#06x}", kind, preposition, start),
+            Some((start, length)) => format!(
+                "the next {} within {:#06x} words at {:#06x}",
+                kind, length, start
+            ),
+            None => format!("the next {}", kind),
+        }
+    }
+}
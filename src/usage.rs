@@ -0,0 +1,58 @@
+// The ways in which the program may have used a word of RAM. A word may have been used in several
+// ways, so these are combined as flags.
+pub const EXECUTED: u8 = 0b001;
+pub const WRITTEN: u8 = 0b010;
+pub const READ: u8 = 0b100;
+
+// The number of words summarized by each cell of the minimap.
+pub const BLOCK_SIZE: usize = 0x100;
+
+// A record of how the program has used each word of RAM since the image was loaded, which is used
+// to color the RAM pane.
+#[derive(Clone)]
+pub struct Usage {
+    flags: Vec<u8>,
+    // The instruction count at which each block of RAM was last used, if it has been used at all,
+    // which determines how hot the block is shown on the minimap.
+    last_used: Vec<Option<u32>>,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            flags: vec![0; 0x10000],
+            last_used: vec![None; 0x10000 / BLOCK_SIZE],
+        }
+    }
+}
+
+impl Usage {
+    // Record that a word of RAM has been used in a given way, as of a given instruction count.
+    pub fn mark(&mut self, index: usize, flag: u8, instructions: u32) {
+        self.flags[index] |= flag;
+        self.last_used[index / BLOCK_SIZE] = Some(instructions);
+    }
+
+    // Determine the ways in which any word of a block of RAM has been used.
+    pub fn block(&self, block: usize) -> u8 {
+        self.flags[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE]
+            .iter()
+            .fold(0, |flags, &flag| flags | flag)
+    }
+
+    // Determine the instruction count at which a block of RAM was last used, if it has been used.
+    pub fn last_used(&self, block: usize) -> Option<u32> {
+        self.last_used[block]
+    }
+
+    // Determine the ways in which a word of RAM has been used.
+    pub fn get(&self, index: usize) -> u8 {
+        self.flags[index]
+    }
+
+    // Forget how RAM has been used, such as when a new image is loaded over it.
+    pub fn clear(&mut self) {
+        self.flags.fill(0);
+        self.last_used.fill(None);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::Deserialize;
+
+use crate::cpu::Cpu;
+use crate::disassemble::disassemble;
+
+// What is done when an instruction overflows. The ISA has no flags, so signed overflow is otherwise
+// invisible.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    #[default]
+    Off,
+    // The overflow is noted in the console, and the simulation carries on.
+    Warn,
+    // The simulation is halted once the instruction has completed.
+    Halt,
+}
+
+// Check the instruction to which the program counter currently points for anything suspicious
+// about what it's about to do. Everything flagged here is perfectly well defined, and the machine
+// carries on regardless, but it's rarely what the programmer meant.
+pub fn check(cpu: &Cpu) -> Option<String> {
+    let instruction = cpu.peek(cpu.program_counter);
+    let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+    let s = usize::from((instruction & 0b1111100000000000) >> 11);
+    let source = cpu.registers[s];
+    let text = disassemble(instruction, immediate);
+    let text = text.trim();
+
+    let warning = match instruction & 0b0000000000111111 {
+        // A shift by a register shifts the other way when the amount is negative, which is easy to
+        // fall into by accident with an amount that has been computed.
+        opcode @ 0b000101..=0b000111 => {
+            let reverse = match opcode {
+                0b000101 => "right",
+                _ => "left",
+            };
+            shift(source, Some(reverse))
+        }
+        // SFTI is the only way to shift right logically by an immediate, so a negative amount is
+        // expected of it, but not of SRAI.
+        0b001101 => shift(immediate, None),
+        0b001111 => shift(immediate, Some("left")),
+        0b010001 if s == 0 => Some("stores through r00, so it writes to 0x0000".to_string()),
+        0b011001 if s == 0 && immediate == 0x0000 => {
+            Some("stores at an offset of 0 from r00, so it writes to 0x0000".to_string())
+        }
+        _ => None,
+    }?;
+    Some(format!(
+        "Warning at {:#06x}: {} {}.",
+        cpu.program_counter, text, warning
+    ))
+}
+
+// Check the amount of a shift, given the direction which a negative amount shifts in if that is
+// suspicious in itself.
+fn shift(amount: u16, reverse: Option<&str>) -> Option<String> {
+    let amount = amount as i16;
+    if amount.unsigned_abs() >= 16 {
+        Some(format!(
+            "shifts by {}, which is at least the width of a word",
+            amount
+        ))
+    } else if let (true, Some(direction)) = (amount < 0, reverse) {
+        Some(format!(
+            "shifts by {}, so it actually shifts {}",
+            amount, direction
+        ))
+    } else {
+        None
+    }
+}
+
+// Check whether the instruction to which the program counter currently points is an ADD, SUB, or
+// ADDI which is about to overflow, taking its operands as signed, and describe the overflow if so.
+pub fn overflow(cpu: &Cpu) -> Option<String> {
+    let instruction = cpu.peek(cpu.program_counter);
+    let immediate = cpu.peek(cpu.program_counter.wrapping_add(1));
+    let source = cpu.registers[usize::from((instruction & 0b1111100000000000) >> 11)];
+    let destination = cpu.registers[usize::from((instruction & 0b0000011111000000) >> 6)];
+
+    let d = destination as i16;
+    let (description, (result, overflowed)) = match instruction & 0b0000000000111111 {
+        0b000000 => (
+            format!("adding {} and {}", signed(destination), signed(source)),
+            d.overflowing_add(source as i16),
+        ),
+        0b000001 => (
+            format!(
+                "subtracting {} from {}",
+                signed(source),
+                signed(destination)
+            ),
+            d.overflowing_sub(source as i16),
+        ),
+        0b001000 => (
+            format!("adding {} and {}", signed(source), signed(immediate)),
+            (source as i16).overflowing_add(immediate as i16),
+        ),
+        _ => return None,
+    };
+    if !overflowed {
+        return None;
+    }
+    Some(format!(
+        "Signed overflow at {:#06x}: {} overflowed {}, giving {}.",
+        cpu.program_counter,
+        disassemble(instruction, immediate).trim(),
+        description,
+        signed(result as u16)
+    ))
+}
+
+fn signed(value: u16) -> String {
+    format!("{:#06x} ({})", value, value as i16)
+}
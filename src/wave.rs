@@ -0,0 +1,156 @@
+use anyhow::Result;
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::cpu::Cpu;
+
+// A VCD file into which the architectural state of the CPU is recorded after every instruction,
+// for viewing in a waveform viewer such as GTKWave alongside a simulation of the hardware. The
+// program counter, the chosen registers, and every interrupt line to which a device is wired are
+// recorded, with time measured in emulated cycles.
+pub struct Wave {
+    pub filename: String,
+    file: BufWriter<File>,
+    registers: Vec<usize>,
+    irqs: Vec<u8>,
+    // The value of every signal as it was last written, in the order in which they're declared, so
+    // that only changes need to be written.
+    values: Vec<u16>,
+    // The time of the last sample, which keeps counting up even if the cycle counter is reset.
+    time: u64,
+    cycles: u32,
+}
+
+impl Wave {
+    // Create a VCD file, declaring its signals and recording their initial values.
+    pub fn create(filename: &str, registers: Vec<usize>, cpu: &Cpu) -> Result<Self> {
+        let irqs = cpu
+            .devices
+            .attached
+            .iter()
+            .filter_map(|attached| attached.irq)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut file = BufWriter::new(File::create(filename)?);
+        writeln!(
+            file,
+            "$comment Recorded by ilo, with one time unit per emulated cycle. $end"
+        )?;
+        writeln!(file, "$timescale 1ns $end")?;
+        writeln!(file, "$scope module cpu $end")?;
+        let mut index = 0;
+        writeln!(file, "$var wire 16 {} pc [15:0] $end", identifier(index))?;
+        for register in &registers {
+            index += 1;
+            writeln!(
+                file,
+                "$var wire 16 {} r{:02} [15:0] $end",
+                identifier(index),
+                register
+            )?;
+        }
+        for irq in &irqs {
+            index += 1;
+            writeln!(file, "$var wire 1 {} irq{} $end", identifier(index), irq)?;
+        }
+        writeln!(file, "$upscope $end")?;
+        writeln!(file, "$enddefinitions $end")?;
+
+        let mut wave = Self {
+            filename: filename.to_string(),
+            file,
+            registers,
+            irqs,
+            values: Vec::new(),
+            time: 0,
+            cycles: cpu.counters.cycles,
+        };
+        wave.values = wave.signals(cpu);
+        writeln!(wave.file, "#0")?;
+        writeln!(wave.file, "$dumpvars")?;
+        for index in 0..wave.values.len() {
+            wave.write_value(index)?;
+        }
+        writeln!(wave.file, "$end")?;
+        Ok(wave)
+    }
+
+    // Record the state of the CPU after an instruction, writing whichever signals changed.
+    pub fn sample(&mut self, cpu: &Cpu) -> Result<()> {
+        // NOTE: Every instruction takes at least one cycle, and if the cycle counter went backwards
+        // then it was reset, and counts the cycles since the reset.
+        let elapsed = match cpu.counters.cycles.checked_sub(self.cycles) {
+            Some(elapsed) => elapsed,
+            None => cpu.counters.cycles,
+        };
+        self.time += u64::from(elapsed.max(1));
+        self.cycles = cpu.counters.cycles;
+
+        let signals = self.signals(cpu);
+        let changed = (0..signals.len())
+            .filter(|&index| signals[index] != self.values[index])
+            .collect::<Vec<_>>();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.values = signals;
+        writeln!(self.file, "#{}", self.time)?;
+        for index in changed {
+            self.write_value(index)?;
+        }
+        Ok(())
+    }
+
+    // Flush everything which has been recorded to the file.
+    pub fn finish(mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+
+    // The current value of every signal, in the order in which they're declared.
+    fn signals(&self, cpu: &Cpu) -> Vec<u16> {
+        let mut signals = vec![cpu.program_counter];
+        signals.extend(
+            self.registers
+                .iter()
+                .map(|&register| cpu.registers[register]),
+        );
+        signals.extend(self.irqs.iter().map(|&irq| {
+            u16::from(
+                cpu.devices
+                    .attached
+                    .iter()
+                    .any(|attached| attached.irq == Some(irq) && attached.interrupt()),
+            )
+        }));
+        signals
+    }
+
+    fn write_value(&mut self, index: usize) -> Result<()> {
+        let value = self.values[index];
+        if index > self.registers.len() {
+            writeln!(self.file, "{}{}", value, identifier(index))?;
+        } else {
+            writeln!(self.file, "b{:b} {}", value, identifier(index))?;
+        }
+        Ok(())
+    }
+}
+
+// The short identifier by which a signal is referred to in the body of a VCD file, which is made up
+// of printable characters other than spaces.
+fn identifier(mut index: usize) -> String {
+    let mut identifier = String::new();
+    loop {
+        identifier.push(char::from(b'!' + (index % 94) as u8));
+        index /= 94;
+        if index == 0 {
+            return identifier;
+        }
+        index -= 1;
+    }
+}
@@ -0,0 +1,70 @@
+use crate::cpu::Cpu;
+use crate::explain::dataflow;
+
+// The instruction which last wrote to a register or a word of RAM.
+#[derive(Clone, Copy)]
+pub struct Writer {
+    // The address of the instruction.
+    pub address: u16,
+    // The number of steps which had been taken before it, since the image was loaded.
+    pub step: u64,
+}
+
+// The instruction which last wrote to each register and word of RAM, for answering who wrote a
+// value without bisecting a trace. Only writes made by the program are recorded, so a value which
+// was loaded, assembled, or SET has no writer.
+pub struct Writers {
+    registers: [Option<Writer>; 0x20],
+    // Writers of RAM, indexed as the fitted RAM is, so that mirrored addresses agree.
+    memory: Vec<Option<Writer>>,
+}
+
+impl Writers {
+    pub fn new() -> Self {
+        Self {
+            registers: [None; 0x20],
+            memory: vec![None; 0x10000],
+        }
+    }
+
+    pub fn register(&self, register: usize) -> Option<Writer> {
+        self.registers[register]
+    }
+
+    pub fn memory(&self, cpu: &Cpu, address: u16) -> Option<Writer> {
+        self.memory[cpu.memory.decode(address)?]
+    }
+
+    // Forget the writers of the registers, which are cleared when the machine is reset.
+    pub fn reset_registers(&mut self) {
+        self.registers = [None; 0x20];
+    }
+
+    // Forget every writer, as when a new image is loaded.
+    pub fn clear(&mut self) {
+        self.reset_registers();
+        self.memory.fill(None);
+    }
+
+    // Record the writes of the instruction to which the program counter currently points, given
+    // the number of steps taken so far. This must be done before it is executed, while the address
+    // that it stores to can still be found.
+    pub fn record(&mut self, cpu: &Cpu, step: u64) {
+        let writer = Some(Writer {
+            address: cpu.program_counter,
+            step,
+        });
+        let dataflow = dataflow(cpu);
+        for register in dataflow.writes {
+            self.registers[register] = writer;
+        }
+        // Stores to devices and the performance counters don't land in RAM.
+        if let Some((address, true)) = dataflow.memory {
+            if cpu.devices.peek(address).is_none() && !cpu.counters.contains(address) {
+                if let Some(index) = cpu.memory.decode(address) {
+                    self.memory[index] = writer;
+                }
+            }
+        }
+    }
+}